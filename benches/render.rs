@@ -0,0 +1,119 @@
+// Mediciones de rendimiento: `cargo bench`. Sin dependencias nuevas, solo std::time;
+// cada caso se repite varias veces y se informa la mediana
+use raylib::prelude::*;
+use std::time::{Duration, Instant};
+
+use computer_graphics_v3::accumulation::AccumulationBuffer;
+use computer_graphics_v3::blocks::{get_layers, BlockGrid};
+use computer_graphics_v3::camera::Camera;
+use computer_graphics_v3::framebuffer::Framebuffer;
+use computer_graphics_v3::material::Material;
+use computer_graphics_v3::mesh::TriangleMesh;
+use computer_graphics_v3::ray_intersect::inverse_direction;
+use computer_graphics_v3::scene::Scene;
+use computer_graphics_v3::settings::RenderSettings;
+use computer_graphics_v3::textures::TextureManager;
+use computer_graphics_v3::{cast_ray, daynight, pixel_sampler, render, scene_objects, update_culling, RayPath};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+const RUNS: usize = 10;
+const RAYS_PER_RUN: usize = 4096;
+// Vértices por lado de la grilla de triángulos del armado de la jerarquía de la malla
+const MESH_SIDE: usize = 128;
+
+fn median(mut times: Vec<Duration>) -> Duration {
+    times.sort();
+    times[times.len() / 2]
+}
+
+fn bench(name: &str, mut run: impl FnMut()) {
+    run();
+    let times = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .collect();
+    println!("{:<24} {:>10.3} ms", name, median(times).as_secs_f64() * 1000.0);
+}
+
+fn main() {
+    let settings = RenderSettings::default();
+    let grid = BlockGrid::from_layers(get_layers());
+    let lights = daynight::create_lights(8.0);
+    let mut scene = Scene::new(scene_objects(&grid, true, None, &settings), lights, TextureManager::new());
+    scene.preload_textures();
+
+    let camera = Camera::new(
+        Vector3::new(0.0, 0.0, 5.0),
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+    );
+    update_culling(&mut scene, &camera, &settings, WIDTH as f32 / HEIGHT as f32);
+
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    let mut accumulation = AccumulationBuffer::new(WIDTH, HEIGHT);
+    bench(&format!("render {}x{}", WIDTH, HEIGHT), || {
+        accumulation.reset();
+        render(&mut framebuffer, &scene, &camera, &settings, &mut accumulation, None);
+    });
+
+    // Mismo cuadro con paquetes de rayos primarios: la diferencia sale del cielo alrededor del diorama
+    let packet_settings = RenderSettings { packet_tracing: true, ..RenderSettings::default() };
+    bench(&format!("render {}x{} packets", WIDTH, HEIGHT), || {
+        accumulation.reset();
+        render(&mut framebuffer, &scene, &camera, &packet_settings, &mut accumulation, None);
+    });
+
+    // Direcciones fijas en una grilla sobre el diorama, desde el ojo de la cámara
+    let directions: Vec<Vector3> = (0..RAYS_PER_RUN)
+        .map(|i| {
+            let x = (i % 64) as f32 / 63.0 - 0.5;
+            let y = (i / 64) as f32 / 63.0 - 0.5;
+            Vector3::new(x, y, -1.0).normalized()
+        })
+        .collect();
+    bench(&format!("cast_ray x{}", RAYS_PER_RUN), || {
+        for (index, direction) in directions.iter().enumerate() {
+            let mut sampler = pixel_sampler(0, index, 0);
+            std::hint::black_box(cast_ray(&camera.eye, direction, &scene, &settings, RayPath::primary(), &mut sampler));
+        }
+    });
+
+    // Los mismos rayos contra cada objeto, calculando la inversa de la dirección en cada prueba
+    // o una sola vez por rayo
+    bench(&format!("intersect x{} plain", RAYS_PER_RUN), || {
+        for direction in &directions {
+            for object in scene.objects() {
+                std::hint::black_box(object.ray_intersect(&camera.eye, direction));
+            }
+        }
+    });
+    bench(&format!("intersect x{} inverse", RAYS_PER_RUN), || {
+        for direction in &directions {
+            let inverse = inverse_direction(direction);
+            for object in scene.objects() {
+                std::hint::black_box(object.ray_intersect_inverse(&camera.eye, direction, &inverse));
+            }
+        }
+    });
+
+    // Armado de la jerarquía de cajas de una malla grande (una grilla ondulada de triángulos)
+    let positions: Vec<Vector3> = (0..MESH_SIDE * MESH_SIDE)
+        .map(|i| {
+            let (x, z) = ((i % MESH_SIDE) as f32, (i / MESH_SIDE) as f32);
+            Vector3::new(x, (x * 0.7).sin() + (z * 0.3).cos(), z)
+        })
+        .collect();
+    let triangles: Vec<[usize; 3]> = (0..(MESH_SIDE - 1) * (MESH_SIDE - 1))
+        .flat_map(|cell| {
+            let corner = cell / (MESH_SIDE - 1) * MESH_SIDE + cell % (MESH_SIDE - 1);
+            [[corner, corner + MESH_SIDE, corner + 1], [corner + 1, corner + MESH_SIDE, corner + MESH_SIDE + 1]]
+        })
+        .collect();
+    bench(&format!("mesh bvh x{}", triangles.len()), || {
+        std::hint::black_box(TriangleMesh::new(positions.clone(), Vec::new(), Vec::new(), triangles.clone(), Material::black()));
+    });
+}
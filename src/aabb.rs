@@ -0,0 +1,69 @@
+use raylib::prelude::Vector3;
+
+// Caja alineada a los ejes (esquinas mínima y máxima); puede ser infinita en los ejes sin límite
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        Aabb { min, max }
+    }
+
+    // Caja vacía: unirla con otra devuelve la otra
+    pub fn empty() -> Self {
+        Aabb {
+            min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            max: Vector3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+        }
+    }
+
+    pub fn centroid(&self) -> Vector3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn is_finite(&self) -> bool {
+        (self.max - self.min).length().is_finite()
+    }
+
+    // Slab test con la inversa de la dirección ya calculada: distancias de entrada y salida
+    // a lo largo del rayo, o None si no la toca (o queda entera detrás del origen)
+    pub fn intersect(&self, ray_origin: &Vector3, inv_direction: &Vector3) -> Option<(f32, f32)> {
+        let t1 = (self.min.x - ray_origin.x) * inv_direction.x;
+        let t2 = (self.max.x - ray_origin.x) * inv_direction.x;
+        let t3 = (self.min.y - ray_origin.y) * inv_direction.y;
+        let t4 = (self.max.y - ray_origin.y) * inv_direction.y;
+        let t5 = (self.min.z - ray_origin.z) * inv_direction.z;
+        let t6 = (self.max.z - ray_origin.z) * inv_direction.z;
+
+        let tmin = t1.min(t2).max(t3.min(t4)).max(t5.min(t6));
+        let tmax = t1.max(t2).min(t3.max(t4)).min(t5.max(t6));
+
+        (tmax >= 0.0 && tmin <= tmax).then_some((tmin, tmax))
+    }
+
+    // Slab test de un paquete de rayos que salen del mismo origen: las distancias del origen a
+    // los planos de la caja se calculan una vez para todos. Cada rayo da lo mismo que `intersect`
+    pub fn intersect_packet<const N: usize>(&self, ray_origin: &Vector3, inv_directions: &[Vector3; N]) -> [bool; N] {
+        let to_min = self.min - *ray_origin;
+        let to_max = self.max - *ray_origin;
+        inv_directions.map(|inv| {
+            let (t1, t2) = (to_min.x * inv.x, to_max.x * inv.x);
+            let (t3, t4) = (to_min.y * inv.y, to_max.y * inv.y);
+            let (t5, t6) = (to_min.z * inv.z, to_max.z * inv.z);
+
+            let tmin = t1.min(t2).max(t3.min(t4)).max(t5.min(t6));
+            let tmax = t1.max(t2).min(t3.max(t4)).min(t5.max(t6));
+            tmax >= 0.0 && tmin <= tmax
+        })
+    }
+}
@@ -0,0 +1,53 @@
+use raylib::prelude::Vector3;
+use rayon::prelude::*;
+use crate::camera::Camera;
+
+// Buffer HDR que promedia los cuadros renderizados mientras la escena está quieta
+pub struct AccumulationBuffer {
+    pub colors: Vec<Vector3>,
+    pub frame_count: u32,
+    // Vista con la que se llenó `colors`, para la reproyección temporal. `reset` no la borra:
+    // al mover la cámara el promedio anterior sirve de historia; `invalidate_history` sí
+    pub history: Option<FrameHistory>,
+}
+
+// Cómo se vio el cuadro guardado: cámara, giro del diorama, firma de la iluminación y el
+// punto (en el marco del diorama) que tocó el rayo primario de cada píxel (None = cielo)
+pub struct FrameHistory {
+    pub camera: Camera,
+    pub rotation: f32,
+    pub rotation_axis: Vector3,
+    pub lighting: Vec<f32>,
+    pub positions: Vec<Option<Vector3>>,
+}
+
+impl AccumulationBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        AccumulationBuffer {
+            colors: vec![Vector3::zero(); (width * height) as usize],
+            frame_count: 0,
+            history: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.frame_count = 0;
+    }
+
+    // Lo guardado ya no corresponde a la escena (se editaron bloques, cambió un material...)
+    pub fn invalidate_history(&mut self) {
+        self.history = None;
+    }
+
+    // Mezcla un nuevo cuadro con el promedio actual
+    pub fn add_frame(&mut self, frame: &[Vector3]) {
+        let previous_weight = self.frame_count as f32;
+        let total_weight = previous_weight + 1.0;
+
+        self.colors.par_iter_mut().zip(frame.par_iter()).for_each(|(average, new_color)| {
+            *average = (*average * previous_weight + *new_color) / total_weight;
+        });
+
+        self.frame_count += 1;
+    }
+}
@@ -0,0 +1,92 @@
+use raylib::prelude::*;
+use std::io;
+use crate::exr;
+use crate::material::vector3_to_color;
+
+// Valores del primer impacto de un rayo primario, sin iluminación
+#[derive(Clone, Copy)]
+pub struct AovSample {
+    pub albedo: Vector3,
+    pub normal: Vector3,
+    pub depth: f32,
+    pub position: Vector3,
+}
+
+impl AovSample {
+    // Los píxeles de cielo guardan el color del cielo, normal nula y profundidad y posición infinitas
+    pub fn sky(sky_color: Vector3) -> Self {
+        AovSample {
+            albedo: sky_color,
+            normal: Vector3::zero(),
+            depth: f32::INFINITY,
+            position: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        }
+    }
+}
+
+// Buffers auxiliares (albedo, normal y posición en espacio mundo y profundidad lineal) para
+// composición y denoising
+pub struct AovBuffers {
+    pub width: u32,
+    pub height: u32,
+    pub albedo: Vec<Vector3>,
+    pub normal: Vec<Vector3>,
+    pub depth: Vec<f32>,
+    pub position: Vec<Vector3>,
+}
+
+impl AovBuffers {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        AovBuffers {
+            width,
+            height,
+            albedo: vec![Vector3::zero(); size],
+            normal: vec![Vector3::zero(); size],
+            depth: vec![f32::INFINITY; size],
+            position: vec![Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY); size],
+        }
+    }
+
+    pub fn set(&mut self, index: usize, sample: AovSample) {
+        self.albedo[index] = sample.albedo;
+        self.normal[index] = sample.normal;
+        self.depth[index] = sample.depth;
+        self.position[index] = sample.position;
+    }
+
+    pub fn save_albedo_png(&self, path: &str) {
+        self.save_png(path, |index| self.albedo[index]);
+    }
+
+    // Las normales se guardan remapeadas de [-1, 1] a [0, 1]
+    pub fn save_normal_png(&self, path: &str) {
+        self.save_png(path, |index| self.normal[index] * 0.5 + Vector3::new(0.5, 0.5, 0.5));
+    }
+
+    // La profundidad se normaliza por la máxima distancia finita; el cielo queda blanco
+    pub fn save_depth_png(&self, path: &str) {
+        let max_depth = self.depth.iter().copied().filter(|d| d.is_finite()).fold(0.0, f32::max);
+        self.save_png(path, |index| {
+            let depth = self.depth[index];
+            let value = if depth.is_finite() && max_depth > 0.0 { depth / max_depth } else { 1.0 };
+            Vector3::new(value, value, value)
+        });
+    }
+
+    // La posición no cabe en [0, 1]: va sin remapear a un EXR en float
+    pub fn save_position_exr(&self, path: &str) -> io::Result<()> {
+        exr::save_exr(path, self.width, self.height, &self.position)
+    }
+
+    fn save_png(&self, path: &str, color_at: impl Fn(usize) -> Vector3) {
+        let mut image = Image::gen_image_color(self.width as i32, self.height as i32, Color::BLACK);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = color_at((y * self.width + x) as usize);
+                image.draw_pixel(x as i32, y as i32, vector3_to_color(color));
+            }
+        }
+        image.export_image(path);
+    }
+}
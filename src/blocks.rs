@@ -1,113 +1,150 @@
-use raylib::prelude::Vector3;
+use raylib::prelude::{Image, Matrix, Vector3};
+use std::f32::consts::PI;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use crate::cube::Cube;
-use crate::material::Material;
+use crate::material::{EmissionWave, Material, IOR_GLASS, IOR_WATER};
+use crate::cone::Cone;
+use crate::csg::Difference;
+use crate::cylinder::Cylinder;
+use crate::disk::Disk;
+use crate::instance::{Instance, SharedGeometry};
+use crate::scene_graph::SceneNode;
+use crate::plane::Plane;
+use crate::presets;
+use crate::quad::Quad;
+use crate::scene::SceneObject;
+use crate::sdf::{smooth_union, Sdf, SdfBox, SdfObject, SdfSphere};
+use crate::torus::Torus;
 
-pub const GRID_SIZE_X: usize = 9;
-pub const GRID_SIZE_Y: usize = 5;
+// Tamaño y separación por defecto de los bloques; el ancho y fondo de la grilla salen de las capas
 pub const CUBE_SIZE: f32 = 0.5;
 pub const CUBE_SPACING: f32 = 0.5;
+// Diferencia entre spacing y cube_size, relativa al bloque, que todavía cuenta como bloques
+// pegados (los tamaños leídos de un archivo o calculados rara vez son exactamente iguales)
+const SPACING_TOLERANCE: f32 = 1e-4;
 
 fn get_material_from_letter(letter: char) -> Option<Material> {
     match letter {
-        'R' => Some(Material::new(
-            Vector3::new(0.8, 0.2, 0.2),
-            10.0,
-            [0.9, 0.1, 0.0, 0.0],
-            0.0,
-            Some("assets/Furnace.png".to_string()),
-            None,
-        )),
-        'B' => Some(Material::new(
-            Vector3::new(0.8, 0.4, 0.2),
-            20.0,
-            [0.8, 0.2, 0.0, 0.0],
-            0.0,
-            Some("assets/Bookshelf.png".to_string()),
-            None,
-        )),
-        'I' => Some(Material::new(
-            Vector3::new(0.4, 0.4, 0.3),
-            50.0,
-            [0.6, 0.3, 0.1, 0.0],
-            0.0,
-            Some("assets/obsidiana.png".to_string()),
-            None,
-        )),
-        'G' => Some(Material::new(
-            Vector3::new(0.5, 0.8, 1.0), // Azul celeste
-            125.0,
-            [0.0, 0.3, 0.4, 0.8], // Aumentada reflectividad (albedo[2]) para hacerlo más reflejante
-            3.2, // Índice de refracción muy alto para efecto reflejante pronunciado
-            Some("assets/glass.png".to_string()),
-            None,
-        )),
-        'Y' => Some(Material::new_emissive(
-            Vector3::new(0.9, 0.9, 0.2),
-            30.0,
-            [0.7, 0.3, 0.0, 0.0],
-            0.0,
-            Some("assets/glowstone.png".to_string()),
-            None,
-            0.6, // Intensidad de emisión (reducida)
-            Vector3::new(1.0, 0.95, 0.3), // Color de emisión (amarillo más puro)
-        )),
-        'P' => Some(Material::new(
-            Vector3::new(0.8, 0.2, 0.8),
-            15.0,
-            [0.8, 0.2, 0.0, 0.0],
-            0.0,
-            Some("assets/chest.png".to_string()),
-            None,
-        )),
-        'C' => Some(Material::new(
-            Vector3::new(0.2, 0.8, 0.8),
-            25.0,
-            [0.7, 0.3, 0.0, 0.0],
-            0.0,
-            Some("assets/chest.png".to_string()),
-            None,
-        )),
-        'W' => Some(Material::new(
-            Vector3::new(0.9, 0.9, 0.9),
-            40.0,
-            [0.6, 0.4, 0.0, 0.0],
-            0.0,
-            Some("assets/wood_planks.png".to_string()),
-            None,
-        )),
-        'K' => Some(Material::new(
-            Vector3::new(0.1, 0.1, 0.1),
-            5.0,
-            [0.9, 0.1, 0.0, 0.0],
-            0.0,
-            Some("assets/obsidiana.png".to_string()),
-            None,
-        )),
+        'R' => Some(presets::matte(Vector3::new(0.8, 0.2, 0.2)).into_builder()
+            .texture("assets/Furnace.png")
+            .emissive(Vector3::new(1.0, 0.55, 0.2), 0.35) // Horno encendido: el fuego parpadea
+            .emission_animation(EmissionWave::Flicker, 0.4, 0.2)
+            .build()),
+        'B' => Some(Material::builder()
+            .diffuse(Vector3::new(0.8, 0.4, 0.2))
+            .specular(20.0)
+            .albedo([0.8, 0.2, 0.0, 0.0])
+            .texture("assets/Bookshelf.png")
+            .build()),
+        'I' => Some(Material::builder()
+            .diffuse(Vector3::new(0.4, 0.4, 0.3))
+            .specular(50.0)
+            .albedo([0.6, 0.3, 0.1, 0.0])
+            .texture("assets/obsidiana.png")
+            .build()),
+        'G' => Some(presets::glass(IOR_GLASS).into_builder()
+            .diffuse(Vector3::new(0.5, 0.8, 1.0)) // Azul celeste
+            .dispersion(40.0) // Bordes levemente irisados
+            .texture("assets/glass.png")
+            .build()),
+        'L' => Some(Material::builder()
+            .diffuse(Vector3::one()) // Follaje: entre las hojas (alpha 0) la luz pasa sin desviarse
+            .specular(10.0)
+            .albedo([0.9, 0.1, 0.0, 1.0])
+            .refractive_index(1.0)
+            .texture("assets/leaves.png")
+            .build()),
+        'F' => Some(presets::glass(IOR_GLASS).into_builder()
+            .diffuse(Vector3::new(0.85, 0.92, 1.0)) // Vidrio esmerilado: lo de atrás se ve borroso
+            .roughness(0.15)
+            .build()),
+        'A' => Some(Material::builder()
+            .diffuse(Vector3::new(0.2, 0.45, 0.8)) // Agua azul semitransparente
+            .specular(90.0)
+            .albedo([0.1, 0.3, 0.3, 0.6])
+            .refractive_index(IOR_WATER)
+            .ripple(0.25) // Ondas animadas en la superficie
+            .build()),
+        'Y' => Some(presets::lamp(Vector3::new(1.0, 0.95, 0.3), 0.6).into_builder() // Amarillo más puro, intensidad reducida
+            .diffuse(Vector3::new(0.9, 0.9, 0.2))
+            .texture("assets/glowstone.png")
+            .build()),
+        'E' => Some(presets::mirror()),
+        'U' => Some(presets::metal(Vector3::new(1.0, 0.78, 0.34)).into_builder() // Oro
+            .diffuse(Vector3::new(0.55, 0.4, 0.1))
+            .build()),
+        'P' => Some(Material::builder()
+            .diffuse(Vector3::new(0.8, 0.2, 0.8))
+            .specular(15.0)
+            .albedo([0.8, 0.2, 0.0, 0.0])
+            .texture("assets/chest.png")
+            .build()),
+        'C' => Some(Material::builder()
+            .diffuse(Vector3::new(0.2, 0.8, 0.8))
+            .specular(25.0)
+            .albedo([0.7, 0.3, 0.0, 0.0])
+            .texture("assets/chest.png")
+            .build()),
+        'W' => Some(Material::builder()
+            .diffuse(Vector3::new(0.9, 0.9, 0.9))
+            .specular(40.0)
+            .albedo([0.9, 0.1, 0.0, 0.0])
+            .texture("assets/wood_planks.png")
+            .clearcoat(0.8, 0.05) // Tablas mate con una capa de barniz brillante
+            .build()),
+        'T' => Some(Material::builder()
+            .diffuse(Vector3::new(0.9, 0.9, 0.9)) // Madera con sombreado de dibujo animado
+            .specular(40.0)
+            .albedo([0.9, 0.1, 0.0, 0.0])
+            .texture("assets/wood_planks.png")
+            .toon(3, 0.35)
+            .build()),
+        'K' => Some(Material::builder()
+            .diffuse(Vector3::new(0.1, 0.1, 0.1))
+            .specular(5.0)
+            .albedo([0.9, 0.1, 0.0, 0.0])
+            .texture("assets/obsidiana.png")
+            .build()),
+        'O' => Some(Material::builder()
+            .diffuse(Vector3::new(0.08, 0.08, 0.1)) // Bloque decorativo con brillo de mancha de aceite
+            .specular(40.0)
+            .albedo([0.4, 0.6, 0.0, 0.0])
+            .iridescence(1.0, 380.0, Vector3::one())
+            .build()),
+        'M' => Some(Material::builder()
+            .diffuse(Vector3::new(0.35, 0.36, 0.38)) // Metal cepillado: el brillo se estira en horizontal
+            .specular(80.0)
+            .albedo([0.5, 0.5, 0.0, 0.0])
+            .anisotropy(0.9, Vector3::new(1.0, 0.0, 0.0))
+            .build()),
+        // Terreno generado desde un mapa de alturas
+        'S' => Some(Material::builder()
+            .diffuse(Vector3::new(0.5, 0.5, 0.52)) // Piedra
+            .specular_roughness(0.55)
+            .albedo([0.85, 0.15, 0.0, 0.0])
+            .double_sided(false)
+            .build()),
+        'D' => Some(Material::builder()
+            .diffuse(Vector3::new(0.45, 0.3, 0.18)) // Tierra
+            .specular_roughness(0.73)
+            .albedo([0.95, 0.05, 0.0, 0.0])
+            .double_sided(false)
+            .build()),
+        'H' => Some(Material::builder()
+            .diffuse(Vector3::new(0.3, 0.65, 0.25)) // Pasto
+            .specular_roughness(0.67)
+            .albedo([0.9, 0.1, 0.0, 0.0])
+            .double_sided(false)
+            .build()),
         _ => None,
     }
 }
 
-fn create_cube_from_letter(
-    letter: char,
-    grid_x: usize,
-    grid_y: usize,
-    layer: usize,
-) -> Option<Cube> {
-    if let Some(material) = get_material_from_letter(letter) {
-        let offset_x = (GRID_SIZE_X as f32 - 1.0) * CUBE_SPACING / 2.0;
-        let offset_z = (GRID_SIZE_Y as f32 - 1.0) * CUBE_SPACING / 2.0;
-        let x = grid_x as f32 * CUBE_SPACING - offset_x;
-        let y = layer as f32 * CUBE_SPACING;
-        let z = grid_y as f32 * CUBE_SPACING - offset_z;
-        
-        Some(Cube {
-            center: Vector3::new(x, y, z),
-            size: CUBE_SIZE,
-            material,
-        })
-    } else {
-        None
-    }
+// Solo se fusionan bloques opacos y sin brillo propio: en el vidrio las caras internas
+// refractan, en los recortes se ven a través y los emisivos iluminan desde su centro
+fn is_mergeable(material: &Material) -> bool {
+    !material.is_emissive && material.albedo[3] <= 0.0 && material.alpha_cutout.is_none()
 }
 
 const LAYER_0: &[&str] = &[
@@ -122,14 +159,14 @@ const LAYER_1: &[&str] = &[
     "         ",
     " BBBBBBB ",
     " GR IYPG ",
-    "         ",
-    "         ",
+    "  AAAAA  ",
+    "   MOF   ",
 ];
 
 const LAYER_2: &[&str] = &[
     "         ",
     "  BBBBB  ",
-    "     Y   ",
+    "  T LY   ",
     "         ",
     "         ",
 ];
@@ -148,28 +185,679 @@ pub fn get_layers() -> &'static [&'static [&'static str]] {
     LAYERS
 }
 
-pub fn create_cubes_from_layers(layers: &[&[&str]]) -> Vec<Cube> {
-    let mut cubes = Vec::new();
-    
-    for (layer_idx, layer) in layers.iter().enumerate() {
-        for (y, line) in layer.iter().enumerate() {
-            if y >= GRID_SIZE_Y {
-                break;
+// Letra de cada celda de una capa, completada con huecos hasta width x depth (' ' = vacío)
+fn layer_grid(layer: &[&str], width: usize, depth: usize) -> Vec<Vec<char>> {
+    let mut grid = vec![vec![' '; width]; depth];
+    for (y, line) in layer.iter().take(depth).enumerate() {
+        for (x, ch) in line.chars().take(width).enumerate() {
+            grid[y][x] = ch;
+        }
+    }
+    grid
+}
+
+// Celda de la grilla: (x, fila, capa)
+pub type GridCell = (usize, usize, usize);
+
+// Letras de todas las capas, editables en tiempo real; to_cubes arma los bloques de nuevo.
+// La grilla mide width x depth celdas y se centra en el origen.
+// `spacing` es la distancia entre centros de celdas vecinas y `cube_size` el lado de cada bloque:
+// iguales dan vóxeles pegados, spacing > cube_size deja huecos (botones sueltos) y
+// spacing < cube_size hace que los bloques se encimen
+pub struct BlockGrid {
+    layers: Vec<Vec<Vec<char>>>,
+    width: usize,
+    depth: usize,
+    cube_size: f32,
+    spacing: f32,
+}
+
+impl BlockGrid {
+    pub fn empty(width: usize, depth: usize) -> Self {
+        BlockGrid {
+            layers: Vec::new(),
+            width: width.max(1),
+            depth: depth.max(1),
+            cube_size: CUBE_SIZE,
+            spacing: CUBE_SPACING,
+        }
+    }
+
+    // El ancho sale de la fila más larga y el fondo de la capa con más filas
+    pub fn from_layers(layers: &[&[&str]]) -> Self {
+        let width = layers.iter().flat_map(|layer| layer.iter()).map(|line| line.chars().count()).max().unwrap_or(0);
+        let depth = layers.iter().map(|layer| layer.len()).max().unwrap_or(0);
+        let mut grid = BlockGrid::empty(width, depth);
+        grid.layers = layers.iter().map(|layer| layer_grid(layer, grid.width, grid.depth)).collect();
+        grid
+    }
+
+    pub fn with_block_size(mut self, cube_size: f32, spacing: f32) -> Self {
+        self.cube_size = cube_size;
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn cube_size(&self) -> f32 {
+        self.cube_size
+    }
+
+    // Medidas de la grilla en el mundo (x, z)
+    pub fn footprint(&self) -> (f32, f32) {
+        (self.width as f32 * self.spacing, self.depth as f32 * self.spacing)
+    }
+
+    // Centro del bloque de una celda; la grilla queda centrada en X y Z
+    pub fn cell_center(&self, grid_x: usize, grid_y: usize, layer: usize) -> Vector3 {
+        let offset_x = (self.width as f32 - 1.0) * self.spacing / 2.0;
+        let offset_z = (self.depth as f32 - 1.0) * self.spacing / 2.0;
+        let x = grid_x as f32 * self.spacing - offset_x;
+        let y = layer as f32 * self.spacing;
+        let z = grid_y as f32 * self.spacing - offset_z;
+        Vector3::new(x, y, z)
+    }
+
+    fn create_cube_from_letter(&self, letter: char, grid_x: usize, grid_y: usize, layer: usize) -> Option<Cube> {
+        get_material_from_letter(letter)
+            .map(|material| Cube::new(self.cell_center(grid_x, grid_y, layer), self.cube_size, material))
+    }
+
+    pub fn to_cubes(&self) -> Vec<Cube> {
+        self.create_merged_cubes()
+    }
+
+    // Igual que create_cubes, pero une los bloques vecinos de la misma letra en
+    // cajas más grandes (mallado voraz por capa: primero a lo largo de X, luego de Z).
+    // Las caras internas desaparecen y la textura se repite por bloque, así que la imagen no cambia
+    fn create_merged_cubes(&self) -> Vec<Cube> {
+        // Con separación entre bloques una caja fusionada taparía los huecos
+        if (self.spacing - self.cube_size).abs() > SPACING_TOLERANCE * self.cube_size {
+            return self.create_cubes();
+        }
+
+        let mut cubes = Vec::new();
+
+        for (layer_idx, grid) in self.layers.iter().enumerate() {
+            let mut used = vec![vec![false; self.width]; self.depth];
+
+            for y in 0..self.depth {
+                for x in 0..self.width {
+                    if used[y][x] {
+                        continue;
+                    }
+                    let letter = grid[y][x];
+                    let Some(material) = get_material_from_letter(letter) else {
+                        continue;
+                    };
+
+                    if !is_mergeable(&material) {
+                        cubes.push(Cube::new(self.cell_center(x, y, layer_idx), self.cube_size, material));
+                        continue;
+                    }
+
+                    let fits = |cx: usize, cy: usize| grid[cy][cx] == letter && !used[cy][cx];
+
+                    let mut width = 1;
+                    while x + width < self.width && fits(x + width, y) {
+                        width += 1;
+                    }
+                    let mut depth = 1;
+                    while y + depth < self.depth && (x..x + width).all(|cx| fits(cx, y + depth)) {
+                        depth += 1;
+                    }
+
+                    for row in used.iter_mut().skip(y).take(depth) {
+                        for cell in row.iter_mut().skip(x).take(width) {
+                            *cell = true;
+                        }
+                    }
+
+                    // La caja va del borde exterior del primer bloque al del último
+                    let first = self.cell_center(x, y, layer_idx);
+                    let last = self.cell_center(x + width - 1, y + depth - 1, layer_idx);
+                    cubes.push(Cube::with_size(
+                        (first + last) / 2.0,
+                        Vector3::new(last.x - first.x + self.cube_size, self.cube_size, last.z - first.z + self.cube_size),
+                        self.cube_size,
+                        material,
+                    ));
+                }
             }
-            
-            let chars: Vec<char> = line.chars().collect();
-            for (x, &ch) in chars.iter().enumerate() {
-                if x >= GRID_SIZE_X {
-                    break;
+        }
+
+        cubes
+    }
+
+    fn create_cubes(&self) -> Vec<Cube> {
+        let mut cubes = Vec::new();
+
+        for (layer_idx, grid) in self.layers.iter().enumerate() {
+            for (y, row) in grid.iter().enumerate() {
+                for (x, &ch) in row.iter().enumerate() {
+                    if let Some(cube) = self.create_cube_from_letter(ch, x, y, layer_idx) {
+                        cubes.push(cube);
+                    }
                 }
-                
-                if let Some(cube) = create_cube_from_letter(ch, x, y, layer_idx) {
-                    cubes.push(cube);
+            }
+        }
+
+        cubes
+    }
+
+    // Los bloques como formas de distancia: una por letra, con sus bloques de aristas
+    // redondeadas (`rounding`) fundidos entre sí en una franja de ancho `blend`.
+    // Los emisivos se vuelven esferas brillantes
+    pub fn to_sdf_objects(&self, rounding: f32, blend: f32) -> Vec<SdfObject> {
+        let half = self.cube_size / 2.0;
+        let mut groups: BTreeMap<char, Vec<Box<dyn Sdf>>> = BTreeMap::new();
+        let mut materials = BTreeMap::new();
+
+        for (layer_idx, grid) in self.layers.iter().enumerate() {
+            for (y, row) in grid.iter().enumerate() {
+                for (x, &letter) in row.iter().enumerate() {
+                    let Some(material) = get_material_from_letter(letter) else {
+                        continue;
+                    };
+                    let center = self.cell_center(x, y, layer_idx);
+                    let shape: Box<dyn Sdf> = if material.is_emissive {
+                        Box::new(SdfSphere { center, radius: half })
+                    } else {
+                        Box::new(SdfBox { center, half_size: Vector3::new(half, half, half), rounding })
+                    };
+                    groups.entry(letter).or_default().push(shape);
+                    materials.entry(letter).or_insert(material);
                 }
             }
         }
+
+        groups
+            .into_iter()
+            .map(|(letter, shapes)| SdfObject::new(Box::new(smooth_union(shapes, blend)), materials[&letter].clone(), self.cube_size))
+            .collect()
+    }
+
+    // Celda que contiene `point` (en el marco del diorama); None si cae fuera de la grilla.
+    // La capa puede estar por encima de la última: `set` la crea
+    pub fn cell_at(&self, point: Vector3) -> Option<GridCell> {
+        let origin = self.cell_center(0, 0, 0);
+        let x = ((point.x - origin.x) / self.spacing).round();
+        let y = ((point.z - origin.z) / self.spacing).round();
+        let layer = ((point.y - origin.y) / self.spacing).round();
+        if x < 0.0 || y < 0.0 || layer < 0.0 || x >= self.width as f32 || y >= self.depth as f32 {
+            return None;
+        }
+        Some((x as usize, y as usize, layer as usize))
+    }
+
+    // Celda vecina en la dirección de una normal de cara
+    pub fn neighbor(&self, (x, y, layer): GridCell, normal: Vector3) -> Option<GridCell> {
+        self.cell_at(self.cell_center(x, y, layer) + normal * self.spacing)
+    }
+
+    pub fn letter(&self, (x, y, layer): GridCell) -> char {
+        self.layers.get(layer).map_or(' ', |grid| grid[y][x])
+    }
+
+    // Pone una letra en la celda (' ' la vacía), agregando capas vacías si hace falta
+    pub fn set(&mut self, (x, y, layer): GridCell, letter: char) {
+        while self.layers.len() <= layer {
+            self.layers.push(vec![vec![' '; self.width]; self.depth]);
+        }
+        self.layers[layer][y][x] = letter;
+    }
+}
+
+// Vista, hora del sol y título de ventana con que arranca una escena de archivo; lo que falte
+// queda como en la escena incorporada
+#[derive(Clone, Default)]
+pub struct SceneDefaults {
+    pub camera: Option<(Vector3, Vector3)>, // Posición y punto mirado
+    pub sun_angle: Option<f32>,             // Radianes, 0 = mediodía
+    pub title: Option<String>,
+    pub block_size: Option<(f32, f32)>,     // Lado de los bloques y separación entre centros
+}
+
+// Escena cargada de un archivo de capas
+pub struct SceneFile {
+    pub path: String,
+    pub grid: BlockGrid,
+    pub defaults: SceneDefaults,
+}
+
+// Carga un archivo de capas con el mismo formato que LAYER_0..3: una fila por línea,
+// una letra por bloque (cualquier otro carácter deja el hueco vacío) y `---` entre capas.
+// Las líneas que empiezan con @ fijan los valores iniciales de la escena:
+// `@camera x y z tx ty tz` (posición y punto mirado), `@sun grados` (0 = mediodía),
+// `@title texto` (título de la ventana) y `@size lado separación` (medidas de los bloques)
+pub fn load_scene_file(path: &str) -> Result<SceneFile, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|error| format!("No se pudo leer {}: {}", path, error))?;
+
+    let mut defaults = SceneDefaults::default();
+    let mut layers: Vec<Vec<&str>> = vec![Vec::new()];
+    for (number, line) in content.lines().enumerate() {
+        if let Some(directive) = line.trim().strip_prefix('@') {
+            parse_directive(directive, &mut defaults)
+                .map_err(|error| format!("{}:{}: {}", path, number + 1, error))?;
+        } else if line.trim() == "---" {
+            layers.push(Vec::new());
+        } else if let Some(layer) = layers.last_mut() {
+            layer.push(line);
+        }
+    }
+
+    let layers: Vec<&[&str]> = layers.iter().map(|layer| layer.as_slice()).collect();
+    let mut grid = BlockGrid::from_layers(&layers);
+    if let Some((cube_size, spacing)) = defaults.block_size {
+        grid = grid.with_block_size(cube_size, spacing);
+    }
+    Ok(SceneFile { path: path.to_string(), grid, defaults })
+}
+
+fn parse_directive(directive: &str, defaults: &mut SceneDefaults) -> Result<(), String> {
+    let mut words = directive.split_whitespace();
+    let name = words.next().unwrap_or("");
+    if name == "title" {
+        let title = directive.trim_start()[name.len()..].trim();
+        if title.is_empty() {
+            return Err("@title espera un texto".to_string());
+        }
+        defaults.title = Some(title.to_string());
+        return Ok(());
+    }
+    let numbers = words.map(str::parse::<f32>).collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("valores inválidos en @{}", name))?;
+    match (name, numbers.as_slice()) {
+        ("camera", &[x, y, z, tx, ty, tz]) => defaults.camera = Some((Vector3::new(x, y, z), Vector3::new(tx, ty, tz))),
+        ("sun", &[degrees]) => defaults.sun_angle = Some(degrees.to_radians()),
+        ("size", &[cube_size, spacing]) if cube_size > 0.0 && spacing > 0.0 => defaults.block_size = Some((cube_size, spacing)),
+        ("camera", _) => return Err("@camera espera x y z tx ty tz".to_string()),
+        ("sun", _) => return Err("@sun espera un ángulo en grados".to_string()),
+        ("size", _) => return Err("@size espera el lado y la separación, mayores que cero".to_string()),
+        _ => return Err(format!("directiva desconocida: @{}", name)),
+    }
+    Ok(())
+}
+
+// Capas de terreno que puede alcanzar el blanco de un mapa de alturas
+pub const HEIGHTMAP_LAYERS: usize = 6;
+
+// Letra de un bloque de terreno según la franja de altura de su capa:
+// piedra abajo, tierra en el medio y pasto arriba
+fn terrain_letter(layer: usize) -> char {
+    let band = layer as f32 / HEIGHTMAP_LAYERS as f32;
+    if band < 0.4 {
+        'S'
+    } else if band < 0.75 {
+        'D'
+    } else {
+        'H'
+    }
+}
+
+// Lado máximo (en celdas) del terreno generado; las imágenes más grandes se reducen
+pub const HEIGHTMAP_MAX_CELLS: usize = 32;
+
+// Genera un paisaje desde una imagen en escala de grises: cada celda de la grilla toma el
+// píxel más cercano y apila bloques hasta su brillo (negro = vacío, blanco = HEIGHTMAP_LAYERS)
+pub fn load_heightmap(path: &str) -> Result<BlockGrid, String> {
+    let image = Image::load_image(path)
+        .map_err(|error| format!("No se pudo leer {}: {}", path, error))?;
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if width == 0 || height == 0 {
+        return Err(format!("El mapa de alturas {} está vacío", path));
+    }
+    let colors = image.get_image_data();
+
+    let (cells_x, cells_z) = (width.min(HEIGHTMAP_MAX_CELLS), height.min(HEIGHTMAP_MAX_CELLS));
+    let mut grid = BlockGrid::empty(cells_x, cells_z);
+    for y in 0..cells_z {
+        for x in 0..cells_x {
+            let color = colors[(y * height / cells_z) * width + x * width / cells_x];
+            let luminance = (0.2126 * color.r as f32 + 0.7152 * color.g as f32 + 0.0722 * color.b as f32) / 255.0;
+            let column_height = (luminance * HEIGHTMAP_LAYERS as f32).round() as usize;
+            for layer in 0..column_height {
+                grid.set((x, y, layer), terrain_letter(layer));
+            }
+        }
+    }
+
+    Ok(grid)
+}
+
+// Escribe la grilla en el formato de load_scene_file (sin las capas vacías de arriba), con los
+// valores iniciales y las medidas de los bloques delante. Las filas se completan con espacios
+// hasta el ancho de la grilla, así guardar y volver a cargar da la misma escena
+pub fn save_grid_to_file(grid: &BlockGrid, defaults: &SceneDefaults, path: &str) -> Result<(), String> {
+    let used_layers = grid.layers.iter()
+        .rposition(|layer| layer.iter().flatten().any(|&letter| letter != ' '))
+        .map_or(0, |last| last + 1);
+    let empty_layer = vec![vec![' '; grid.width]; grid.depth];
+
+    let mut header = String::new();
+    if let Some((position, target)) = defaults.camera {
+        header += &format!("@camera {} {} {} {} {} {}\n", position.x, position.y, position.z, target.x, target.y, target.z);
+    }
+    if let Some(angle) = defaults.sun_angle {
+        header += &format!("@sun {}\n", angle.to_degrees());
+    }
+    if let Some(title) = &defaults.title {
+        header += &format!("@title {}\n", title);
     }
-    
-    cubes
+    header += &format!("@size {} {}\n", grid.cube_size, grid.spacing);
+
+    // Sin bloques igual se escribe una capa vacía, que es la que guarda el ancho y el fondo
+    let layers: Vec<String> = (0..used_layers.max(1))
+        .map(|layer| {
+            grid.layers.get(layer).unwrap_or(&empty_layer).iter()
+                .map(|row| row.iter().collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect();
+
+    std::fs::write(path, header + &layers.join("\n---\n") + "\n")
+        .map_err(|error| format!("No se pudo escribir {}: {}", path, error))
+}
+
+// Piso pulido justo bajo el diorama, para que los bloques se reflejen en él;
+// `wet` le da algo de rugosidad al reflejo, como un piso mojado
+pub fn create_ground_plane(grid: &BlockGrid, wet: bool) -> Plane {
+    let material = Material::builder()
+        .diffuse(Vector3::new(0.2, 0.2, 0.22))
+        .specular(80.0)
+        .albedo([0.6, 0.2, 0.35, 0.0])
+        .roughness(if wet { 0.08 } else { 0.0 })
+        .build();
+    Plane::new(Vector3::new(0.0, -grid.cube_size / 2.0, 0.0), Vector3::new(0.0, 1.0, 0.0), grid.cube_size, material)
+}
+
+// Adornos que no son bloques, pensados para la escena incorporada (LAYERS)
+pub fn create_decorations(grid: &BlockGrid) -> Vec<SceneObject> {
+    let mut decorations: Vec<SceneObject> = vec![
+        Box::new(create_roof(grid)),
+        Box::new(create_float_ring(grid)),
+        Box::new(create_ceiling_panel(grid)),
+    ];
+    decorations.extend(create_crystal_ring(grid));
+    decorations.extend(create_carved_blocks(grid));
+    decorations.extend(create_stool(grid));
+    decorations
+}
+
+// Dos bloques tallados con CSG en las esquinas delanteras: un marco de ventana (bloque menos
+// caja) y un bloque perforado (bloque menos cilindro). Lo que se resta sobresale del bloque
+// para que no queden caras coincidentes
+fn create_carved_blocks(grid: &BlockGrid) -> Vec<SceneObject> {
+    let frame_center = grid.cell_center(0, 4, 1);
+    let frame = Cube::new(frame_center, grid.cube_size, Material::builder()
+        .diffuse(Vector3::new(0.9, 0.9, 0.9))
+        .specular(40.0)
+        .albedo([0.9, 0.1, 0.0, 0.0])
+        .texture("assets/wood_planks.png")
+        .build());
+    let window = Cube::with_size(frame_center, Vector3::new(0.6, 0.6, 1.2) * grid.cube_size, grid.cube_size, Material::black());
+
+    let pipe_center = grid.cell_center(8, 4, 1);
+    let pipe = Cube::new(pipe_center, grid.cube_size, Material::builder()
+        .diffuse(Vector3::new(0.5, 0.5, 0.52))
+        .specular(30.0)
+        .albedo([0.85, 0.15, 0.0, 0.0])
+        .build());
+    let bore = Cylinder::new(pipe_center, Vector3::new(0.0, 0.0, 1.0), 0.3 * grid.cube_size, 1.2 * grid.cube_size, Material::black());
+
+    vec![
+        Box::new(Difference::new(Box::new(frame), Box::new(window))),
+        Box::new(Difference::new(Box::new(pipe), Box::new(bore))),
+    ]
+}
+
+// Banquito junto al diorama: asiento y cuatro patas del mismo cubo, agrupados en un nodo que se
+// gira y se coloca como una sola pieza
+fn create_stool(grid: &BlockGrid) -> Vec<SceneObject> {
+    let cube: SharedGeometry = Arc::new(Cube::new(Vector3::zero(), 1.0, table_material()));
+    let (leg_height, seat_thickness) = (0.35, 0.05);
+
+    let mut stool = SceneNode::new(Matrix::rotate_y(PI / 6.0) * Matrix::translate(-(grid.footprint().0 / 2.0 + 1.5), -grid.cube_size / 2.0, 0.0))
+        .with_geometry(cube.clone(), Matrix::scale(0.5, seat_thickness, 0.5) * Matrix::translate(0.0, leg_height + seat_thickness / 2.0, 0.0));
+    for (x, z) in [(-0.2, -0.2), (0.2, -0.2), (-0.2, 0.2), (0.2, 0.2)] {
+        stool = stool.with_geometry(cube.clone(), Matrix::scale(0.06, leg_height, 0.06) * Matrix::translate(x, leg_height / 2.0, z));
+    }
+    stool.flatten(Matrix::identity())
+}
+
+// Doce cristales inclinados en círculo alrededor del diorama: instancias de un único cubo
+fn create_crystal_ring(grid: &BlockGrid) -> Vec<SceneObject> {
+    let crystal: SharedGeometry = Arc::new(Cube::new(Vector3::zero(), 1.0, Material::builder()
+        .diffuse(Vector3::new(0.35, 0.2, 0.6))
+        .specular(90.0)
+        .albedo([0.6, 0.3, 0.1, 0.0])
+        .texture("assets/obsidiana.png")
+        .build()));
+
+    let count = 12;
+    let radius = grid.footprint().0 / 2.0 + 0.75;
+    let tilt_axis = Vector3::new(1.0, 0.0, 1.0).normalized();
+    (0..count)
+        .map(|i| {
+            let angle = i as f32 / count as f32 * 2.0 * PI;
+            // Se aplica de izquierda a derecha: escalar, inclinar, girar hacia el centro y trasladar
+            let transform = Matrix::scale(0.2, 0.2, 0.2)
+                * Matrix::rotate(tilt_axis, PI / 4.0)
+                * Matrix::rotate_y(angle)
+                * Matrix::translate(radius * angle.cos(), 0.0, radius * angle.sin());
+            Box::new(Instance::new(crystal.clone(), transform, None)) as SceneObject
+        })
+        .collect()
 }
 
+// Salvavidas flotando sobre la pileta de agua de la capa 1
+fn create_float_ring(grid: &BlockGrid) -> Torus {
+    let minor_radius = 0.07;
+    let water_top = grid.cell_center(4, 3, 1) + Vector3::new(0.0, grid.cube_size / 2.0, 0.0);
+    let material = Material::builder()
+        .diffuse(Vector3::new(0.95, 0.35, 0.2))
+        .specular(40.0)
+        .albedo([0.85, 0.15, 0.0, 0.0])
+        .build();
+    Torus::new(
+        water_top + Vector3::new(0.3, minor_radius * 0.4, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        0.18,
+        minor_radius,
+        material,
+    )
+}
+
+// Panel de luz pegado bajo el alero del techo, mirando hacia abajo: una luz de área que
+// además se ve (y se refleja) como un rectángulo brillante
+fn create_ceiling_panel(grid: &BlockGrid) -> Quad {
+    let tower_top = grid.cell_center(4, 1, LAYERS.len() - 1) + Vector3::new(0.0, grid.cube_size / 2.0, 0.0);
+    let (width, depth) = (0.5 * grid.cube_size, 0.8 * grid.cube_size);
+    let material = Material::builder()
+        .diffuse(Vector3::new(1.0, 0.95, 0.85))
+        .albedo([1.0, 0.0, 0.0, 0.0])
+        .emissive(Vector3::new(1.0, 0.92, 0.8), 2.5)
+        .build();
+    // Lados en X y luego Z: la normal X × Z apunta hacia abajo
+    Quad::new(
+        tower_top + Vector3::new(0.8 * grid.cube_size, -0.01, -depth / 2.0),
+        Vector3::new(width, 0.0, 0.0),
+        Vector3::new(0.0, 0.0, depth),
+        material,
+    )
+}
+
+// Techo cónico sobre la torre de estantes de la capa superior de LAYERS
+fn create_roof(grid: &BlockGrid) -> Cone {
+    let tower_top = grid.cell_center(4, 1, LAYERS.len() - 1) + Vector3::new(0.0, grid.cube_size / 2.0, 0.0);
+    let height = 2.0 * grid.cube_size;
+    let base_radius = 1.7 * grid.cube_size;
+    let material = Material::builder()
+        .diffuse(Vector3::new(0.55, 0.12, 0.1))
+        .specular(35.0)
+        .albedo([0.85, 0.15, 0.0, 0.0])
+        .build();
+    Cone::new(
+        tower_top + Vector3::new(0.0, height, 0.0),
+        Vector3::new(0.0, -1.0, 0.0),
+        (base_radius / height).atan(),
+        height,
+        material,
+    )
+}
+
+// Lo que hay bajo el diorama; la tecla G recorre las opciones
+#[derive(Clone, Copy, PartialEq)]
+pub enum Ground {
+    Polished,
+    Wet,
+    RoundTable,
+    SquareTable,
+}
+
+impl Ground {
+    // Siguiente opción del ciclo, pasando por "sin piso" (None)
+    pub fn next(ground: Option<Ground>) -> Option<Ground> {
+        match ground {
+            None => Some(Ground::Polished),
+            Some(Ground::Polished) => Some(Ground::Wet),
+            Some(Ground::Wet) => Some(Ground::RoundTable),
+            Some(Ground::RoundTable) => Some(Ground::SquareTable),
+            Some(Ground::SquareTable) => None,
+        }
+    }
+}
+
+fn table_material() -> Material {
+    Material::builder()
+        .diffuse(Vector3::new(0.45, 0.28, 0.15))
+        .specular(60.0)
+        .albedo([0.8, 0.2, 0.15, 0.0])
+        .build()
+}
+
+// Mesa redonda bajo el diorama: un disco un poco más ancho que la grilla
+pub fn create_round_table(grid: &BlockGrid) -> Disk {
+    let (width, depth) = grid.footprint();
+    let radius = width.max(depth) / 2.0 + grid.cube_size;
+    Disk::new(Vector3::new(0.0, -grid.cube_size / 2.0, 0.0), Vector3::new(0.0, 1.0, 0.0), radius, table_material())
+}
+
+// Mesa cuadrada: un quad que sobresale un bloque por cada lado de la grilla
+pub fn create_square_table(grid: &BlockGrid) -> Quad {
+    let (width, depth) = grid.footprint();
+    let (width, depth) = (width + 2.0 * grid.cube_size, depth + 2.0 * grid.cube_size);
+    Quad::new(
+        Vector3::new(-width / 2.0, -grid.cube_size / 2.0, -depth / 2.0),
+        Vector3::new(0.0, 0.0, depth),
+        Vector3::new(width, 0.0, 0.0),
+        table_material(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_intersect::RayIntersect;
+    use raylib::prelude::Color;
+
+    #[test]
+    fn heightmap_stacks_columns_by_brightness() {
+        // Negro = vacío, blanco = HEIGHTMAP_LAYERS bloques, gris medio = la mitad
+        let mut image = Image::gen_image_color(3, 1, Color::BLACK);
+        image.draw_pixel(1, 0, Color::WHITE);
+        image.draw_pixel(2, 0, Color::new(128, 128, 128, 255));
+        let path = std::env::temp_dir().join("blocks_heightmap_test.png");
+        image.export_image(path.to_str().unwrap());
+
+        let grid = load_heightmap(path.to_str().unwrap()).unwrap();
+        assert_eq!(grid.create_cubes().len(), HEIGHTMAP_LAYERS + HEIGHTMAP_LAYERS / 2);
+        assert_eq!(grid.letter((0, 0, 0)), ' ');
+        assert_eq!(grid.letter((1, 0, HEIGHTMAP_LAYERS - 1)), 'H');
+        assert_eq!(grid.letter((1, 0, HEIGHTMAP_LAYERS / 2)), 'D');
+        assert_eq!(grid.letter((1, 0, 0)), 'S');
+        assert_eq!(grid.letter((2, 0, HEIGHTMAP_LAYERS / 2 - 1)), 'S');
+        assert_eq!(grid.letter((2, 0, HEIGHTMAP_LAYERS / 2)), ' ');
+    }
+
+    #[test]
+    fn saved_scene_loads_back_with_the_same_cubes() {
+        // Columnas y filas vacías a la derecha y al fondo, y medidas que no son las de siempre
+        let mut grid = BlockGrid::from_layers(&[&["W   ", "    ", "    "]]).with_block_size(0.3, 0.4);
+        grid.set((1, 0, 1), 'B');
+        let defaults = SceneDefaults { sun_angle: Some(0.5), ..SceneDefaults::default() };
+        let path = std::env::temp_dir().join(format!("blocks_{}_saved_scene.txt", std::process::id()));
+        save_grid_to_file(&grid, &defaults, path.to_str().unwrap()).unwrap();
+        let file = load_scene_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let file = file.unwrap();
+        assert!((file.defaults.sun_angle.unwrap() - 0.5).abs() < 1e-5);
+        let bounds = |grid: &BlockGrid| grid.create_cubes().iter().map(|cube| cube.bounds()).collect::<Vec<_>>();
+        assert_eq!(bounds(&file.grid), bounds(&grid));
+        assert_eq!(file.grid.footprint(), grid.footprint());
+    }
+
+    // Hueco a lo largo de X entre los bloques de dos celdas vecinas (negativo si se enciman)
+    fn gap_between_neighbors(cube_size: f32, spacing: f32) -> f32 {
+        let grid = BlockGrid::from_layers(&[&["SS"]]).with_block_size(cube_size, spacing);
+        let cubes = grid.create_cubes();
+        let (_, left_max) = cubes[0].bounds();
+        let (right_min, _) = cubes[1].bounds();
+        right_min.x - left_max.x
+    }
+
+    #[test]
+    fn equal_size_and_spacing_leaves_blocks_touching() {
+        assert!(gap_between_neighbors(CUBE_SIZE, CUBE_SPACING).abs() < 1e-6);
+        assert!(gap_between_neighbors(0.3, 0.3).abs() < 1e-6);
+        assert!((gap_between_neighbors(0.3, 0.5) - 0.2).abs() < 1e-6);
+        assert!(gap_between_neighbors(0.5, 0.3) < 0.0);
+    }
+
+    #[test]
+    fn merged_blocks_render_like_separate_ones() {
+        use crate::accumulation::AccumulationBuffer;
+        use crate::camera::Camera;
+        use crate::framebuffer::Framebuffer;
+        use crate::scene::{Scene, SceneObject};
+        use crate::settings::RenderSettings;
+        use crate::textures::TextureManager;
+        use crate::{daynight, render, update_culling};
+
+        // Medidas calculadas que no quedan exactamente iguales igual se fusionan
+        let nearly = BlockGrid::from_layers(get_layers()).with_block_size(0.7 - 0.4, 0.3);
+        assert!(nearly.create_merged_cubes().len() < nearly.create_cubes().len() / 2);
+
+        let grid = BlockGrid::from_layers(get_layers());
+        let (separate, merged) = (grid.create_cubes(), grid.create_merged_cubes());
+        assert!(merged.len() < separate.len() / 2, "{} de {}", merged.len(), separate.len());
+
+        let (width, height) = (64, 48);
+        let settings = RenderSettings::default();
+        let camera = Camera::new(Vector3::new(2.5, 3.0, 5.0), Vector3::new(0.0, 0.5, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        let render_cubes = |cubes: Vec<Cube>| {
+            let objects = cubes.into_iter().map(|cube| Box::new(cube) as SceneObject).collect();
+            let mut lights = daynight::create_lights(8.0);
+            daynight::update_day_night(&mut lights, 0.4, 8.0, &daynight::sun_ramp());
+            let mut scene = Scene::new(objects, lights, TextureManager::new());
+            update_culling(&mut scene, &camera, &settings, width as f32 / height as f32);
+            let mut framebuffer = Framebuffer::new(width, height);
+            render(&mut framebuffer, &scene, &camera, &settings, &mut AccumulationBuffer::new(width, height), None);
+            framebuffer
+        };
+        let (mut separate, mut merged) = (render_cubes(separate), render_cubes(merged));
+
+        let mut differing = 0;
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let (a, b) = (separate.color_buffer.get_color(x, y), merged.color_buffer.get_color(x, y));
+                if a.r.abs_diff(b.r).max(a.g.abs_diff(b.g)).max(a.b.abs_diff(b.b)) > 2 {
+                    differing += 1;
+                }
+            }
+        }
+        assert_eq!(differing, 0);
+    }
+}
@@ -0,0 +1,133 @@
+use raylib::prelude::Vector3;
+use std::fs;
+use std::io;
+use crate::camera::Camera;
+
+pub const SLOT_COUNT: usize = 9;
+
+// Una vista guardada de la cámara
+#[derive(Clone, Copy)]
+pub struct Bookmark {
+    pub eye: Vector3,
+    pub center: Vector3,
+    pub up: Vector3,
+}
+
+impl Bookmark {
+    pub fn to_camera(self) -> Camera {
+        Camera::new(self.eye, self.center, self.up)
+    }
+}
+
+// Vistas guardadas en casillas numeradas, persistidas en un archivo de texto:
+// una línea por casilla ocupada con "casilla eye.x eye.y eye.z center.x ... up.z"
+pub struct CameraBookmarks {
+    path: String,
+    slots: [Option<Bookmark>; SLOT_COUNT],
+}
+
+impl CameraBookmarks {
+    // Si el archivo no existe o tiene líneas inválidas, esas casillas quedan vacías
+    pub fn load(path: &str) -> Self {
+        let mut bookmarks = CameraBookmarks {
+            path: path.to_string(),
+            slots: [None; SLOT_COUNT],
+        };
+
+        let Ok(content) = fs::read_to_string(path) else {
+            return bookmarks;
+        };
+        for line in content.lines() {
+            let values: Vec<f32> = line.split_whitespace().filter_map(|value| value.parse().ok()).collect();
+            if values.len() != 10 {
+                continue;
+            }
+            let slot = values[0] as usize;
+            if slot < SLOT_COUNT {
+                bookmarks.slots[slot] = Some(Bookmark {
+                    eye: Vector3::new(values[1], values[2], values[3]),
+                    center: Vector3::new(values[4], values[5], values[6]),
+                    up: Vector3::new(values[7], values[8], values[9]),
+                });
+            }
+        }
+        bookmarks
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut content = String::new();
+        for (slot, bookmark) in self.slots.iter().enumerate() {
+            if let Some(b) = bookmark {
+                content += &format!(
+                    "{} {} {} {} {} {} {} {} {} {}\n",
+                    slot, b.eye.x, b.eye.y, b.eye.z, b.center.x, b.center.y, b.center.z, b.up.x, b.up.y, b.up.z
+                );
+            }
+        }
+        fs::write(&self.path, content)
+    }
+
+    // Devuelve false (sin guardar nada) si la casilla no existe
+    pub fn store(&mut self, slot: usize, camera: &Camera) -> bool {
+        let Some(entry) = self.slots.get_mut(slot) else {
+            return false;
+        };
+        *entry = Some(Bookmark {
+            eye: camera.eye,
+            center: camera.center,
+            up: camera.up,
+        });
+        true
+    }
+
+    pub fn get(&self, slot: usize) -> Option<Bookmark> {
+        self.slots.get(slot).copied().flatten()
+    }
+}
+
+// Transición entre dos vistas a lo largo de `frames` cuadros, con aceleración y frenado suaves
+pub struct CameraGlide {
+    from: Camera,
+    to: Camera,
+    frame: u32,
+    frames: u32,
+}
+
+impl CameraGlide {
+    pub fn new(from: Camera, to: Camera, frames: u32) -> Self {
+        CameraGlide { from, to, frame: 0, frames: frames.max(1) }
+    }
+
+    pub fn from_bookmarks(from: &Bookmark, to: &Bookmark, frames: u32) -> Self {
+        CameraGlide::new(from.to_camera(), to.to_camera(), frames)
+    }
+
+    // Cámara del cuadro actual; avanza al siguiente
+    pub fn step(&mut self) -> Camera {
+        let t = self.frame as f32 / self.frames as f32;
+        self.frame = (self.frame + 1).min(self.frames + 1);
+        let eased = t * t * (3.0 - 2.0 * t);
+        self.from.lerp_to(&self.to, eased)
+    }
+
+    // Ya se entregó el último cuadro (la vista de destino)
+    pub fn is_done(&self) -> bool {
+        self.frame > self.frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_past_the_last_are_ignored() {
+        let mut bookmarks = CameraBookmarks::load("no_existe_bookmarks.txt");
+        let camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+        assert!(!bookmarks.store(SLOT_COUNT, &camera));
+        assert!(bookmarks.get(SLOT_COUNT).is_none());
+
+        assert!(bookmarks.store(SLOT_COUNT - 1, &camera));
+        assert!(bookmarks.get(SLOT_COUNT - 1).is_some_and(|bookmark| bookmark.eye == camera.eye));
+    }
+}
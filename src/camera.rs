@@ -1,6 +1,11 @@
 use raylib::prelude::*;
+use std::f32::consts::PI;
+use crate::aabb::Aabb;
+use crate::rotate_around_axis;
+use crate::scene::Scene;
 
 /// A 3D camera that maintains its position and orientation in world space
+#[derive(Clone)]
 pub struct Camera {
     pub eye: Vector3,     // Camera position in world coordinates
     pub center: Vector3,  // Point the camera is looking at
@@ -35,8 +40,15 @@ impl Camera {
         
         // Step 2: Calculate right direction using cross product
         // forward × up gives us a vector perpendicular to both (pointing right)
-        // This assumes up is roughly correct but may not be perfectly orthogonal
-        self.right = self.forward.cross(self.up).normalized();
+        // This assumes up is roughly correct but may not be perfectly orthogonal.
+        // Looking straight along up (or with a zero up) the product vanishes, so the
+        // world axis least aligned with forward stands in as the reference instead
+        let mut right = self.forward.cross(self.up);
+        if right.length() < 1e-4 {
+            let reference = if self.forward.z.abs() < 0.9 { Vector3::new(0.0, 0.0, -1.0) } else { Vector3::new(1.0, 0.0, 0.0) };
+            right = self.forward.cross(reference);
+        }
+        self.right = right.normalized();
         
         // Step 3: Recalculate up to ensure perfect orthogonality
         // right × forward gives us a vector perpendicular to both
@@ -86,12 +98,88 @@ impl Camera {
         self.update_basis_vectors();
     }
 
+    /// Tilts the camera by rotating `up` around the forward axis (positive leans the top of the view right).
+    /// Orbiting afterwards keeps the tilt, since the basis is rebuilt from the rolled `up`
+    pub fn roll(&mut self, angle: f32) {
+        let (sin, cos) = angle.sin_cos();
+        // Rodrigues' rotation of up around the unit forward axis
+        self.up = self.up * cos
+            + self.forward.cross(self.up) * sin
+            + self.forward * (self.forward.dot(self.up) * (1.0 - cos));
+        self.update_basis_vectors();
+    }
+
     pub fn zoom(&mut self, amount: f32) {
         let forward = (self.center - self.eye).normalized();
         self.eye += forward * amount;
         self.update_basis_vectors();
     }
 
+    /// Teleports the camera to a new view (e.g. a saved bookmark or an animation key) and rebuilds the basis
+    pub fn set_position(&mut self, eye: Vector3, center: Vector3, up: Vector3) {
+        self.eye = eye;
+        self.center = center;
+        self.up = up;
+        self.update_basis_vectors();
+    }
+
+    /// Aims at the center of the objects' bounding box and backs the eye off along the current view
+    /// direction until the box's bounding sphere fits the narrower of the two fields of view.
+    /// The center follows the diorama's rotation, since the objects are stored unrotated.
+    /// Unbounded objects (the ground plane) are left out; with nothing finite the camera is unchanged
+    pub fn frame_scene(&mut self, scene: &Scene, vertical_fov: f32, aspect_ratio: f32) {
+        let bounds = scene.objects().iter()
+            .map(|object| object.bounding_box())
+            .filter(Aabb::is_finite)
+            .fold(Aabb::empty(), |bounds, object| bounds.union(&object));
+        if !bounds.is_finite() {
+            return;
+        }
+
+        let center = rotate_around_axis(bounds.centroid(), scene.rotation_axis, scene.rotation);
+        let radius = ((bounds.max - bounds.min).length() / 2.0).max(1e-3);
+        let half_vertical = vertical_fov / 2.0;
+        let half_horizontal = (half_vertical.tan() * aspect_ratio).atan();
+        let distance = radius / half_vertical.min(half_horizontal).sin();
+
+        let forward = (self.center - self.eye).normalized();
+        let forward = if forward.length() > 0.0 { forward } else { Vector3::new(0.0, 0.0, -1.0) };
+        self.center = center;
+        self.eye = center - forward * distance;
+        self.update_basis_vectors();
+    }
+
+    /// Returns the view a fraction `t` of the way from this camera to `target`.
+    /// The orbit around the center is interpolated by angle (taking the short way around)
+    /// and distance, so the eye sweeps along an arc instead of cutting through the scene
+    pub fn lerp_to(&self, target: &Camera, t: f32) -> Camera {
+        let orbit = |camera: &Camera| {
+            let relative_pos = camera.eye - camera.center;
+            let radius = relative_pos.length();
+            let yaw = relative_pos.z.atan2(relative_pos.x);
+            let pitch = (relative_pos.y / radius).asin();
+            (yaw, pitch, radius)
+        };
+        let (yaw_a, pitch_a, radius_a) = orbit(self);
+        let (yaw_b, pitch_b, radius_b) = orbit(target);
+
+        // Wrap the yaw difference into [-PI, PI] so the camera never takes the long way
+        let yaw_delta = (yaw_b - yaw_a + PI).rem_euclid(2.0 * PI) - PI;
+        let yaw = yaw_a + yaw_delta * t;
+        let pitch = pitch_a + (pitch_b - pitch_a) * t;
+        let radius = radius_a + (radius_b - radius_a) * t;
+
+        let center = self.center + (target.center - self.center) * t;
+        let up = (self.up + (target.up - self.up) * t).normalized();
+        let eye = center + Vector3::new(
+            radius * pitch.cos() * yaw.cos(),
+            radius * pitch.sin(),
+            radius * pitch.cos() * yaw.sin(),
+        );
+
+        Camera::new(eye, center, up)
+    }
+
     pub fn is_changed(&mut self) -> bool {
         let changed = self.changed;
         self.changed = false;
@@ -128,3 +216,87 @@ impl Camera {
         // result will be -self.forward in world space
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_orthonormal(camera: &Camera) {
+        for axis in [camera.forward, camera.right, camera.up] {
+            assert!(!axis.x.is_nan() && (axis.length() - 1.0).abs() < 1e-4, "{axis:?}");
+        }
+        assert!(camera.forward.dot(camera.right).abs() < 1e-4);
+        assert!(camera.forward.dot(camera.up).abs() < 1e-4);
+        assert!(camera.right.dot(camera.up).abs() < 1e-4);
+    }
+
+    #[test]
+    fn looking_along_up_keeps_a_valid_basis() {
+        let world_up = Vector3::new(0.0, 1.0, 0.0);
+        // Justo arriba, justo abajo, casi vertical y con up nulo
+        for (eye, up) in [
+            (Vector3::new(0.0, 10.0, 0.0), world_up),
+            (Vector3::new(0.0, -10.0, 0.0), world_up),
+            (Vector3::new(1e-5, 10.0, 0.0), world_up),
+            (Vector3::new(0.0, 0.0, 5.0), Vector3::zero()),
+        ] {
+            let camera = Camera::new(eye, Vector3::zero(), up);
+            assert_orthonormal(&camera);
+            // El rayo del centro de la imagen sigue yendo hacia el punto mirado
+            let center_ray = camera.basis_change(&Vector3::new(0.0, 0.0, -1.0));
+            assert!((center_ray - (Vector3::zero() - eye).normalized()).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn custom_up_rolls_the_view() {
+        // Con +X como arriba, lo que está arriba en la imagen queda hacia +X del mundo
+        let camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(1.0, 0.0, 0.0));
+        assert_orthonormal(&camera);
+        assert!((camera.basis_change(&Vector3::new(0.0, 1.0, 0.0)) - Vector3::new(1.0, 0.0, 0.0)).length() < 1e-4);
+        assert!((camera.basis_change(&Vector3::new(1.0, 0.0, 0.0)) - Vector3::new(0.0, -1.0, 0.0)).length() < 1e-4);
+
+        // Un up inclinado se ortogonaliza contra la dirección de vista
+        let tilted = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(0.0, 1.0, 1.0));
+        assert_orthonormal(&tilted);
+        assert!((tilted.up - Vector3::new(0.0, 1.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn orbiting_over_the_top_never_degenerates() {
+        let mut camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+        for _ in 0..40 {
+            camera.orbit(0.1, 0.1);
+            assert_orthonormal(&camera);
+        }
+    }
+
+    #[test]
+    fn frame_scene_fits_a_known_box() {
+        use crate::cube::Cube;
+        use crate::material::Material;
+        use crate::scene::SceneObject;
+        use crate::textures::TextureManager;
+
+        // Un cubo de lado 2 centrado en (2, 0, 0): esfera de radio raíz de 3, que con 90° entra a raíz de 6
+        let objects: Vec<SceneObject> = vec![Box::new(Cube::new(Vector3::new(2.0, 0.0, 0.0), 2.0, Material::black()))];
+        let mut scene = Scene::new(objects, Vec::new(), TextureManager::new());
+        let look = |scene: &Scene| {
+            let mut camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+            camera.frame_scene(scene, PI / 2.0, 1.0);
+            camera
+        };
+
+        let camera = look(&scene);
+        assert!((camera.center - Vector3::new(2.0, 0.0, 0.0)).length() < 1e-4, "{:?}", camera.center);
+        assert!(((camera.eye - camera.center).length() - 6.0f32.sqrt()).abs() < 1e-4);
+        // Mantiene la dirección en que miraba
+        assert!((camera.forward - Vector3::new(0.0, 0.0, -1.0)).length() < 1e-4);
+
+        // Con el diorama girado un cuarto de vuelta el cubo queda sobre +Z
+        scene.rotation = PI / 2.0;
+        let camera = look(&scene);
+        assert!((camera.center - Vector3::new(0.0, 0.0, 2.0)).length() < 1e-4, "{:?}", camera.center);
+        assert!(((camera.eye - camera.center).length() - 6.0f32.sqrt()).abs() < 1e-4);
+    }
+}
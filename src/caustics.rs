@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use raylib::prelude::Vector3;
+use rand::{Rng, SeedableRng};
+use rand::rngs::SmallRng;
+use rayon::prelude::*;
+use crate::aabb::Aabb;
+use crate::light::Light;
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+use crate::{find_closest_hit, offset_origin, reflect, refract};
+
+// Un fotón que no llegó a una superficie difusa tras tantos rebotes se descarta
+const MAX_PHOTON_BOUNCES: u32 = 8;
+
+// Mapa de cáusticas: luz que atravesó objetos transparentes y cayó sobre superficies difusas.
+// Se guarda por celdas de una grilla 3D en el marco del diorama
+#[derive(Clone)]
+pub struct CausticMap {
+    cell_size: f32,
+    cells: HashMap<(i32, i32, i32), Vector3>,
+}
+
+impl CausticMap {
+    // Lanza settings.caustic_photons fotones desde la luz hacia los objetos transparentes (los
+    // únicos que pueden enfocar luz) y suma su energía en la celda donde terminan
+    pub fn build(scene: &Scene, light: &Light, settings: &RenderSettings) -> Self {
+        let (photon_count, cell_size) = (settings.caustic_photons, settings.caustic_cell_size);
+        let mut map = CausticMap { cell_size, cells: HashMap::new() };
+
+        let targets: Vec<Aabb> = scene.objects().iter()
+            .filter(|object| object.material().albedo[3] > 0.0 && object.material().visible)
+            .map(|object| object.bounding_box())
+            .filter(Aabb::is_finite)
+            .collect();
+        if targets.is_empty() || light.intensity <= 0.0 {
+            return map;
+        }
+
+        let photons_per_target = (photon_count / targets.len() as u32).max(1);
+        let light_color = light.color;
+        let cell_area = cell_size * cell_size;
+
+        let deposits: Vec<((i32, i32, i32), Vector3)> = targets.par_iter().enumerate()
+            .flat_map_iter(|(target_index, target_box)| {
+                let size = target_box.max - target_box.min;
+                let to_target = (target_box.centroid() - light.position).normalized();
+                // Área de la caja vista desde la luz: reparte entre los fotones la luz que la cruza
+                let projected_area = to_target.x.abs() * size.y * size.z
+                    + to_target.y.abs() * size.x * size.z
+                    + to_target.z.abs() * size.x * size.y;
+                let photon_power = light_color * (light.intensity * projected_area / photons_per_target as f32 / cell_area);
+
+                let mut rng = SmallRng::seed_from_u64(target_index as u64);
+                (0..photons_per_target).filter_map(move |_| {
+                    let target = target_box.min + size * Vector3::new(rng.random::<f32>(), rng.random::<f32>(), rng.random::<f32>());
+                    let direction = (target - light.position).normalized();
+                    trace_photon(scene, light.position, direction, photon_power, settings)
+                        .map(|(point, power)| (cell_of(point, cell_size), power))
+                })
+            })
+            .collect();
+
+        for (cell, power) in deposits {
+            *map.cells.entry(cell).or_insert(Vector3::zero()) += power;
+        }
+        map
+    }
+
+    // Irradiancia de cáusticas en un punto (color de luz ya incluido)
+    pub fn irradiance_at(&self, point: &Vector3) -> Vector3 {
+        self.cells.get(&cell_of(*point, self.cell_size)).copied().unwrap_or(Vector3::zero())
+    }
+}
+
+fn cell_of(point: Vector3, cell_size: f32) -> (i32, i32, i32) {
+    (
+        (point.x / cell_size).floor() as i32,
+        (point.y / cell_size).floor() as i32,
+        (point.z / cell_size).floor() as i32,
+    )
+}
+
+// Sigue el fotón a través de los objetos transparentes; solo cuenta si pasó por al menos uno
+// antes de caer en una superficie difusa. Cada objeto atravesado lo tiñe como en las sombras
+fn trace_photon(scene: &Scene, origin: Vector3, direction: Vector3, power: Vector3, settings: &RenderSettings) -> Option<(Vector3, Vector3)> {
+    let (mut origin, mut direction, mut power) = (origin, direction, power);
+    let mut through_glass = false;
+
+    for _ in 0..MAX_PHOTON_BOUNCES {
+        let hit = find_closest_hit(&origin, &direction, scene.objects().iter().enumerate(), &scene.texture_manager);
+        if !hit.is_intersecting {
+            return None;
+        }
+
+        let transparency = hit.effective_material().albedo[3];
+        if transparency <= 0.0 {
+            return through_glass.then_some((hit.point, power));
+        }
+
+        // El tinte se aplica al entrar, no en cada cara
+        if direction.dot(hit.normal) < 0.0 {
+            power = power * hit.effective_material().diffuse * transparency;
+        }
+        through_glass = true;
+
+        direction = refract(&direction, &hit.normal, hit.effective_material().refractive_index)
+            .unwrap_or_else(|| reflect(&direction, &hit.normal))
+            .normalized();
+        origin = offset_origin(&hit, &direction, settings);
+    }
+
+    None
+}
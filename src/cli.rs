@@ -0,0 +1,230 @@
+use computer_graphics_v3::settings::CropRect;
+use std::str::FromStr;
+
+pub const USAGE: &str = "Uso: raytracer [--width N] [--height N] [--samples N] [--spin R] [--shutter F] [--out archivo] [--out-exr archivo] [--scene archivo] [--heightmap imagen] [--cube-size L] [--spacing L] [--headless] [--glide A:B] [--frames N] [--compare imagen] [--tolerance N] [--flip-y] [--cubemap carpeta] [--crop X,Y,W,H] [--gltf archivo] [--linear] [--bvh-stats] [--replay archivo.json]
+
+  --width, --height  Resolución de la imagen (por defecto 1300x900)
+  --samples          Cuadros acumulados por píxel al renderizar sin ventana (por defecto 1)
+  --spin             Sin ventana: giro del diorama por cuadro en radianes mientras el obturador
+                     está abierto; los bloques se barren (desenfoque de movimiento)
+  --shutter          Fracción del cuadro con el obturador abierto, de 0 a 1 (por defecto 1 con
+                     --spin y 0 sin él, imagen estática)
+  --out              Archivo de salida: .exr (HDR lineal), .ppm o .png; en modo interactivo lo usa F12
+                     Al lado se escribe un .json con la cámara, el sol, las muestras y la resolución
+  --out-exr          Igual que --headless --out archivo.exr (se mantiene por compatibilidad)
+  --scene            Archivo de capas de bloques (una letra por bloque, capas separadas por ---).
+                     Repetido carga varias escenas: Ctrl + 1-9 cambia entre ellas en la ventana
+  --heightmap        Imagen en escala de grises: genera un terreno de bloques (ignora --scene)
+  --cube-size        Lado de cada bloque (por defecto 0.5)
+  --spacing          Distancia entre centros de bloques vecinos (por defecto igual a --cube-size;
+                     mayor deja huecos entre bloques, igual los deja pegados)
+  --headless         Renderizar sin abrir ventana, guardar en --out y salir
+  --glide A:B        Sin ventana: recorrer de la vista guardada A a la B (1-9) guardando cada cuadro
+  --frames           Cuadros del recorrido de --glide (por defecto 60)
+  --compare          Con --headless: comparar el resultado con esta imagen de referencia y
+                     terminar con error si algún píxel difiere más que --tolerance
+  --tolerance        Diferencia máxima por canal (0-255) aceptada por --compare (por defecto 2)
+  --flip-y           Guardar las imágenes con la fila de abajo primero (por defecto la de arriba,
+                     como se ven en la ventana); aplica también a cada cuadro de --glide y a F12
+  --cubemap          Carpeta con las seis caras del cielo: px.png, nx.png, py.png, ny.png,
+                     pz.png y nz.png (+X, -X, +Y, -Y, +Z, -Z)
+  --crop X,Y,W,H     Trazar solo ese rectángulo de la imagen (esquina superior izquierda y tamaño);
+                     el resto queda del color de fondo (negro)
+  --gltf             Modelo .gltf o .glb (mallas de triángulos y materiales) que se suma al diorama
+  --linear           Iluminación lineal: las texturas de color se decodifican de sRGB y la imagen
+                     se codifica a sRGB al guardarla y mostrarla
+  --bvh-stats        Medir con un rayo por píxel cuántos nodos y triángulos prueban las jerarquías
+                     de las mallas, comparado con probar todos los triángulos (al arrancar y, en la
+                     ventana, en cada cuadro cuya medida cambie)
+  --replay           Repetir un render a partir del .json que se guardó con la imagen: toma de ahí
+                     la cámara, el sol, la resolución, las muestras y los ajustes del render";
+
+// Opciones de línea de comandos; los valores por defecto reproducen el modo interactivo
+pub struct CliOptions {
+    pub width: i32,
+    pub height: i32,
+    pub samples: u32,
+    // Giro del diorama durante el obturador (radianes por cuadro) y fracción abierta;
+    // None deja el obturador abierto solo si hay giro
+    pub spin: f32,
+    pub shutter: Option<f32>,
+    pub out: Option<String>,
+    // Archivos de --scene en el orden dado; la primera es la que se abre
+    pub scenes: Vec<String>,
+    pub heightmap: Option<String>,
+    pub cube_size: Option<f32>,
+    pub spacing: Option<f32>,
+    pub headless: bool,
+    // Casillas de vista (desde 0) de origen y destino para exportar un recorrido
+    pub glide: Option<(usize, usize)>,
+    pub frames: u32,
+    // Imagen de referencia contra la que se verifica el render sin ventana
+    pub compare: Option<String>,
+    pub tolerance: u8,
+    pub flip_y: bool,
+    pub cubemap: Option<String>,
+    pub crop: Option<CropRect>,
+    pub gltf: Option<String>,
+    pub linear_lighting: bool,
+    pub bvh_stats: bool,
+    // Metadatos (.json) de un render anterior que se repite
+    pub replay: Option<String>,
+}
+
+impl Default for CliOptions {
+    fn default() -> Self {
+        CliOptions {
+            width: 1300,
+            height: 900,
+            samples: 1,
+            spin: 0.0,
+            shutter: None,
+            out: None,
+            scenes: Vec::new(),
+            heightmap: None,
+            cube_size: None,
+            spacing: None,
+            headless: false,
+            glide: None,
+            frames: 60,
+            compare: None,
+            tolerance: 2,
+            flip_y: false,
+            cubemap: None,
+            crop: None,
+            gltf: None,
+            linear_lighting: false,
+            bvh_stats: false,
+            replay: None,
+        }
+    }
+}
+
+// `args` sin el nombre del programa
+pub fn parse_args(args: &[String]) -> Result<CliOptions, String> {
+    let mut options = CliOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--width" => options.width = parse_value(arg, args.next())?,
+            "--height" => options.height = parse_value(arg, args.next())?,
+            "--samples" => options.samples = parse_value(arg, args.next())?,
+            "--spin" => options.spin = parse_value(arg, args.next())?,
+            "--shutter" => options.shutter = Some(parse_value(arg, args.next())?),
+            "--out" => options.out = Some(parse_value(arg, args.next())?),
+            "--out-exr" => {
+                options.out = Some(parse_value(arg, args.next())?);
+                options.headless = true;
+            }
+            "--scene" => options.scenes.push(parse_value(arg, args.next())?),
+            "--heightmap" => options.heightmap = Some(parse_value(arg, args.next())?),
+            "--cube-size" => options.cube_size = Some(parse_value(arg, args.next())?),
+            "--spacing" => options.spacing = Some(parse_value(arg, args.next())?),
+            "--headless" => options.headless = true,
+            "--bvh-stats" => options.bvh_stats = true,
+            "--linear" => options.linear_lighting = true,
+            "--flip-y" => options.flip_y = true,
+            "--cubemap" => options.cubemap = Some(parse_value(arg, args.next())?),
+            "--crop" => options.crop = Some(parse_crop(&parse_value::<String>(arg, args.next())?)?),
+            "--gltf" => options.gltf = Some(parse_value(arg, args.next())?),
+            "--glide" => options.glide = Some(parse_glide(&parse_value::<String>(arg, args.next())?)?),
+            "--frames" => options.frames = parse_value(arg, args.next())?,
+            "--compare" => options.compare = Some(parse_value(arg, args.next())?),
+            "--tolerance" => options.tolerance = parse_value(arg, args.next())?,
+            "--replay" => options.replay = Some(parse_value(arg, args.next())?),
+            _ => return Err(format!("Argumento desconocido: {}", arg)),
+        }
+    }
+
+    if options.width <= 0 || options.height <= 0 {
+        return Err("La resolución debe ser mayor que cero".to_string());
+    }
+    if options.cube_size.is_some_and(|size| size <= 0.0) || options.spacing.is_some_and(|spacing| spacing <= 0.0) {
+        return Err("--cube-size y --spacing deben ser mayores que cero".to_string());
+    }
+    if options.compare.is_some() && (!options.headless || options.glide.is_some()) {
+        return Err("--compare solo funciona con --headless y sin --glide".to_string());
+    }
+    if options.crop.is_some_and(|crop| {
+        crop.width == 0
+            || crop.height == 0
+            || crop.x.checked_add(crop.width).is_none_or(|end| end > options.width as u32)
+            || crop.y.checked_add(crop.height).is_none_or(|end| end > options.height as u32)
+    }) {
+        return Err("--crop debe ser un rectángulo no vacío dentro de la imagen".to_string());
+    }
+    if options.scenes.len() > 9 {
+        return Err("--scene admite hasta 9 escenas (Ctrl + 1-9)".to_string());
+    }
+    if options.shutter.is_some_and(|shutter| !(0.0..=1.0).contains(&shutter)) {
+        return Err("--shutter debe estar entre 0 y 1".to_string());
+    }
+    if options.samples == 0 {
+        return Err("--samples debe ser al menos 1".to_string());
+    }
+
+    Ok(options)
+}
+
+// "A:B" con casillas de 1 a 9
+fn parse_glide(value: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("Valor inválido para --glide: {} (se espera A:B, con casillas de 1 a 9)", value);
+    let (from, to) = value.split_once(':').ok_or_else(invalid)?;
+    let slot = |s: &str| match s.parse::<usize>() {
+        Ok(n) if (1..=9).contains(&n) => Ok(n - 1),
+        _ => Err(invalid()),
+    };
+    Ok((slot(from)?, slot(to)?))
+}
+
+// "X,Y,W,H" en píxeles
+fn parse_crop(value: &str) -> Result<CropRect, String> {
+    let invalid = || format!("Valor inválido para --crop: {} (se espera X,Y,W,H)", value);
+    let numbers = value.split(',').map(|n| n.trim().parse::<u32>()).collect::<Result<Vec<_>, _>>().map_err(|_| invalid())?;
+    match numbers[..] {
+        [x, y, width, height] => Ok(CropRect { x, y, width, height }),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("Falta el valor de {}", flag))?;
+    value.parse().map_err(|_| format!("Valor inválido para {}: {}", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliOptions, String> {
+        parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn out_exr_is_a_headless_out() {
+        let options = parse(&["--out-exr", "frame.exr"]).unwrap();
+        assert_eq!(options.out.as_deref(), Some("frame.exr"));
+        assert!(options.headless);
+    }
+
+    #[test]
+    fn defaults_match_the_interactive_window() {
+        let options = parse(&[]).unwrap();
+        assert_eq!((options.width, options.height, options.samples), (1300, 900, 1));
+        assert!(!options.headless && options.out.is_none());
+    }
+
+    #[test]
+    fn crop_past_u32_max_is_rejected() {
+        assert!(parse(&["--crop", "4294967295,0,1,1"]).is_err());
+        assert!(parse(&["--crop", "0,4294967295,1,1"]).is_err());
+        assert!(parse(&["--crop", "0,0,1300,900"]).is_ok());
+    }
+
+    #[test]
+    fn replay_takes_the_sidecar_path() {
+        assert_eq!(parse(&["--replay", "render_0007.json"]).unwrap().replay.as_deref(), Some("render_0007.json"));
+        assert!(parse(&["--replay"]).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use raylib::prelude::Vector3;
+use std::f32::consts::PI;
+use crate::disk::Disk;
+use crate::ray_intersect::{Intersect, RayIntersect, SURFACE_EPSILON};
+use crate::material::Material;
+use crate::tangent_frame;
+
+// Cono sólido con punta en `apex` que se abre a lo largo de `axis` con semiángulo `angle`
+// (radianes) hasta `height`, cerrado por una tapa en la base (techos, árboles)
+pub struct Cone {
+    pub apex: Vector3,
+    pub axis: Vector3,
+    pub angle: f32,
+    pub height: f32,
+    pub material: Material,
+    cap: Disk,
+}
+
+impl Cone {
+    pub fn new(apex: Vector3, axis: Vector3, angle: f32, height: f32, material: Material) -> Self {
+        let axis = axis.normalized();
+        let cap = Disk::new(apex + axis * height, axis, height * angle.tan(), material.clone());
+        Cone { apex, axis, angle, height, material, cap }
+    }
+
+    // Impacto con la superficie inclinada: cuadrática del cono infinito, quedándose solo con
+    // la mitad que se abre hacia `axis` y hasta `height`
+    fn side_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Option<(f32, Vector3)> {
+        let cos2 = self.angle.cos().powi(2);
+        let co = *ray_origin - self.apex;
+        let dir_axis = ray_direction.dot(self.axis);
+        let co_axis = co.dot(self.axis);
+
+        let a = dir_axis * dir_axis - cos2;
+        let b = 2.0 * (dir_axis * co_axis - ray_direction.dot(co) * cos2);
+        let c = co_axis * co_axis - co.dot(co) * cos2;
+
+        let roots = if a.abs() < 1e-8 {
+            // Rayo paralelo a la pared del cono: una sola solución
+            if b.abs() < 1e-8 {
+                return None;
+            }
+            [-c / b, f32::INFINITY]
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let sqrt_d = discriminant.sqrt();
+            let t1 = (-b - sqrt_d) / (2.0 * a);
+            let t2 = (-b + sqrt_d) / (2.0 * a);
+            [t1.min(t2), t1.max(t2)]
+        };
+
+        roots.into_iter().find_map(|t| {
+            if t <= SURFACE_EPSILON || !t.is_finite() {
+                return None;
+            }
+            let point = *ray_origin + *ray_direction * t;
+            // h < 0 es la mitad reflejada del cono (por encima de la punta)
+            let h = (point - self.apex).dot(self.axis);
+            (0.0..=self.height).contains(&h).then_some((t, point))
+        })
+    }
+}
+
+impl RayIntersect for Cone {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        let mut closest = self.cap.ray_intersect(ray_origin, ray_direction);
+
+        if let Some((t, point)) = self.side_intersect(ray_origin, ray_direction)
+            && (!closest.is_intersecting || t < closest.distance)
+        {
+            // Gradiente de la superficie del cono, hacia afuera
+            let cp = point - self.apex;
+            let h = cp.dot(self.axis);
+            let normal = (cp * self.angle.cos().powi(2) - self.axis * h).normalized();
+
+            // u da la vuelta alrededor del eje, v baja de la punta a la base
+            let (tangent, bitangent) = tangent_frame(&self.axis);
+            let angle = cp.dot(bitangent).atan2(cp.dot(tangent));
+            let u = (angle + PI) / (2.0 * PI);
+            let v = h / self.height;
+
+            closest = Intersect::new(point, normal, t, self.material.clone(), u, v);
+        }
+
+        closest
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.apex + self.axis * (self.height * 0.75)
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        let (cap_min, cap_max) = self.cap.bounds();
+        (
+            Vector3::new(cap_min.x.min(self.apex.x), cap_min.y.min(self.apex.y), cap_min.z.min(self.apex.z)),
+            Vector3::new(cap_max.x.max(self.apex.x), cap_max.y.max(self.apex.y), cap_max.z.max(self.apex.z)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Techo: punta en y = 1, se abre hacia abajo a 45° hasta la base de radio 1 en y = 0
+    fn roof() -> Cone {
+        Cone::new(Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0), PI / 4.0, 1.0, Material::black())
+    }
+
+    #[test]
+    fn side_hit_has_the_slanted_outward_normal() {
+        let hit = roof().ray_intersect(&Vector3::new(2.0, 0.5, 0.0), &Vector3::new(-1.0, 0.0, 0.0));
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 1.5).abs() < 1e-4);
+        assert!(hit.normal.dot(Vector3::new(1.0, 1.0, 0.0).normalized()) > 0.9999);
+        assert!((hit.v - 0.5).abs() < 1e-4);
+        assert!((0.0..=1.0).contains(&hit.u));
+    }
+
+    #[test]
+    fn cap_hit_faces_away_from_the_apex() {
+        let hit = roof().ray_intersect(&Vector3::new(0.3, -1.0, 0.2), &Vector3::new(0.0, 1.0, 0.0));
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 1.0).abs() < 1e-4);
+        assert_eq!(hit.normal, Vector3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn mirrored_nappe_above_the_apex_is_rejected() {
+        let hit = roof().ray_intersect(&Vector3::new(2.0, 1.5, 0.0), &Vector3::new(-1.0, 0.0, 0.0));
+        assert!(!hit.is_intersecting);
+    }
+
+    #[test]
+    fn u_wraps_around_the_axis() {
+        let roof = roof();
+        let u_from = |x: f32, z: f32| roof.ray_intersect(&Vector3::new(x * 2.0, 0.5, z * 2.0), &Vector3::new(-x, 0.0, -z)).u;
+        let us = [u_from(1.0, 0.0), u_from(0.0, 1.0), u_from(-1.0, 0.0), u_from(0.0, -1.0)];
+        for pair in us.windows(2) {
+            let step = (pair[1] - pair[0]).rem_euclid(1.0);
+            assert!((step - 0.25).abs() < 1e-3 || (step - 0.75).abs() < 1e-3, "{us:?}");
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use raylib::prelude::Vector3;
+use crate::ray_intersect::{Intersect, RayIntersect, SURFACE_EPSILON};
+use crate::material::Material;
+use crate::scene::SceneObject;
+
+// Resta CSG: el sólido `a` sin lo que ocupa `b` (un marco de ventana, un bloque perforado).
+// Los dos tienen que ser sólidos cerrados y convexos, como Cube o Cylinder, que lanzados desde
+// adentro devuelven la cara de salida. Las caras talladas son las de `b` con la normal invertida
+// y el material de `a`
+pub struct Difference {
+    pub a: SceneObject,
+    pub b: SceneObject,
+}
+
+impl Difference {
+    pub fn new(a: SceneObject, b: SceneObject) -> Self {
+        Difference { a, b }
+    }
+}
+
+// Tramo de la recta del rayo dentro de `object`: (distancia, impacto) de la entrada y de la
+// salida. Con el origen adentro la entrada queda en -infinito, sin impacto
+fn span(object: &SceneObject, ray_origin: &Vector3, ray_direction: &Vector3) -> Option<[(f32, Intersect); 2]> {
+    let first = object.ray_intersect(ray_origin, ray_direction);
+    if !first.is_intersecting {
+        return None;
+    }
+    if first.normal.dot(*ray_direction) > 0.0 {
+        return Some([(f32::NEG_INFINITY, Intersect::empty()), (first.distance, first)]);
+    }
+
+    // Se vuelve a lanzar apenas pasada la entrada para encontrar la salida
+    let beyond = first.point + *ray_direction * SURFACE_EPSILON;
+    let mut exit = object.ray_intersect(&beyond, ray_direction);
+    // Solo rozó una arista
+    if !exit.is_intersecting {
+        return None;
+    }
+    exit.distance += first.distance + SURFACE_EPSILON;
+    Some([(first.distance, first), (exit.distance, exit)])
+}
+
+impl RayIntersect for Difference {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        let Some([a_in, a_out]) = span(&self.a, ray_origin, ray_direction) else {
+            return Intersect::empty();
+        };
+        let (a_start, a_end) = (a_in.0, a_out.0);
+        let hole = span(&self.b, ray_origin, ray_direction);
+        let (b_start, b_end) = hole.as_ref().map_or((f32::INFINITY, f32::INFINITY), |[b_in, b_out]| (b_in.0, b_out.0));
+
+        // Bordes del resultado: las caras de `a` que quedan fuera de `b` y las de `b` que caen
+        // dentro de `a` (talladas)
+        let outside_hole = |t: f32| t < b_start || t > b_end;
+        let mut boundaries: Vec<(Intersect, bool)> = [a_in, a_out].into_iter()
+            .filter(|(t, _)| outside_hole(*t))
+            .map(|(_, hit)| (hit, false))
+            .collect();
+        if let Some(hole) = hole {
+            boundaries.extend(hole.into_iter()
+                .filter(|(t, _)| *t > a_start && *t < a_end)
+                .map(|(_, hit)| (hit, true)));
+        }
+
+        let Some((mut intersect, carved)) = boundaries.into_iter()
+            .filter(|(hit, _)| hit.is_intersecting && hit.distance > 0.0)
+            .min_by(|(a, _), (b, _)| a.distance.total_cmp(&b.distance))
+        else {
+            return Intersect::empty();
+        };
+        if carved {
+            intersect.normal = -intersect.normal;
+            intersect.face_normal = -intersect.face_normal;
+            intersect.material = self.a.material().clone();
+        }
+        intersect
+    }
+
+    fn material(&self) -> &Material {
+        self.a.material()
+    }
+
+    fn center(&self) -> Vector3 {
+        self.a.center()
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        self.a.bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::cylinder::Cylinder;
+
+    const RED: Vector3 = Vector3 { x: 1.0, y: 0.0, z: 0.0 };
+
+    // Cubo de lado 2 perforado por un túnel de radio 0.5 a lo largo de Z
+    fn drilled_cube() -> Difference {
+        let block = Cube::new(Vector3::zero(), 2.0, Material::builder().diffuse(RED).build());
+        let tunnel = Cylinder::new(Vector3::zero(), Vector3::new(0.0, 0.0, 1.0), 0.5, 4.0, Material::black());
+        Difference::new(Box::new(block), Box::new(tunnel))
+    }
+
+    fn assert_hit(hit: &Intersect, distance: f32, normal: Vector3) {
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - distance).abs() < 1e-3, "distancia {}", hit.distance);
+        assert!((hit.normal - normal).length() < 1e-4, "normal {:?}", hit.normal);
+        assert_eq!(hit.material.diffuse, RED);
+    }
+
+    #[test]
+    fn rays_through_the_hole_pass_and_beside_it_hit_the_block() {
+        let solid = drilled_cube();
+        assert!(!solid.ray_intersect(&Vector3::new(0.1, 0.0, 5.0), &Vector3::new(0.0, 0.0, -1.0)).is_intersecting);
+        assert_hit(&solid.ray_intersect(&Vector3::new(0.8, 0.0, 5.0), &Vector3::new(0.0, 0.0, -1.0)), 4.0, Vector3::new(0.0, 0.0, 1.0));
+        assert_hit(&solid.ray_intersect(&Vector3::new(0.0, 3.0, 0.0), &Vector3::new(0.0, -1.0, 0.0)), 2.0, Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn carved_faces_point_into_the_hole() {
+        let solid = drilled_cube();
+        // Desde adentro del material hacia el túnel: la pared tallada mira hacia el túnel
+        assert_hit(&solid.ray_intersect(&Vector3::new(0.0, 0.8, 0.0), &Vector3::new(0.0, -1.0, 0.0)), 0.3, Vector3::new(0.0, -1.0, 0.0));
+        assert_hit(&solid.ray_intersect(&Vector3::new(0.0, -0.8, 0.0), &Vector3::new(0.0, 1.0, 0.0)), 0.3, Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn rays_starting_inside_the_hole_see_the_carved_wall() {
+        let solid = drilled_cube();
+        // Hacia la pared: la primera superficie es la del túnel, con la normal hacia el rayo
+        assert_hit(&solid.ray_intersect(&Vector3::zero(), &Vector3::new(0.0, 1.0, 0.0)), 0.5, Vector3::new(0.0, -1.0, 0.0));
+        let sideways = Vector3::new(1.0, 0.0, 0.0);
+        assert_hit(&solid.ray_intersect(&Vector3::new(0.0, 0.0, 0.4), &sideways), 0.5, -sideways);
+        // A lo largo del túnel sale sin tocar nada
+        assert!(!solid.ray_intersect(&Vector3::zero(), &Vector3::new(0.0, 0.0, -1.0)).is_intersecting);
+    }
+}
@@ -1,32 +1,63 @@
 use raylib::prelude::Vector3;
-use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::aabb::Aabb;
+use crate::ray_intersect::{inverse_direction, Intersect, RayIntersect, SURFACE_EPSILON};
 use crate::material::Material;
 
+#[derive(Clone)]
 pub struct Cube {
     pub center: Vector3,
-    pub size: f32,
+    // Tamaño en cada eje: un bloque suelto tiene los tres iguales, una caja fusionada no
+    pub size: Vector3,
+    // Lado de un bloque; la textura se repite cada block_size para que una caja fusionada
+    // se vea igual que los bloques que reemplaza
+    pub block_size: f32,
     pub material: Material,
+    // Radio de la esfera que envuelve la caja, para descartar rayos antes del slab test
+    bounding_radius: f32,
 }
 
 impl Cube {
-    fn get_uv(&self, point: &Vector3, normal: &Vector3) -> (f32, f32) {
-        let local = *point - self.center;
-        let half_size = self.size / 2.0;
-        
-        // Determinar qué cara del cubo estamos mirando basándonos en la normal
-        let abs_normal = Vector3::new(normal.x.abs(), normal.y.abs(), normal.z.abs());
-        
-        let (u, v) = if abs_normal.x > abs_normal.y && abs_normal.x > abs_normal.z {
+    pub fn new(center: Vector3, size: f32, material: Material) -> Self {
+        Cube::with_size(center, Vector3::new(size, size, size), size, material)
+    }
+
+    // Caja de lados distintos (varios bloques fusionados) con la textura repetida cada block_size
+    pub fn with_size(center: Vector3, size: Vector3, block_size: f32, material: Material) -> Self {
+        Cube {
+            center,
+            size,
+            block_size,
+            material,
+            bounding_radius: size.length() / 2.0,
+        }
+    }
+
+    // El rayo pasa lejos de la esfera envolvente (o se aleja de ella estando afuera)
+    fn misses_bounding_sphere(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> bool {
+        let oc = *ray_origin - self.center;
+        let b = oc.dot(*ray_direction);
+        let c = oc.dot(oc) - self.bounding_radius * self.bounding_radius;
+        c > 0.0 && (b > 0.0 || b * b < ray_direction.dot(*ray_direction) * c)
+    }
+
+    // UV de la cara perpendicular a `axis` (0 = x, 1 = y, 2 = z). La cara la decide el slab
+    // test y no la normal, así un punto sobre una arista siempre usa la misma
+    fn get_uv(&self, point: &Vector3, axis: usize) -> (f32, f32) {
+        // Posición desde la esquina mínima, medida en bloques
+        let local = (*point - self.center + self.size / 2.0) / self.block_size;
+        // Repetir cada bloque; lo que cae apenas fuera de la caja por redondeo se queda en el
+        // borde en vez de leer los texeles del otro lado de la textura
+        let tile = |t: f32| (if t >= 1.0 { t - (t - 1e-4).floor() } else { t }).clamp(0.0, 1.0);
+
+        let (u, v) = match axis {
             // Cara X (izquierda/derecha)
-            ((local.z / half_size + 1.0) / 2.0, (local.y / half_size + 1.0) / 2.0)
-        } else if abs_normal.y > abs_normal.z {
+            0 => (tile(local.z), tile(local.y)),
             // Cara Y (arriba/abajo)
-            ((local.x / half_size + 1.0) / 2.0, (local.z / half_size + 1.0) / 2.0)
-        } else {
+            1 => (tile(local.x), tile(local.z)),
             // Cara Z (frente/atrás)
-            ((local.x / half_size + 1.0) / 2.0, (local.y / half_size + 1.0) / 2.0)
+            _ => (tile(local.x), tile(local.y)),
         };
-        
+
         // Invertir horizontal y verticalmente
         (1.0 - u, 1.0 - v)
     }
@@ -34,24 +65,19 @@ impl Cube {
 
 impl RayIntersect for Cube {
     fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        self.ray_intersect_inverse(ray_origin, ray_direction, &inverse_direction(ray_direction))
+    }
+
+    fn ray_intersect_inverse(&self, ray_origin: &Vector3, ray_direction: &Vector3, inv_dir: &Vector3) -> Intersect {
+        if self.misses_bounding_sphere(ray_origin, ray_direction) {
+            return Intersect::empty();
+        }
+
         let half_size = self.size / 2.0;
-        let min = Vector3::new(
-            self.center.x - half_size,
-            self.center.y - half_size,
-            self.center.z - half_size,
-        );
-        let max = Vector3::new(
-            self.center.x + half_size,
-            self.center.y + half_size,
-            self.center.z + half_size,
-        );
+        let min = self.center - half_size;
+        let max = self.center + half_size;
 
         // Algoritmo de intersección ray-box (slab method)
-        let inv_dir = Vector3::new(
-            1.0 / ray_direction.x,
-            1.0 / ray_direction.y,
-            1.0 / ray_direction.z,
-        );
 
         let t1 = (min.x - ray_origin.x) * inv_dir.x;
         let t2 = (max.x - ray_origin.x) * inv_dir.x;
@@ -60,11 +86,15 @@ impl RayIntersect for Cube {
         let t5 = (min.z - ray_origin.z) * inv_dir.z;
         let t6 = (max.z - ray_origin.z) * inv_dir.z;
 
-        let tmin = t1.min(t2).max(t3.min(t4)).max(t5.min(t6));
-        let tmax = t1.max(t2).min(t3.max(t4)).min(t5.max(t6));
+        // Entrada y salida por eje; la entrada a la caja es la última entrada y la salida la primera salida
+        let near = [t1.min(t2), t3.min(t4), t5.min(t6)];
+        let far = [t1.max(t2), t3.max(t4), t5.max(t6)];
+        let tmin = near[0].max(near[1]).max(near[2]);
+        let tmax = far[0].min(far[1]).min(far[2]);
 
-        // Si tmax < 0, el cubo está detrás del rayo
-        if tmax < 0.0 {
+        // Si tmax no pasa del origen, el cubo está detrás del rayo (o el rayo sale de una de sus
+        // caras hacia afuera: esa cara es la del origen y no cuenta)
+        if tmax <= SURFACE_EPSILON {
             return Intersect::empty();
         }
 
@@ -73,32 +103,176 @@ impl RayIntersect for Cube {
             return Intersect::empty();
         }
 
-        // Usar tmin si es positivo, de lo contrario tmax (estamos dentro del cubo)
-        let t = if tmin > 0.0 { tmin } else { tmax };
+        // Usar tmin si es positivo, de lo contrario tmax (estamos dentro del cubo). Un origen
+        // apoyado en una cara cuenta como adentro, así el resultado no depende del signo del redondeo
+        let inside = tmin <= SURFACE_EPSILON;
+        let t = if inside { tmax } else { tmin };
 
         let point = *ray_origin + *ray_direction * t;
 
-        // Calcular la normal basada en qué cara del cubo golpeamos
+        // La cara golpeada es la del eje que fijó t, sin comparar el punto contra las caras
+        // (eso fallaba en aristas y lejos del origen). En un empate, justo sobre una arista, gana
+        // x, después y. La normal siempre apunta hacia afuera: al entrar es opuesta al rayo y al
+        // salir desde adentro va a favor del rayo
+        let axis = if inside {
+            if far[0] <= far[1] && far[0] <= far[2] { 0 } else if far[1] <= far[2] { 1 } else { 2 }
+        } else if near[0] >= near[1] && near[0] >= near[2] {
+            0
+        } else if near[1] >= near[2] {
+            1
+        } else {
+            2
+        };
+        let direction = [ray_direction.x, ray_direction.y, ray_direction.z];
+        let outward = if inside { direction[axis].signum() } else { -direction[axis].signum() };
         let mut normal = Vector3::zero();
-        let epsilon = 0.0001;
-        
-        if (point.x - min.x).abs() < epsilon {
-            normal = Vector3::new(-1.0, 0.0, 0.0);
-        } else if (point.x - max.x).abs() < epsilon {
-            normal = Vector3::new(1.0, 0.0, 0.0);
-        } else if (point.y - min.y).abs() < epsilon {
-            normal = Vector3::new(0.0, -1.0, 0.0);
-        } else if (point.y - max.y).abs() < epsilon {
-            normal = Vector3::new(0.0, 1.0, 0.0);
-        } else if (point.z - min.z).abs() < epsilon {
-            normal = Vector3::new(0.0, 0.0, -1.0);
-        } else if (point.z - max.z).abs() < epsilon {
-            normal = Vector3::new(0.0, 0.0, 1.0);
+        match axis {
+            0 => normal.x = outward,
+            1 => normal.y = outward,
+            _ => normal.z = outward,
         }
 
-        let (u, v) = self.get_uv(&point, &normal);
+        let (u, v) = self.get_uv(&point, axis);
 
         Intersect::new(point, normal, t, self.material.clone(), u, v)
     }
+
+    // El mismo slab test, sin normal, UV ni copia del material
+    fn any_hit(&self, ray_origin: &Vector3, ray_direction: &Vector3, inv_dir: &Vector3, max_distance: f32) -> bool {
+        if self.misses_bounding_sphere(ray_origin, ray_direction) {
+            return false;
+        }
+        let (min, max) = self.bounds();
+        Aabb::new(min, max)
+            .intersect(ray_origin, inv_dir)
+            .is_some_and(|(tmin, tmax)| tmax > SURFACE_EPSILON && if tmin <= SURFACE_EPSILON { tmax } else { tmin } < max_distance)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.center
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        (self.center - self.size / 2.0, self.center + self.size / 2.0)
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_vector(rng: &mut SmallRng, scale: f32) -> Vector3 {
+        Vector3::new(rng.random_range(-scale..scale), rng.random_range(-scale..scale), rng.random_range(-scale..scale))
+    }
+
+    #[test]
+    fn bounding_sphere_never_rejects_a_slab_hit() {
+        // Caja alargada (bloques fusionados): la esfera queda holgada en dos ejes
+        let cube = Cube::with_size(Vector3::new(0.5, 0.0, -0.25), Vector3::new(3.0, 0.5, 1.0), 0.5, Material::black());
+        let (min, max) = cube.bounds();
+        let aabb = Aabb::new(min, max);
+        let mut rng = SmallRng::seed_from_u64(618);
+        for _ in 0..20_000 {
+            let origin = random_vector(&mut rng, 4.0);
+            let direction = random_vector(&mut rng, 1.0).normalized();
+            let slab_hit = aabb.intersect(&origin, &inverse_direction(&direction)).is_some_and(|(_, tmax)| tmax > SURFACE_EPSILON);
+            if cube.misses_bounding_sphere(&origin, &direction) {
+                assert!(!slab_hit, "la esfera descartó un rayo que golpea: {origin:?} {direction:?}");
+            }
+            assert_eq!(cube.ray_intersect(&origin, &direction).is_intersecting, slab_hit);
+        }
+    }
+
+    fn block() -> Cube {
+        Cube::new(Vector3::zero(), 1.0, Material::black())
+    }
+
+    #[test]
+    fn origin_at_the_center_hits_the_exit_face() {
+        let axes = [
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(-1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        ];
+        for direction in axes {
+            // Tres puntos de la misma cara para no depender del centro
+            for offset in [Vector3::zero(), Vector3::new(0.1, 0.2, 0.3), Vector3::new(-0.2, 0.1, -0.15)] {
+                let offset = offset - direction * offset.dot(direction);
+                let inside = block().ray_intersect(&offset, &direction);
+                assert!(inside.is_intersecting);
+                assert!((inside.distance - 0.5).abs() < 1e-5);
+                // La normal apunta hacia afuera de la cara de salida, a favor del rayo
+                assert_eq!(inside.normal, direction);
+
+                // La misma cara vista desde afuera da el mismo punto y las mismas UV
+                let outside = block().ray_intersect(&(offset + direction * 3.0), &-direction);
+                assert!((outside.point - inside.point).length() < 1e-5);
+                assert!((outside.u - inside.u).abs() < 1e-5 && (outside.v - inside.v).abs() < 1e-5);
+            }
+        }
+        let diagonal = block().ray_intersect(&Vector3::zero(), &Vector3::new(1.0, 0.3, -0.2).normalized());
+        assert_eq!(diagonal.normal, Vector3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn origin_on_a_face_ignores_that_face() {
+        let on_face = Vector3::new(0.5, 0.1, -0.2);
+
+        let leaving = block().ray_intersect(&on_face, &Vector3::new(1.0, 0.0, 0.0));
+        assert!(!leaving.is_intersecting);
+        let outward = Vector3::new(1.0, 0.0, 0.0);
+        assert!(!block().any_hit(&on_face, &outward, &inverse_direction(&outward), f32::INFINITY));
+
+        // Hacia adentro cuenta como interior: sale por la cara opuesta
+        let entering = block().ray_intersect(&on_face, &Vector3::new(-1.0, 0.0, 0.0));
+        assert!(entering.is_intersecting);
+        assert!((entering.distance - 1.0).abs() < 1e-5);
+        assert_eq!(entering.normal, Vector3::new(-1.0, 0.0, 0.0));
+
+        // Rasante sobre la cara no entra al cubo: no lo toca, ni trae NaN
+        let along = Vector3::new(0.0, 1.0, 0.0);
+        let grazing = block().ray_intersect(&on_face, &along);
+        assert!(!grazing.is_intersecting);
+        assert!(!block().any_hit(&on_face, &along, &inverse_direction(&along), f32::INFINITY));
+    }
+
+    #[test]
+    fn edge_hits_pick_a_fixed_face_and_uv() {
+        // Justo sobre la arista entre +Y y +Z: empate, gana y
+        let diagonal = Vector3::new(0.0, -1.0, -1.0).normalized();
+        let edge = block().ray_intersect(&Vector3::new(0.2, 2.0, 2.0), &diagonal);
+        assert_eq!(edge.normal, Vector3::new(0.0, 1.0, 0.0));
+        assert!((edge.u - 0.3).abs() < 1e-5 && edge.v.abs() < 1e-5);
+
+        // Entre +X y +Y gana x
+        let corner = block().ray_intersect(&Vector3::new(2.0, 2.0, 0.1), &Vector3::new(-1.0, -1.0, 0.0).normalized());
+        assert_eq!(corner.normal, Vector3::new(1.0, 0.0, 0.0));
+
+        // A cada lado de la arista la UV cambia poco, sin saltar al otro lado de la textura
+        for nudge in [-1e-3, 1e-3] {
+            let near = block().ray_intersect(&Vector3::new(0.2, 2.0 + nudge, 2.0), &diagonal);
+            assert!((near.u - edge.u).abs() < 1e-2 && (near.v - edge.v).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn uv_just_outside_the_box_stays_on_the_border() {
+        let cube = block();
+        for point in [Vector3::new(0.5 + 1e-6, 0.5, 0.1), Vector3::new(-0.5 - 1e-6, 0.5, 0.1)] {
+            let (u, v) = cube.get_uv(&point, 1);
+            assert!((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v));
+        }
+        assert!(cube.get_uv(&Vector3::new(0.5 + 1e-6, 0.5, 0.1), 1).0 < 1e-3);
+        assert!(cube.get_uv(&Vector3::new(-0.5 - 1e-6, 0.5, 0.1), 1).0 > 1.0 - 1e-3);
+    }
+}
@@ -0,0 +1,166 @@
+use raylib::prelude::Vector3;
+use std::f32::consts::PI;
+use crate::ray_intersect::{Intersect, RayIntersect, SURFACE_EPSILON};
+use crate::material::Material;
+use crate::tangent_frame;
+
+// Cilindro cerrado con tapas, centrado en `center`, a lo largo de `axis`
+pub struct Cylinder {
+    pub center: Vector3,
+    pub axis: Vector3,
+    pub radius: f32,
+    pub height: f32,
+    pub material: Material,
+    // Ejes perpendiculares al eje, para pasar puntos al espacio local (eje = Y local)
+    tangent: Vector3,
+    bitangent: Vector3,
+}
+
+impl Cylinder {
+    pub fn new(center: Vector3, axis: Vector3, radius: f32, height: f32, material: Material) -> Self {
+        let axis = axis.normalized();
+        let (tangent, bitangent) = tangent_frame(&axis);
+        Cylinder { center, axis, radius, height, material, tangent, bitangent }
+    }
+
+    fn local_vector(&self, v: Vector3) -> Vector3 {
+        Vector3::new(v.dot(self.tangent), v.dot(self.axis), v.dot(self.bitangent))
+    }
+}
+
+impl RayIntersect for Cylinder {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        let origin = self.local_vector(*ray_origin - self.center);
+        let direction = self.local_vector(*ray_direction);
+        let half_height = self.height / 2.0;
+
+        // Tramo dentro del cilindro infinito (x² + z² ≤ r²)
+        let a = direction.x * direction.x + direction.z * direction.z;
+        let b = origin.x * direction.x + origin.z * direction.z;
+        let c = origin.x * origin.x + origin.z * origin.z - self.radius * self.radius;
+        let (side_in, side_out) = if a < 1e-12 {
+            if c > 0.0 {
+                return Intersect::empty();
+            }
+            (f32::NEG_INFINITY, f32::INFINITY)
+        } else {
+            let discriminant = b * b - a * c;
+            if discriminant < 0.0 {
+                return Intersect::empty();
+            }
+            let sqrt_d = discriminant.sqrt();
+            ((-b - sqrt_d) / a, (-b + sqrt_d) / a)
+        };
+
+        // Tramo entre las dos tapas
+        let (cap_in, cap_out) = if direction.y.abs() < 1e-12 {
+            if origin.y.abs() > half_height {
+                return Intersect::empty();
+            }
+            (f32::NEG_INFINITY, f32::INFINITY)
+        } else {
+            let t1 = (-half_height - origin.y) / direction.y;
+            let t2 = (half_height - origin.y) / direction.y;
+            (t1.min(t2), t1.max(t2))
+        };
+
+        let tmin = side_in.max(cap_in);
+        let tmax = side_out.min(cap_out);
+        if tmin > tmax || tmax <= SURFACE_EPSILON {
+            return Intersect::empty();
+        }
+
+        // Usar tmin si queda delante, de lo contrario tmax (estamos dentro del cilindro)
+        let (t, on_cap) = if tmin > SURFACE_EPSILON { (tmin, cap_in > side_in) } else { (tmax, cap_out < side_out) };
+        let local = origin + direction * t;
+
+        let (normal, u, v) = if on_cap {
+            let sign = local.y.signum();
+            let u = (local.x / self.radius) * 0.5 + 0.5;
+            let v = (local.z / self.radius) * 0.5 + 0.5;
+            (self.axis * sign, u, v)
+        } else {
+            let radial = self.tangent * local.x + self.bitangent * local.z;
+            let u = local.z.atan2(local.x) / (2.0 * PI) + 0.5;
+            let v = local.y / self.height + 0.5;
+            (radial.normalized(), u, v)
+        };
+
+        let point = *ray_origin + *ray_direction * t;
+        Intersect::new(point, normal, t, self.material.clone(), u, v)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.center
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        // Como en Disk, el borde de cada tapa se aleja radio * sqrt(1 - n²) por eje, más la mitad
+        // del eje hasta la tapa
+        let extent = |n: f32| self.radius * (1.0 - n * n).max(0.0).sqrt() + n.abs() * self.height / 2.0;
+        let half = Vector3::new(extent(self.axis.x), extent(self.axis.y), extent(self.axis.z));
+        (self.center - half, self.center + half)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Columna de radio 0.5 y alto 2 parada sobre Y, corrida a x = 1
+    fn column() -> Cylinder {
+        Cylinder::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), 0.5, 2.0, Material::black())
+    }
+
+    fn assert_hit(hit: &Intersect, distance: f32, normal: Vector3) {
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - distance).abs() < 1e-5, "distancia {}", hit.distance);
+        assert!((hit.normal - normal).length() < 1e-5, "normal {:?}", hit.normal);
+    }
+
+    #[test]
+    fn caps_are_hit_along_the_axis() {
+        let cylinder = column();
+        let top = cylinder.ray_intersect(&Vector3::new(1.1, 5.0, 0.2), &Vector3::new(0.0, -1.0, 0.0));
+        assert_hit(&top, 4.0, Vector3::new(0.0, 1.0, 0.0));
+        assert!((top.point.y - 1.0).abs() < 1e-5);
+        let bottom = cylinder.ray_intersect(&Vector3::new(0.8, -4.0, -0.1), &Vector3::new(0.0, 1.0, 0.0));
+        assert_hit(&bottom, 3.0, Vector3::new(0.0, -1.0, 0.0));
+
+        // Justo afuera del borde de la tapa no toca
+        assert!(!cylinder.ray_intersect(&Vector3::new(1.6, 5.0, 0.0), &Vector3::new(0.0, -1.0, 0.0)).is_intersecting);
+    }
+
+    #[test]
+    fn side_is_hit_across_the_axis() {
+        let cylinder = column();
+        assert_hit(&cylinder.ray_intersect(&Vector3::new(1.0, 0.3, 5.0), &Vector3::new(0.0, 0.0, -1.0)), 4.5, Vector3::new(0.0, 0.0, 1.0));
+        assert_hit(&cylinder.ray_intersect(&Vector3::new(-3.0, -0.9, 0.0), &Vector3::new(1.0, 0.0, 0.0)), 3.5, Vector3::new(-1.0, 0.0, 0.0));
+
+        // Por arriba de la tapa, al costado o alejándose
+        assert!(!cylinder.ray_intersect(&Vector3::new(1.0, 1.5, 5.0), &Vector3::new(0.0, 0.0, -1.0)).is_intersecting);
+        assert!(!cylinder.ray_intersect(&Vector3::new(2.0, 0.0, 5.0), &Vector3::new(0.0, 0.0, -1.0)).is_intersecting);
+        assert!(!cylinder.ray_intersect(&Vector3::new(1.0, 0.0, 5.0), &Vector3::new(0.0, 0.0, 1.0)).is_intersecting);
+    }
+
+    #[test]
+    fn from_inside_the_exit_faces_outward() {
+        let cylinder = column();
+        assert_hit(&cylinder.ray_intersect(&Vector3::new(1.0, 0.0, 0.0), &Vector3::new(1.0, 0.0, 0.0)), 0.5, Vector3::new(1.0, 0.0, 0.0));
+        assert_hit(&cylinder.ray_intersect(&Vector3::new(1.0, 0.5, 0.0), &Vector3::new(0.0, 1.0, 0.0)), 0.5, Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn tilted_axis_keeps_caps_and_side_apart() {
+        let axis = Vector3::new(1.0, 1.0, 0.0).normalized();
+        let cylinder = Cylinder::new(Vector3::zero(), axis, 0.5, 2.0, Material::black());
+        // A lo largo del eje entra por la tapa y de costado por el lado
+        assert_hit(&cylinder.ray_intersect(&(axis * 5.0), &-axis), 4.0, axis);
+        let across = Vector3::new(0.0, 0.0, 1.0);
+        assert_hit(&cylinder.ray_intersect(&(across * 5.0), &-across), 4.5, across);
+    }
+}
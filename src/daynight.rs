@@ -0,0 +1,163 @@
+use raylib::prelude::*;
+use crate::light::Light;
+
+// Posiciones de cada luz en Scene::lights
+pub const SUN: usize = 0;
+pub const MOON: usize = 1;
+
+const SUN_INTENSITY: f32 = 1.5;
+// La luna es tenue y azulada; su radio la vuelve una luz de área con sombras suaves
+const MOON_INTENSITY: f32 = 0.35;
+const MOON_RADIUS: f32 = 0.8;
+
+const DAY_SKY: Vector3 = Vector3::new(0.26, 0.55, 0.89);
+const SUNSET_SKY: Vector3 = Vector3::new(0.95, 0.5, 0.25);
+const NIGHT_SKY: Vector3 = Vector3::new(0.02, 0.03, 0.08);
+// Color cálido del sol bajo, que conserva de noche mientras se apaga
+const LOW_SUN: Vector3 = Vector3::new(1.0, 0.784, 0.588);
+// Por debajo de esta altura normalizada el sol ya se puso
+const SUNSET_HEIGHT: f32 = 0.3;
+
+// Degradado de color por tramos: paradas (posición, color) ordenadas por posición, interpoladas
+// linealmente entre sí. Fuera del rango se mantiene el color de la parada más cercana
+pub struct ColorRamp {
+    pub stops: Vec<(f32, Vector3)>,
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<(f32, Vector3)>) -> Self {
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ColorRamp { stops }
+    }
+
+    pub fn sample(&self, t: f32) -> Vector3 {
+        let Some(&(first_t, first)) = self.stops.first() else {
+            return Vector3::one();
+        };
+        if t <= first_t {
+            return first;
+        }
+        for pair in self.stops.windows(2) {
+            let ((t0, c0), (t1, c1)) = (pair[0], pair[1]);
+            if t <= t1 {
+                return c0 + (c1 - c0) * ((t - t0) / (t1 - t0).max(f32::EPSILON));
+            }
+        }
+        self.stops[self.stops.len() - 1].1
+    }
+}
+
+// Color del sol según su altura normalizada (0 = medianoche, 1 = mediodía): cálido al ponerse
+// y blanco en cuanto sube. El verde se blanquea antes que el azul
+pub fn sun_ramp() -> ColorRamp {
+    ColorRamp::new(vec![
+        (SUNSET_HEIGHT, LOW_SUN),
+        (0.4025, Vector3::new(1.0, 1.0, 0.769)),
+        (0.5334, Vector3::one()),
+    ])
+}
+
+// Color del cielo según la misma altura: azul oscuro de noche, naranja al atardecer y azul de
+// día. El naranja se va rápido apenas el sol sube
+pub fn sky_ramp() -> ColorRamp {
+    ColorRamp::new(vec![
+        (SUNSET_HEIGHT, NIGHT_SKY),
+        (0.7, SUNSET_SKY),
+        (0.8, DAY_SKY),
+    ])
+}
+
+// Altura del sol de 0 (medianoche) a 1 (mediodía), la clave de los degradados
+fn normalized_height(sun_angle: f32) -> f32 {
+    (sun_angle.cos() + 1.0) / 2.0
+}
+
+pub fn create_lights(sun_radius: f32) -> Vec<Light> {
+    let sun = Light::new(
+        Vector3::new(0.0, sun_radius, 0.0),
+        Vector3::one(),
+        SUN_INTENSITY,
+    );
+    let mut moon = Light::new(
+        Vector3::new(0.0, -sun_radius, 0.0),
+        Vector3::new(0.59, 0.67, 1.0),
+        0.0,
+    );
+    moon.radius = MOON_RADIUS;
+    vec![sun, moon]
+}
+
+// Sol y luna para un ángulo del ciclo: la luna va en el lado opuesto de la órbita y
+// entra a medida que el sol se apaga bajo el horizonte. `sun_ramp` da el color del sol
+pub fn update_day_night(lights: &mut [Light], sun_angle: f32, sun_radius: f32, sun_ramp: &ColorRamp) {
+    let sun_fade = update_sun(&mut lights[SUN], sun_angle, sun_radius, sun_ramp);
+    let sun_position = lights[SUN].position;
+
+    let moon = &mut lights[MOON];
+    moon.position = -sun_position;
+    moon.intensity = MOON_INTENSITY * (1.0 - sun_fade);
+}
+
+// Color del cielo para un ángulo del ciclo
+pub fn sky_color(sun_angle: f32, sky_ramp: &ColorRamp) -> Vector3 {
+    sky_ramp.sample(normalized_height(sun_angle))
+}
+
+// Cuánto se ven las estrellas: aparecen cuando el sol termina de ponerse y se van al amanecer
+pub fn star_visibility(sun_angle: f32) -> f32 {
+    ((0.35 - normalized_height(sun_angle)) / 0.15).clamp(0.0, 1.0)
+}
+
+// Brillo de estrella en la dirección `direction` (unitaria): el cielo se parte en celdas y cada
+// una tiene, con probabilidad `density`, una estrella en un punto al azar fijo por celda
+pub fn star_field(direction: &Vector3, density: f32) -> f32 {
+    const STAR_GRID: f32 = 150.0;
+    if direction.y <= 0.0 {
+        return 0.0;
+    }
+
+    let p = *direction * STAR_GRID;
+    let cell = (p.x.floor(), p.y.floor(), p.z.floor());
+    let hash = |seed: u32| {
+        let mut h = (cell.0 as i32 as u32).wrapping_mul(73_856_093)
+            ^ (cell.1 as i32 as u32).wrapping_mul(19_349_663)
+            ^ (cell.2 as i32 as u32).wrapping_mul(83_492_791)
+            ^ seed.wrapping_mul(2_654_435_761);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x2c1b_3c6d);
+        h ^= h >> 12;
+        h as f32 / u32::MAX as f32
+    };
+    if hash(0) > density {
+        return 0.0;
+    }
+
+    let star = Vector3::new(cell.0 + hash(1), cell.1 + hash(2), cell.2 + hash(3));
+    // Punto de un tercio de celda de radio con borde suave; algunas estrellas más tenues que otras
+    (1.0 - (p - star).length() * 3.0).max(0.0) * (0.3 + 0.7 * hash(4))
+}
+
+// Devuelve cuánto del sol queda encendido (1 de día, 0 de noche)
+fn update_sun(light: &mut Light, sun_angle: f32, sun_radius: f32, sun_ramp: &ColorRamp) -> f32 {
+    // Calcular posición del sol (rotación en el plano XZ, altura en Y)
+    // El sol se mueve en un arco: alto durante el día, bajo durante la noche
+    // sun_angle: 0 = mediodía (alto), PI/2 = atardecer, PI = medianoche (bajo), 3*PI/2 = amanecer
+    let sun_height = sun_angle.cos(); // 1 (mediodía) a -1 (medianoche)
+    // Rotación horizontal alrededor del eje Y
+    let sun_x = sun_radius * sun_angle.cos();
+    let sun_y = sun_radius * sun_height; // Altura del sol
+    let sun_z = sun_radius * sun_angle.sin();
+
+    light.position = Vector3::new(sun_x, sun_y, sun_z);
+
+    // Intensidad de 0.1 (noche) a SUN_INTENSITY (mediodía) según la altura del sol
+    let normalized_height = normalized_height(sun_angle);
+    light.intensity = 0.1 + normalized_height * (SUN_INTENSITY - 0.1);
+    light.color = sun_ramp.sample(normalized_height);
+
+    // Noche: el sol conserva su color y se apaga por intensidad, así no arrastra
+    // los brillos especulares a negro; la luna toma el relevo
+    let sun_fade = (normalized_height / SUNSET_HEIGHT).min(1.0);
+    light.intensity *= sun_fade;
+    sun_fade
+}
@@ -0,0 +1,102 @@
+use raylib::prelude::Vector3;
+use std::f32::consts::PI;
+use crate::ray_intersect::{Intersect, RayIntersect, SURFACE_EPSILON};
+use crate::material::Material;
+use crate::tangent_frame;
+
+// Disco plano de radio `radius` centrado en `center` (tapas, mesas redondas, luces de área)
+pub struct Disk {
+    pub center: Vector3,
+    pub normal: Vector3,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl Disk {
+    pub fn new(center: Vector3, normal: Vector3, radius: f32, material: Material) -> Self {
+        Disk {
+            center,
+            normal: normal.normalized(),
+            radius,
+            material,
+        }
+    }
+}
+
+impl RayIntersect for Disk {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        let denom = self.normal.dot(*ray_direction);
+        // Rayo paralelo al disco
+        if denom.abs() < 1e-6 {
+            return Intersect::empty();
+        }
+
+        let t = (self.center - *ray_origin).dot(self.normal) / denom;
+        if t <= SURFACE_EPSILON {
+            return Intersect::empty();
+        }
+
+        let point = *ray_origin + *ray_direction * t;
+        let local = point - self.center;
+        let distance = local.length();
+        if distance > self.radius {
+            return Intersect::empty();
+        }
+
+        // u = ángulo alrededor del centro, v = distancia al centro relativa al radio
+        let (tangent, bitangent) = tangent_frame(&self.normal);
+        let angle = local.dot(bitangent).atan2(local.dot(tangent));
+        let u = (angle + PI) / (2.0 * PI);
+        let v = distance / self.radius;
+
+        Intersect::new(point, self.normal, t, self.material.clone(), u, v)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.center
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        // En cada eje el borde del disco se aleja del centro radio * sqrt(1 - n²)
+        let extent = |n: f32| self.radius * (1.0 - n * n).max(0.0).sqrt();
+        let half = Vector3::new(extent(self.normal.x), extent(self.normal.y), extent(self.normal.z));
+        (self.center - half, self.center + half)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disk() -> Disk {
+        Disk::new(Vector3::zero(), Vector3::new(0.0, 2.0, 0.0), 1.0, Material::black())
+    }
+
+    #[test]
+    fn hits_inside_the_radius_with_the_disk_normal() {
+        let hit = disk().ray_intersect(&Vector3::new(0.5, 2.0, 0.0), &Vector3::new(0.0, -1.0, 0.0));
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 2.0).abs() < 1e-5);
+        assert_eq!(hit.normal, Vector3::new(0.0, 1.0, 0.0));
+        assert!((hit.v - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn misses_outside_the_radius_parallel_and_behind() {
+        let down = Vector3::new(0.0, -1.0, 0.0);
+        assert!(!disk().ray_intersect(&Vector3::new(1.1, 2.0, 0.0), &down).is_intersecting);
+        assert!(!disk().ray_intersect(&Vector3::new(-2.0, 0.0, 0.0), &Vector3::new(1.0, 0.0, 0.0)).is_intersecting);
+        assert!(!disk().ray_intersect(&Vector3::new(0.0, -1.0, 0.0), &down).is_intersecting);
+    }
+
+    #[test]
+    fn bounds_are_flat_along_the_normal() {
+        let (min, max) = disk().bounds();
+        assert_eq!(min, Vector3::new(-1.0, 0.0, -1.0));
+        assert_eq!(max, Vector3::new(1.0, 0.0, 1.0));
+    }
+}
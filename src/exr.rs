@@ -0,0 +1,74 @@
+use raylib::prelude::Vector3;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+// Escritor mínimo de OpenEXR: una imagen RGB en float de 32 bits, sin compresión,
+// una línea por bloque. Suficiente para guardar el buffer HDR lineal sin dependencias.
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+pub fn save_exr(path: &str, width: u32, height: u32, pixels: &[Vector3]) -> io::Result<()> {
+    let mut header = Vec::new();
+
+    // Canales en orden alfabético, como exige el formato
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear + reservado
+        channels.extend_from_slice(&1i32.to_le_bytes()); // xSampling
+        channels.extend_from_slice(&1i32.to_le_bytes()); // ySampling
+    }
+    channels.push(0);
+    write_attribute(&mut header, "channels", "chlist", &channels);
+
+    write_attribute(&mut header, "compression", "compression", &[0]);
+
+    let mut window = Vec::new();
+    for value in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0u8; 8]);
+    write_attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&[0x76, 0x2f, 0x31, 0x01])?; // Número mágico
+    writer.write_all(&2u32.to_le_bytes())?; // Versión 2, archivo scanline simple
+    writer.write_all(&header)?;
+
+    // Tabla de offsets: posición absoluta de cada línea en el archivo
+    let line_data_size = (width * 3 * 4) as u64;
+    let line_block_size = 8 + line_data_size;
+    let first_line_offset = 8 + header.len() as u64 + height as u64 * 8;
+    for y in 0..height as u64 {
+        writer.write_all(&(first_line_offset + y * line_block_size).to_le_bytes())?;
+    }
+
+    for y in 0..height {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_data_size as i32).to_le_bytes())?;
+
+        let row = &pixels[(y * width) as usize..((y + 1) * width) as usize];
+        for channel in [|c: &Vector3| c.z, |c: &Vector3| c.y, |c: &Vector3| c.x] {
+            for color in row {
+                writer.write_all(&channel(color).to_le_bytes())?;
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(attribute_type.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    header.extend_from_slice(value);
+}
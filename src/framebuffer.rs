@@ -1,6 +1,9 @@
 // framebuffer.rs
 
 use raylib::prelude::*;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 
 pub struct Framebuffer {
     pub width: u32,
@@ -22,8 +25,10 @@ impl Framebuffer {
         }
     }
 
-    pub fn clear(&mut self) {
-        self.color_buffer = Image::gen_image_color(self.width as i32, self.height as i32, self.background_color);
+    /// Fills the whole buffer with `color`, which also becomes the background color
+    pub fn clear(&mut self, color: Color) {
+        self.background_color = color;
+        self.color_buffer = Image::gen_image_color(self.width as i32, self.height as i32, color);
     }
 
     pub fn set_pixel(&mut self, x: u32, y: u32) {
@@ -38,6 +43,11 @@ impl Framebuffer {
         }
     }
 
+    /// Draws a line into the color buffer; endpoints outside the image are clipped by raylib
+    pub fn draw_line(&mut self, start: Vector2, end: Vector2, color: Color) {
+        self.color_buffer.draw_line(start.x as i32, start.y as i32, end.x as i32, end.y as i32, color);
+    }
+
     pub fn set_background_color(&mut self, color: Color) {
         self.background_color = color;
     }
@@ -46,8 +56,85 @@ impl Framebuffer {
         self.current_color = color;
     }
 
-    pub fn _render_to_file(&self, file_path: &str) {
-        self.color_buffer.export_image(file_path);
+    /// Source-over blends a `width` x `height` RGBA image onto the buffer with its top-left
+    /// corner at (x, y). Parts that fall outside the framebuffer are clipped
+    pub fn composite_overlay(&mut self, overlay: &[Color], x: i32, y: i32, width: u32, height: u32) {
+        for row in 0..height as i32 {
+            for column in 0..width as i32 {
+                let (target_x, target_y) = (x + column, y + row);
+                if target_x < 0 || target_y < 0 || target_x >= self.width as i32 || target_y >= self.height as i32 {
+                    continue;
+                }
+                let Some(&source) = overlay.get((row * width as i32 + column) as usize) else {
+                    return;
+                };
+                if source.a == 0 {
+                    continue;
+                }
+
+                let destination = self.color_buffer.get_color(target_x, target_y);
+                let alpha = source.a as f32 / 255.0;
+                let over = |s: u8, d: u8| (s as f32 * alpha + d as f32 * (1.0 - alpha)).round() as u8;
+                let blended = Color::new(
+                    over(source.r, destination.r),
+                    over(source.g, destination.g),
+                    over(source.b, destination.b),
+                    (source.a as f32 + destination.a as f32 * (1.0 - alpha)).round() as u8,
+                );
+                self.color_buffer.draw_pixel(target_x, target_y, blended);
+            }
+        }
+    }
+
+    /// Mirrors the color buffer top to bottom (row 0 is normally the top of the image)
+    pub fn flip_vertical(&mut self) {
+        self.color_buffer.flip_vertical();
+    }
+
+    /// Exports the color buffer in the format given by the extension. `Image::export_image` drops
+    /// raylib's result, so this calls `ExportImage` directly to report a failed write
+    pub fn render_to_file(&self, file_path: &str) -> std::io::Result<()> {
+        let c_path = CString::new(file_path).map_err(std::io::Error::other)?;
+        // SAFETY: color_buffer owns a valid image and c_path outlives the call
+        let exported = unsafe { raylib::ffi::ExportImage(*self.color_buffer.as_ref(), c_path.as_ptr()) };
+        if exported {
+            Ok(())
+        } else {
+            Err(std::io::Error::other(format!("raylib no pudo exportar {}", file_path)))
+        }
+    }
+
+    /// Writes the color buffer as a binary P6 PPM (header plus raw RGB bytes)
+    pub fn save_to_ppm(&self, file_path: &str) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+
+        let colors = self.color_buffer.get_image_data();
+        for color in colors.iter() {
+            writer.write_all(&[color.r, color.g, color.b])?;
+        }
+
+        writer.flush()
+    }
+
+    /// Counts the pixels whose RGB differs from the image at `file_path` by more than `tolerance`
+    /// in any channel. Fails if the reference can't be loaded or its size doesn't match
+    pub fn count_differences(&self, file_path: &str, tolerance: u8) -> Result<usize, String> {
+        let reference = Image::load_image(file_path).map_err(|_| format!("Failed to load image {}", file_path))?;
+        if reference.width as u32 != self.width || reference.height as u32 != self.height {
+            return Err(format!(
+                "{} is {}x{}, expected {}x{}",
+                file_path, reference.width, reference.height, self.width, self.height
+            ));
+        }
+
+        let differs = |a: u8, b: u8| a.abs_diff(b) > tolerance;
+        let rendered = self.color_buffer.get_image_data();
+        Ok(rendered
+            .iter()
+            .zip(reference.get_image_data().iter())
+            .filter(|(a, b)| differs(a.r, b.r) || differs(a.g, b.g) || differs(a.b, b.b))
+            .count())
     }
 
     pub fn swap_buffers(
@@ -61,3 +148,58 @@ impl Framebuffer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white(width: u32, height: u32) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(width, height);
+        framebuffer.clear(Color::WHITE);
+        framebuffer
+    }
+
+    #[test]
+    fn half_transparent_red_over_white_is_pink() {
+        let mut framebuffer = white(2, 1);
+        framebuffer.composite_overlay(&[Color::new(255, 0, 0, 128)], 1, 0, 1, 1);
+        // alfa 128/255: al blanco le queda 255 · (1 - 0.502) ≈ 127
+        assert_eq!(framebuffer.color_buffer.get_color(1, 0), Color::new(255, 127, 127, 255));
+        // El píxel fuera de la imagen superpuesta no cambia
+        assert_eq!(framebuffer.color_buffer.get_color(0, 0), Color::WHITE);
+    }
+
+    #[test]
+    fn opaque_texels_replace_and_transparent_ones_are_skipped() {
+        let mut framebuffer = white(2, 1);
+        framebuffer.composite_overlay(&[Color::new(0, 0, 255, 255), Color::new(0, 0, 0, 0)], 0, 0, 2, 1);
+        assert_eq!(framebuffer.color_buffer.get_color(0, 0), Color::new(0, 0, 255, 255));
+        assert_eq!(framebuffer.color_buffer.get_color(1, 0), Color::WHITE);
+    }
+
+    #[test]
+    fn overlay_is_clipped_at_the_borders() {
+        let mut framebuffer = white(2, 2);
+        // 3x3 negro corrido (-2, -2): solo su esquina inferior derecha cae en el píxel (0, 0)
+        framebuffer.composite_overlay(&[Color::BLACK; 9], -2, -2, 3, 3);
+        assert_eq!(framebuffer.color_buffer.get_color(0, 0), Color::BLACK);
+        for (x, y) in [(1, 0), (0, 1), (1, 1)] {
+            assert_eq!(framebuffer.color_buffer.get_color(x, y), Color::WHITE);
+        }
+        // Del todo afuera no toca nada
+        framebuffer.composite_overlay(&[Color::BLACK; 4], 2, 0, 2, 2);
+        assert_eq!(framebuffer.color_buffer.get_color(1, 0), Color::WHITE);
+    }
+
+    #[test]
+    fn failed_exports_are_errors() {
+        let framebuffer = white(2, 2);
+        let path = std::env::temp_dir().join("framebuffer_export_test.png");
+        let path = path.to_str().unwrap();
+        assert!(framebuffer.render_to_file(path).is_ok());
+        std::fs::remove_file(path).unwrap();
+        // Ni la carpeta ni la extensión existen
+        assert!(framebuffer.render_to_file("/no/existe/imagen.png").is_err());
+        assert!(framebuffer.render_to_file(&path.replace(".png", ".xyz")).is_err());
+    }
+}
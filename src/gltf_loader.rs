@@ -0,0 +1,873 @@
+use raylib::prelude::*;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::Path;
+use std::sync::Arc;
+use crate::instance::{Instance, SharedGeometry};
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::presets;
+use crate::scene::SceneObject;
+use crate::textures::{TextureFilter, TextureManager, TextureWrap};
+
+// Modelo glTF 2.0 importado: cada primitiva es una malla compartida, colocada una vez por cada
+// nodo que la usa. Solo geometría estática en triángulos; sin animaciones, skins ni cámaras
+pub struct GltfModel {
+    parts: Vec<(SharedGeometry, Matrix)>,
+    // Primitivas de puntos o líneas que no se cargaron, para que quien carga pueda avisar
+    pub skipped_primitives: usize,
+}
+
+impl GltfModel {
+    // Objetos listos para sumar a la escena (instancias de las mallas ya cargadas)
+    pub fn instances(&self) -> Vec<SceneObject> {
+        self.parts.iter()
+            .map(|(geometry, transform)| Box::new(Instance::new(geometry.clone(), *transform, None)) as SceneObject)
+            .collect()
+    }
+}
+
+// Carga un .gltf (buffers externos o embebidos en base64) o un .glb. Las texturas embebidas
+// se registran en `texture_manager` con el nombre "archivo#imagenN"
+pub fn load_gltf(path: &str, texture_manager: &TextureManager) -> Result<GltfModel, String> {
+    let bytes = std::fs::read(path).map_err(|error| format!("No se pudo leer {}: {}", path, error))?;
+    let folder = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let (document, glb_binary) = if bytes.starts_with(b"glTF") {
+        split_glb(&bytes)?
+    } else {
+        (String::from_utf8(bytes).map_err(|_| format!("{} no es texto UTF-8", path))?, None)
+    };
+    let document = Json::parse(&document).map_err(|error| format!("JSON inválido en {}: {}", path, error))?;
+
+    let buffers = document.get("buffers").items().iter()
+        .map(|buffer| match buffer.get("uri").as_str() {
+            Some(uri) => load_uri(uri, folder),
+            None => glb_binary.clone().ok_or_else(|| "Buffer sin uri fuera de un .glb".to_string()),
+        })
+        .collect::<Result<Vec<Vec<u8>>, String>>()?;
+    let gltf = Gltf { document: &document, buffers, folder, path, texture_manager };
+
+    let mut meshes: HashMap<usize, Vec<SharedGeometry>> = HashMap::new();
+    let mut parts = Vec::new();
+    let mut skipped_primitives = 0;
+    let roots: Vec<usize> = match document.get("scenes").index(document.get("scene").as_usize().unwrap_or(0)) {
+        Json::Null => {
+            // Sin escenas: las raíces son los nodos que no son hijos de ningún otro
+            let children: Vec<usize> = document.get("nodes").items().iter()
+                .flat_map(|node| node.get("children").items().iter().filter_map(Json::as_usize))
+                .collect();
+            (0..document.get("nodes").items().len()).filter(|node| !children.contains(node)).collect()
+        }
+        scene => scene.get("nodes").items().iter().filter_map(Json::as_usize).collect(),
+    };
+
+    let mut pending: Vec<(usize, [f32; 16])> = roots.into_iter().map(|node| (node, IDENTITY)).collect();
+    let mut visited = 0;
+    while let Some((node_index, parent)) = pending.pop() {
+        // Un archivo con ciclos en la jerarquía no debe colgar la carga
+        visited += 1;
+        if visited > 100_000 {
+            return Err(format!("Jerarquía de nodos inválida en {}", path));
+        }
+        let node = document.get("nodes").index(node_index);
+        let transform = multiply(&parent, &node_transform(node));
+
+        if let Some(mesh_index) = node.get("mesh").as_usize() {
+            if let Entry::Vacant(entry) = meshes.entry(mesh_index) {
+                let (geometries, skipped) = gltf.mesh(mesh_index)?;
+                skipped_primitives += skipped;
+                entry.insert(geometries);
+            }
+            for geometry in &meshes[&mesh_index] {
+                parts.push((geometry.clone(), to_matrix(&transform)));
+            }
+        }
+        pending.extend(node.get("children").items().iter().filter_map(Json::as_usize).map(|child| (child, transform)));
+    }
+
+    Ok(GltfModel { parts, skipped_primitives })
+}
+
+// Separa un .glb en su texto JSON y su bloque binario
+fn split_glb(bytes: &[u8]) -> Result<(String, Option<Vec<u8>>), String> {
+    let read_u32 = |offset: usize| {
+        bytes.get(offset..offset + 4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]) as usize)
+            .ok_or_else(|| "Archivo .glb truncado".to_string())
+    };
+
+    let (mut json, mut binary) = (None, None);
+    let mut offset = 12;
+    while offset + 8 <= bytes.len().min(read_u32(8)?) {
+        let (length, kind) = (read_u32(offset)?, read_u32(offset + 4)?);
+        let chunk = bytes.get(offset + 8..offset + 8 + length).ok_or_else(|| "Archivo .glb truncado".to_string())?;
+        match kind {
+            0x4E4F534A => json = Some(String::from_utf8_lossy(chunk).into_owned()),
+            0x004E4942 => binary = Some(chunk.to_vec()),
+            _ => {}
+        }
+        offset += 8 + length;
+    }
+    Ok((json.ok_or_else(|| "Archivo .glb sin bloque JSON".to_string())?, binary))
+}
+
+// Contenido de un buffer o imagen: embebido como "data:...;base64," o un archivo junto al modelo
+fn load_uri(uri: &str, folder: &Path) -> Result<Vec<u8>, String> {
+    match uri.strip_prefix("data:") {
+        Some(data) => {
+            let (_, encoded) = data.split_once(";base64,").ok_or_else(|| "URI de datos sin base64".to_string())?;
+            decode_base64(encoded)
+        }
+        None => {
+            let file = folder.join(uri);
+            std::fs::read(&file).map_err(|error| format!("No se pudo leer {}: {}", file.display(), error))
+        }
+    }
+}
+
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in encoded.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err("Carácter inválido en base64".to_string()),
+        };
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+struct Gltf<'a> {
+    document: &'a Json,
+    buffers: Vec<Vec<u8>>,
+    folder: &'a Path,
+    path: &'a str,
+    texture_manager: &'a TextureManager,
+}
+
+impl Gltf<'_> {
+    // Una malla por primitiva en triángulos; las de puntos o líneas se saltean y se cuentan
+    fn mesh(&self, index: usize) -> Result<(Vec<SharedGeometry>, usize), String> {
+        let mut geometries = Vec::new();
+        let mut skipped = 0;
+        for primitive in self.document.get("meshes").index(index).get("primitives").items() {
+            if primitive.get("mode").as_usize().unwrap_or(4) != 4 {
+                skipped += 1;
+                continue;
+            }
+            let attributes = primitive.get("attributes");
+            let Some(position_accessor) = attributes.get("POSITION").as_usize() else {
+                continue;
+            };
+
+            let positions: Vec<Vector3> = self.accessor(position_accessor)?.chunks_exact(3)
+                .map(|p| Vector3::new(p[0] as f32, p[1] as f32, p[2] as f32))
+                .collect();
+            let normals: Vec<Vector3> = match attributes.get("NORMAL").as_usize() {
+                Some(accessor) => self.accessor(accessor)?.chunks_exact(3)
+                    .map(|n| Vector3::new(n[0] as f32, n[1] as f32, n[2] as f32))
+                    .collect(),
+                None => Vec::new(),
+            };
+            let uvs: Vec<(f32, f32)> = match attributes.get("TEXCOORD_0").as_usize() {
+                Some(accessor) => self.accessor(accessor)?.chunks_exact(2).map(|uv| (uv[0] as f32, uv[1] as f32)).collect(),
+                None => Vec::new(),
+            };
+            let indices: Vec<usize> = match primitive.get("indices").as_usize() {
+                Some(accessor) => self.accessor(accessor)?.into_iter().map(|i| i as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+            let triangles = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+
+            let material = self.material(primitive.get("material").as_usize())?;
+            geometries.push(Arc::new(TriangleMesh::new(positions, normals, uvs, triangles, material)) as SharedGeometry);
+        }
+        Ok((geometries, skipped))
+    }
+
+    // Componentes de un accessor en orden, como f64 (alcanza para índices u32 exactos)
+    fn accessor(&self, index: usize) -> Result<Vec<f64>, String> {
+        let accessor = self.document.get("accessors").index(index);
+        let invalid = || format!("{}: accessor {} inválido", self.path, index);
+        let count = accessor.get("count").as_usize().ok_or_else(invalid)?;
+        let components = match accessor.get("type").as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            _ => return Err(invalid()),
+        };
+        if !matches!(accessor.get("sparse"), Json::Null) {
+            return Err(format!("{}: los accessors dispersos no están soportados", self.path));
+        }
+        let Some(view_index) = accessor.get("bufferView").as_usize() else {
+            return Ok(vec![0.0; count.checked_mul(components).ok_or_else(invalid)?]);
+        };
+
+        let (size, normalized) = (
+            match accessor.get("componentType").as_usize() {
+                Some(5120 | 5121) => 1,
+                Some(5122 | 5123) => 2,
+                Some(5125 | 5126) => 4,
+                _ => return Err(invalid()),
+            },
+            accessor.get("normalized").as_bool().unwrap_or(false),
+        );
+        let component_type = accessor.get("componentType").as_usize().unwrap_or(0);
+        let view = self.document.get("bufferViews").index(view_index);
+        let buffer = self.buffers.get(view.get("buffer").as_usize().ok_or_else(invalid)?).ok_or_else(invalid)?;
+        let start = view.get("byteOffset").as_usize().unwrap_or(0)
+            .checked_add(accessor.get("byteOffset").as_usize().unwrap_or(0))
+            .ok_or_else(invalid)?;
+        let element_size = size * components;
+        let stride = view.get("byteStride").as_usize().unwrap_or(element_size);
+
+        // El último elemento tiene que caber entero en el buffer antes de reservar memoria: un
+        // count o un stride corruptos no deben pedir gigas ni desbordar la cuenta
+        let end = match count.checked_sub(1) {
+            Some(last) => last.checked_mul(stride)
+                .and_then(|bytes| bytes.checked_add(start))
+                .and_then(|bytes| bytes.checked_add(element_size)),
+            None => Some(0),
+        };
+        if stride < element_size || end.is_none_or(|end| end > buffer.len()) {
+            return Err(invalid());
+        }
+
+        let mut values = Vec::with_capacity(count * components);
+        for element in 0..count {
+            for component in 0..components {
+                let offset = start + element * stride + component * size;
+                let bytes = buffer.get(offset..offset + size).ok_or_else(invalid)?;
+                let value = match component_type {
+                    5120 => bytes[0] as i8 as f64 / if normalized { 127.0 } else { 1.0 },
+                    5121 => bytes[0] as f64 / if normalized { 255.0 } else { 1.0 },
+                    5122 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64 / if normalized { 32767.0 } else { 1.0 },
+                    5123 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64 / if normalized { 65535.0 } else { 1.0 },
+                    5125 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                };
+                values.push(value);
+            }
+        }
+        Ok(values)
+    }
+
+    // Traduce el material metálico-rugoso de glTF al modelo de Phong del trazador: el metal
+    // refleja y tiñe el brillo con su color, la rugosidad ensancha el brillo y desenfoca el reflejo
+    fn material(&self, index: Option<usize>) -> Result<Material, String> {
+        let Some(index) = index else {
+            return Ok(presets::matte(Vector3::one()));
+        };
+        let material = self.document.get("materials").index(index);
+        let invalid = || format!("{}: material {} inválido", self.path, index);
+        // Los factores que vienen tienen que tener todos sus componentes
+        let factor = |value: &Json, length: usize, default: f32| match value {
+            Json::Null => Ok(vec![default; length]),
+            value => value.floats().filter(|factor| factor.len() == length).ok_or_else(invalid),
+        };
+        let pbr = material.get("pbrMetallicRoughness");
+        let base = factor(pbr.get("baseColorFactor"), 4, 1.0)?;
+        let base_color = Vector3::new(base[0], base[1], base[2]);
+        let alpha = base[3];
+        let metallic = pbr.get("metallicFactor").as_f32().unwrap_or(1.0).clamp(0.0, 1.0);
+        let roughness = pbr.get("roughnessFactor").as_f32().unwrap_or(1.0).clamp(0.0, 1.0);
+
+        let transparency = if material.get("alphaMode").as_str() == Some("BLEND") { 1.0 - alpha } else { 0.0 };
+        let reflectivity = metallic * (1.0 - roughness);
+        let mut builder = Material::builder()
+            .diffuse(base_color)
+            .albedo([(1.0 - reflectivity) * (1.0 - transparency), 0.3 + 0.7 * metallic, reflectivity, transparency])
+            .specular_roughness(roughness)
+            .specular_color(Vector3::one() + (base_color - Vector3::one()) * metallic)
+            .roughness(roughness * 0.25)
+            .refractive_index(1.0)
+            .double_sided(material.get("doubleSided").as_bool().unwrap_or(false));
+
+        if let Some((path, filter, wrap)) = self.texture(pbr.get("baseColorTexture"), true)? {
+            builder = builder.texture(&path).filter(filter).wrap(wrap);
+        }
+        if let Some((path, _, _)) = self.texture(material.get("normalTexture"), false)? {
+            builder = builder.normal_map(&path)
+                .normal_strength(material.get("normalTexture").get("scale").as_f32().unwrap_or(1.0));
+        }
+        if let Some((path, _, _)) = self.texture(material.get("emissiveTexture"), true)? {
+            builder = builder.emission_map(&path);
+        }
+        let emissive = factor(material.get("emissiveFactor"), 3, 0.0)?;
+        let peak = emissive.iter().copied().fold(0.0, f32::max);
+        if peak > 0.0 {
+            let strength = material.get("extensions").get("KHR_materials_emissive_strength").get("emissiveStrength").as_f32().unwrap_or(1.0);
+            builder = builder.emissive(Vector3::new(emissive[0], emissive[1], emissive[2]) / peak, peak * strength);
+        }
+        if material.get("alphaMode").as_str() == Some("MASK") {
+            builder = builder.alpha_cutout(material.get("alphaCutoff").as_f32().unwrap_or(0.5));
+        }
+        Ok(builder.build())
+    }
+
+    // Ruta de la imagen de una referencia a textura y cómo leerla según su sampler. Las imágenes
+    // embebidas se decodifican una sola vez y quedan en el caché con un nombre propio; las de
+    // normales (is_srgb falso) no se pasan a lineal
+    fn texture(&self, reference: &Json, is_srgb: bool) -> Result<Option<(String, TextureFilter, TextureWrap)>, String> {
+        let Some(texture_index) = reference.get("index").as_usize() else {
+            return Ok(None);
+        };
+        let texture = self.document.get("textures").index(texture_index);
+        let Some(image_index) = texture.get("source").as_usize() else {
+            return Ok(None);
+        };
+        let image = self.document.get("images").index(image_index);
+
+        let sampler = self.document.get("samplers").index(texture.get("sampler").as_usize().unwrap_or(usize::MAX));
+        // 9728 = NEAREST, 33071 = CLAMP_TO_EDGE; glTF filtra y repite por defecto
+        let filter = if sampler.get("magFilter").as_usize() == Some(9728) { TextureFilter::Nearest } else { TextureFilter::Bilinear };
+        let wrap = if sampler.get("wrapS").as_usize() == Some(33071) { TextureWrap::Clamp } else { TextureWrap::Repeat };
+
+        let path = match image.get("uri").as_str() {
+            Some(uri) if !uri.starts_with("data:") => self.folder.join(uri).to_string_lossy().into_owned(),
+            uri => {
+                let extension = match image.get("mimeType").as_str().or(uri) {
+                    Some(mime) if mime.contains("image/jpeg") => "jpg",
+                    _ => "png",
+                };
+                let name = format!("{}#image{}.{}", self.path, image_index, extension);
+                if self.texture_manager.get_texture(&name).is_none() {
+                    let bytes = match (uri, image.get("bufferView").as_usize()) {
+                        (Some(uri), _) => load_uri(uri, self.folder)?,
+                        (None, Some(view)) => self.buffer_view(view)?.to_vec(),
+                        (None, None) => return Err(format!("{}: imagen {} sin uri ni bufferView", self.path, image_index)),
+                    };
+                    self.texture_manager.load_texture_from_memory(&name, &bytes, is_srgb);
+                }
+                name
+            }
+        };
+        Ok(Some((path, filter, wrap)))
+    }
+
+    fn buffer_view(&self, index: usize) -> Result<&[u8], String> {
+        let view = self.document.get("bufferViews").index(index);
+        let invalid = || format!("{}: bufferView {} inválido", self.path, index);
+        let start = view.get("byteOffset").as_usize().unwrap_or(0);
+        let end = start.checked_add(view.get("byteLength").as_usize().ok_or_else(invalid)?).ok_or_else(invalid)?;
+        self.buffers.get(view.get("buffer").as_usize().ok_or_else(invalid)?)
+            .and_then(|buffer| buffer.get(start..end))
+            .ok_or_else(invalid)
+    }
+}
+
+// Matrices de 4x4 por columnas, como las guarda glTF
+const IDENTITY: [f32; 16] = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+
+fn multiply(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
+    let mut result = [0.0; 16];
+    for column in 0..4 {
+        for row in 0..4 {
+            result[column * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[column * 4 + k]).sum();
+        }
+    }
+    result
+}
+
+// Transformación local de un nodo: su matriz, o traslación * rotación * escala
+fn node_transform(node: &Json) -> [f32; 16] {
+    if let Some(matrix) = node.get("matrix").floats().filter(|matrix| matrix.len() == 16) {
+        let mut result = [0.0; 16];
+        result.copy_from_slice(&matrix);
+        return result;
+    }
+
+    let t = node.get("translation").floats().filter(|t| t.len() == 3).unwrap_or(vec![0.0; 3]);
+    let r = node.get("rotation").floats().filter(|r| r.len() == 4).unwrap_or(vec![0.0, 0.0, 0.0, 1.0]);
+    let s = node.get("scale").floats().filter(|s| s.len() == 3).unwrap_or(vec![1.0; 3]);
+    let (x, y, z, w) = (r[0], r[1], r[2], r[3]);
+    [
+        (1.0 - 2.0 * (y * y + z * z)) * s[0], 2.0 * (x * y + z * w) * s[0], 2.0 * (x * z - y * w) * s[0], 0.0,
+        2.0 * (x * y - z * w) * s[1], (1.0 - 2.0 * (x * x + z * z)) * s[1], 2.0 * (y * z + x * w) * s[1], 0.0,
+        2.0 * (x * z + y * w) * s[2], 2.0 * (y * z - x * w) * s[2], (1.0 - 2.0 * (x * x + y * y)) * s[2], 0.0,
+        t[0], t[1], t[2], 1.0,
+    ]
+}
+
+// raylib también guarda la matriz por columnas (m12..m14 es la traslación)
+fn to_matrix(m: &[f32; 16]) -> Matrix {
+    Matrix {
+        m0: m[0], m1: m[1], m2: m[2], m3: m[3],
+        m4: m[4], m5: m[5], m6: m[6], m7: m[7],
+        m8: m[8], m9: m[9], m10: m[10], m11: m[11],
+        m12: m[12], m13: m[13], m14: m[14], m15: m[15],
+    }
+}
+
+// Valor JSON mínimo para leer el documento glTF. Las consultas sobre claves o índices que no
+// existen devuelven Null, así los campos opcionales se leen sin encadenar Option
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+const NULL: Json = Json::Null;
+
+impl Json {
+    fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map_or(&NULL, |(_, value)| value),
+            _ => &NULL,
+        }
+    }
+
+    fn index(&self, index: usize) -> &Json {
+        self.items().get(index).unwrap_or(&NULL)
+    }
+
+    fn items(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_f32(&self) -> Option<f32> {
+        match self {
+            Json::Number(value) => Some(*value as f32),
+            _ => None,
+        }
+    }
+
+    fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(value) if *value >= 0.0 && value.fract() == 0.0 => Some(*value as usize),
+            _ => None,
+        }
+    }
+
+    fn floats(&self) -> Option<Vec<f32>> {
+        match self {
+            Json::Array(items) => items.iter().map(Json::as_f32).collect(),
+            _ => None,
+        }
+    }
+
+    fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser { bytes: text.as_bytes(), position: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.bytes.len() {
+            return Err(format!("texto sobrante en la posición {}", parser.position));
+        }
+        Ok(value)
+    }
+}
+
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.position).is_some_and(u8::is_ascii_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.bytes.get(self.position) == Some(&byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(format!("se esperaba '{}' en la posición {}", byte as char, self.position))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        let rest = &self.bytes[self.position..];
+        match rest.first() {
+            Some(b'{') => {
+                self.position += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.position) == Some(&b'}') {
+                    self.position += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.bytes.get(self.position) {
+                        Some(b',') => self.position += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b'}')?;
+                Ok(Json::Object(fields))
+            }
+            Some(b'[') => {
+                self.position += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.position) == Some(&b']') {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.bytes.get(self.position) {
+                        Some(b',') => self.position += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b']')?;
+                Ok(Json::Array(items))
+            }
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            _ if rest.starts_with(b"true") => {
+                self.position += 4;
+                Ok(Json::Bool(true))
+            }
+            _ if rest.starts_with(b"false") => {
+                self.position += 5;
+                Ok(Json::Bool(false))
+            }
+            _ if rest.starts_with(b"null") => {
+                self.position += 4;
+                Ok(Json::Null)
+            }
+            _ => {
+                let length = rest.iter().take_while(|c| c.is_ascii_digit() || b"+-.eE".contains(c)).count();
+                let number = std::str::from_utf8(&rest[..length]).ok().and_then(|number| number.parse().ok());
+                self.position += length;
+                number.map(Json::Number).ok_or_else(|| format!("valor inválido en la posición {}", self.position - length))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut text = Vec::new();
+        loop {
+            let byte = *self.bytes.get(self.position).ok_or("texto sin cerrar")?;
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = *self.bytes.get(self.position).ok_or("texto sin cerrar")?;
+                    self.position += 1;
+                    let character = match escaped {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let code = self.bytes.get(self.position..self.position + 4)
+                                .and_then(|hex| std::str::from_utf8(hex).ok())
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .ok_or("escape \\u inválido")?;
+                            self.position += 4;
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        other => other as char,
+                    };
+                    text.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                _ => text.push(byte),
+            }
+        }
+        String::from_utf8(text).map_err(|_| "texto con UTF-8 inválido".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Un .glb mínimo: encabezado, bloque JSON y bloque binario, ambos rellenados a 4 bytes
+    fn glb(json: &str, binary: &[u8]) -> Vec<u8> {
+        let chunk = |data: &[u8], kind: u32, padding: u8| {
+            let mut chunk = data.to_vec();
+            chunk.resize(data.len().div_ceil(4) * 4, padding);
+            [(chunk.len() as u32).to_le_bytes(), kind.to_le_bytes()].concat().into_iter().chain(chunk).collect::<Vec<u8>>()
+        };
+        let chunks = [chunk(json.as_bytes(), 0x4E4F534A, b' '), chunk(binary, 0x004E4942, 0)].concat();
+        [b"glTF".to_vec(), 2u32.to_le_bytes().to_vec(), (12 + chunks.len() as u32).to_le_bytes().to_vec(), chunks].concat()
+    }
+
+    fn floats_as_bytes(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_le_bytes()).collect()
+    }
+
+    fn accessor_of(document: &str, buffer: Vec<u8>) -> Result<Vec<f64>, String> {
+        let document = Json::parse(document).unwrap();
+        let texture_manager = TextureManager::new();
+        let gltf = Gltf { document: &document, buffers: vec![buffer], folder: Path::new(""), path: "test.gltf", texture_manager: &texture_manager };
+        gltf.accessor(0)
+    }
+
+    // Aplica una matriz por columnas a un punto
+    fn transform_point(m: &[f32; 16], p: Vector3) -> Vector3 {
+        Vector3::new(
+            m[0] * p.x + m[4] * p.y + m[8] * p.z + m[12],
+            m[1] * p.x + m[5] * p.y + m[9] * p.z + m[13],
+            m[2] * p.x + m[6] * p.y + m[10] * p.z + m[14],
+        )
+    }
+
+    #[test]
+    fn json_parses_nested_values_and_escapes() {
+        let json = Json::parse(r#" { "list": [1, -2.5e1, true, false, null, []], "text": "a\"b\\c\né", "empty": {} } "#).unwrap();
+        let list = json.get("list").items();
+        assert_eq!(list.len(), 6);
+        assert_eq!(list[0].as_usize(), Some(1));
+        assert_eq!(list[1].as_f32(), Some(-25.0));
+        assert_eq!((list[2].as_bool(), list[3].as_bool()), (Some(true), Some(false)));
+        assert!(matches!(list[4], Json::Null));
+        assert!(list[5].items().is_empty());
+        assert_eq!(json.get("text").as_str(), Some("a\"b\\c\né"));
+        assert!(matches!(json.get("empty"), Json::Object(fields) if fields.is_empty()));
+
+        // Lo que falta o no tiene el tipo pedido se lee como Null o None
+        assert!(matches!(json.get("missing").get("deeper").index(3), Json::Null));
+        assert_eq!(json.get("text").as_usize(), None);
+        assert_eq!(list[1].as_usize(), None);
+        assert_eq!(json.get("list").floats(), None);
+    }
+
+    #[test]
+    fn json_rejects_malformed_documents() {
+        for text in ["", "{", "[1,]", r#"{"a" 1}"#, r#"{"a": 1} x"#, r#""open"#, "1.2.3", r#""\u12""#, "nul"] {
+            assert!(Json::parse(text).is_err(), "{text:?} debería fallar");
+        }
+    }
+
+    #[test]
+    fn base64_decodes_standard_and_url_safe_text() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVs\nbG8").unwrap(), b"hello");
+        assert_eq!(decode_base64("Pz8/").unwrap(), decode_base64("Pz8_").unwrap());
+        assert_eq!(decode_base64("+/+/").unwrap(), decode_base64("-_-_").unwrap());
+        assert!(decode_base64("").unwrap().is_empty());
+        assert!(decode_base64("aGVs*G8=").is_err());
+
+        assert_eq!(load_uri("data:application/octet-stream;base64,AQID", Path::new("")).unwrap(), vec![1, 2, 3]);
+        assert!(load_uri("data:text/plain,hola", Path::new("")).is_err());
+    }
+
+    #[test]
+    fn glb_splits_into_json_and_binary() {
+        let bytes = glb(r#"{"asset":{}}"#, &[1, 2, 3]);
+        let (json, binary) = split_glb(&bytes).unwrap();
+        assert_eq!(json.trim_end(), r#"{"asset":{}}"#);
+        assert_eq!(&binary.unwrap()[..3], &[1, 2, 3]);
+
+        // Cortado a la mitad de un bloque, o sin JSON, no se acepta
+        assert!(split_glb(&bytes[..bytes.len() - 4]).is_err());
+        assert!(split_glb(&bytes[..10]).is_err());
+        let no_json = [b"glTF".to_vec(), 2u32.to_le_bytes().to_vec(), 12u32.to_le_bytes().to_vec()].concat();
+        assert!(split_glb(&no_json).is_err());
+    }
+
+    #[test]
+    fn node_transform_applies_scale_then_rotation_then_translation() {
+        // Escala x2 en X, un cuarto de vuelta sobre Y y una traslación
+        let half = std::f32::consts::FRAC_PI_4;
+        let node = Json::parse(&format!(
+            r#"{{"translation": [1, 2, 3], "rotation": [0, {}, 0, {}], "scale": [2, 1, 1]}}"#,
+            half.sin(), half.cos()
+        )).unwrap();
+        let point = transform_point(&node_transform(&node), Vector3::new(1.0, 0.0, 0.0));
+        assert!((point - Vector3::new(1.0, 2.0, 1.0)).length() < 1e-5, "{point:?}");
+
+        // Una matriz explícita gana sobre TRS y se usa tal cual
+        let matrix = Json::parse(r#"{"matrix": [1,0,0,0, 0,1,0,0, 0,0,1,0, 5,6,7,1], "translation": [9, 9, 9]}"#).unwrap();
+        assert_eq!(transform_point(&node_transform(&matrix), Vector3::zero()), Vector3::new(5.0, 6.0, 7.0));
+
+        // El hijo se transforma primero y después el padre
+        let parent = Json::parse(r#"{"scale": [2, 2, 2]}"#).unwrap();
+        let combined = multiply(&node_transform(&parent), &node_transform(&matrix));
+        assert_eq!(transform_point(&combined, Vector3::new(1.0, 0.0, 0.0)), Vector3::new(12.0, 12.0, 14.0));
+        assert_eq!(multiply(&IDENTITY, &combined), combined);
+    }
+
+    #[test]
+    fn accessor_reads_strided_and_normalized_components() {
+        // Dos VEC2 de u8 normalizados separados por un byte de relleno
+        let document = r#"{
+            "accessors": [{"bufferView": 0, "byteOffset": 1, "componentType": 5121, "normalized": true, "count": 2, "type": "VEC2"}],
+            "bufferViews": [{"buffer": 0, "byteStride": 3}]
+        }"#;
+        let values = accessor_of(document, vec![9, 255, 0, 9, 51, 255]).unwrap();
+        assert_eq!(values, vec![1.0, 0.0, 0.2, 1.0]);
+
+        // Sin bufferView son ceros
+        let zeros = r#"{"accessors": [{"componentType": 5126, "count": 2, "type": "VEC3"}]}"#;
+        assert_eq!(accessor_of(zeros, Vec::new()).unwrap(), vec![0.0; 6]);
+    }
+
+    #[test]
+    fn accessor_rejects_ranges_outside_the_buffer() {
+        let accessor = |count: &str, stride: &str, offset: &str| format!(
+            r#"{{"accessors": [{{"bufferView": 0, "byteOffset": {offset}, "componentType": 5126, "count": {count}, "type": "VEC3"}}],
+                "bufferViews": [{{"buffer": 0, "byteStride": {stride}}}]}}"#
+        );
+        let buffer = floats_as_bytes(&[0.0; 6]);
+        assert!(accessor_of(&accessor("2", "12", "0"), buffer.clone()).is_ok());
+
+        // Un elemento de más, un corrimiento, un stride más corto que el elemento y cuentas que
+        // desbordan usize tienen que fallar sin reservar memoria
+        for (count, stride, offset) in [("3", "12", "0"), ("2", "12", "4"), ("2", "4", "0"), ("1e19", "12", "0"), ("2", "1e19", "0"), ("1", "12", "1e19")] {
+            assert!(accessor_of(&accessor(count, stride, offset), buffer.clone()).is_err(), "{count} {stride} {offset}");
+        }
+        let missing_buffer = r#"{"accessors": [{"bufferView": 0, "componentType": 5126, "count": 1, "type": "SCALAR"}], "bufferViews": [{"buffer": 3}]}"#;
+        assert!(accessor_of(missing_buffer, buffer).is_err());
+    }
+
+    #[test]
+    fn embedded_glb_triangle_lands_where_its_node_puts_it() {
+        // Un triángulo en el plano z = 0, colocado por su nodo en z = -2
+        let positions = floats_as_bytes(&[-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0]);
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0]}],
+            "nodes": [{"mesh": 0, "translation": [0, 0, -2]}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "buffers": [{"byteLength": 36}]
+        }"#;
+        let path = std::env::temp_dir().join(format!("gltf_loader_{}_triangle.glb", std::process::id()));
+        std::fs::write(&path, glb(json, &positions)).unwrap();
+        let model = load_gltf(path.to_str().unwrap(), &TextureManager::new());
+        std::fs::remove_file(&path).unwrap();
+
+        let instances = model.unwrap().instances();
+        assert_eq!(instances.len(), 1);
+        let hit = instances[0].ray_intersect(&Vector3::new(0.0, 0.0, 3.0), &Vector3::new(0.0, 0.0, -1.0));
+        assert!(hit.is_intersecting);
+        assert!((hit.point.z + 2.0).abs() < 1e-4, "{:?}", hit.point);
+        assert!(!instances[0].ray_intersect(&Vector3::new(3.0, 0.0, 3.0), &Vector3::new(0.0, 0.0, -1.0)).is_intersecting);
+    }
+
+    #[test]
+    fn line_primitives_are_counted_as_skipped() {
+        let positions = floats_as_bytes(&[-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0]);
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "nodes": [{"mesh": 0}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}, {"attributes": {"POSITION": 0}, "mode": 1}]}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "buffers": [{"byteLength": 36}]
+        }"#;
+        let path = std::env::temp_dir().join(format!("gltf_loader_{}_lines.glb", std::process::id()));
+        std::fs::write(&path, glb(json, &positions)).unwrap();
+        let model = load_gltf(path.to_str().unwrap(), &TextureManager::new());
+        std::fs::remove_file(&path).unwrap();
+
+        let model = model.unwrap();
+        assert_eq!(model.instances().len(), 1);
+        assert_eq!(model.skipped_primitives, 1);
+    }
+
+    #[test]
+    fn malformed_material_factors_and_views_are_errors() {
+        let positions = floats_as_bytes(&[-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0]);
+        let load = |material: &str, image_view: &str| {
+            let json = format!(r#"{{
+                "asset": {{"version": "2.0"}},
+                "nodes": [{{"mesh": 0}}],
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "material": 0}}]}}],
+                "materials": [{material}],
+                "textures": [{{"source": 0}}],
+                "images": [{{"bufferView": 1, "mimeType": "image/png"}}],
+                "accessors": [{{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}}],
+                "bufferViews": [{{"buffer": 0, "byteLength": 36}}, {image_view}],
+                "buffers": [{{"byteLength": 36}}]
+            }}"#);
+            let path = std::env::temp_dir().join(format!("gltf_loader_{}_malformed.glb", std::process::id()));
+            std::fs::write(&path, glb(&json, &positions)).unwrap();
+            let model = load_gltf(path.to_str().unwrap(), &TextureManager::new());
+            std::fs::remove_file(&path).unwrap();
+            model
+        };
+        let view = r#"{"buffer": 0, "byteOffset": 32, "byteLength": 4}"#;
+        assert!(load(r#"{"pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1]}, "emissiveFactor": [1, 0.5, 0]}"#, view).is_ok());
+
+        // Factores cortos, una imagen que se sale del buffer y un largo que desborda usize
+        for (material, view) in [
+            (r#"{"pbrMetallicRoughness": {"baseColorFactor": [1, 0]}}"#, view),
+            (r#"{"emissiveFactor": [1]}"#, view),
+            (r#"{"normalTexture": {"index": 0}}"#, r#"{"buffer": 0, "byteOffset": 30, "byteLength": 16}"#),
+            (r#"{"normalTexture": {"index": 0}}"#, r#"{"buffer": 0, "byteOffset": 1e19, "byteLength": 1e19}"#),
+        ] {
+            assert!(load(material, view).is_err(), "{material} {view}");
+        }
+    }
+
+    #[test]
+    fn embedded_normal_map_stays_linear_with_linear_lighting() {
+        // Un mapa de normales plano (128, 128, 255) embebido detrás de los vértices
+        let positions = floats_as_bytes(&[-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0]);
+        let image = Image::gen_image_color(4, 4, Color::new(128, 128, 255, 255));
+        let png = image.export_image_to_memory(".png").unwrap();
+        let json = format!(r#"{{
+            "asset": {{"version": "2.0"}},
+            "nodes": [{{"mesh": 0}}],
+            "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "material": 0}}]}}],
+            "materials": [{{"normalTexture": {{"index": 0}}}}],
+            "textures": [{{"source": 0}}],
+            "images": [{{"bufferView": 1, "mimeType": "image/png"}}],
+            "accessors": [{{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}}],
+            "bufferViews": [{{"buffer": 0, "byteLength": 36}}, {{"buffer": 0, "byteOffset": 36, "byteLength": {}}}],
+            "buffers": [{{"byteLength": {}}}]
+        }}"#, png.len(), 36 + png.len());
+        let path = std::env::temp_dir().join(format!("gltf_loader_{}_normal_map.glb", std::process::id()));
+        std::fs::write(&path, glb(&json, &[positions, png.to_vec()].concat())).unwrap();
+        let mut texture_manager = TextureManager::new();
+        texture_manager.linear_lighting = true;
+        let model = load_gltf(path.to_str().unwrap(), &texture_manager);
+        std::fs::remove_file(&path).unwrap();
+        model.unwrap();
+
+        let name = format!("{}#image0.png", path.to_str().unwrap());
+        let normal = texture_manager.get_normal_from_map(&name, 0.5, 0.5, TextureFilter::Bilinear, TextureWrap::Repeat).unwrap();
+        assert!((normal - Vector3::new(0.0, 0.0, 1.0)).length() < 0.01, "{normal:?}");
+    }
+}
@@ -0,0 +1,159 @@
+use raylib::prelude::*;
+use std::sync::Arc;
+use crate::material::Material;
+use crate::ray_intersect::{Intersect, RayIntersect, TraversalCost};
+
+pub type SharedGeometry = Arc<dyn RayIntersect + Send + Sync>;
+
+// Copia de una geometría compartida colocada con `transform` (espacio local -> mundo).
+// El rayo se lleva al espacio local, se intersecta la geometría original y el impacto vuelve
+// al mundo, así cien instancias de un mismo objeto ocupan la memoria de uno
+pub struct Instance {
+    pub geometry: SharedGeometry,
+    pub transform: Matrix,
+    pub material_override: Option<Material>,
+    inverse: Matrix,
+}
+
+impl Instance {
+    pub fn new(geometry: SharedGeometry, transform: Matrix, material_override: Option<Material>) -> Self {
+        Instance {
+            geometry,
+            transform,
+            material_override,
+            inverse: transform.inverted(),
+        }
+    }
+}
+
+fn transform_point(m: &Matrix, p: Vector3) -> Vector3 {
+    Vector3::new(
+        m.m0 * p.x + m.m4 * p.y + m.m8 * p.z + m.m12,
+        m.m1 * p.x + m.m5 * p.y + m.m9 * p.z + m.m13,
+        m.m2 * p.x + m.m6 * p.y + m.m10 * p.z + m.m14,
+    )
+}
+
+// Solo la parte lineal: las direcciones no se trasladan
+fn transform_vector(m: &Matrix, v: Vector3) -> Vector3 {
+    Vector3::new(
+        m.m0 * v.x + m.m4 * v.y + m.m8 * v.z,
+        m.m1 * v.x + m.m5 * v.y + m.m9 * v.z,
+        m.m2 * v.x + m.m6 * v.y + m.m10 * v.z,
+    )
+}
+
+// Las normales usan la transpuesta de la inversa, para seguir perpendiculares con escalas no uniformes
+pub(crate) fn transform_normal(inverse: &Matrix, n: Vector3) -> Vector3 {
+    Vector3::new(
+        inverse.m0 * n.x + inverse.m1 * n.y + inverse.m2 * n.z,
+        inverse.m4 * n.x + inverse.m5 * n.y + inverse.m6 * n.z,
+        inverse.m8 * n.x + inverse.m9 * n.y + inverse.m10 * n.z,
+    )
+    .normalized()
+}
+
+impl RayIntersect for Instance {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        // Con escala la dirección local cambia de largo. Las primitivas que marchan o comparan
+        // distancias esperan una dirección unitaria: se normaliza y la distancia se vuelve a
+        // llevar a la escala del rayo del mundo
+        let local_origin = transform_point(&self.inverse, *ray_origin);
+        let local_direction = transform_vector(&self.inverse, *ray_direction);
+        let scale = local_direction.length();
+        if scale <= 0.0 || !scale.is_finite() {
+            return Intersect::empty();
+        }
+
+        let mut intersect = self.geometry.ray_intersect(&local_origin, &(local_direction / scale));
+        if !intersect.is_intersecting {
+            return intersect;
+        }
+
+        intersect.distance /= scale;
+        intersect.point = *ray_origin + *ray_direction * intersect.distance;
+        intersect.normal = transform_normal(&self.inverse, intersect.normal);
+        intersect.face_normal = transform_normal(&self.inverse, intersect.face_normal);
+        // Instancias anidadas: primero se deshace esta y después la de adentro
+        intersect.object_inverse = Some(match intersect.object_inverse {
+            Some(inner) => self.inverse * inner,
+            None => self.inverse,
+        });
+        if self.material_override.is_some() {
+            intersect.material_override = self.material_override.clone();
+        }
+        intersect
+    }
+
+    fn material(&self) -> &Material {
+        self.material_override.as_ref().unwrap_or_else(|| self.geometry.material())
+    }
+
+    fn center(&self) -> Vector3 {
+        transform_point(&self.transform, self.geometry.center())
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        let (min, max) = self.geometry.bounds();
+        let finite = |v: Vector3| v.x.is_finite() && v.y.is_finite() && v.z.is_finite();
+        if !finite(min) || !finite(max) {
+            let infinity = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+            return (-infinity, infinity);
+        }
+
+        // Caja que envuelve las ocho esquinas transformadas
+        let mut world_min = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut world_max = -world_min;
+        for i in 0..8 {
+            let corner = Vector3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            let p = transform_point(&self.transform, corner);
+            world_min = Vector3::new(world_min.x.min(p.x), world_min.y.min(p.y), world_min.z.min(p.z));
+            world_max = Vector3::new(world_max.x.max(p.x), world_max.y.max(p.y), world_max.z.max(p.z));
+        }
+        (world_min, world_max)
+    }
+
+    fn traversal_cost(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Option<TraversalCost> {
+        self.geometry.traversal_cost(&transform_point(&self.inverse, *ray_origin), &transform_vector(&self.inverse, *ray_direction).normalized())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cylinder::Cylinder;
+    use crate::torus::Torus;
+
+    #[test]
+    fn scaled_instances_hit_at_world_distances() {
+        // Cilindro de radio 1 y alto 2 estirado a semiejes 2, 0.5 y 1
+        let cylinder: SharedGeometry = Arc::new(Cylinder::new(Vector3::zero(), Vector3::new(0.0, 1.0, 0.0), 1.0, 2.0, Material::black()));
+        let stretched = Instance::new(cylinder, Matrix::scale(2.0, 0.5, 1.0), None);
+        for (origin, direction, distance, normal) in [
+            (Vector3::new(5.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0), 3.0, Vector3::new(1.0, 0.0, 0.0)),
+            (Vector3::new(0.0, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0), 4.5, Vector3::new(0.0, 1.0, 0.0)),
+            (Vector3::new(0.0, 0.0, -3.0), Vector3::new(0.0, 0.0, 1.0), 2.0, Vector3::new(0.0, 0.0, -1.0)),
+        ] {
+            let hit = stretched.ray_intersect(&origin, &direction);
+            assert!(hit.is_intersecting);
+            assert!((hit.distance - distance).abs() < 1e-4, "{} en vez de {}", hit.distance, distance);
+            assert!((hit.point - (origin + direction * distance)).length() < 1e-4);
+            assert!((hit.normal - normal).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn marched_primitives_work_inside_scaled_instances() {
+        // El toro se intersecta marchando: con una dirección local de largo 4 se pasaba de largo
+        let torus: SharedGeometry = Arc::new(Torus::new(Vector3::zero(), Vector3::new(0.0, 1.0, 0.0), 1.0, 0.25, Material::black()));
+        let squashed = Instance::new(torus, Matrix::scale(1.0, 0.25, 1.0), None);
+        let hit = squashed.ray_intersect(&Vector3::new(1.0, 3.0, 0.0), &Vector3::new(0.0, -1.0, 0.0));
+        assert!(hit.is_intersecting);
+        assert!((hit.point.y - 0.0625).abs() < 1e-3, "{:?}", hit.point);
+        assert!((hit.distance - (3.0 - 0.0625)).abs() < 1e-3);
+    }
+}
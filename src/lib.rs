@@ -0,0 +1,2995 @@
+// Núcleo del raytracer: escena, primitivas, texturas, cast_ray y render. El binario (main.rs)
+// solo lee los argumentos y maneja la ventana, así el render puede usarse desde otras herramientas
+use raylib::prelude::*;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use rayon::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand::rngs::SmallRng;
+
+pub mod framebuffer;
+pub mod ray_intersect;
+pub mod aabb;
+pub mod cube;
+pub mod plane;
+pub mod disk;
+pub mod sphere;
+pub mod cone;
+pub mod torus;
+pub mod instance;
+pub mod cylinder;
+pub mod csg;
+pub mod scene_graph;
+pub mod mesh;
+pub mod sdf;
+pub mod quad;
+pub mod camera;
+pub mod light;
+pub mod material;
+pub mod presets;
+pub mod textures;
+pub mod blocks;
+pub mod settings;
+pub mod accumulation;
+pub mod scene;
+pub mod aov;
+pub mod postprocess;
+pub mod exr;
+pub mod daynight;
+pub mod noise;
+pub mod caustics;
+pub mod shadow_map;
+pub mod bookmarks;
+pub mod metadata;
+pub mod overlay;
+pub mod gltf_loader;
+
+use framebuffer::Framebuffer;
+use ray_intersect::{inverse_direction, Intersect, RayIntersect, TraversalCost};
+use camera::Camera;
+use material::{vector3_to_color, vector3_to_color_dithered, Material, NormalMapSpace};
+use textures::{linear_to_srgb, TextureManager};
+use blocks::{create_decorations, create_ground_plane, create_round_table, create_square_table, BlockGrid, Ground};
+use settings::{AaPattern, CropRect, RenderMode, RenderSettings, StereoLayout};
+use accumulation::{AccumulationBuffer, FrameHistory};
+use scene::{Scene, SceneObject};
+use sphere::Sphere;
+use aov::{AovBuffers, AovSample};
+use caustics::CausticMap;
+use shadow_map::ShadowMap;
+
+// Margen numérico interno (paso para atravesar caras descartadas, distancias mínimas); el sesgo
+// de los rayos secundarios se ajusta con settings.origin_bias
+const ORIGIN_BIAS: f32 = 1e-4;
+const MIN_BIAS_SCALE: f32 = 0.01;
+const TILE_SIZE: u32 = 32;
+
+// Rota un vector alrededor del eje unitario `axis` (Rodrigues). Con el eje Y, un ángulo positivo
+// lleva +X hacia +Z, el sentido de siempre del giro del diorama
+fn rotate_around_axis(point: Vector3, axis: Vector3, angle: f32) -> Vector3 {
+    let (sin_a, cos_a) = angle.sin_cos();
+    point * cos_a + point.cross(axis) * sin_a + axis * (axis.dot(point) * (1.0 - cos_a))
+}
+
+// Origen de un rayo secundario, despegado de la superficie según settings.origin_bias
+fn offset_origin(intersect: &Intersect, direction: &Vector3, settings: &RenderSettings) -> Vector3 {
+    offset_origin_by(intersect, direction, settings.origin_bias * bias_scale(intersect))
+}
+
+// El error de redondeo del punto de impacto crece con el tamaño de sus coordenadas y con la
+// distancia que recorrió el rayo: un sesgo fijo se queda corto lejos del origen (acné) y sobra
+// cerca, donde atraviesa objetos finos. Los sesgos se escalan por esto, con un piso chico para
+// los impactos en el mismo origen
+fn bias_scale(intersect: &Intersect) -> f32 {
+    let point = intersect.point;
+    point.x.abs().max(point.y.abs()).max(point.z.abs()).max(intersect.distance).max(MIN_BIAS_SCALE)
+}
+
+fn offset_origin_by(intersect: &Intersect, direction: &Vector3, bias: f32) -> Vector3 {
+    // Sin una normal válida (impacto degenerado) se avanza a lo largo del rayo, nunca a NaN
+    let normal_length = intersect.normal.length();
+    if normal_length.is_nan() || normal_length < 0.5 {
+        return intersect.point + direction.normalized() * bias;
+    }
+    let offset = intersect.normal * bias;
+    if direction.dot(intersect.normal) < 0.0 {
+        intersect.point - offset
+    } else {
+        intersect.point + offset
+    }
+}
+
+// Base tangente robusta: parte del eje del mundo menos alineado con la normal,
+// así no degenera en ninguna de las seis caras del cubo
+fn tangent_frame(normal: &Vector3) -> (Vector3, Vector3) {
+    let abs_normal = Vector3::new(normal.x.abs(), normal.y.abs(), normal.z.abs());
+    let reference = if abs_normal.x <= abs_normal.y && abs_normal.x <= abs_normal.z {
+        Vector3::new(1.0, 0.0, 0.0)
+    } else if abs_normal.y <= abs_normal.z {
+        Vector3::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3::new(0.0, 0.0, 1.0)
+    };
+
+    let tangent = reference.cross(*normal).normalized();
+    let bitangent = normal.cross(tangent);
+    (tangent, bitangent)
+}
+
+// Dirección al azar, uniforme en ángulo sólido, dentro del cono de semiángulo `half_angle`
+// alrededor de `axis` (unitario)
+fn sample_cone(axis: &Vector3, half_angle: f32, rng: &mut SmallRng) -> Vector3 {
+    let cos_theta = 1.0 - rng.random::<f32>() * (1.0 - half_angle.cos());
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.random::<f32>();
+    let (tangent, bitangent) = tangent_frame(axis);
+    tangent * (phi.cos() * sin_theta) + bitangent * (phi.sin() * sin_theta) + *axis * cos_theta
+}
+
+fn reflect(incident: &Vector3, normal: &Vector3) -> Vector3 {
+    *incident - *normal * 2.0 * incident.dot(*normal)
+}
+
+// `normal` apunta hacia afuera del objeto. Si el rayo sale (cosi > 0) se invierten los
+// índices y la normal; None indica reflexión interna total
+fn refract(incident: &Vector3, normal: &Vector3, refractive_index: f32) -> Option<Vector3> {
+    if incident.dot(*normal) > 0.0 {
+        refract_between(incident, normal, refractive_index, 1.0)
+    } else {
+        refract_between(incident, normal, 1.0, refractive_index)
+    }
+}
+
+// Snell del medio de índice `from` al de índice `to`; la normal se da vuelta hacia el lado
+// del rayo incidente. None indica reflexión interna total
+fn refract_between(incident: &Vector3, normal: &Vector3, from: f32, to: f32) -> Option<Vector3> {
+    let n = if incident.dot(*normal) > 0.0 { -*normal } else { *normal };
+    let cosi = -incident.dot(n).clamp(-1.0, 1.0);
+    let eta = from / to;
+    let k = 1.0 - eta * eta * (1.0 - cosi * cosi);
+
+    if k < 0.0 {
+        None
+    } else {
+        Some(*incident * eta + n * (eta * cosi - k.sqrt()))
+    }
+}
+
+// Color de una película delgada vista con coseno `cos_view`: la luz que rebota arriba y abajo
+// de la película recorre 2·n·grosor·cos(θt) de más, y cada longitud de onda (R, G, B) se refuerza
+// o se cancela según cuántas ondas entran en esa diferencia
+fn thin_film_tint(cos_view: f32, thickness: f32) -> Vector3 {
+    const FILM_INDEX: f32 = 1.4;
+    const WAVELENGTHS: [f32; 3] = [650.0, 510.0, 475.0];
+    let sin_t = (1.0 - cos_view * cos_view).max(0.0).sqrt() / FILM_INDEX;
+    let cos_t = (1.0 - sin_t * sin_t).sqrt();
+    let path_difference = 2.0 * FILM_INDEX * thickness * cos_t;
+    let band = |wavelength: f32| 0.5 + 0.5 * (2.0 * PI * path_difference / wavelength).cos();
+    Vector3::new(band(WAVELENGTHS[0]), band(WAVELENGTHS[1]), band(WAVELENGTHS[2]))
+}
+
+// Color que ve un rayo que escapa de la escena (en el marco del diorama): el cubemap si hay uno
+// cargado o el color del cielo, con las estrellas y las nubes fijas al mundo y no al diorama.
+// Sin dirección queda el color plano del cielo (el corte por profundidad, la luz de relleno).
+// Todo lo que lee el cielo pasa por acá, así settings.skybox_intensity lo escala en un solo lugar
+fn sky_color(ray_direction: Option<&Vector3>, scene: &Scene, settings: &RenderSettings) -> Vector3 {
+    let radiance = match ray_direction {
+        Some(direction) => sky_radiance(direction, scene, settings),
+        None => settings.sky_color,
+    };
+    radiance * settings.skybox_intensity
+}
+
+fn sky_radiance(ray_direction: &Vector3, scene: &Scene, settings: &RenderSettings) -> Vector3 {
+    let world_direction = rotate_around_axis(*ray_direction, scene.rotation_axis, scene.rotation);
+    let base = scene.texture_manager.sample_cubemap(&world_direction).unwrap_or(settings.sky_color);
+    if settings.star_visibility <= 0.0 && settings.cloud_coverage <= 0.0 {
+        return base;
+    }
+
+    let mut color = base;
+    if settings.star_visibility > 0.0 {
+        let star = daynight::star_field(&world_direction, settings.star_density);
+        color += Vector3::one() * (star * settings.star_brightness * settings.star_visibility);
+    }
+    if settings.cloud_coverage > 0.0 {
+        color = cloud_layer(color, &world_direction, scene.time, settings);
+    }
+    color
+}
+
+// Capa de nubes sobre el horizonte: el rayo se proyecta sobre un plano alto y ahí se evalúa
+// ruido fractal que se desplaza con el tiempo. Las nubes toman el brillo del cielo, así de
+// noche quedan oscuras
+fn cloud_layer(sky: Vector3, direction: &Vector3, time: f32, settings: &RenderSettings) -> Vector3 {
+    const CLOUD_SCALE: f32 = 1.5;
+    if direction.y <= 0.0 {
+        return sky;
+    }
+
+    let drift = time * settings.cloud_drift;
+    let plane = Vector3::new(direction.x / direction.y + drift, direction.z / direction.y, 0.0) * CLOUD_SCALE;
+    let density = noise::fbm(plane, 5);
+
+    let threshold = 1.0 - settings.cloud_coverage;
+    let t = ((density - threshold) / settings.cloud_softness.max(1e-3)).clamp(0.0, 1.0);
+    // Cerca del horizonte las nubes se ven de canto y se desvanecen
+    let amount = t * t * (3.0 - 2.0 * t) * (direction.y * 4.0).min(1.0);
+
+    let brightness = sky.x.max(sky.y).max(sky.z);
+    let cloud = Vector3::one() * (brightness * 0.9) + sky * 0.2;
+    sky + (cloud - sky) * amount
+}
+
+// Luz difusa que recibe una superficie con normal `normal` bajo la cúpula del cielo (de color
+// uniforme, con el suelo oscuro bajo el horizonte): la integral del coseno sobre la parte
+// visible de la cúpula se reduce a mezclar según cuánto mira la normal hacia arriba
+fn sky_irradiance(sky_color: Vector3, normal: &Vector3) -> Vector3 {
+    sky_color * ((1.0 + normal.y) * 0.5)
+}
+
+// Dirección al azar con distribución coseno alrededor de `normal`: la normal más un punto al
+// azar de la esfera unitaria. None en el caso degenerado (punto opuesto a la normal)
+fn sample_cosine(normal: &Vector3, rng: &mut SmallRng) -> Option<Vector3> {
+    let z = rng.random::<f32>() * 2.0 - 1.0;
+    let phi = rng.random::<f32>() * 2.0 * PI;
+    let ring = (1.0 - z * z).sqrt();
+    let direction = *normal + Vector3::new(ring * phi.cos(), ring * phi.sin(), z);
+    (direction.length() > 1e-6).then(|| direction.normalized())
+}
+
+// Luz del cielo que llega al punto mirando qué parte de la cúpula está libre: rayos con
+// distribución coseno alrededor de la normal que, si no chocan nada en
+// settings.sky_occlusion_distance, traen el cielo de su dirección. Bajo el horizonte se ve
+// suelo oscuro, así que sin obstáculos da lo mismo que sky_irradiance
+fn occluded_sky_irradiance(intersect: &Intersect, normal: &Vector3, scene: &Scene, settings: &RenderSettings, samples: u32, rng: &mut SmallRng) -> Vector3 {
+    let mut sum = Vector3::zero();
+    for _ in 0..samples {
+        let Some(direction) = sample_cosine(normal, rng).filter(|direction| direction.y > 0.0) else {
+            continue;
+        };
+        let origin = offset_origin(intersect, &direction, settings);
+        let transmittance = light_transmittance(&origin, &(origin + direction * settings.sky_occlusion_distance), scene);
+        sum += sky_color(Some(&direction), scene, settings) * transmittance;
+    }
+    sum / samples.max(1) as f32
+}
+
+// Factor de la sombra de contacto: un rayo con distribución coseno de largo
+// settings.contact_shadow_distance; si choca algo, oscurece más cuanto más cerca esté
+fn contact_shadow(intersect: &Intersect, normal: &Vector3, scene: &Scene, settings: &RenderSettings, rng: &mut SmallRng) -> f32 {
+    let Some(direction) = sample_cosine(normal, rng) else {
+        return 1.0;
+    };
+    let origin = offset_origin(intersect, &direction, settings);
+    match closest_hit_within(&origin, &direction, settings.contact_shadow_distance, scene.objects(), &scene.texture_manager) {
+        Some(distance) => 1.0 - settings.contact_shadow_strength * (1.0 - distance / settings.contact_shadow_distance),
+        None => 1.0,
+    }
+}
+
+// Aproximación de Schlick del reflejo de Fresnel de un barniz (índice ~1.5)
+fn clearcoat_fresnel(cos_view: f32) -> f32 {
+    const COAT_REFLECTANCE: f32 = 0.04;
+    COAT_REFLECTANCE + (1.0 - COAT_REFLECTANCE) * (1.0 - cos_view).clamp(0.0, 1.0).powi(5)
+}
+
+// Desvía la dirección al azar según la rugosidad (reflejo borroso al acumular cuadros),
+// sin dejar que cruce al otro lado de la superficie
+fn perturb_direction(direction: Vector3, normal: &Vector3, roughness: f32, rng: &mut SmallRng) -> Vector3 {
+    let offset = Vector3::new(
+        rng.random::<f32>() * 2.0 - 1.0,
+        rng.random::<f32>() * 2.0 - 1.0,
+        rng.random::<f32>() * 2.0 - 1.0,
+    );
+    let perturbed = (direction + offset * roughness).normalized();
+    if perturbed.dot(*normal) * direction.dot(*normal) > 0.0 {
+        perturbed
+    } else {
+        direction
+    }
+}
+
+// Cuánto difuso da una luz que llega con coseno `cos_theta`: el término de Lambert tal cual o,
+// con bandas, el escalón de la rampa que le toca (la cara sin luz sigue en 0)
+fn diffuse_response(material: &Material, cos_theta: f32) -> f32 {
+    let lambert = cos_theta.max(0.0);
+    if material.toon_bands == 0 || lambert <= 0.0 {
+        return lambert;
+    }
+    if material.toon_bands == 1 {
+        return 1.0;
+    }
+    let bands = material.toon_bands as f32;
+    let band = (lambert * bands).ceil().min(bands);
+    material.toon_min + (1.0 - material.toon_min) * (band - 1.0) / (bands - 1.0)
+}
+
+// Brillo especular hacia el ojo. Phong clásico compara la vista con el reflejo de la luz;
+// Blinn-Phong compara la normal con el vector medio entre vista y luz, que da un brillo más
+// suave en ángulos rasantes. Su lóbulo es más ancho, así que el exponente se multiplica por 4
+// para que el brillo quede del mismo tamaño que con Phong
+fn specular_lobe(view_dir: &Vector3, light_dir: &Vector3, normal: &Vector3, exponent: f32, blinn: bool) -> f32 {
+    if blinn {
+        let half_vector = (*view_dir + *light_dir).normalized();
+        normal.dot(half_vector).max(0.0).powf(exponent * 4.0)
+    } else {
+        let reflect_dir = reflect(&-*light_dir, normal).normalized();
+        view_dir.dot(reflect_dir).max(0.0).powf(exponent)
+    }
+}
+
+// Brillo anisotrópico de Ashikhmin-Shirley: el exponente es menor a lo largo de la tangente, así
+// que el brillo se estira en esa dirección. La tangente es la dirección del material proyectada
+// sobre la cara; si es casi perpendicular a ella se usa la misma base que el mapa de normales
+fn anisotropic_lobe(view_dir: &Vector3, light_dir: &Vector3, normal: &Vector3, exponent: f32, aniso: f32, direction: &Vector3) -> f32 {
+    let projected = *direction - *normal * normal.dot(*direction);
+    let (tangent, bitangent) = if projected.length() > 1e-3 {
+        let tangent = projected.normalized();
+        (tangent, normal.cross(tangent))
+    } else {
+        tangent_frame(normal)
+    };
+
+    let half_vector = (*view_dir + *light_dir).normalized();
+    let n_dot_h = normal.dot(half_vector).max(0.0);
+    let sin_squared = 1.0 - n_dot_h * n_dot_h;
+    if sin_squared < 1e-6 {
+        return n_dot_h;
+    }
+    let aniso = aniso.clamp(0.0, 0.95);
+    let (along, across) = (exponent * 4.0 * (1.0 - aniso), exponent * 4.0 * (1.0 + aniso));
+    let h_t = half_vector.dot(tangent);
+    let h_b = half_vector.dot(bitangent);
+    n_dot_h.powf((along * h_t * h_t + across * h_b * h_b) / sin_squared)
+}
+
+fn emission_reach(material: &Material, settings: &RenderSettings) -> f32 {
+    if material.emission_reach > 0.0 { material.emission_reach } else { settings.emissive_cutoff }
+}
+
+// Si el emisivo puede iluminar `point`: el propio bloque emisivo no se ilumina a sí mismo
+fn lights_point(object: &SceneObject, point: &Vector3) -> bool {
+    let material = object.material();
+    if !material.is_emissive || !material.visible {
+        return false;
+    }
+    let (min, max) = object.bounds();
+    let inside = |p: f32, low: f32, high: f32| p >= low - ORIGIN_BIAS && p <= high + ORIGIN_BIAS;
+    !(inside(point.x, min.x, max.x) && inside(point.y, min.y, max.y) && inside(point.z, min.z, max.z))
+}
+
+// Distancia del centro del emisivo a `point` y su aporte estimado ahí, sin sombras y con el
+// brillo del instante `time`
+fn emitter_estimate(object: &SceneObject, point: &Vector3, time: f32, settings: &RenderSettings) -> (f32, f32) {
+    let (min, max) = object.bounds();
+    let to_point = *point - object.center();
+    let distance = to_point.length();
+    // El alcance se vuelve a medir con el punto muestreado de la fuente; acá solo pesa en la
+    // estimación, desde el borde más cercano posible (un panel grande llega más lejos que su centro)
+    let nearest = distance - (max - min).length() * 0.5;
+    let estimate = if nearest < emission_reach(object.material(), settings) {
+        object.material().emission_intensity_at(time) * object.emitting_area(&(to_point / distance.max(ORIGIN_BIAS)))
+            / (distance * distance).max(ORIGIN_BIAS)
+    } else {
+        0.0
+    };
+    (distance, estimate)
+}
+
+// Pasa a `visit` los emisivos que iluminan `point` y devuelve cuánto escalar su luz. Sin
+// límite (settings.max_emissive_lights = 0) van todos, sin estimar nada. Con más candidatos
+// que el límite quedan los más cercanos (por su centro y, a igual distancia, por orden en la
+// escena: la elección no cambia entre cuadros y no parpadea) y la escala aproxima la luz de
+// todos según el aporte estimado. Con settings.emissive_importance_sampling queda uno solo,
+// sorteado
+fn nearby_emitters<'a>(
+    objects: &'a [SceneObject],
+    point: &Vector3,
+    time: f32,
+    settings: &RenderSettings,
+    rng: &mut SmallRng,
+    mut visit: impl FnMut(&'a SceneObject, &mut SmallRng),
+) -> f32 {
+    let emitters = objects.iter().filter(|object| lights_point(object, point));
+    let limit = settings.max_emissive_lights;
+    if !settings.emissive_importance_sampling && limit == 0 {
+        for object in emitters {
+            visit(object, rng);
+        }
+        return 1.0;
+    }
+
+    // Muestreo por importancia en una sola pasada (reservorio): queda un emisivo elegido con
+    // probabilidad estimación / total, y su luz se divide por esa probabilidad
+    if settings.emissive_importance_sampling {
+        let mut total = 0.0;
+        let mut chosen = None;
+        for object in emitters {
+            let (_, estimate) = emitter_estimate(object, point, time, settings);
+            if estimate <= 0.0 {
+                continue;
+            }
+            total += estimate;
+            if rng.random::<f32>() * total < estimate {
+                chosen = Some((estimate, object));
+            }
+        }
+        return match chosen {
+            Some((estimate, object)) => {
+                visit(object, rng);
+                total / estimate
+            }
+            None => 1.0,
+        };
+    }
+
+    let mut candidates: Vec<(f32, f32, &SceneObject)> = emitters
+        .map(|object| {
+            let (distance, estimate) = emitter_estimate(object, point, time, settings);
+            (distance, estimate, object)
+        })
+        .collect();
+    let total: f32 = candidates.iter().map(|(_, estimate, _)| estimate).sum();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+    candidates.truncate(limit);
+    let kept: f32 = candidates.iter().map(|(_, estimate, _)| estimate).sum();
+    for &(_, _, object) in &candidates {
+        visit(object, rng);
+    }
+    if kept > 0.0 { total / kept } else { 1.0 }
+}
+
+// Emisión propia del objeto golpeado; el mapa de emisión (o, sin él, la textura difusa) define
+// el patrón de brillo y la animación del material su intensidad en el instante `time`
+fn self_emission(intersect: &Intersect, time: f32, texture_manager: &TextureManager) -> Vector3 {
+    let material = intersect.effective_material();
+    if !material.is_emissive {
+        return Vector3::zero();
+    }
+
+    let emission_base = material.emission_color * material.emission_intensity_at(time);
+    match material.emission_map_id.as_ref().or(material.texture_id.as_ref()) {
+        Some(emission_path) => {
+            let lod = texture_manager.texture_lod(emission_path, intersect.uv_footprint);
+            emission_base * texture_manager.get_pixel_color(emission_path, intersect.u, intersect.v, lod, material.filter, material.wrap)
+        }
+        None => emission_base,
+    }
+}
+
+// Cara trasera de un material de una sola cara: el rayo la atraviesa sin verla. Se mira la normal
+// de la cara, porque la suavizada de una malla puede apuntar hacia atrás cerca del contorno
+fn is_back_face(intersect: &Intersect, ray_direction: &Vector3) -> bool {
+    !intersect.effective_material().double_sided && ray_direction.dot(intersect.face_normal) > 0.0
+}
+
+fn is_cut_out(intersect: &Intersect, texture_manager: &TextureManager) -> bool {
+    match (intersect.effective_material().alpha_cutout, &intersect.effective_material().texture_id) {
+        (Some(threshold), Some(texture_path)) => {
+            texture_manager.get_pixel_alpha(texture_path, intersect.u, intersect.v, intersect.effective_material().wrap) < threshold
+        }
+        _ => false,
+    }
+}
+
+// Consulta de oclusión: ¿el objeto corta el rayo antes de max_distance? Los recortes por alpha
+// y las caras traseras necesitan el impacto completo; lo demás usa la prueba rápida del objeto
+fn blocks_ray(
+    object: &dyn RayIntersect,
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    inv_direction: &Vector3,
+    max_distance: f32,
+    texture_manager: &TextureManager,
+) -> bool {
+    let material = object.material();
+    if material.alpha_cutout.is_some() || !material.double_sided {
+        let intersect = intersect_object(object, ray_origin, ray_direction, inv_direction, texture_manager);
+        return intersect.is_intersecting && intersect.distance < max_distance;
+    }
+    object.any_hit(ray_origin, ray_direction, inv_direction, max_distance)
+}
+
+// Intersección con un objeto que atraviesa los texels recortados (alpha_cutout) y las caras
+// traseras de materiales de una cara: el rayo continúa hasta la siguiente cara del mismo objeto
+fn intersect_object(
+    object: &dyn RayIntersect,
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    inv_direction: &Vector3,
+    texture_manager: &TextureManager,
+) -> Intersect {
+    let mut intersect = object.ray_intersect_inverse(ray_origin, ray_direction, inv_direction);
+    let mut traveled = 0.0;
+
+    while intersect.is_intersecting && (is_back_face(&intersect, ray_direction) || is_cut_out(&intersect, texture_manager)) {
+        traveled += intersect.distance + ORIGIN_BIAS;
+        let next_origin = intersect.point + *ray_direction * ORIGIN_BIAS;
+        intersect = object.ray_intersect_inverse(&next_origin, ray_direction, inv_direction);
+    }
+
+    intersect.distance += traveled;
+    intersect
+}
+
+// Objeto más cercano que toca el rayo, entre `objects` (pares índice-objeto): devuelve el
+// índice del objeto en la lista de la escena junto con el impacto
+fn trace_closest<'a>(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    objects: impl Iterator<Item = (usize, &'a SceneObject)>,
+    texture_manager: &TextureManager,
+) -> Option<(usize, Intersect)> {
+    let mut closest: Option<(usize, Intersect)> = None;
+    let inv_direction = inverse_direction(ray_direction);
+
+    for (index, object) in objects {
+        if !object.material().visible {
+            continue;
+        }
+        let i = intersect_object(object.as_ref(), ray_origin, ray_direction, &inv_direction, texture_manager);
+        if i.is_intersecting && closest.as_ref().is_none_or(|(_, hit)| i.distance < hit.distance) {
+            closest = Some((index, i));
+        }
+    }
+
+    closest
+}
+
+// Distancia al impacto más cercano antes de `max_distance`, para rayos cortos: los objetos cuya
+// caja empieza más lejos que el mejor impacto hasta ahora ni se intersectan
+fn closest_hit_within(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    max_distance: f32,
+    objects: &[SceneObject],
+    texture_manager: &TextureManager,
+) -> Option<f32> {
+    let inv_direction = inverse_direction(ray_direction);
+    let mut closest = max_distance;
+    for object in objects {
+        if !object.material().visible {
+            continue;
+        }
+        if !object.bounding_box().intersect(ray_origin, &inv_direction).is_some_and(|(near, _)| near < closest) {
+            continue;
+        }
+        let hit = intersect_object(object.as_ref(), ray_origin, ray_direction, &inv_direction, texture_manager);
+        if hit.is_intersecting && hit.distance < closest {
+            closest = hit.distance;
+        }
+    }
+    (closest < max_distance).then_some(closest)
+}
+
+// Radio de la marca de una luz puntual; las luces con esfera se marcan con su tamaño
+const LIGHT_MARKER_RADIUS: f32 = 0.15;
+
+// Marcas de `show_light_markers`: una esfera en cada luz que no está entre los objetos de la
+// escena, así no da sombra, no ilumina ni aparece en reflejos. Si el rayo la ve antes que a
+// cualquier objeto devuelve el color de la luz
+fn light_marker_color(ray_origin: &Vector3, ray_direction: &Vector3, scene: &Scene, rotation: f32) -> Option<Vector3> {
+    let (distance, color) = scene.lights.iter()
+        .filter_map(|light| {
+            let center = rotate_around_axis(light.position, scene.rotation_axis, -rotation);
+            let marker = Sphere { center, radius: light.radius.max(LIGHT_MARKER_RADIUS), material: Material::black() };
+            let hit = marker.ray_intersect(ray_origin, ray_direction);
+            hit.is_intersecting.then_some((hit.distance, light.color))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))?;
+    closest_hit_within(ray_origin, ray_direction, distance, scene.objects(), &scene.texture_manager)
+        .is_none()
+        .then_some(color)
+}
+
+fn find_closest_hit<'a>(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    objects: impl Iterator<Item = (usize, &'a SceneObject)>,
+    texture_manager: &TextureManager,
+) -> Intersect {
+    trace_closest(ray_origin, ray_direction, objects, texture_manager).map_or_else(Intersect::empty, |(_, hit)| hit)
+}
+
+// Cuánto cambian las UV entre un píxel y sus vecinos: el mismo objeto se intersecta con los rayos
+// de los píxeles de al lado (`neighbors`). Las UV de los cubos se repiten por bloque, así que se
+// toma la diferencia más corta alrededor del ciclo
+fn uv_footprint(object: &dyn RayIntersect, ray_origin: &Vector3, intersect: &Intersect, neighbors: (Vector3, Vector3)) -> f32 {
+    let wrapped = |d: f32| {
+        let d = d.abs().fract();
+        d.min(1.0 - d)
+    };
+    [neighbors.0, neighbors.1]
+        .iter()
+        .filter_map(|direction| {
+            let neighbor = object.ray_intersect(ray_origin, direction);
+            neighbor.is_intersecting.then(|| {
+                let du = wrapped(neighbor.u - intersect.u);
+                let dv = wrapped(neighbor.v - intersect.v);
+                (du * du + dv * dv).sqrt()
+            })
+        })
+        .fold(0.0, f32::max)
+}
+
+// Pendiente de las ondas en el punto (u, v) del plano tangente al instante `time` (en cuadros):
+// unas pocas ondas senoidales en direcciones distintas, sin textura
+fn ripple_slope(u: f32, v: f32, time: f32) -> (f32, f32) {
+    const WAVES: [(f32, f32, f32, f32); 3] = [
+        // (dirección u, dirección v, número de onda, velocidad en radianes por cuadro)
+        (1.0, 0.3, 14.0, 0.12),
+        (-0.4, 1.0, 19.0, 0.17),
+        (0.7, -0.7, 27.0, 0.23),
+    ];
+    let mut slope = (0.0, 0.0);
+    for (du, dv, frequency, speed) in WAVES {
+        let length = (du * du + dv * dv).sqrt();
+        let (du, dv) = (du / length, dv / length);
+        let derivative = (frequency * (du * u + dv * v) + speed * time).cos() / WAVES.len() as f32;
+        slope.0 += du * derivative;
+        slope.1 += dv * derivative;
+    }
+    slope
+}
+
+// Normal de sombreado: la geométrica, perturbada por el mapa de normales si el material tiene uno
+// y por las ondas animadas si tiene `ripple`
+fn shading_normal(intersect: &Intersect, time: f32, texture_manager: &TextureManager) -> Vector3 {
+    let mut normal = intersect.normal;
+    if intersect.effective_material().ripple > 0.0 {
+        let (tangent, bitangent) = tangent_frame(&normal);
+        let (slope_u, slope_v) = ripple_slope(intersect.point.dot(tangent), intersect.point.dot(bitangent), time);
+        let ripple = intersect.effective_material().ripple;
+        normal = (normal - tangent * (slope_u * ripple) - bitangent * (slope_v * ripple)).normalized();
+    }
+    if let Some(normal_map_path) = &intersect.effective_material().normal_map_id {
+        let (filter, wrap) = (intersect.effective_material().filter, intersect.effective_material().wrap);
+        let space = intersect.effective_material().normal_map_space;
+        let tex_normal = match space {
+            NormalMapSpace::Tangent => texture_manager.get_normal_from_map(normal_map_path, intersect.u, intersect.v, filter, wrap),
+            NormalMapSpace::Object => texture_manager.get_object_normal_from_map(normal_map_path, intersect.u, intersect.v, filter, wrap),
+        };
+        if let Some(tex_normal) = tex_normal {
+            let mapped_normal = match space {
+                NormalMapSpace::Tangent => {
+                    let (tangent, bitangent) = tangent_frame(&normal);
+
+                    let transformed_normal_x = tex_normal.x * tangent.x + tex_normal.y * bitangent.x + tex_normal.z * normal.x;
+                    let transformed_normal_y = tex_normal.x * tangent.y + tex_normal.y * bitangent.y + tex_normal.z * normal.y;
+                    let transformed_normal_z = tex_normal.x * tangent.z + tex_normal.y * bitangent.z + tex_normal.z * normal.z;
+
+                    Vector3::new(transformed_normal_x, transformed_normal_y, transformed_normal_z).normalized()
+                }
+                // En una instancia el mapa está en el espacio de la geometría original
+                NormalMapSpace::Object => match &intersect.object_inverse {
+                    Some(inverse) => instance::transform_normal(inverse, tex_normal),
+                    None => tex_normal,
+                },
+            };
+
+            // Interpolar entre la normal geométrica y la del mapa según la intensidad del material;
+            // si la mezcla casi se anula (intensidades mayores a 1) queda la geométrica
+            let strength = intersect.effective_material().normal_strength;
+            let blended = normal + (mapped_normal - normal) * strength;
+            if blended.length() > 1e-3 {
+                normal = blended.normalized();
+            }
+        }
+    }
+
+    normal
+}
+
+// Cuánto cubre la textura el punto (alpha filtrado entre texels vecinos): 0 deja ver solo
+// el material y 1 solo la textura. Sin textura no hay nada que cubra
+fn texture_coverage(intersect: &Intersect, texture_manager: &TextureManager) -> f32 {
+    match &intersect.effective_material().texture_id {
+        Some(texture_path) => texture_manager.get_alpha_bilinear(texture_path, intersect.u, intersect.v, intersect.effective_material().wrap),
+        None => 0.0,
+    }
+}
+
+// Impacto lejano: se sombrea con el material sin texturas, mapa de normales ni de emisión.
+// Dentro de la franja previa al límite la probabilidad de aplanar crece de 0 a 1
+fn flatten_distant(intersect: &mut Intersect, settings: &RenderSettings, rng: &mut SmallRng) {
+    let material = intersect.effective_material();
+    if material.texture_id.is_none() && material.normal_map_id.is_none() && material.emission_map_id.is_none() {
+        return;
+    }
+    let band_start = settings.texture_lod_distance - settings.texture_lod_band;
+    if intersect.distance <= band_start {
+        return;
+    }
+    let flat_probability = ((intersect.distance - band_start) / settings.texture_lod_band.max(1e-4)).min(1.0);
+    if flat_probability < 1.0 && rng.random::<f32>() >= flat_probability {
+        return;
+    }
+
+    let mut flat = material.clone();
+    flat.texture_id = None;
+    flat.normal_map_id = None;
+    flat.emission_map_id = None;
+    intersect.material_override = Some(flat);
+}
+
+// Color difuso en el punto de impacto (textura mezclada con el difuso según su alpha)
+fn surface_color(intersect: &Intersect, texture_manager: &TextureManager) -> Vector3 {
+    if let Some(texture_path) = &intersect.effective_material().texture_id {
+        let lod = texture_manager.texture_lod(texture_path, intersect.uv_footprint);
+        let (filter, wrap) = (intersect.effective_material().filter, intersect.effective_material().wrap);
+        let texture_color = texture_manager.get_pixel_color(texture_path, intersect.u, intersect.v, lod, filter, wrap);
+        let texture_alpha = texture_coverage(intersect, texture_manager);
+        
+        // Si el píxel es transparente, mezclar con el color difuso del material
+        // Para materiales transparentes como vidrio, esto permite que la refracción se vea mejor
+        intersect.effective_material().diffuse * (1.0 - texture_alpha) + texture_color * texture_alpha
+    } else {
+        intersect.effective_material().diffuse
+    }
+}
+
+// Valores del primer impacto sin iluminación, para las AOV
+fn sample_aovs(ray_origin: &Vector3, ray_direction: &Vector3, scene: &Scene, settings: &RenderSettings, camera: &Camera) -> AovSample {
+    let diorama_origin = rotate_around_axis(*ray_origin, scene.rotation_axis, -scene.rotation);
+    let diorama_direction = rotate_around_axis(*ray_direction, scene.rotation_axis, -scene.rotation);
+    let intersect = find_closest_hit(&diorama_origin, &diorama_direction, scene.primary_objects(), &scene.texture_manager);
+    if !intersect.is_intersecting {
+        return AovSample::sky(sky_color(Some(&diorama_direction), scene, settings));
+    }
+
+    AovSample {
+        albedo: surface_color(&intersect, &scene.texture_manager),
+        // La normal vuelve del marco del diorama al del mundo
+        normal: rotate_around_axis(shading_normal(&intersect, scene.time, &scene.texture_manager), scene.rotation_axis, scene.rotation),
+        // Profundidad lineal: distancia a lo largo del eje de la cámara
+        depth: intersect.distance * ray_direction.dot(camera.forward),
+        position: rotate_around_axis(intersect.point, scene.rotation_axis, scene.rotation),
+    }
+}
+
+// Devuelve la fracción (por canal) de luz que llega al punto: 1 = iluminado, 0 = sombra.
+// Los objetos transparentes dejan pasar luz teñida por su color, así el vidrio proyecta sombra azulada.
+// Con `cone_angle` > 0 la luz tiene tamaño aparente (el sol): el rayo sale hacia una dirección al
+// azar de ese cono, a la misma distancia que la luz
+fn cast_shadow(
+    intersect: &Intersect,
+    light_position: &Vector3,
+    cone_angle: f32,
+    scene: &Scene,
+    settings: &RenderSettings,
+    rng: &mut SmallRng,
+) -> Vector3 {
+    if !settings.enable_shadows {
+        return Vector3::one();
+    }
+    let to_light = *light_position - intersect.point;
+    let mut light_dir = to_light.normalized();
+    let mut light_position = *light_position;
+    if cone_angle > 0.0 {
+        light_dir = sample_cone(&light_dir, cone_angle, rng);
+        light_position = intersect.point + light_dir * to_light.length();
+    }
+
+    // Con luz rasante la superficie necesita más sesgo para no sombrearse a sí misma
+    let cos_theta = intersect.normal.dot(light_dir).abs().min(1.0);
+    let bias = (settings.shadow_bias + settings.shadow_slope_bias * (1.0 - cos_theta)) * bias_scale(intersect);
+    let shadow_ray_origin = offset_origin_by(intersect, &light_dir, bias);
+
+    light_transmittance(&shadow_ray_origin, &light_position, scene)
+}
+
+// Semiángulo del cono del sol en radianes
+fn sun_cone(settings: &RenderSettings) -> f32 {
+    settings.sun_angular_size.max(0.0).to_radians() * 0.5
+}
+
+// Luz que llega de `light_position` a `point` después de cruzar los objetos en el camino
+fn light_transmittance(point: &Vector3, light_position: &Vector3, scene: &Scene) -> Vector3 {
+    let light_dir = (*light_position - *point).normalized();
+    let light_distance = (*light_position - *point).length();
+    let inv_light_dir = inverse_direction(&light_dir);
+    let mut transmittance = Vector3::one();
+
+    for object in scene.objects() {
+        // Ignorar bloques emisivos (glowstone), ocultos o que no dan sombra
+        let material = object.material();
+        if material.is_emissive || !material.visible || !material.casts_shadow {
+            continue;
+        }
+        
+        if blocks_ray(object.as_ref(), point, &light_dir, &inv_light_dir, light_distance, &scene.texture_manager) {
+            let transparency = occluder_transparency(object.as_ref(), point, &light_dir, &inv_light_dir, &scene.texture_manager);
+            if transparency <= 0.0 {
+                return Vector3::zero();
+            }
+            // El oclusor bloquea (1 - transparencia) de la luz y tiñe el resto con su color
+            transmittance = transmittance * object.material().diffuse * transparency;
+        }
+    }
+
+    transmittance
+}
+
+// Cuánta luz deja pasar un oclusor transparente. Con textura se mira el texel donde lo cruza el
+// rayo y las partes cubiertas (hojas, el marco del vidrio) tapan según su alpha, igual que al
+// verlo de frente: así las hojas sobre un material transparente dan sombra moteada
+fn occluder_transparency(
+    object: &dyn RayIntersect,
+    point: &Vector3,
+    light_dir: &Vector3,
+    inv_light_dir: &Vector3,
+    texture_manager: &TextureManager,
+) -> f32 {
+    let material = object.material();
+    if material.albedo[3] <= 0.0 || material.texture_id.is_none() {
+        return material.albedo[3];
+    }
+    let intersect = intersect_object(object, point, light_dir, inv_light_dir, texture_manager);
+    material.albedo[3] * (1.0 - texture_coverage(&intersect, texture_manager))
+}
+
+// Niebla uniforme con dispersión simple: se avanza por el rayo primario en pasos y en cada uno
+// se suma la luz de cada fuente que llega sin sombra (rayos de luz visibles entre los bloques).
+// `color` es lo que se ve detrás de la niebla; el cielo cuenta como si estuviera a fog_max_distance
+fn march_fog(
+    color: Vector3,
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    scene: &Scene,
+    settings: &RenderSettings,
+    rotation: f32,
+    rng: &mut SmallRng,
+) -> Vector3 {
+    let hit = find_closest_hit(ray_origin, ray_direction, scene.primary_objects(), &scene.texture_manager);
+    let distance = if hit.is_intersecting { hit.distance.min(settings.fog_max_distance) } else { settings.fog_max_distance };
+
+    let step = distance / settings.fog_steps as f32;
+    let step_transmittance = (-settings.fog_density * step).exp();
+    // Desfase aleatorio del primer paso: cambia las bandas de los pasos por ruido que se promedia
+    let mut t = step * rng.random::<f32>();
+    let mut view_transmittance = 1.0;
+    let mut in_scattered = Vector3::zero();
+
+    // Función de fase de Henyey-Greenstein: g > 0 concentra la luz al mirar hacia la fuente
+    let g = settings.fog_anisotropy;
+    let phase = |cos_theta: f32| (1.0 - g * g) / (4.0 * PI * (1.0 + g * g - 2.0 * g * cos_theta).powf(1.5));
+
+    for _ in 0..settings.fog_steps {
+        let point = *ray_origin + *ray_direction * t;
+        for light in &scene.lights {
+            if light.intensity <= 0.0 {
+                continue;
+            }
+            let light_position = rotate_around_axis(light.position, scene.rotation_axis, -rotation);
+            let cos_theta = ray_direction.dot((light_position - point).normalized());
+            in_scattered += light.color * light_transmittance(&point, &light_position, scene)
+                * (light.intensity * phase(cos_theta) * settings.fog_density * step * view_transmittance);
+        }
+        view_transmittance *= step_transmittance;
+        t += step;
+    }
+
+    color * view_transmittance + in_scattered
+}
+
+// `throughput` es el peso acumulado del camino (producto de reflectividades/transparencias);
+// solo lo usa la ruleta rusa para decidir qué rayos profundos terminar
+pub fn cast_ray(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    scene: &Scene,
+    settings: &RenderSettings,
+    path: RayPath,
+    sampler: &mut Sampler,
+) -> Vector3 {
+    if settings.show_light_markers && path.depth == 0
+        && let Some(color) = light_marker_color(ray_origin, ray_direction, scene, scene.rotation_at(sampler.time))
+    {
+        return color;
+    }
+    if settings.render_mode == RenderMode::PathTraced {
+        return trace_path(ray_origin, ray_direction, scene, settings, path.in_bounds, sampler);
+    }
+    let RayPath { depth, throughput, .. } = path;
+    sampler.stats.rays += 1;
+    sampler.stats.max_depth = sampler.stats.max_depth.max(depth);
+    let max_depth = if settings.russian_roulette { settings.roulette_max_depth } else { settings.max_depth };
+    // Pasado el límite ya no se sombrea, pero un reflejo o una refracción que da con un emisivo
+    // lo sigue viendo con toda su emisión: si no, una fuente vista en un espejo al final del
+    // camino quedaría del color del cielo
+    if depth > max_depth {
+        if path.specular {
+            let intersect = find_closest_hit(ray_origin, ray_direction, scene.objects().iter().enumerate(), &scene.texture_manager);
+            if intersect.is_intersecting && intersect.effective_material().is_emissive {
+                return self_emission(&intersect, scene.time - sampler.time, &scene.texture_manager);
+            }
+        }
+        return sky_color(Some(ray_direction), scene, settings);
+    }
+
+    // Ruleta rusa: los rayos de poco aporte terminan al azar y los supervivientes
+    // se escalan por 1/p para que el promedio no cambie
+    let mut survival_weight = 1.0;
+    if settings.russian_roulette && depth > settings.roulette_min_depth {
+        let survival_probability = throughput.clamp(0.05, 1.0);
+        if sampler.rng.random::<f32>() > survival_probability {
+            return Vector3::zero();
+        }
+        survival_weight = 1.0 / survival_probability;
+    }
+
+    // Los rayos que no tocan la caja de la escena van directo al cielo
+    if !path.in_bounds && !scene.ray_hits_bounds(ray_origin, ray_direction) {
+        return sky_color(Some(ray_direction), scene, settings);
+    }
+
+    let objects = scene.objects();
+    let texture_manager = &scene.texture_manager;
+
+    // Los rayos primarios solo prueban lo que está dentro del frustum de la cámara
+    let mut intersect = if depth == 0 {
+        let Some((index, mut intersect)) = trace_closest(ray_origin, ray_direction, scene.primary_objects(), texture_manager) else {
+            return sky_color(Some(ray_direction), scene, settings);
+        };
+        if let Some(neighbors) = sampler.differentials {
+            intersect.uv_footprint = uv_footprint(objects[index].as_ref(), ray_origin, &intersect, neighbors);
+        }
+        intersect
+    } else {
+        find_closest_hit(ray_origin, ray_direction, objects.iter().enumerate(), texture_manager)
+    };
+    if !intersect.is_intersecting {
+        return sky_color(Some(ray_direction), scene, settings);
+    }
+    if settings.texture_lod {
+        flatten_distant(&mut intersect, settings, &mut sampler.rng);
+    }
+
+    let view_dir = (*ray_origin - intersect.point).normalized();
+
+    let normal = shading_normal(&intersect, scene.time - sampler.time, texture_manager);
+
+    // El rayo ya viene en el marco del diorama girado; las luces se llevan a ese mismo marco
+    let rotation = scene.rotation_at(sampler.time);
+
+    if settings.debug_shadow_mask && depth == 0 {
+        let main_light_position = rotate_around_axis(scene.lights[0].position, scene.rotation_axis, -rotation);
+        return cast_shadow(&intersect, &main_light_position, sun_cone(settings), scene, settings, &mut sampler.rng);
+    }
+
+    let diffuse_color = surface_color(&intersect, texture_manager);
+    let albedo = intersect.effective_material().albedo;
+
+    // Tinte del brillo: el color especular del material y, encima, el iridiscente,
+    // que cambia con el ángulo de visión
+    let material = intersect.effective_material();
+    let specular_tint = if material.iridescence > 0.0 {
+        let tint = material.film_color * thin_film_tint(normal.dot(view_dir).abs(), material.film_thickness) * 2.0;
+        material.specular_color * (Vector3::one() + (tint - Vector3::one()) * material.iridescence)
+    } else {
+        material.specular_color
+    };
+
+    // Barniz: su propio brillo (más afilado cuanto menos rugoso) y un reflejo débil que crece en
+    // ángulos rasantes; lo que refleja el barniz ya no llega a la capa de abajo
+    let clearcoat = intersect.effective_material().clearcoat;
+    let coat_fresnel = if clearcoat > 0.0 { clearcoat * clearcoat_fresnel(normal.dot(view_dir).abs()) } else { 0.0 };
+    let coat_shininess = 2.0 / intersect.effective_material().clearcoat_roughness.max(0.02).powi(2);
+    let mut coat_color = Vector3::zero();
+
+    // Phong de cada luz, cada una con su propia sombra
+    let blinn = settings.blinn_phong || intersect.effective_material().blinn_phong;
+    let mut phong_color = Vector3::zero();
+    for (index, light) in scene.lights.iter().enumerate() {
+        if light.intensity <= 0.0 {
+            continue;
+        }
+
+        let light_position = rotate_around_axis(light.sample_position(&mut sampler.rng), scene.rotation_axis, -rotation);
+        let to_light = light_position - intersect.point;
+        let light_dir = to_light.normalized();
+
+        let cone_angle = if index == daynight::SUN { sun_cone(settings) } else { 0.0 };
+        let shadow_samples = if light.radius > 0.0 || cone_angle > 0.0 { settings.shadow_samples } else { settings.shadow_samples.min(1) };
+        // El mapa de sombras solo vale para el sol visto directamente desde la cámara
+        let mapped = match &scene.shadow_map {
+            Some(shadow_map) if index == daynight::SUN && depth == 0 && settings.enable_shadows && shadow_samples > 0 => {
+                shadow_map.visibility(&intersect.point, &normal, settings.shadow_map_pcf)
+            }
+            _ => None,
+        };
+        let light_transmittance = if let Some(visibility) = mapped {
+            visibility
+        } else if shadow_samples == 0 {
+            Vector3::one()
+        } else {
+            let mut sum = cast_shadow(&intersect, &light_position, cone_angle, scene, settings, &mut sampler.rng);
+            for _ in 1..shadow_samples {
+                let extra_position = rotate_around_axis(light.sample_position(&mut sampler.rng), scene.rotation_axis, -rotation);
+                sum += cast_shadow(&intersect, &extra_position, cone_angle, scene, settings, &mut sampler.rng);
+            }
+            sampler.stats.shadow_rays += shadow_samples as u64;
+            sum / shadow_samples as f32
+        };
+        let light_intensity = light.intensity * light.attenuation(to_light.length());
+
+        let diffuse_intensity = diffuse_response(intersect.effective_material(), normal.dot(light_dir)) * light_intensity;
+        let diffuse = diffuse_color * diffuse_intensity * light_transmittance;
+
+        let lobe = if material.aniso > 0.0 {
+            anisotropic_lobe(&view_dir, &light_dir, &normal, material.specular, material.aniso, &material.aniso_tangent)
+        } else {
+            specular_lobe(&view_dir, &light_dir, &normal, material.specular, blinn)
+        };
+        let specular_intensity = lobe * light_intensity;
+        let specular = light.color * specular_tint * specular_intensity * light_transmittance;
+
+        phong_color += diffuse * albedo[0] + specular * albedo[1];
+
+        if clearcoat > 0.0 {
+            let coat_intensity = specular_lobe(&view_dir, &light_dir, &normal, coat_shininess, blinn) * light_intensity;
+            coat_color += light.color * coat_intensity * light_transmittance * clearcoat;
+        }
+    }
+
+    if settings.sky_lighting {
+        let sky_light = if settings.sky_occlusion && settings.sky_occlusion_samples > 0 {
+            let samples = if depth == 0 { settings.sky_occlusion_samples } else { 1 };
+            sampler.stats.shadow_rays += samples as u64;
+            occluded_sky_irradiance(&intersect, &normal, scene, settings, samples, &mut sampler.rng)
+        } else {
+            sky_irradiance(sky_color(None, scene, settings), &normal)
+        };
+        phong_color += diffuse_color * sky_light * settings.sky_lighting_intensity * albedo[0];
+    }
+
+    if let Some(caustics) = &scene.caustics {
+        phong_color += diffuse_color * caustics.irradiance_at(&intersect.point) * albedo[0];
+    }
+
+    if settings.contact_shadows && depth == 0 {
+        sampler.stats.shadow_rays += 1;
+        phong_color *= contact_shadow(&intersect, &normal, scene, settings, &mut sampler.rng);
+    }
+
+    // Calcular iluminación de los objetos emisivos (glowstone, paneles). Cada uno es una fuente
+    // de área: ilumina desde el punto que elige `sample_emitter` con el área que muestra hacia
+    // el punto (`emitting_area`), y esa área cae con 1/d². Cerca de la fuente la distancia se
+    // limita al radio de una esfera de la misma área proyectada, así el brillo no se dispara
+    // al tocarla
+    let mut emissive_light = Vector3::zero();
+    if settings.enable_emissive {
+        let time = scene.time - sampler.time;
+        let stats = &mut sampler.stats;
+        let emitter_scale = nearby_emitters(objects, &intersect.point, time, settings, &mut sampler.rng, |object, rng| {
+            let emitter_point = object.sample_emitter(rng);
+            let emissive_dir = (emitter_point - intersect.point).normalized();
+            let emissive_distance = (emitter_point - intersect.point).length();
+
+            // Solo considerar bloques emisivos dentro de su alcance
+            if emissive_distance < emission_reach(object.material(), settings) {
+                // Verificar si hay sombra entre el punto y el bloque emisivo
+                stats.shadow_rays += 1;
+                let mut blocked = false;
+                let emissive_ray_origin = offset_origin(&intersect, &emissive_dir, settings);
+                let inv_emissive_dir = inverse_direction(&emissive_dir);
+
+                for other_object in objects {
+                    // Ignorar el propio objeto emisivo, otros emisivos y lo que no da sombra
+                    let material = other_object.material();
+                    if material.is_emissive || !material.visible || !material.casts_shadow {
+                        continue;
+                    }
+
+                    if blocks_ray(other_object.as_ref(), &emissive_ray_origin, &emissive_dir, &inv_emissive_dir, emissive_distance, texture_manager) {
+                        blocked = true;
+                        break;
+                    }
+                }
+
+                if !blocked {
+                    let projected_area = object.emitting_area(&-emissive_dir);
+                    let min_distance_sq = projected_area / PI;
+                    let attenuation = settings.emissive_strength * projected_area
+                        / (emissive_distance * emissive_distance).max(min_distance_sq);
+                    let emissive_intensity = normal.dot(emissive_dir).max(0.0) * object.material().emission_intensity_at(time) * attenuation;
+                    // Multiplicar por el color de la textura del objeto iluminado para que se vea la textura
+                    emissive_light += object.material().emission_color * emissive_intensity * diffuse_color;
+                }
+            }
+        });
+        emissive_light *= emitter_scale;
+    }
+
+    let self_emission = self_emission(&intersect, scene.time - sampler.time, texture_manager);
+
+    // Sin reflejos ni refracciones la superficie se sombrea como si fuera opaca y mate
+    let reflectivity = if settings.enable_reflections { intersect.effective_material().albedo[2] } else { 0.0 };
+    let reflect_color = if reflectivity > 0.0 {
+        let mut reflect_dir = reflect(ray_direction, &normal).normalized();
+        if intersect.effective_material().roughness > 0.0 {
+            reflect_dir = perturb_direction(reflect_dir, &normal, intersect.effective_material().roughness, &mut sampler.rng);
+        }
+        let reflect_origin = offset_origin(&intersect, &reflect_dir, settings);
+        cast_ray(&reflect_origin, &reflect_dir, scene, settings, path.bounce(reflectivity), sampler)
+    } else {
+        Vector3::zero()
+    };
+
+    if coat_fresnel > 0.0 && settings.enable_reflections {
+        let mut coat_dir = reflect(ray_direction, &normal).normalized();
+        if intersect.effective_material().clearcoat_roughness > 0.0 {
+            coat_dir = perturb_direction(coat_dir, &normal, intersect.effective_material().clearcoat_roughness, &mut sampler.rng);
+        }
+        let coat_origin = offset_origin(&intersect, &coat_dir, settings);
+        coat_color += cast_ray(&coat_origin, &coat_dir, scene, settings, path.bounce(coat_fresnel), sampler) * coat_fresnel;
+    }
+
+    // Las partes opacas de la textura (el marco del vidrio) no dejan pasar luz; la cobertura
+    // filtrada suaviza el borde entre marco y vidrio
+    let transparency = if settings.enable_refractions {
+        intersect.effective_material().albedo[3] * (1.0 - texture_coverage(&intersect, texture_manager))
+    } else {
+        0.0
+    };
+    // El camino sabe si el rayo viaja dentro del objeto: al entrar va del aire al índice del
+    // material y al salir al revés; la reflexión interna total lo deja del mismo lado
+    // Con rugosidad (vidrio esmerilado) la dirección refractada se desvía como el reflejo borroso
+    // y se promedian varias muestras; solo en el primer impacto, para no multiplicar los rayos
+    // en cada cara que cruza. Los cuadros acumulados completan el desenfoque
+    let transmission_roughness = intersect.effective_material().roughness;
+    let trace_refraction = |refractive_index: f32, path: RayPath, sampler: &mut Sampler| {
+        let (from, to) = if path.inside { (refractive_index, 1.0) } else { (1.0, refractive_index) };
+        if let Some(refract_dir) = refract_between(ray_direction, &normal, from, to) {
+            if transmission_roughness <= 0.0 || settings.frosted_samples == 0 {
+                let refract_origin = offset_origin(&intersect, &refract_dir, settings);
+                return cast_ray(&refract_origin, &refract_dir, scene, settings, path.cross(transparency), sampler);
+            }
+            let samples = if depth == 0 { settings.frosted_samples } else { 1 };
+            let mut transmitted = Vector3::zero();
+            for _ in 0..samples {
+                let blurred_dir = perturb_direction(refract_dir.normalized(), &normal, transmission_roughness, &mut sampler.rng);
+                let refract_origin = offset_origin(&intersect, &blurred_dir, settings);
+                transmitted += cast_ray(&refract_origin, &blurred_dir, scene, settings, path.cross(transparency), sampler);
+            }
+            transmitted / samples as f32
+        } else {
+            let reflect_dir = reflect(ray_direction, &normal).normalized();
+            let reflect_origin = offset_origin(&intersect, &reflect_dir, settings);
+            cast_ray(&reflect_origin, &reflect_dir, scene, settings, path.bounce(transparency), sampler)
+        }
+    };
+    let refractive_index = intersect.effective_material().refractive_index;
+    let abbe_number = intersect.effective_material().abbe_number;
+    let refract_color = if transparency > 0.0 && abbe_number > 0.0 {
+        // Dispersión: el rojo se desvía menos y el azul más; la separación entre los dos
+        // extremos es (n - 1) / Abbe, como entre las líneas C y F del vidrio real. El rayo se
+        // abre en tres canales solo en el primer vidrio; después cada uno sigue con su índice
+        let spread = (refractive_index - 1.0) / abbe_number;
+        let channel_index = |channel: usize| refractive_index + spread * (channel as f32 - 1.0) * 0.5;
+        match path.channel {
+            Some(channel) => trace_refraction(channel_index(channel), path, sampler),
+            None => {
+                let mut split = [0.0; 3];
+                for (channel, value) in split.iter_mut().enumerate() {
+                    let traced = trace_refraction(channel_index(channel), RayPath { channel: Some(channel), ..path }, sampler);
+                    *value = [traced.x, traced.y, traced.z][channel];
+                }
+                Vector3::new(split[0], split[1], split[2])
+            }
+        }
+    } else if transparency > 0.0 {
+        trace_refraction(refractive_index, path, sampler)
+    } else {
+        Vector3::zero()
+    };
+
+    // Los pesos no negativos garantizan que ningún término reste color, aunque el material
+    // (o un barniz exagerado) se pase de 1
+    let base_color = phong_color * (1.0 - reflectivity - transparency).max(0.0) + reflect_color * reflectivity + refract_color * transparency + emissive_light;
+    let color = base_color * (1.0 - coat_fresnel).max(0.0) + coat_color + self_emission;
+    color * survival_weight
+}
+
+// Diferencia máxima por canal entre un píxel y sus cuatro vecinos
+fn is_edge_pixel(buffer: &[Vector3], width: usize, x: usize, y: usize, threshold: f32) -> bool {
+    let height = buffer.len() / width;
+    let color = buffer[y * width + x];
+    let differs = |nx: usize, ny: usize| {
+        let diff = buffer[ny * width + nx] - color;
+        diff.x.abs().max(diff.y.abs()).max(diff.z.abs()) > threshold
+    };
+
+    (x > 0 && differs(x - 1, y))
+        || (x + 1 < width && differs(x + 1, y))
+        || (y > 0 && differs(x, y - 1))
+        || (y + 1 < height && differs(x, y + 1))
+}
+
+// Luz directa de las luces de la escena (sol y luna) en un punto difuso del trazado de caminos:
+// el mismo Phong de cast_ray con una sombra por luz, sin barniz ni brillo iridiscente. Las
+// luces son puntos o esferas chicas que un rebote al azar nunca encontraría
+fn direct_lighting(intersect: &Intersect, normal: &Vector3, view_dir: &Vector3, scene: &Scene, settings: &RenderSettings, sampler: &mut Sampler) -> Vector3 {
+    let material = intersect.effective_material();
+    let diffuse_color = surface_color(intersect, &scene.texture_manager);
+    let rotation = scene.rotation_at(sampler.time);
+    let blinn = settings.blinn_phong || material.blinn_phong;
+    let mut color = Vector3::zero();
+    for (index, light) in scene.lights.iter().enumerate() {
+        if light.intensity <= 0.0 {
+            continue;
+        }
+        let light_position = rotate_around_axis(light.sample_position(&mut sampler.rng), scene.rotation_axis, -rotation);
+        let to_light = light_position - intersect.point;
+        let light_dir = to_light.normalized();
+        let cone_angle = if index == daynight::SUN { sun_cone(settings) } else { 0.0 };
+        sampler.stats.shadow_rays += 1;
+        let transmittance = cast_shadow(intersect, &light_position, cone_angle, scene, settings, &mut sampler.rng);
+        let light_intensity = light.intensity * light.attenuation(to_light.length());
+
+        let diffuse = diffuse_color * diffuse_response(material, normal.dot(light_dir)) * light_intensity;
+        let specular = light.color * material.specular_color * specular_lobe(view_dir, &light_dir, normal, material.specular, blinn) * light_intensity;
+        color += (diffuse * material.albedo[0] + specular * material.albedo[1]) * transmittance;
+    }
+    color
+}
+
+// Trazado de caminos (RenderMode::PathTraced): en cada impacto se elige al azar reflejar,
+// refractar o rebotar en lo difuso con las probabilidades de los pesos del material, así el
+// promedio da lo mismo que la mezcla de cast_ray. El rebote difuso sigue el coseno, que con
+// Lambert deja como peso solo el color de la superficie; el cielo y los emisivos (el
+// glowstone) iluminan cuando el camino los toca, y las luces con una sombra en cada rebote
+fn trace_path(ray_origin: &Vector3, ray_direction: &Vector3, scene: &Scene, settings: &RenderSettings, in_bounds: bool, sampler: &mut Sampler) -> Vector3 {
+    let texture_manager = &scene.texture_manager;
+    let max_depth = if settings.russian_roulette { settings.roulette_max_depth } else { settings.max_depth };
+    let (mut origin, mut direction) = (*ray_origin, *ray_direction);
+    let mut throughput = Vector3::one();
+    let mut radiance = Vector3::zero();
+
+    for depth in 0..=max_depth {
+        sampler.stats.rays += 1;
+        sampler.stats.max_depth = sampler.stats.max_depth.max(depth);
+        // El paquete ya probó la caja de la escena para el rayo primario
+        let known_in_bounds = depth == 0 && in_bounds;
+        if !known_in_bounds && !scene.ray_hits_bounds(&origin, &direction) {
+            radiance += throughput * sky_color(Some(&direction), scene, settings);
+            break;
+        }
+        let intersect = find_closest_hit(&origin, &direction, scene.objects().iter().enumerate(), texture_manager);
+        if !intersect.is_intersecting {
+            radiance += throughput * sky_color(Some(&direction), scene, settings);
+            break;
+        }
+        radiance += throughput * self_emission(&intersect, scene.time - sampler.time, texture_manager);
+
+        // Ruleta rusa sobre el canal más fuerte del peso del camino
+        if settings.russian_roulette && depth > settings.roulette_min_depth {
+            let survival_probability = throughput.x.max(throughput.y).max(throughput.z).clamp(0.05, 1.0);
+            if sampler.rng.random::<f32>() > survival_probability {
+                break;
+            }
+            throughput /= survival_probability;
+        }
+
+        let material = intersect.effective_material();
+        let normal = shading_normal(&intersect, scene.time - sampler.time, texture_manager);
+        let reflectivity = if settings.enable_reflections { material.albedo[2] } else { 0.0 };
+        let transparency = if settings.enable_refractions {
+            material.albedo[3] * (1.0 - texture_coverage(&intersect, texture_manager))
+        } else {
+            0.0
+        };
+
+        let choice = sampler.rng.random::<f32>();
+        if choice < reflectivity {
+            direction = reflect(&direction, &normal).normalized();
+            if material.roughness > 0.0 {
+                direction = perturb_direction(direction, &normal, material.roughness, &mut sampler.rng);
+            }
+        } else if choice < reflectivity + transparency {
+            // Al entrar al objeto el camino toma su color, una vez por objeto como la sombra
+            // que tiñe light_transmittance
+            if direction.dot(normal) < 0.0 {
+                throughput *= material.diffuse;
+            }
+            let refracted = refract(&direction, &normal, material.refractive_index).unwrap_or_else(|| reflect(&direction, &normal));
+            direction = refracted.normalized();
+            if material.roughness > 0.0 {
+                direction = perturb_direction(direction, &normal, material.roughness, &mut sampler.rng);
+            }
+        } else {
+            let view_dir = -direction;
+            radiance += throughput * direct_lighting(&intersect, &normal, &view_dir, scene, settings, sampler);
+            // El rebote sale del lado de la cara por el que llegó el rayo
+            let facing = if normal.dot(direction) > 0.0 { -normal } else { normal };
+            let Some(bounce) = sample_cosine(&facing, &mut sampler.rng) else {
+                break;
+            };
+            throughput = throughput * surface_color(&intersect, texture_manager) * material.albedo[0];
+            direction = bounce;
+        }
+        origin = offset_origin(&intersect, &direction, settings);
+    }
+
+    radiance
+}
+
+// Costo de un cuadro: rayos que entraron a cast_ray, rayos de sombra hacia luces y bloques
+// emisivos, y el rebote más hondo al que se llegó
+#[derive(Clone, Copy, Default)]
+pub struct RenderStats {
+    pub rays: u64,
+    pub shadow_rays: u64,
+    pub max_depth: u32,
+}
+
+impl RenderStats {
+    pub fn merge(self, other: RenderStats) -> RenderStats {
+        RenderStats {
+            rays: self.rays + other.rays,
+            shadow_rays: self.shadow_rays + other.shadow_rays,
+            max_depth: self.max_depth.max(other.max_depth),
+        }
+    }
+}
+
+// Estado aleatorio de una muestra: su generador y el instante dentro del obturador.
+// `differentials` son las direcciones de los rayos primarios del píxel de la derecha y el de
+// abajo (en el marco del diorama), para elegir el nivel de mipmap de las texturas.
+// Cada muestra cuenta su propio costo en `stats`: cada hilo suma lo suyo sin compartir nada
+pub struct Sampler {
+    pub rng: SmallRng,
+    pub time: f32,
+    pub differentials: Option<(Vector3, Vector3)>,
+    pub stats: RenderStats,
+}
+
+// Estado del camino de un rayo a lo largo de la recursión: rebotes hechos, cuánto aporta
+// todavía al píxel, si viaja dentro de un objeto transparente y, después de un vidrio con
+// dispersión, el único canal (0 = rojo, 1 = verde, 2 = azul) que todavía importa.
+// `in_bounds` marca un rayo primario que ya pasó la prueba contra la caja de la escena en un
+// paquete, así cast_ray no la repite. `specular` marca la continuación de un reflejo o una
+// refracción, la única que pasado el límite de profundidad todavía busca un emisivo
+#[derive(Clone, Copy)]
+pub struct RayPath {
+    pub depth: u32,
+    pub throughput: f32,
+    pub inside: bool,
+    pub channel: Option<usize>,
+    pub in_bounds: bool,
+    pub specular: bool,
+}
+
+impl RayPath {
+    pub fn primary() -> Self {
+        RayPath { depth: 0, throughput: 1.0, inside: false, channel: None, in_bounds: false, specular: false }
+    }
+
+    // Rebote que se queda del mismo lado de la superficie
+    fn bounce(self, weight: f32) -> Self {
+        RayPath { depth: self.depth + 1, throughput: self.throughput * weight, in_bounds: false, specular: true, ..self }
+    }
+
+    // Refracción: el rayo cruza la superficie y entra o sale del medio
+    fn cross(self, weight: f32) -> Self {
+        RayPath { inside: !self.inside, ..self.bounce(weight) }
+    }
+}
+
+// Generador determinista por píxel y cuadro: el resultado no depende del orden de los hilos
+// ni de cómo se corta la imagen en bloques. La semilla por fila de antes no sirve con bloques:
+// cada píxel consume una cantidad variable de números y un bloque no sabe dónde empieza su parte
+pub fn pixel_sampler(frame_index: u32, pixel_index: usize, pass: u64) -> Sampler {
+    Sampler {
+        rng: SmallRng::seed_from_u64(((frame_index as u64) << 32 | pixel_index as u64) ^ (pass << 60)),
+        time: 0.0,
+        differentials: None,
+        stats: RenderStats::default(),
+    }
+}
+
+// Todo lo que afecta el sombreado fuera de la cámara y la geometría: si cambia entre cuadros,
+// los colores guardados ya no sirven para reproyectar
+fn lighting_signature(scene: &Scene, settings: &RenderSettings) -> Vec<f32> {
+    let mut signature = vec![scene.time, settings.sky_color.x, settings.sky_color.y, settings.sky_color.z, settings.skybox_intensity];
+    for light in &scene.lights {
+        signature.extend([light.position.x, light.position.y, light.position.z, light.intensity]);
+    }
+    signature
+}
+
+// Color del cuadro guardado en el mismo punto de la escena, si ese punto se veía ahí:
+// se proyecta con la cámara anterior y se compara con lo que tocó aquel píxel
+fn reproject(history: &FrameHistory, colors: &[Vector3], point: Vector3, settings: &RenderSettings, width: u32, height: u32) -> Option<Vector3> {
+    let world_point = rotate_around_axis(point, history.rotation_axis, history.rotation);
+    let previous = overlay::project(&history.camera, settings, world_point, width as f32, height as f32)?;
+    if previous.x < 0.0 || previous.y < 0.0 || previous.x >= width as f32 || previous.y >= height as f32 {
+        return None;
+    }
+    let index = (previous.y as u32 * width + previous.x as u32) as usize;
+    let previous_point = history.positions[index]?;
+    ((previous_point - point).length() < settings.reprojection_tolerance).then(|| colors[index])
+}
+
+// Lo que devuelve cada bloque: colores, AOV, puntos primarios (para la reproyección) y costo
+struct RenderedTile {
+    tile: Tile,
+    colors: Vec<Vector3>,
+    aov_samples: Vec<AovSample>,
+    positions: Vec<Option<Vector3>>,
+    stats: RenderStats,
+}
+
+// Bloque rectangular de la imagen que se renderiza como una sola tarea
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+fn split_into_tiles(region: CropRect) -> Vec<Tile> {
+    let (right, bottom) = (region.x + region.width, region.y + region.height);
+    let mut tiles = Vec::new();
+    for y in (region.y..bottom).step_by(TILE_SIZE as usize) {
+        for x in (region.x..right).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: TILE_SIZE.min(right - x),
+                height: TILE_SIZE.min(bottom - y),
+            });
+        }
+    }
+    tiles
+}
+
+// Posiciones de la subgrilla que recorren los cuadros acumulados con Grid y RotatedGrid (4x4)
+const JITTER_PATTERN_SAMPLES: u32 = 16;
+
+// Índice de Halton de la muestra `sample` del cuadro `frame_index`: cada cuadro usa su propio
+// tramo de aa_samples índices, el primero para el desplazamiento del cuadro y el resto para el
+// antialiasing de bordes, así ninguna muestra repite la posición de otra
+fn halton_index(frame_index: u32, sample: u32, settings: &RenderSettings) -> u32 {
+    frame_index * settings.aa_samples.max(1) + sample
+}
+
+// Inverso radical de `index` en `base`: refleja sus dígitos tras la coma (1 -> 0.5, 2 -> 0.25...)
+fn radical_inverse(mut index: u32, base: u32) -> f32 {
+    let mut inverse = 0.0;
+    let mut digit_weight = 1.0 / base as f32;
+    while index > 0 {
+        inverse += (index % base) as f32 * digit_weight;
+        index /= base;
+        digit_weight /= base as f32;
+    }
+    inverse
+}
+
+// Muestra `index` de `count` dentro del píxel (de 0 a 1 en cada eje). Random la saca de `rng`, el
+// sampler del píxel. Halton no usa `count`: su índice sigue sin repetirse y solo se desplaza según
+// el píxel para no alinear sus vecinos
+pub fn pattern_offset(pattern: AaPattern, index: u32, count: u32, pixel_index: usize, rng: &mut SmallRng) -> (f32, f32) {
+    let count = count.max(1);
+    let side = (count as f32).sqrt().ceil() as u32;
+    let (column, row) = (index % count % side, index % count / side);
+    match pattern {
+        AaPattern::Random => (rng.random::<f32>(), rng.random::<f32>()),
+        AaPattern::Grid => ((column as f32 + 0.5) / side as f32, (row as f32 + 0.5) / side as f32),
+        AaPattern::RotatedGrid => {
+            let fine = (side * side) as f32;
+            (((column * side + row) as f32 + 0.5) / fine, ((row * side + column) as f32 + 0.5) / fine)
+        }
+        AaPattern::Halton => {
+            let mut shift = SmallRng::seed_from_u64(pixel_index as u64);
+            (
+                (radical_inverse(index + 1, 2) + shift.random::<f32>()).fract(),
+                (radical_inverse(index + 1, 3) + shift.random::<f32>()).fract(),
+            )
+        }
+    }
+}
+
+// Antialiasing adaptativo: solo los píxeles que difieren de sus vecinos reciben muestras extra
+fn refine_edges(
+    buffer: &[Vector3],
+    width: usize,
+    region: CropRect,
+    settings: &RenderSettings,
+    frame_index: u32,
+    trace_pixel: &(dyn Fn(f32, f32, &mut Sampler) -> Vector3 + Sync),
+) -> (Vec<Vector3>, RenderStats) {
+    let mut refined = buffer.to_vec();
+    let columns = region.x as usize..(region.x + region.width) as usize;
+    let rows = region.y as usize..(region.y + region.height) as usize;
+
+    let stats = refined.par_chunks_mut(width).enumerate().map(|(y, row)| {
+        let mut stats = RenderStats::default();
+        for (x, pixel) in row.iter_mut().enumerate() {
+            if !rows.contains(&y) || !columns.contains(&x) || !is_edge_pixel(buffer, width, x, y, settings.aa_threshold) {
+                continue;
+            }
+
+            let pixel_index = y * width + x;
+            let mut sampler = pixel_sampler(frame_index, pixel_index, 1);
+
+            let mut sum = *pixel;
+            for sample in 1..settings.aa_samples {
+                // Halton sigue la secuencia de un cuadro al siguiente en vez de repetirla
+                let index = if settings.aa_pattern == AaPattern::Halton { halton_index(frame_index, sample, settings) } else { sample };
+                let (offset_x, offset_y) = pattern_offset(settings.aa_pattern, index, settings.aa_samples, pixel_index, &mut sampler.rng);
+                sum += trace_pixel(x as f32 + offset_x, y as f32 + offset_y, &mut sampler);
+            }
+            *pixel = sum / settings.aa_samples as f32;
+            stats = stats.merge(sampler.stats);
+        }
+        stats
+    }).reduce(RenderStats::default, RenderStats::merge);
+
+    (refined, stats)
+}
+
+// Mitad del ancho y del alto de la vista a distancia 1 de la cámara. Con solo el campo vertical,
+// el horizontal sale de la proporción de la ventana y nada se estira al cambiarla
+pub fn view_extent(settings: &RenderSettings, aspect_ratio: f32) -> (f32, f32) {
+    let half_height = (settings.vertical_fov * 0.5).tan();
+    let half_width = match settings.horizontal_fov {
+        Some(horizontal_fov) => (horizontal_fov * 0.5).tan(),
+        None => half_height * aspect_ratio,
+    };
+    (half_width, half_height)
+}
+
+// Modo de depuración: el color dice hasta qué rebote llegó el camino del rayo primario,
+// de azul (solo el primer impacto, o el cielo) a verde y rojo (el límite de profundidad)
+fn depth_debug_color(ray_origin: &Vector3, ray_direction: &Vector3, scene: &Scene, settings: &RenderSettings, sampler: &mut Sampler) -> Vector3 {
+    let previous_max = sampler.stats.max_depth;
+    sampler.stats.max_depth = 0;
+    cast_ray(ray_origin, ray_direction, scene, settings, RayPath::primary(), sampler);
+    let reached = sampler.stats.max_depth;
+    sampler.stats.max_depth = previous_max.max(reached);
+
+    let max_depth = if settings.russian_roulette { settings.roulette_max_depth } else { settings.max_depth };
+    let t = (reached as f32 / max_depth.max(1) as f32).min(1.0);
+    if t < 0.5 {
+        Vector3::new(0.0, t * 2.0, 1.0 - t * 2.0)
+    } else {
+        Vector3::new(t * 2.0 - 1.0, 2.0 - t * 2.0, 0.0)
+    }
+}
+
+// Dirección (en el mundo) del rayo que pasa por el píxel (x, y) de una vista de width x height
+fn view_direction(camera: &Camera, settings: &RenderSettings, x: f32, y: f32, width: f32, height: f32) -> Vector3 {
+    let (half_width, half_height) = view_extent(settings, width / height);
+    let screen_x = ((2.0 * x) / width - 1.0) * half_width;
+    let screen_y = (-(2.0 * y) / height + 1.0) * half_height;
+
+    let ray_direction = Vector3::new(screen_x, screen_y, -1.0).normalized();
+    camera.basis_change(&ray_direction)
+}
+
+// Objeto bajo el píxel (x, y) y su índice en la escena: el punto y la normal de la cara
+// golpeada quedan en el marco del diorama, que es donde viven las celdas de la grilla
+pub fn pick(scene: &Scene, camera: &Camera, settings: &RenderSettings, x: f32, y: f32, width: f32, height: f32) -> Option<(usize, Intersect)> {
+    let direction = view_direction(camera, settings, x, y, width, height);
+    let eye = rotate_around_axis(camera.eye, scene.rotation_axis, -scene.rotation);
+    let direction = rotate_around_axis(direction, scene.rotation_axis, -scene.rotation);
+    trace_closest(&eye, &direction, scene.objects().iter().enumerate(), &scene.texture_manager)
+}
+
+// Resumen de --bvh-stats: rayos primarios que llegaron a alguna malla, con los nodos y
+// triángulos que costaron (totales y el peor rayo) y los triángulos de la búsqueda lineal
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct TraversalReport {
+    pub rays: u64,
+    pub nodes: u64,
+    pub tests: u64,
+    pub baseline: u64,
+    pub max_nodes: u32,
+    pub max_tests: u32,
+}
+
+impl TraversalReport {
+    fn add(mut self, cost: TraversalCost) -> Self {
+        self.rays += 1;
+        self.nodes += cost.nodes as u64;
+        self.tests += cost.tests as u64;
+        self.baseline += cost.baseline as u64;
+        self.max_nodes = self.max_nodes.max(cost.nodes);
+        self.max_tests = self.max_tests.max(cost.tests);
+        self
+    }
+
+    fn merge(self, other: TraversalReport) -> TraversalReport {
+        TraversalReport {
+            rays: self.rays + other.rays,
+            nodes: self.nodes + other.nodes,
+            tests: self.tests + other.tests,
+            baseline: self.baseline + other.baseline,
+            max_nodes: self.max_nodes.max(other.max_nodes),
+            max_tests: self.max_tests.max(other.max_tests),
+        }
+    }
+}
+
+// Lanza un rayo primario por píxel de una vista de width x height y mide cuánto trabajan las
+// jerarquías de las mallas que cruza; un rayo que cruza varias mallas suma el costo de todas
+pub fn measure_traversal(scene: &Scene, camera: &Camera, settings: &RenderSettings, width: u32, height: u32) -> TraversalReport {
+    let eye = rotate_around_axis(camera.eye, scene.rotation_axis, -scene.rotation);
+    (0..height).into_par_iter()
+        .map(|y| {
+            let mut report = TraversalReport::default();
+            for x in 0..width {
+                let direction = view_direction(camera, settings, x as f32 + 0.5, y as f32 + 0.5, width as f32, height as f32);
+                let direction = rotate_around_axis(direction, scene.rotation_axis, -scene.rotation);
+                let cost = scene.objects().iter()
+                    .filter_map(|object| object.traversal_cost(&eye, &direction))
+                    .reduce(|a, b| TraversalCost { nodes: a.nodes + b.nodes, tests: a.tests + b.tests, baseline: a.baseline + b.baseline });
+                if let Some(cost) = cost {
+                    report = report.add(cost);
+                }
+            }
+            report
+        })
+        .reduce(TraversalReport::default, TraversalReport::merge)
+}
+
+// Control de un render largo desde otro hilo: `cancel` se revisa antes de cada bloque y
+// `on_progress` recibe (bloques terminados, bloques totales) a medida que avanzan
+pub struct RenderControl<'a> {
+    pub cancel: &'a AtomicBool,
+    pub on_progress: &'a (dyn Fn(usize, usize) + Sync),
+}
+
+impl RenderControl<'_> {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+pub fn render(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+    accumulation: &mut AccumulationBuffer,
+    aovs: Option<&mut AovBuffers>,
+) -> RenderStats {
+    render_controlled(framebuffer, scene, camera, settings, accumulation, aovs, None).0
+}
+
+// Cuadro rápido a 1/`scale` de resolución, agrandado por vecino más cercano: cada píxel trazado
+// cubre un bloque de scale x scale. `preview` y `preview_accumulation` son del tamaño que da
+// preview_size; los guarda quien llama, así no se piden cada cuadro. Se vacían antes de trazar,
+// así no se mezclan cuadros en movimiento ni tocan la historia del render completo
+pub fn render_preview(
+    framebuffer: &mut Framebuffer,
+    preview: &mut Framebuffer,
+    preview_accumulation: &mut AccumulationBuffer,
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+    scale: u32,
+) -> RenderStats {
+    let scale = scale.max(1);
+    debug_assert_eq!((preview.width, preview.height), preview_size(framebuffer.width, framebuffer.height, scale));
+    preview_accumulation.reset();
+    preview_accumulation.invalidate_history();
+    let (stats, _) = render_controlled(preview, scene, camera, settings, preview_accumulation, None, None);
+
+    let width = preview.width;
+    let colors = preview.color_buffer.get_image_data();
+    for y in 0..framebuffer.height {
+        for x in 0..framebuffer.width {
+            framebuffer.set_pixel_color(x, y, colors[((y / scale) * width + x / scale) as usize]);
+        }
+    }
+    stats
+}
+
+// Tamaño de los buffers de render_preview para una imagen de width x height
+pub fn preview_size(width: u32, height: u32, scale: u32) -> (u32, u32) {
+    (width.div_ceil(scale.max(1)), height.div_ceil(scale.max(1)))
+}
+
+// Acumula `samples` cuadros para un render sin ventana. Si `control` se cancela a mitad de
+// camino devuelve false y el framebuffer queda con lo acumulado hasta ahí (un cuadro cortado
+// se descarta, salvo el primero, que se muestra con los bloques que llegó a trazar)
+pub fn render_samples(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+    accumulation: &mut AccumulationBuffer,
+    samples: u32,
+    control: &RenderControl,
+) -> (RenderStats, bool) {
+    let mut stats = RenderStats::default();
+    for sample in 0..samples as usize {
+        if control.is_cancelled() {
+            return (stats, false);
+        }
+        let on_progress = |done: usize, total: usize| (control.on_progress)(sample * total + done, samples as usize * total);
+        let pass_control = RenderControl { cancel: control.cancel, on_progress: &on_progress };
+        let (pass_stats, complete) = render_controlled(framebuffer, scene, camera, settings, accumulation, None, Some(&pass_control));
+        stats = stats.merge(pass_stats);
+        if !complete {
+            return (stats, false);
+        }
+    }
+    (stats, true)
+}
+
+// Además de las estadísticas devuelve si se trazaron todos los bloques (false si `control` cortó el cuadro)
+fn render_controlled(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    camera: &Camera,
+    settings: &RenderSettings,
+    accumulation: &mut AccumulationBuffer,
+    aovs: Option<&mut AovBuffers>,
+    control: Option<&RenderControl>,
+) -> (RenderStats, bool) {
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+
+    if !settings.accumulate {
+        accumulation.reset();
+    }
+    let frame_index = accumulation.frame_count;
+
+    // Rectángulo a trazar, recortado a la imagen. Si la cubre entera se renderiza como siempre
+    let full_frame = CropRect { x: 0, y: 0, width: framebuffer.width, height: framebuffer.height };
+    let crop = settings.crop.map(|crop| {
+        let (x, y) = (crop.x.min(framebuffer.width), crop.y.min(framebuffer.height));
+        CropRect { x, y, width: crop.width.min(framebuffer.width - x), height: crop.height.min(framebuffer.height - y) }
+    }).filter(|crop| *crop != full_frame);
+    let region = crop.unwrap_or(full_frame);
+
+    // Crear un buffer temporal para almacenar los colores (HDR) de los píxeles. Lo que no se traza
+    // queda del color de fondo; con recorte y cuadros ya acumulados, fuera del rectángulo se repite
+    // el promedio actual para que la acumulación no lo cambie
+    let mut pixel_buffer: Vec<Vector3> = if crop.is_some() && frame_index > 0 {
+        accumulation.colors.clone()
+    } else {
+        vec![settings.clear_color; (framebuffer.width * framebuffer.height) as usize]
+    };
+    // Lo que quedó fuera del recorte es de otra vista: se borra al empezar a acumular
+    if crop.is_some() && frame_index == 0 {
+        framebuffer.clear(vector3_to_color(settings.clear_color));
+    }
+
+    // Origen y dirección del rayo primario. En estéreo cada ojo ocupa media imagen
+    // y se corre interocular/2 a lo largo de camera.right
+    let primary_ray = |pixel_x: f32, pixel_y: f32| -> (Vector3, Vector3) {
+        let (mut view_x, mut view_y, mut view_width, mut view_height) = (pixel_x, pixel_y, width, height);
+        let mut eye_side = 0.0;
+        match settings.stereo {
+            Some(StereoLayout::SideBySide) => {
+                view_width = width / 2.0;
+                let right_eye = pixel_x >= view_width;
+                if right_eye {
+                    view_x -= view_width;
+                }
+                eye_side = if right_eye { 1.0 } else { -1.0 };
+            }
+            Some(StereoLayout::TopBottom) => {
+                view_height = height / 2.0;
+                let right_eye = pixel_y >= view_height;
+                if right_eye {
+                    view_y -= view_height;
+                }
+                eye_side = if right_eye { 1.0 } else { -1.0 };
+            }
+            None => {}
+        }
+
+        let eye = camera.eye + camera.right * (eye_side * settings.interocular_distance / 2.0);
+
+        (eye, view_direction(camera, settings, view_x, view_y, view_width, view_height))
+    };
+
+    // Cada muestra elige su instante dentro del obturador y mira el diorama girado a ese
+    // instante (se gira el rayo en sentido contrario en vez de mover los objetos)
+    let trace_pixel_path = |pixel_x: f32, pixel_y: f32, path: RayPath, sampler: &mut Sampler| -> Vector3 {
+        sampler.time = if settings.shutter > 0.0 { sampler.rng.random::<f32>() * settings.shutter } else { 0.0 };
+        let rotation = scene.rotation_at(sampler.time);
+        let (eye, direction) = primary_ray(pixel_x, pixel_y);
+        let ray_origin = rotate_around_axis(eye, scene.rotation_axis, -rotation);
+        let ray_direction = rotate_around_axis(direction, scene.rotation_axis, -rotation);
+        let (_, right_direction) = primary_ray(pixel_x + 1.0, pixel_y);
+        let (_, down_direction) = primary_ray(pixel_x, pixel_y + 1.0);
+        sampler.differentials = Some((rotate_around_axis(right_direction, scene.rotation_axis, -rotation), rotate_around_axis(down_direction, scene.rotation_axis, -rotation)));
+        if settings.debug_depth {
+            return depth_debug_color(&ray_origin, &ray_direction, scene, settings, sampler);
+        }
+        let color = cast_ray(&ray_origin, &ray_direction, scene, settings, path, sampler);
+        if settings.fog_density > 0.0 {
+            march_fog(color, &ray_origin, &ray_direction, scene, settings, rotation, &mut sampler.rng)
+        } else {
+            color
+        }
+    };
+    let trace_pixel = |pixel_x: f32, pixel_y: f32, sampler: &mut Sampler| trace_pixel_path(pixel_x, pixel_y, RayPath::primary(), sampler);
+
+    // El primer cuadro muestrea la esquina del píxel como siempre;
+    // los siguientes recorren el patrón de antialiasing dentro del píxel
+    let pixel_jitter = |pixel_index: usize, sampler: &mut Sampler| -> (f32, f32) {
+        if frame_index == 0 {
+            return (0.0, 0.0);
+        }
+        let index = if settings.aa_pattern == AaPattern::Halton { halton_index(frame_index, 0, settings) } else { frame_index - 1 };
+        pattern_offset(settings.aa_pattern, index, JITTER_PATTERN_SAMPLES, pixel_index, &mut sampler.rng)
+    };
+
+    // Paquetes de 2x2 rayos primarios: todos salen del mismo ojo, así que se prueban juntos
+    // contra la caja de la escena. Devuelve, por píxel del bloque, la dirección de los rayos que
+    // la esquivan (ya en el marco del diorama), que solo ven el cielo, o None si la tocan; los
+    // píxeles sueltos del borde impar del bloque quedan fuera de la máscara
+    let image_width = framebuffer.width;
+    let use_packets = settings.packet_tracing && !settings.debug_depth && !settings.show_light_markers && settings.stereo.is_none() && settings.shutter <= 0.0 && settings.fog_density <= 0.0;
+    let packet_sky_mask = |tile: &Tile| -> Vec<Option<Option<Vector3>>> {
+        let mut mask = vec![None; (tile.width * tile.height) as usize];
+        let rotation = scene.rotation_at(0.0);
+        for y in (tile.y..tile.y + tile.height - 1).step_by(2) {
+            for x in (tile.x..tile.x + tile.width - 1).step_by(2) {
+                let pixels = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)];
+                let rays = pixels.map(|(px, py)| {
+                    // El mismo sampler que usa el píxel al trazarse, así Random da el mismo desplazamiento
+                    let pixel_index = (py * image_width + px) as usize;
+                    let (jitter_x, jitter_y) = pixel_jitter(pixel_index, &mut pixel_sampler(frame_index, pixel_index, 0));
+                    primary_ray(px as f32 + jitter_x, py as f32 + jitter_y)
+                });
+                let ray_origin = rotate_around_axis(rays[0].0, scene.rotation_axis, -rotation);
+                let directions = rays.map(|(_, direction)| rotate_around_axis(direction, scene.rotation_axis, -rotation));
+                let hits = scene.packet_hits_bounds(&ray_origin, &directions);
+                for (((px, py), direction), hit) in pixels.into_iter().zip(directions).zip(hits) {
+                    mask[((py - tile.y) * tile.width + px - tile.x) as usize] = Some((!hit).then_some(direction));
+                }
+            }
+        }
+        mask
+    };
+
+    // El denoiser y los contornos necesitan las AOV aunque no se hayan pedido
+    let mut denoise_guides = if (settings.denoise || settings.outline) && aovs.is_none() {
+        Some(AovBuffers::new(framebuffer.width, framebuffer.height))
+    } else {
+        None
+    };
+    let mut aovs = aovs.or(denoise_guides.as_mut());
+    let want_aovs = aovs.is_some();
+
+    // Reproyección: solo en el primer cuadro tras mover la cámara (quieta, la acumulación ya
+    // converge) y con la misma vista del diorama y la misma luz que la historia
+    let lighting = lighting_signature(scene, settings);
+    let record_positions = settings.temporal_reprojection && frame_index == 0 && settings.shutter <= 0.0 && settings.stereo.is_none() && crop.is_none();
+    let history = accumulation.history.as_ref().filter(|history| {
+        record_positions
+            && history.rotation == scene.rotation
+            && history.rotation_axis == scene.rotation_axis
+            && history.lighting == lighting
+            && history.positions.len() == pixel_buffer.len()
+    });
+    let history_colors = &accumulation.colors;
+    let primary_point = |pixel_x: f32, pixel_y: f32| -> Option<Vector3> {
+        let (eye, direction) = primary_ray(pixel_x, pixel_y);
+        let ray_origin = rotate_around_axis(eye, scene.rotation_axis, -scene.rotation);
+        let ray_direction = rotate_around_axis(direction, scene.rotation_axis, -scene.rotation);
+        trace_closest(&ray_origin, &ray_direction, scene.primary_objects(), &scene.texture_manager).map(|(_, hit)| hit.point)
+    };
+
+    // Renderizar por bloques de TILE_SIZE x TILE_SIZE: reparte mejor el trabajo entre hilos
+    // (el cielo vacío y el diorama denso quedan mezclados) y los rayos vecinos comparten caché
+    let tiles = split_into_tiles(region);
+    let tile_count = tiles.len();
+    let tiles_done = AtomicUsize::new(0);
+    let rendered_tiles: Vec<RenderedTile> = tiles
+        .into_par_iter()
+        .filter_map(|tile| {
+            if control.is_some_and(RenderControl::is_cancelled) {
+                return None;
+            }
+            let mut colors = Vec::with_capacity((tile.width * tile.height) as usize);
+            let mut aov_samples = Vec::new();
+            let mut positions = Vec::new();
+            let mut tile_stats = RenderStats::default();
+            let sky_mask = if use_packets { packet_sky_mask(&tile) } else { Vec::new() };
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    let mut sampler = pixel_sampler(frame_index, (y * image_width + x) as usize, 0);
+                    let (jitter_x, jitter_y) = pixel_jitter((y * image_width + x) as usize, &mut sampler);
+
+                    let point = if record_positions { primary_point(x as f32 + jitter_x, y as f32 + jitter_y) } else { None };
+                    let reused = history.zip(point).and_then(|(history, point)| {
+                        reproject(history, history_colors, point, settings, image_width, framebuffer.height)
+                    });
+
+                    let local_index = ((y - tile.y) * tile.width + x - tile.x) as usize;
+                    colors.push(match (reused, sky_mask.get(local_index)) {
+                        (Some(color), _) => color,
+                        (None, Some(Some(Some(direction)))) => sky_color(Some(direction), scene, settings),
+                        // El paquete ya probó la caja de la escena
+                        (None, Some(Some(None))) => {
+                            let path = RayPath { in_bounds: true, ..RayPath::primary() };
+                            trace_pixel_path(x as f32 + jitter_x, y as f32 + jitter_y, path, &mut sampler)
+                        }
+                        _ => trace_pixel(x as f32 + jitter_x, y as f32 + jitter_y, &mut sampler),
+                    });
+                    if record_positions {
+                        positions.push(point);
+                    }
+                    tile_stats = tile_stats.merge(sampler.stats);
+
+                    if want_aovs {
+                        let (eye, direction) = primary_ray(x as f32 + jitter_x, y as f32 + jitter_y);
+                        aov_samples.push(sample_aovs(&eye, &direction, scene, settings, camera));
+                    }
+                }
+            }
+            if let Some(control) = control {
+                (control.on_progress)(tiles_done.fetch_add(1, Ordering::Relaxed) + 1, tile_count);
+            }
+            Some(RenderedTile { tile, colors, aov_samples, positions, stats: tile_stats })
+        })
+        .collect();
+    // Cancelado a mitad del cuadro: los bloques que faltan no se trazaron
+    let cancelled = rendered_tiles.len() < tile_count;
+
+    let mut stats = RenderStats::default();
+    let mut positions = if record_positions { vec![None; pixel_buffer.len()] } else { Vec::new() };
+    for RenderedTile { tile, colors, aov_samples, positions: tile_positions, stats: tile_stats } in rendered_tiles {
+        stats = stats.merge(tile_stats);
+        for (i, point) in tile_positions.into_iter().enumerate() {
+            positions[((tile.y + i as u32 / tile.width) * image_width + tile.x + i as u32 % tile.width) as usize] = point;
+        }
+        for (row, tile_row) in colors.chunks(tile.width as usize).enumerate() {
+            let start = ((tile.y + row as u32) * image_width + tile.x) as usize;
+            pixel_buffer[start..start + tile_row.len()].copy_from_slice(tile_row);
+        }
+
+        if let Some(aovs) = aovs.as_deref_mut() {
+            for (i, sample) in aov_samples.into_iter().enumerate() {
+                let x = tile.x + i as u32 % tile.width;
+                let y = tile.y + i as u32 / tile.width;
+                aovs.set((y * image_width + x) as usize, sample);
+            }
+        }
+    }
+
+    if settings.adaptive_aa && settings.aa_samples > 1 && !cancelled {
+        let (refined, refine_stats) = refine_edges(&pixel_buffer, framebuffer.width as usize, region, settings, frame_index, &trace_pixel);
+        pixel_buffer = refined;
+        stats = stats.merge(refine_stats);
+    }
+
+    // Un cuadro cortado no entra al promedio, salvo que sea el primero (si no, no habría nada que
+    // mostrar); tampoco sirve como historia para reproyectar
+    if !cancelled || frame_index == 0 {
+        accumulation.add_frame(&pixel_buffer);
+    }
+    if record_positions && !cancelled {
+        accumulation.history = Some(FrameHistory { camera: camera.clone(), rotation: scene.rotation, rotation_axis: scene.rotation_axis, lighting, positions });
+    } else if !settings.temporal_reprojection || cancelled {
+        accumulation.history = None;
+    }
+
+    // Posproceso, siempre sobre color lineal HDR y en este orden: denoiser (guiado por las AOV),
+    // resplandor, corrección de color, contornos y recién al final la cuantización a 8 bits. Todo
+    // esto solo afecta lo que se muestra; el promedio acumulado queda intacto
+    let denoised;
+    let display_colors = match &aovs {
+        Some(guides) if settings.denoise => {
+            denoised = postprocess::denoise(&accumulation.colors, guides, settings);
+            &denoised
+        }
+        _ => &accumulation.colors,
+    };
+
+    let bloomed;
+    let display_colors = if settings.bloom_strength > 0.0 {
+        bloomed = postprocess::bloom(display_colors, framebuffer.width as usize, settings);
+        &bloomed
+    } else {
+        display_colors
+    };
+
+    let graded;
+    let display_colors = if postprocess::is_grading_neutral(settings) {
+        display_colors
+    } else {
+        graded = postprocess::color_grade(display_colors, framebuffer.width as usize, settings);
+        &graded
+    };
+
+    let outlined;
+    let display_colors = match &aovs {
+        Some(guides) if settings.outline => {
+            outlined = postprocess::outline(display_colors, guides, settings);
+            &outlined
+        }
+        _ => display_colors,
+    };
+
+    // Cuantizar en paralelo por filas y después copiar al framebuffer, que solo se escribe
+    // desde un hilo
+    let columns = region.x..region.x + region.width;
+    let rows: Vec<Vec<Color>> = (region.y..region.y + region.height).into_par_iter().map(|y| {
+        columns.clone().map(|x| {
+            let index = (y * framebuffer.width + x) as usize;
+            // El ruido sale del generador del píxel con un cuadro fijo: no parpadea entre cuadros
+            let noise = if settings.dither { pixel_sampler(0, index, 2).rng.random::<f32>() } else { 0.0 };
+            let color = display_colors[index];
+            let color = if settings.linear_lighting {
+                Vector3::new(linear_to_srgb(color.x), linear_to_srgb(color.y), linear_to_srgb(color.z))
+            } else {
+                color
+            };
+            vector3_to_color_dithered(color, noise)
+        }).collect()
+    }).collect();
+    for (y, row) in (region.y..).zip(rows) {
+        for (x, color) in columns.clone().zip(row) {
+            framebuffer.set_pixel_color(x, y, color);
+        }
+    }
+
+    (stats, !cancelled)
+}
+
+
+// Los bloques, los adornos del diorama incorporado (`decorations`) y, si se pidió, el piso
+// o la mesa bajo ellos
+pub fn scene_objects(grid: &BlockGrid, decorations: bool, ground: Option<Ground>, settings: &RenderSettings) -> Vec<SceneObject> {
+    let mut objects: Vec<SceneObject> = if settings.sdf_blocks {
+        grid.to_sdf_objects(settings.sdf_rounding, settings.sdf_blend)
+            .into_iter()
+            .map(|object| Box::new(object) as SceneObject)
+            .collect()
+    } else {
+        grid.to_cubes().into_iter().map(|cube| Box::new(cube) as SceneObject).collect()
+    };
+    if decorations {
+        objects.extend(create_decorations(grid));
+    }
+    match ground {
+        Some(Ground::Polished) => objects.push(Box::new(create_ground_plane(grid, false))),
+        Some(Ground::Wet) => objects.push(Box::new(create_ground_plane(grid, true))),
+        Some(Ground::RoundTable) => objects.push(Box::new(create_round_table(grid))),
+        Some(Ground::SquareTable) => objects.push(Box::new(create_square_table(grid))),
+        None => {}
+    }
+    objects
+}
+
+// Normales (hacia adentro) de los planos laterales y cercano del frustum; todos pasan por el ojo
+fn frustum_normals(camera: &Camera, settings: &RenderSettings, aspect_ratio: f32) -> [Vector3; 5] {
+    let (half_width, half_height) = view_extent(settings, aspect_ratio);
+
+    let side_normal = |edge: Vector3, axis: Vector3| {
+        let normal = edge.cross(axis).normalized();
+        if normal.dot(camera.forward) < 0.0 { -normal } else { normal }
+    };
+
+    [
+        side_normal(camera.forward - camera.right * half_width, camera.up),
+        side_normal(camera.forward + camera.right * half_width, camera.up),
+        side_normal(camera.forward - camera.up * half_height, camera.right),
+        side_normal(camera.forward + camera.up * half_height, camera.right),
+        camera.forward,
+    ]
+}
+
+// Culling por frustum para los rayos primarios, en el marco del diorama. Con desenfoque de
+// movimiento las muestras ven el diorama en otros ángulos, y en estéreo cada ojo tiene su
+// propio frustum, así que en esos casos no se descarta nada
+pub fn update_culling(scene: &mut Scene, camera: &Camera, settings: &RenderSettings, aspect_ratio: f32) {
+    if (settings.shutter > 0.0 && scene.rotation_speed != 0.0) || settings.stereo.is_some() {
+        scene.show_all();
+        return;
+    }
+
+    let eye = rotate_around_axis(camera.eye, scene.rotation_axis, -scene.rotation);
+    let normals = frustum_normals(camera, settings, aspect_ratio).map(|normal| rotate_around_axis(normal, scene.rotation_axis, -scene.rotation));
+    scene.cull(eye, &normals);
+}
+
+// Rehace el mapa de cáusticas del sol; hace falta cada vez que se mueven el sol o el diorama
+pub fn update_caustics(scene: &mut Scene, settings: &RenderSettings) {
+    scene.caustics = None;
+    if !settings.caustics {
+        return;
+    }
+
+    // Los fotones viajan en el marco del diorama, igual que los rayos
+    let mut sun = scene.lights[daynight::SUN].clone();
+    sun.position = rotate_around_axis(sun.position, scene.rotation_axis, -scene.rotation);
+    scene.caustics = Some(CausticMap::build(scene, &sun, settings));
+}
+
+// Rehace el mapa de sombras del sol; como las cáusticas, hace falta cada vez que se mueven el
+// sol, el diorama o los objetos
+pub fn update_shadow_map(scene: &mut Scene, settings: &RenderSettings) {
+    scene.shadow_map = None;
+    if !settings.shadow_map {
+        return;
+    }
+
+    let sun_position = rotate_around_axis(scene.lights[daynight::SUN].position, scene.rotation_axis, -scene.rotation);
+    scene.shadow_map = ShadowMap::build(scene, &sun_position, settings);
+}
+
+// Cuadro de una animación por lotes: vista, tiempo de los materiales animados, archivo y si se
+// guarda con la fila de abajo primero
+pub struct BatchFrame {
+    pub camera: Camera,
+    pub time: f32,
+    pub sun_angle: f32,
+    pub path: String,
+    pub flip_y: bool,
+}
+
+// Renderiza los cuadros en paralelo (una escena por hilo) y guarda cada uno con sus metadatos
+pub fn render_batch(
+    frames: Vec<BatchFrame>,
+    (width, height): (u32, u32),
+    samples: u32,
+    settings: &RenderSettings,
+    build_scene: &(dyn Fn() -> Scene + Sync),
+    on_frame_done: &(dyn Fn(&str, std::io::Result<()>, RenderStats) + Sync),
+    cancel: &AtomicBool,
+) {
+    frames.into_par_iter().for_each_init(build_scene, |scene, frame| {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        scene.time = frame.time;
+        update_culling(scene, &frame.camera, settings, width as f32 / height as f32);
+        let mut framebuffer = Framebuffer::new(width, height);
+        let mut accumulation = AccumulationBuffer::new(width, height);
+        let control = RenderControl { cancel, on_progress: &|_, _| {} };
+        let (stats, _) = render_samples(&mut framebuffer, scene, &frame.camera, settings, &mut accumulation, samples, &control);
+        if accumulation.frame_count == 0 {
+            return;
+        }
+        let metadata = metadata::RenderMetadata { camera: &frame.camera, sun_angle: frame.sun_angle, samples: accumulation.frame_count, width, height };
+        let result = save_image(&frame.path, &mut framebuffer, &accumulation, frame.flip_y)
+            .and_then(|()| metadata::save_metadata(&frame.path, &metadata, scene, settings));
+        on_frame_done(&frame.path, result, stats);
+    });
+}
+
+// "render.png" -> "render_0007.png"
+pub fn numbered_path(path: &str, frame: u32) -> String {
+    match path.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}_{:04}.{}", stem, frame, extension),
+        None => format!("{}_{:04}", path, frame),
+    }
+}
+
+// Guarda la imagen según la extensión: EXR con el buffer HDR lineal, PPM, o cualquier
+// otro formato que exporte raylib (PNG) desde el framebuffer ya tonemapeado.
+// Todos se escriben con la fila de arriba primero, igual que en la ventana; `flip_y`
+// los invierte para programas que esperan la fila de abajo primero
+pub fn save_image(path: &str, framebuffer: &mut Framebuffer, accumulation: &AccumulationBuffer, flip_y: bool) -> std::io::Result<()> {
+    if path.ends_with(".exr") {
+        if flip_y {
+            let flipped: Vec<Vector3> = accumulation.colors.chunks(framebuffer.width as usize).rev().flatten().copied().collect();
+            return exr::save_exr(path, framebuffer.width, framebuffer.height, &flipped);
+        }
+        return exr::save_exr(path, framebuffer.width, framebuffer.height, &accumulation.colors);
+    }
+
+    if flip_y {
+        framebuffer.flip_vertical();
+    }
+    let result = if path.ends_with(".ppm") {
+        framebuffer.save_to_ppm(path)
+    } else {
+        framebuffer.render_to_file(path)
+    };
+    // La ventana sigue mostrando la imagen al derecho
+    if flip_y {
+        framebuffer.flip_vertical();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "esperaba {expected}, dio {actual}");
+    }
+
+    fn assert_vector_close(actual: Vector3, expected: Vector3) {
+        assert!((actual - expected).length() < 1e-4, "esperaba {expected:?}, dio {actual:?}");
+    }
+
+    const GLASS: f32 = 1.5;
+    const UP: Vector3 = Vector3 { x: 0.0, y: 1.0, z: 0.0 };
+
+    // Dirección que baja hacia la cara +Y con ángulo `degrees` respecto de la normal
+    fn falling_at(degrees: f32) -> Vector3 {
+        let angle = degrees.to_radians();
+        Vector3::new(angle.sin(), -angle.cos(), 0.0)
+    }
+
+    #[test]
+    fn refract_air_to_glass_bends_toward_the_normal() {
+        let refracted = refract(&falling_at(45.0), &UP, GLASS).unwrap();
+        assert_close(refracted.length(), 1.0);
+        assert_close(refracted.x, 45f32.to_radians().sin() / GLASS);
+        assert!(refracted.y < 0.0);
+    }
+
+    #[test]
+    fn refract_glass_to_air_bends_away_from_the_normal() {
+        // Sale por la cara +Y desde adentro: el rayo va en el mismo sentido que la normal
+        let inside = Vector3::new(20f32.to_radians().sin(), 20f32.to_radians().cos(), 0.0);
+        let refracted = refract(&inside, &UP, GLASS).unwrap();
+        assert_close(refracted.length(), 1.0);
+        assert_close(refracted.x, 20f32.to_radians().sin() * GLASS);
+        assert!(refracted.y > 0.0);
+    }
+
+    #[test]
+    fn refract_at_normal_incidence_goes_straight_through() {
+        assert_vector_close(refract(&-UP, &UP, GLASS).unwrap(), -UP);
+    }
+
+    #[test]
+    fn refract_at_grazing_incidence_reaches_the_critical_angle() {
+        let refracted = refract(&falling_at(89.99), &UP, GLASS).unwrap();
+        assert_close(refracted.length(), 1.0);
+        assert!((refracted.x - 1.0 / GLASS).abs() < 1e-3);
+    }
+
+    #[test]
+    fn refract_past_the_critical_angle_is_total_internal_reflection() {
+        let critical = (1.0 / GLASS).asin().to_degrees();
+        let inside = |degrees: f32| Vector3::new(degrees.to_radians().sin(), degrees.to_radians().cos(), 0.0);
+        assert!(refract(&inside(critical - 1.0), &UP, GLASS).is_some());
+        assert!(refract(&inside(critical + 1.0), &UP, GLASS).is_none());
+        assert!(refract(&inside(60.0), &UP, GLASS).is_none());
+    }
+
+    #[test]
+    fn refract_swaps_eta_on_back_face_hits() {
+        // Volver por el mismo camino desde adentro (cara trasera) debe deshacer la refracción
+        let incident = falling_at(35.0);
+        let refracted = refract(&incident, &UP, GLASS).unwrap();
+        assert_vector_close(refract(&-refracted, &UP, GLASS).unwrap(), -incident);
+        // Y equivale a refractar de vidrio a aire con la normal hacia el rayo
+        assert_vector_close(refract(&-refracted, &UP, GLASS).unwrap(), refract_between(&-refracted, &-UP, GLASS, 1.0).unwrap());
+    }
+
+    const FACE_NORMALS: [Vector3; 6] = [
+        Vector3 { x: 1.0, y: 0.0, z: 0.0 },
+        Vector3 { x: -1.0, y: 0.0, z: 0.0 },
+        Vector3 { x: 0.0, y: 1.0, z: 0.0 },
+        Vector3 { x: 0.0, y: -1.0, z: 0.0 },
+        Vector3 { x: 0.0, y: 0.0, z: 1.0 },
+        Vector3 { x: 0.0, y: 0.0, z: -1.0 },
+    ];
+
+    #[test]
+    fn tangent_frame_is_orthonormal_on_every_cube_face() {
+        for normal in FACE_NORMALS {
+            let (tangent, bitangent) = tangent_frame(&normal);
+            assert_close(tangent.length(), 1.0);
+            assert_close(bitangent.length(), 1.0);
+            assert_close(tangent.dot(normal), 0.0);
+            assert_close(bitangent.dot(normal), 0.0);
+            assert_close(tangent.dot(bitangent), 0.0);
+            assert_close(tangent.cross(bitangent).dot(normal), 1.0);
+        }
+    }
+
+    #[test]
+    fn tangent_space_normal_tilts_z_face_like_y_face() {
+        // La misma normal del mapa debe inclinar igual a la cara +Z que a la +Y
+        let tex_normal = Vector3::new(0.3, 0.2, 0.9).normalized();
+        let tilt = |normal: Vector3| {
+            let (tangent, bitangent) = tangent_frame(&normal);
+            let mapped = (tangent * tex_normal.x + bitangent * tex_normal.y + normal * tex_normal.z).normalized();
+            mapped.dot(normal)
+        };
+        assert_close(tilt(Vector3::new(0.0, 0.0, 1.0)), tex_normal.z);
+        assert_close(tilt(Vector3::new(0.0, 1.0, 0.0)), tex_normal.z);
+        assert!(tilt(Vector3::new(0.0, 0.0, 1.0)) < 0.99);
+    }
+
+    // Tres bloques sobre el eje -Z (el de índice 2 es el más cercano) y uno sobre el -X
+    fn row_of_cubes() -> Scene {
+        let objects: Vec<SceneObject> = [(0.0, -6.0), (-3.0, 0.0), (0.0, -2.0), (0.0, -4.0)]
+            .into_iter()
+            .map(|(x, z)| Box::new(cube::Cube::new(Vector3::new(x, 0.0, z), 1.0, Material::black())) as SceneObject)
+            .collect();
+        Scene::new(objects, Vec::new(), TextureManager::new())
+    }
+
+    #[test]
+    fn trace_closest_returns_the_index_of_the_nearest_cube() {
+        let scene = row_of_cubes();
+        let objects = scene.objects().iter().enumerate();
+        let (index, hit) = trace_closest(&Vector3::zero(), &Vector3::new(0.0, 0.0, -1.0), objects, &scene.texture_manager).unwrap();
+        assert_eq!(index, 2);
+        assert_close(hit.distance, 1.5);
+        assert_eq!(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+
+        let objects = scene.objects().iter().enumerate();
+        assert!(trace_closest(&Vector3::zero(), &Vector3::new(0.0, 0.0, 1.0), objects, &scene.texture_manager).is_none());
+    }
+
+    #[test]
+    fn pick_finds_the_cube_under_the_center_pixel() {
+        let mut scene = row_of_cubes();
+        let settings = RenderSettings::default();
+        let camera = Camera::new(Vector3::zero(), Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, 0.0));
+        let (index, _) = pick(&scene, &camera, &settings, 50.0, 50.0, 100.0, 100.0).unwrap();
+        assert_eq!(index, 2);
+        // Con el diorama girado un cuarto de vuelta la cámara mira hacia -X en su marco
+        scene.rotation = PI / 2.0;
+        let (index, hit) = pick(&scene, &camera, &settings, 50.0, 50.0, 100.0, 100.0).unwrap();
+        assert_eq!(index, 1);
+        assert_close(hit.distance, 2.5);
+    }
+
+    fn diorama(width: u32, height: u32) -> (Scene, Camera, RenderSettings) {
+        let settings = RenderSettings::default();
+        let grid = BlockGrid::from_layers(blocks::get_layers());
+        let mut scene = Scene::new(scene_objects(&grid, true, None, &settings), daynight::create_lights(8.0), TextureManager::new());
+        scene.preload_textures();
+        let camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+        update_culling(&mut scene, &camera, &settings, width as f32 / height as f32);
+        (scene, camera, settings)
+    }
+
+    #[test]
+    fn accumulated_frames_do_not_depend_on_thread_count() {
+        let (width, height) = (80, 48);
+        let (scene, camera, settings) = diorama(width, height);
+        let accumulate = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                let mut framebuffer = Framebuffer::new(width, height);
+                let mut accumulation = AccumulationBuffer::new(width, height);
+                for _ in 0..3 {
+                    render(&mut framebuffer, &scene, &camera, &settings, &mut accumulation, None);
+                }
+                accumulation.colors.clone()
+            })
+        };
+        assert_eq!(accumulate(1), accumulate(4));
+    }
+
+    #[test]
+    fn render_samples_reports_whether_every_sample_ran() {
+        let (width, height) = (40, 24);
+        let (scene, camera, settings) = diorama(width, height);
+        let run = |cancel_at: usize| {
+            let cancel = AtomicBool::new(false);
+            let on_progress = |done: usize, _: usize| {
+                if done >= cancel_at {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            };
+            let control = RenderControl { cancel: &cancel, on_progress: &on_progress };
+            let mut framebuffer = Framebuffer::new(width, height);
+            let mut accumulation = AccumulationBuffer::new(width, height);
+            let (_, completed) = render_samples(&mut framebuffer, &scene, &camera, &settings, &mut accumulation, 2, &control);
+            (completed, accumulation.frame_count)
+        };
+        // Cancelar después del último bloque no le quita nada al render
+        let tiles = split_into_tiles(CropRect { x: 0, y: 0, width, height }).len();
+        assert_eq!(run(2 * tiles), (true, 2));
+        // Cancelar durante el primer cuadro lo deja cortado
+        assert!(!run(1).0);
+    }
+
+    #[test]
+    fn spin_only_blurs_while_the_shutter_is_open() {
+        let (width, height) = (80, 48);
+        let (mut scene, camera, _) = diorama(width, height);
+        let frame = |scene: &Scene, shutter: f32| {
+            let settings = RenderSettings { shutter, ..RenderSettings::default() };
+            let mut framebuffer = Framebuffer::new(width, height);
+            let mut accumulation = AccumulationBuffer::new(width, height);
+            for _ in 0..2 {
+                render(&mut framebuffer, scene, &camera, &settings, &mut accumulation, None);
+            }
+            accumulation.colors.clone()
+        };
+        let still = frame(&scene, 0.0);
+        scene.rotation_speed = 0.3;
+        scene.show_all();
+        assert_eq!(frame(&scene, 0.0), still);
+        assert_ne!(frame(&scene, 1.0), still);
+    }
+
+    #[test]
+    fn single_sided_mesh_culls_by_its_face_not_its_smoothed_normal() {
+        // Triángulo de cara hacia +Z con normales por vértice volcadas casi de canto hacia -Z
+        let tilted = Vector3::new(1.0, 0.0, -0.3).normalized();
+        let mesh = mesh::TriangleMesh::new(
+            vec![Vector3::new(-1.0, -1.0, 0.0), Vector3::new(1.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0)],
+            vec![tilted; 3],
+            Vec::new(),
+            vec![[0, 1, 2]],
+            Material::builder().double_sided(false).build(),
+        );
+        let textures = TextureManager::new();
+        let trace = |origin: Vector3, direction: Vector3| {
+            intersect_object(&mesh, &origin, &direction, &inverse_direction(&direction), &textures)
+        };
+
+        // De frente se ve aunque la normal suavizada se aleje; desde atrás se atraviesa
+        let front = trace(Vector3::new(0.0, 0.0, 2.0), Vector3::new(0.0, 0.0, -1.0));
+        assert!(front.is_intersecting);
+        assert_vector_close(front.face_normal, Vector3::new(0.0, 0.0, 1.0));
+        assert_vector_close(front.normal, tilted);
+        assert!(!trace(Vector3::new(0.0, 0.0, -2.0), Vector3::new(0.0, 0.0, 1.0)).is_intersecting);
+    }
+
+    #[test]
+    fn offset_origin_follows_the_normal_or_the_ray() {
+        let direction = Vector3::new(0.0, -1.0, 0.0);
+        let mut hit = Intersect::new(Vector3::zero(), UP, 1.0, Material::black(), 0.0, 0.0);
+        // Un rebote que vuelve a entrar se empuja por debajo de la superficie
+        assert_vector_close(offset_origin_by(&hit, &direction, 0.01), Vector3::new(0.0, -0.01, 0.0));
+        assert_vector_close(offset_origin_by(&hit, &-direction, 0.01), Vector3::new(0.0, 0.01, 0.0));
+
+        // Sin normal válida se avanza por el rayo en vez de producir NaN
+        for normal in [Vector3::zero(), Vector3::new(f32::NAN, 0.0, 0.0)] {
+            hit.normal = normal;
+            let origin = offset_origin_by(&hit, &Vector3::new(2.0, 0.0, 0.0), 0.01);
+            assert_vector_close(origin, Vector3::new(0.01, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn origin_bias_grows_only_away_from_the_origin() {
+        // Cerca del origen y con rayos cortos el sesgo baja del base, hasta un piso en el origen
+        let near = Intersect::new(Vector3::new(0.5, 0.2, -0.3), UP, 0.8, Material::black(), 0.0, 0.0);
+        assert_close(bias_scale(&near), 0.8);
+        let settings = RenderSettings::default();
+        assert_close((offset_origin(&near, &UP, &settings) - near.point).length(), settings.origin_bias * 0.8);
+        let at_origin = Intersect::new(Vector3::zero(), UP, 0.0, Material::black(), 0.0, 0.0);
+        assert_close(bias_scale(&at_origin), MIN_BIAS_SCALE);
+
+        // Crece con la coordenada más grande del punto o con la distancia recorrida
+        let far = Intersect::new(Vector3::new(-3000.0, 2.0, 40.0), UP, 12.0, Material::black(), 0.0, 0.0);
+        assert_close(bias_scale(&far), 3000.0);
+        let long_ray = Intersect::new(Vector3::new(1.0, 0.0, 0.0), UP, 250.0, Material::black(), 0.0, 0.0);
+        assert_close(bias_scale(&long_ray), 250.0);
+    }
+
+    #[test]
+    fn far_surfaces_do_not_shadow_themselves() {
+        let floor = plane::Plane::new(Vector3::zero(), Vector3::new(0.3, 1.0, 0.2), 1.0, Material::black());
+        let eye = Vector3::new(0.0, 40.0, 0.0);
+        let mut rng = SmallRng::seed_from_u64(651);
+        for _ in 0..2000 {
+            // Impactos rasantes a miles de unidades, donde un ulp de f32 ya supera el sesgo base
+            let target = Vector3::new(rng.random_range(2000.0..6000.0), 0.0, rng.random_range(-6000.0..-2000.0));
+            let target = target - floor.normal * (target - floor.point).dot(floor.normal);
+            let hit = floor.ray_intersect(&eye, &(target - eye).normalized());
+            assert!(hit.is_intersecting);
+
+            let toward_light = Vector3::new(0.1, 1.0, -0.4).normalized();
+            let origin = offset_origin(&hit, &toward_light, &RenderSettings::default());
+            assert!(!floor.ray_intersect(&origin, &toward_light).is_intersecting, "acné en {:?}", hit.point);
+        }
+    }
+
+    #[test]
+    fn thin_legs_neither_leak_nor_shadow_themselves() {
+        // Una pata del banquito (0.06 de ancho) donde queda en el diorama: la luz del otro lado
+        // no se filtra por la pata y la del mismo lado, aun rasante, no la sombrea
+        let cube: instance::SharedGeometry = std::sync::Arc::new(cube::Cube::new(Vector3::zero(), 1.0, Material::black()));
+        let leg = instance::Instance::new(cube, Matrix::scale(0.06, 0.35, 0.06) * Matrix::translate(-4.3, -0.3, 0.2), None);
+        let scene = Scene::new(vec![Box::new(leg) as SceneObject], Vec::new(), TextureManager::new());
+        let settings = RenderSettings::default();
+        let mut rng = SmallRng::seed_from_u64(651);
+        let mut checked = 0;
+        for _ in 0..500 {
+            let target = Vector3::new(-4.3 + rng.random_range(-0.03..0.03), rng.random_range(-0.45..-0.15), 0.2 + rng.random_range(-0.03..0.03));
+            let eye = target + Vector3::new(rng.random_range(-1.0..1.0), rng.random_range(-0.3..0.3), rng.random_range(-1.0..1.0)).normalized() * 3.0;
+            let hit = scene.objects()[0].ray_intersect(&eye, &(target - eye).normalized());
+            if !hit.is_intersecting {
+                continue;
+            }
+            checked += 1;
+            let (tangent, _) = tangent_frame(&hit.normal);
+            let slant = rng.random_range(0.0..8.0);
+            let behind = hit.point + (-hit.normal + tangent * rng.random_range(-0.3..0.3)).normalized() * 5.0;
+            let grazing = hit.point + (hit.normal + tangent * slant).normalized() * 5.0;
+            assert_eq!(cast_shadow(&hit, &behind, 0.0, &scene, &settings, &mut rng), Vector3::zero(), "fuga en {:?}", hit.point);
+            assert_eq!(cast_shadow(&hit, &grazing, 0.0, &scene, &settings, &mut rng), Vector3::one(), "acné en {:?}", hit.point);
+        }
+        assert!(checked > 300);
+    }
+
+    const EMISSION: Vector3 = Vector3 { x: 1.0, y: 0.95, z: 0.3 };
+
+    fn glowing() -> Material {
+        Material::builder().diffuse(Vector3::new(0.2, 0.2, 0.2)).emissive(EMISSION, 0.6).build()
+    }
+
+    fn cubes(placed: Vec<(Vector3, Material)>) -> Scene {
+        let objects = placed
+            .into_iter()
+            .map(|(center, material)| Box::new(cube::Cube::new(center, 1.0, material)) as SceneObject)
+            .collect();
+        Scene::new(objects, Vec::new(), TextureManager::new())
+    }
+
+    #[test]
+    fn light_markers_are_only_seen_by_the_camera() {
+        let mut scene = cubes(vec![(Vector3::zero(), Material::builder().diffuse(Vector3::one()).build())]);
+        let light_color = Vector3::new(1.0, 0.9, 0.5);
+        scene.lights = vec![light::Light::new(Vector3::new(0.0, 5.0, 0.0), light_color, 1.0)];
+        let plain = RenderSettings::default();
+        let marked = RenderSettings { show_light_markers: true, ..RenderSettings::default() };
+        let trace = |origin: Vector3, direction: Vector3, settings: &RenderSettings| {
+            cast_ray(&origin, &direction, &scene, settings, RayPath::primary(), &mut pixel_sampler(0, 0, 0))
+        };
+
+        let toward_light = (Vector3::new(3.0, 5.0, 0.0), Vector3::new(-1.0, 0.0, 0.0));
+        assert_vector_close(trace(toward_light.0, toward_light.1, &marked), light_color);
+        assert_vector_close(trace(toward_light.0, toward_light.1, &plain), plain.sky_color);
+        // El cubo tapa la marca que está detrás
+        let through_cube = trace(Vector3::new(0.0, -3.0, 0.0), UP, &marked);
+        assert!((through_cube - light_color).length() > 0.1, "{through_cube:?}");
+        // La cara de arriba del cubo se ilumina igual: la marca no da sombra ni luz
+        let top = (Vector3::new(0.2, 3.0, 0.1), -UP);
+        assert_vector_close(trace(top.0, top.1, &marked), trace(top.0, top.1, &plain));
+    }
+
+    #[test]
+    fn emitters_past_the_depth_limit_keep_their_emission() {
+        let scene = cubes(vec![(Vector3::new(0.0, 0.0, -3.0), glowing()), (Vector3::new(3.0, 0.0, -3.0), Material::black())]);
+        let settings = RenderSettings::default();
+        let spent = RayPath { depth: settings.max_depth + 1, specular: true, ..RayPath::primary() };
+        let mut sampler = pixel_sampler(0, 0, 0);
+
+        let emitter = cast_ray(&Vector3::zero(), &Vector3::new(0.0, 0.0, -1.0), &scene, &settings, spent, &mut sampler);
+        assert_vector_close(emitter, EMISSION * 0.6);
+        // Lo que no emite, o nada, sigue cortando con el cielo
+        let dark = cast_ray(&Vector3::new(3.0, 0.0, 0.0), &Vector3::new(0.0, 0.0, -1.0), &scene, &settings, spent, &mut sampler);
+        assert_vector_close(dark, settings.sky_color);
+        let sky = cast_ray(&Vector3::zero(), &UP, &scene, &settings, spent, &mut sampler);
+        assert_vector_close(sky, settings.sky_color);
+        // Un rayo que no viene de un reflejo ni de una refracción no busca nada
+        let unlit = cast_ray(&Vector3::zero(), &Vector3::new(0.0, 0.0, -1.0), &scene, &settings, RayPath { specular: false, ..spent }, &mut sampler);
+        assert_vector_close(unlit, settings.sky_color);
+    }
+
+    #[test]
+    fn last_mirror_bounce_still_shows_the_emitter() {
+        let mirror = Material::builder().diffuse(Vector3::new(0.8, 0.8, 0.85)).albedo([0.0, 0.0, 1.0, 0.0]).build();
+        // Un solo rebote: el reflejo ya pasa el límite de profundidad
+        let settings = RenderSettings { max_depth: 0, ..RenderSettings::default() };
+        let mut sampler = pixel_sampler(0, 0, 0);
+        let look = Vector3::new(0.0, 0.0, -1.0);
+
+        let scene = cubes(vec![(Vector3::new(0.0, 0.0, -3.0), mirror), (Vector3::new(0.0, 0.0, 3.0), glowing())]);
+        let reflected = cast_ray(&Vector3::zero(), &look, &scene, &settings, RayPath::primary(), &mut sampler);
+
+        // El espejo devuelve la emisión entera (más un poco de su difuso), no el color del cielo
+        let full = EMISSION * 0.6;
+        for (seen, emitted) in [(reflected.x, full.x), (reflected.y, full.y), (reflected.z, full.z)] {
+            assert!(seen >= emitted - 1e-3 && seen < emitted + 0.05, "{reflected:?} en vez de {full:?}");
+        }
+    }
+
+    #[test]
+    fn oversized_weights_never_subtract_color() {
+        // Los campos son públicos: pesos fuera de rango o un barniz exagerado pueden llegar sin
+        // pasar por normalize_albedo
+        let base = Material::builder().diffuse(Vector3::new(0.9, 0.2, 0.1)).specular(40.0).build();
+        let mut over_unity = base.clone();
+        over_unity.albedo = [0.8, 0.6, 0.9, 0.9];
+        let mut thick_coat = base;
+        thick_coat.albedo = [0.8, 0.6, 0.0, 0.0];
+        thick_coat.clearcoat = 6.0;
+
+        // Con el cielo negro el reflejo y la refracción no aportan nada que tape un peso negativo
+        let settings = RenderSettings { sky_color: Vector3::zero(), ..RenderSettings::default() };
+        let mut sampler = pixel_sampler(0, 0, 0);
+        for material in [over_unity, thick_coat] {
+            let mut scene = cubes(vec![(Vector3::new(0.0, 0.0, -3.0), material)]);
+            scene.lights = daynight::create_lights(8.0);
+            daynight::update_day_night(&mut scene.lights, 0.4, 8.0, &daynight::sun_ramp());
+
+            for i in 0..21 {
+                for j in 0..21 {
+                    let target = Vector3::new(i as f32 / 20.0 - 0.5, j as f32 / 20.0 - 0.5, -2.5);
+                    // De frente y rasante a la cara del frente, donde el barniz refleja casi todo
+                    for origin in [Vector3::zero(), Vector3::new(-3.0, target.y, -2.4)] {
+                        let direction = (target - origin).normalized();
+                        let color = cast_ray(&origin, &direction, &scene, &settings, RayPath::primary(), &mut sampler);
+                        assert!(color.x >= 0.0 && color.y >= 0.0 && color.z >= 0.0, "{color:?} desde {origin:?} hacia {target:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reused_preview_buffers_match_fresh_ones() {
+        let (width, height, scale) = (81, 47, 4);
+        assert_eq!(preview_size(width, height, scale), (21, 12));
+        let (scene, camera, settings) = diorama(width, height);
+        let (preview_width, preview_height) = preview_size(width, height, scale);
+        let preview_frame = |camera: &Camera, preview: &mut Framebuffer, accumulation: &mut AccumulationBuffer| {
+            let mut framebuffer = Framebuffer::new(width, height);
+            render_preview(&mut framebuffer, preview, accumulation, &scene, camera, &settings, scale);
+            framebuffer.color_buffer.get_image_data().to_vec()
+        };
+
+        // Los mismos buffers a lo largo de varios cuadros, con la cámara moviéndose en el medio
+        let mut preview = Framebuffer::new(preview_width, preview_height);
+        let mut accumulation = AccumulationBuffer::new(preview_width, preview_height);
+        let mut moved = camera.clone();
+        moved.orbit(0.3, 0.1);
+        preview_frame(&camera, &mut preview, &mut accumulation);
+        preview_frame(&moved, &mut preview, &mut accumulation);
+        let reused = preview_frame(&camera, &mut preview, &mut accumulation);
+
+        let fresh = preview_frame(&camera, &mut Framebuffer::new(preview_width, preview_height), &mut AccumulationBuffer::new(preview_width, preview_height));
+        assert!(reused == fresh, "la vista previa arrastra cuadros anteriores");
+    }
+
+    #[test]
+    fn dispersion_splits_the_ray_only_once() {
+        // Dos cubos de vidrio en fila: el rayo cruza cuatro caras
+        let glass = |abbe: f32| presets::glass(material::IOR_GLASS).into_builder().dispersion(abbe).build();
+        let settings = RenderSettings { max_depth: 8, enable_reflections: false, ..RenderSettings::default() };
+        let rays = |abbe: f32| {
+            let scene = cubes(vec![(Vector3::new(0.0, 0.0, -2.0), glass(abbe)), (Vector3::new(0.0, 0.0, -4.0), glass(abbe))]);
+            let mut sampler = pixel_sampler(0, 0, 0);
+            cast_ray(&Vector3::zero(), &Vector3::new(0.0, 0.0, -1.0), &scene, &settings, RayPath::primary(), &mut sampler);
+            sampler.stats.rays
+        };
+
+        let clear = rays(0.0);
+        assert_eq!(clear, 5);
+        // Se abre en el primer vidrio: el rayo primario más tres caminos de cuatro rayos cada uno
+        assert_eq!(rays(40.0), 1 + 3 * (clear - 1));
+    }
+
+    #[test]
+    fn shared_inverse_direction_gives_the_same_hits() {
+        // Cada objeto del diorama con la inversa precalculada contra su ray_intersect de siempre,
+        // con rayos primarios y rayos desde los impactos, incluidos los paralelos a los ejes
+        let (scene, camera, _) = diorama(40, 24);
+        let mut rays = Vec::new();
+        for y in 0..24 {
+            for x in 0..40 {
+                let direction = Vector3::new(x as f32 / 20.0 - 1.0, y as f32 / 20.0 - 0.6, -1.0).normalized();
+                rays.push((camera.eye, camera.basis_change(&direction)));
+            }
+        }
+        let bounces: Vec<(Vector3, Vector3)> = rays.iter()
+            .filter_map(|(origin, direction)| scene.objects().iter().map(|object| object.ray_intersect(origin, direction)).find(|hit| hit.is_intersecting))
+            .flat_map(|hit| [Vector3::new(0.0, 1.0, 0.0), Vector3::new(-1.0, 0.0, 0.0), Vector3::new(0.6, 0.0, -0.8)].map(|direction| (hit.point, direction)))
+            .collect();
+        rays.extend(bounces);
+
+        for (origin, direction) in rays {
+            let inverse = inverse_direction(&direction);
+            for object in scene.objects() {
+                let (plain, shared) = (object.ray_intersect(&origin, &direction), object.ray_intersect_inverse(&origin, &direction, &inverse));
+                assert_eq!(plain.is_intersecting, shared.is_intersecting);
+                if plain.is_intersecting {
+                    assert_eq!((plain.distance, plain.point, plain.normal), (shared.distance, shared.point, shared.normal));
+                    assert_eq!((plain.u, plain.v), (shared.u, shared.v));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packet_tracing_matches_per_ray_tracing() {
+        // El diorama girado y a un tamaño impar, así quedan píxeles sueltos fuera de los paquetes
+        let (width, height) = (67, 41);
+        let (mut scene, camera, _) = diorama(width, height);
+        scene.rotation = 0.4;
+        for render_mode in [RenderMode::Whitted, RenderMode::PathTraced] {
+            let render_with = |packet_tracing: bool| {
+                let settings = RenderSettings { packet_tracing, render_mode, ..RenderSettings::default() };
+                let mut framebuffer = Framebuffer::new(width, height);
+                let mut accumulation = AccumulationBuffer::new(width, height);
+                for _ in 0..2 {
+                    render(&mut framebuffer, &scene, &camera, &settings, &mut accumulation, None);
+                }
+                accumulation.colors.clone()
+            };
+            assert!(render_with(true) == render_with(false));
+        }
+    }
+
+    #[test]
+    fn escaping_path_traced_rays_see_the_sky() {
+        let settings = RenderSettings { render_mode: RenderMode::PathTraced, ..RenderSettings::default() };
+        let empty = Scene::new(Vec::new(), daynight::create_lights(8.0), TextureManager::new());
+        let beside = cubes(vec![(Vector3::new(0.0, 0.0, -3.0), Material::black())]);
+        for (scene, direction) in [(&empty, Vector3::new(0.0, 0.0, -1.0)), (&beside, Vector3::new(0.0, 1.0, 0.0)), (&beside, Vector3::new(0.6, 0.0, -0.8))] {
+            let color = cast_ray(&Vector3::zero(), &direction, scene, &settings, RayPath::primary(), &mut pixel_sampler(0, 0, 0));
+            assert_eq!(color, sky_color(Some(&direction), scene, &settings));
+        }
+    }
+
+    #[test]
+    fn path_traced_diffuse_converges_to_whitted() {
+        // Cara de arriba de un cubo mate al sol y bajo un cielo uniforme: el rebote difuso del
+        // camino tiene que promediar la luz directa más la del cielo que suma Whitted
+        let matte = Material::builder().diffuse(Vector3::new(0.8, 0.6, 0.4)).albedo([1.0, 0.0, 0.0, 0.0]).build();
+        let mut scene = cubes(vec![(Vector3::zero(), matte)]);
+        scene.lights = daynight::create_lights(8.0);
+        daynight::update_day_night(&mut scene.lights, 0.3, 8.0, &daynight::sun_ramp());
+        let whitted = RenderSettings { sky_lighting: true, sky_lighting_intensity: 1.0, ..RenderSettings::default() };
+        let path_traced = RenderSettings { render_mode: RenderMode::PathTraced, sky_lighting: true, sky_lighting_intensity: 1.0, ..RenderSettings::default() };
+
+        let eye = Vector3::new(0.2, 3.0, 0.1);
+        let direction = (Vector3::new(0.1, 0.5, -0.2) - eye).normalized();
+        let expected = cast_ray(&eye, &direction, &scene, &whitted, RayPath::primary(), &mut pixel_sampler(0, 0, 0));
+        let samples = 2000;
+        let average = (0..samples)
+            .map(|sample| cast_ray(&eye, &direction, &scene, &path_traced, RayPath::primary(), &mut pixel_sampler(0, sample, 0)))
+            .fold(Vector3::zero(), |sum, color| sum + color) / samples as f32;
+        assert!(expected.x > 0.1, "{expected:?}");
+        assert!((average - expected).length() < 0.02, "esperaba {expected:?}, dio {average:?}");
+    }
+
+    #[test]
+    fn path_traced_refraction_takes_the_glass_color() {
+        // Vidrio sin desvío (índice 1): el camino cruza el cubo y sale al cielo teñido una vez
+        let tint = Vector3::new(1.0, 0.5, 0.25);
+        let glass = Material::builder().diffuse(tint).albedo([0.0, 0.0, 0.0, 1.0]).refractive_index(1.0).build();
+        let scene = cubes(vec![(Vector3::new(0.0, 0.0, -3.0), glass)]);
+        let settings = RenderSettings { render_mode: RenderMode::PathTraced, ..RenderSettings::default() };
+        let direction = Vector3::new(0.0, 0.0, -1.0);
+        let color = cast_ray(&Vector3::zero(), &direction, &scene, &settings, RayPath::primary(), &mut pixel_sampler(0, 0, 0));
+        assert_vector_close(color, sky_color(Some(&direction), &scene, &settings) * tint);
+    }
+
+    #[test]
+    fn zero_samples_turn_the_effect_off() {
+        let trace = |scene: &Scene, settings: &RenderSettings| {
+            cast_ray(&Vector3::new(0.1, 0.0, 0.0), &Vector3::new(0.0, 0.0, -1.0), scene, settings, RayPath::primary(), &mut pixel_sampler(0, 0, 0))
+        };
+        // Vidrio esmerilado sin muestras: refracta como el vidrio liso
+        let glass = |roughness: f32| Material::builder().albedo([0.0, 0.0, 0.0, 1.0]).refractive_index(GLASS).roughness(roughness).build();
+        let no_frost = RenderSettings { frosted_samples: 0, ..RenderSettings::default() };
+        let frosted = trace(&cubes(vec![(Vector3::new(0.0, 0.0, -3.0), glass(0.3))]), &no_frost);
+        assert_vector_close(frosted, trace(&cubes(vec![(Vector3::new(0.0, 0.0, -3.0), glass(0.0))]), &RenderSettings::default()));
+
+        // Oclusión del cielo sin rayos: la misma luz del cielo que sin oclusión
+        let scene = cubes(vec![(Vector3::new(0.0, 0.0, -3.0), Material::builder().diffuse(Vector3::one()).build())]);
+        let sky_lit = RenderSettings { sky_lighting: true, ..RenderSettings::default() };
+        let no_rays = RenderSettings { sky_lighting: true, sky_occlusion: true, sky_occlusion_samples: 0, ..RenderSettings::default() };
+        assert_vector_close(trace(&scene, &no_rays), trace(&scene, &sky_lit));
+    }
+
+    #[test]
+    fn object_space_normal_maps_turn_with_their_instance() {
+        // Un mapa uniforme que en el objeto apunta a +X, en un cubo girado un cuarto de vuelta:
+        // en todas las caras la normal mapeada es la de la cara que era +X antes de girar
+        let path = std::env::temp_dir().join(format!("lib_{}_object_normals.png", std::process::id())).to_str().unwrap().to_string();
+        Image::gen_image_color(1, 1, Color::new(255, 128, 128, 255)).export_image(&path);
+        let material = Material::builder().normal_map(&path).normal_map_space(NormalMapSpace::Object).build();
+        let geometry: instance::SharedGeometry = std::sync::Arc::new(cube::Cube::new(Vector3::zero(), 1.0, material));
+        let turned = instance::Instance::new(geometry, Matrix::rotate_y(PI / 2.0), None);
+        let textures = TextureManager::new();
+
+        let sides = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0)];
+        let hits: Vec<Intersect> = sides.iter().map(|side| turned.ray_intersect(&(*side * 3.0), &-*side)).collect();
+        let mapped: Vec<Vector3> = hits.iter().map(|hit| shading_normal(hit, 0.0, &textures)).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        let was_plus_x = hits.iter().find(|hit| (mapped[0] - hit.normal).length() < 0.02).expect("ninguna cara coincide");
+        assert!(was_plus_x.normal.x.abs() < 1e-3, "{:?}", was_plus_x.normal);
+        for normal in &mapped {
+            assert!((*normal - mapped[0]).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn sampled_emitters_converge_to_the_full_sum() {
+        // Tres emisivos de distinto brillo y distancia alrededor de un piso blanco, sin sol ni cielo
+        let white = Material::builder().diffuse(Vector3::one()).albedo([1.0, 0.0, 0.0, 0.0]).build();
+        let dim = Material::builder().diffuse(Vector3::one()).emissive(EMISSION, 0.3).build();
+        let scene = cubes(vec![
+            (Vector3::zero(), white),
+            (Vector3::new(2.0, 1.0, 0.0), glowing()),
+            (Vector3::new(-1.5, 1.0, 1.0), dim),
+            (Vector3::new(0.0, 1.5, -2.5), glowing()),
+        ]);
+        let full = RenderSettings { sky_color: Vector3::zero(), ..RenderSettings::default() };
+        let sampled = RenderSettings { emissive_importance_sampling: true, sky_color: Vector3::zero(), ..RenderSettings::default() };
+
+        let (eye, down) = (Vector3::new(0.1, 3.0, 0.1), Vector3::new(0.0, -1.0, 0.0));
+        let average = |settings: &RenderSettings| {
+            let samples = 4000;
+            let sum = (0..samples).fold(Vector3::zero(), |sum, sample| {
+                sum + cast_ray(&eye, &down, &scene, settings, RayPath::primary(), &mut pixel_sampler(0, sample, 0))
+            });
+            sum / samples as f32
+        };
+        let (expected, estimated) = (average(&full), average(&sampled));
+        assert!(expected.x > 0.05, "{expected:?}");
+        assert!((estimated - expected).length() < 0.03 * expected.length(), "{estimated:?} contra {expected:?}");
+    }
+
+    #[test]
+    fn long_emitters_reach_past_their_center() {
+        // Un panel de 30 de largo con el centro a 16 del punto y el borde a poco más de 1
+        let panel = quad::Quad::new(Vector3::new(1.0, 2.0, -0.5), Vector3::new(30.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0), glowing());
+        let objects: Vec<SceneObject> = vec![Box::new(panel)];
+        let settings = RenderSettings { emissive_importance_sampling: true, ..RenderSettings::default() };
+        assert!((objects[0].center() - Vector3::zero()).length() > settings.emissive_cutoff);
+
+        let mut chosen = 0;
+        nearby_emitters(&objects, &Vector3::zero(), 0.0, &settings, &mut pixel_sampler(0, 0, 0).rng, |_, _| chosen += 1);
+        assert_eq!(chosen, 1);
+    }
+
+    #[test]
+    fn capped_emitters_keep_the_nearest_and_their_energy() {
+        // Cuatro emisivos a 2, 3, 4 y 5 del punto, el más lejano el más brillante
+        let bright = Material::builder().diffuse(Vector3::one()).emissive(EMISSION, 4.0).build();
+        let scene = cubes(vec![
+            (Vector3::new(0.0, 0.0, -4.0), glowing()),
+            (Vector3::new(2.0, 0.0, 0.0), glowing()),
+            (Vector3::new(0.0, 5.0, 0.0), bright),
+            (Vector3::new(-3.0, 0.0, 0.0), glowing()),
+        ]);
+        let objects = scene.objects();
+        let point = Vector3::zero();
+        let visited = |settings: &RenderSettings| {
+            let mut seen = Vec::new();
+            let scale = nearby_emitters(objects, &point, 0.0, settings, &mut pixel_sampler(0, 0, 0).rng, |object, _| seen.push(object.center()));
+            (seen, scale)
+        };
+
+        // Sin límite van todos tal cual
+        let (all, scale) = visited(&RenderSettings::default());
+        assert_eq!(all.len(), 4);
+        assert_eq!(scale, 1.0);
+
+        let capped = RenderSettings { max_emissive_lights: 2, ..RenderSettings::default() };
+        let (kept, scale) = visited(&capped);
+        assert_eq!(kept, vec![Vector3::new(2.0, 0.0, 0.0), Vector3::new(-3.0, 0.0, 0.0)]);
+
+        // Los que quedan, escalados, suman lo mismo que todos según la estimación
+        let estimate = |object: &SceneObject| emitter_estimate(object, &point, 0.0, &capped).1;
+        let total: f32 = objects.iter().map(estimate).sum();
+        let kept_total: f32 = objects.iter().filter(|object| kept.contains(&object.center())).map(estimate).sum();
+        assert!(scale > 1.0);
+        assert_close(scale * kept_total, total);
+    }
+
+    #[test]
+    fn every_sky_lookup_takes_the_skybox_intensity() {
+        let white = Material::builder().diffuse(Vector3::one()).albedo([1.0, 0.0, 0.0, 0.0]).build();
+        let scene = cubes(vec![(Vector3::zero(), white)]);
+        let brighter = |intensity: f32| RenderSettings { skybox_intensity: intensity, star_visibility: 1.0, sky_lighting: true, ..RenderSettings::default() };
+        let settings = brighter(2.0);
+        let mut sampler = pixel_sampler(0, 0, 0);
+
+        // Una dirección hacia arriba donde haya una estrella
+        let star = (0..4096)
+            .map(|step| Vector3::new((step % 64) as f32 / 32.0 - 1.0, 1.0, (step / 64) as f32 / 32.0 - 1.0).normalized())
+            .find(|direction| daynight::star_field(direction, settings.star_density) > 0.0)
+            .unwrap();
+        let eye = Vector3::new(0.0, 3.0, 0.0);
+
+        // Lo que escapa ve el cielo con la estrella, escalado
+        let miss = cast_ray(&eye, &star, &scene, &settings, RayPath::primary(), &mut sampler);
+        assert_vector_close(miss, sky_radiance(&star, &scene, &settings) * 2.0);
+        assert!(miss.x > settings.sky_color.x * 2.0 + 1e-3);
+        // Pasado el límite de profundidad el cielo sigue su dirección, con la estrella y escalado
+        let spent = RayPath { depth: settings.max_depth + 1, specular: true, ..RayPath::primary() };
+        let capped = cast_ray(&eye, &star, &scene, &settings, spent, &mut sampler);
+        assert_vector_close(capped, miss);
+        // El albedo de las AOV en el cielo
+        let camera = Camera::new(eye, eye + star, Vector3::new(1.0, 0.0, 0.0));
+        let aov = sample_aovs(&eye, &star, &scene, &settings, &camera);
+        assert_vector_close(aov.albedo, miss);
+
+        // La luz de relleno del cielo sobre la cara de arriba crece con la intensidad
+        let top = |settings: &RenderSettings| {
+            cast_ray(&Vector3::new(0.1, 3.0, 0.1), &-UP, &scene, settings, RayPath::primary(), &mut pixel_sampler(0, 0, 0))
+        };
+        let (dark, single, double) = (top(&brighter(0.0)), top(&brighter(1.0)), top(&brighter(2.0)));
+        assert!(single.x > dark.x + 0.05, "{single:?}");
+        assert_vector_close(double - dark, (single - dark) * 2.0);
+    }
+
+    #[test]
+    fn contact_shadows_darken_seams_only() {
+        // Dos cubos de piso y uno apoyado sobre el segundo: su pared hace una esquina sobre el primero
+        let white = Material::builder().diffuse(Vector3::one()).albedo([1.0, 0.0, 0.0, 0.0]).build();
+        let scene = cubes(vec![
+            (Vector3::zero(), white.clone()),
+            (Vector3::new(1.0, 0.0, 0.0), white.clone()),
+            (Vector3::new(1.0, 1.0, 0.0), white),
+        ]);
+        let settings = RenderSettings { contact_shadows: true, ..RenderSettings::default() };
+        let average_shadow = |x: f32| {
+            let floor = find_closest_hit(&Vector3::new(x, 3.0, 0.0), &-UP, scene.objects().iter().enumerate(), &scene.texture_manager);
+            let mut rng = pixel_sampler(0, 0, 0).rng;
+            (0..500).map(|_| contact_shadow(&floor, &UP, &scene, &settings, &mut rng)).sum::<f32>() / 500.0
+        };
+
+        assert!(average_shadow(0.48) < 0.9);
+        assert_eq!(average_shadow(-0.3), 1.0);
+
+        // El corte por distancia da lo mismo que buscar el impacto más cercano sin límite
+        let origin = Vector3::new(0.45, 0.51, 0.0);
+        for direction in [Vector3::new(1.0, 0.2, 0.0), Vector3::new(1.0, 1.0, 0.3), UP, Vector3::new(-1.0, 0.1, 0.0)] {
+            let direction = direction.normalized();
+            let full = find_closest_hit(&origin, &direction, scene.objects().iter().enumerate(), &scene.texture_manager);
+            let expected = (full.is_intersecting && full.distance < 0.5).then_some(full.distance);
+            assert_eq!(closest_hit_within(&origin, &direction, 0.5, scene.objects(), &scene.texture_manager), expected);
+        }
+    }
+
+    #[test]
+    fn radical_inverse_mirrors_the_digits() {
+        for (index, expected) in [(0, 0.0), (1, 0.5), (2, 0.25), (3, 0.75), (4, 0.125), (6, 0.375)] {
+            assert_close(radical_inverse(index, 2), expected);
+        }
+        for (index, expected) in [(1, 1.0 / 3.0), (2, 2.0 / 3.0), (3, 1.0 / 9.0), (5, 2.0 / 3.0 + 1.0 / 9.0)] {
+            assert_close(radical_inverse(index, 3), expected);
+        }
+    }
+
+    #[test]
+    fn sample_patterns_cover_the_pixel() {
+        let mut rng = pixel_sampler(0, 0, 0).rng;
+        // Grid: los centros de una subgrilla de 2x2
+        let grid: Vec<(f32, f32)> = (0..4).map(|index| pattern_offset(AaPattern::Grid, index, 4, 7, &mut rng)).collect();
+        assert_eq!(grid, vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]);
+
+        // RotatedGrid: cada muestra en su propia fila y columna de la grilla fina
+        let rotated: Vec<(f32, f32)> = (0..16).map(|index| pattern_offset(AaPattern::RotatedGrid, index, 16, 7, &mut rng)).collect();
+        for axis in [|(x, _): &(f32, f32)| *x, |(_, y): &(f32, f32)| *y] {
+            let mut cells: Vec<u32> = rotated.iter().map(|offset| (axis(offset) * 16.0) as u32).collect();
+            cells.sort();
+            assert_eq!(cells, (0..16).collect::<Vec<u32>>());
+        }
+
+        // Halton: dentro del píxel, fijo para el mismo píxel y corrido en el vecino
+        let halton = |index: u32, pixel: usize, rng: &mut SmallRng| pattern_offset(AaPattern::Halton, index, 4, pixel, rng);
+        for index in 0..64 {
+            let (x, y) = halton(index, 7, &mut rng);
+            assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+            assert_eq!((x, y), halton(index, 7, &mut rng));
+        }
+        assert_ne!(halton(0, 7, &mut rng), halton(0, 8, &mut rng));
+
+        // Random: lo que dé el generador del píxel
+        let mut expected = pixel_sampler(3, 7, 0).rng;
+        let offset = pattern_offset(AaPattern::Random, 0, 16, 7, &mut pixel_sampler(3, 7, 0).rng);
+        assert_eq!(offset, (expected.random::<f32>(), expected.random::<f32>()));
+    }
+
+    #[test]
+    fn halton_frames_never_reuse_an_index() {
+        let settings = RenderSettings { aa_samples: 4, ..RenderSettings::default() };
+        let mut used: Vec<u32> = (1..20).flat_map(|frame| (0..settings.aa_samples).map(move |sample| (frame, sample)))
+            .map(|(frame, sample)| halton_index(frame, sample, &settings))
+            .collect();
+        let count = used.len();
+        used.sort();
+        used.dedup();
+        assert_eq!(used.len(), count);
+        assert!(RenderSettings::default().aa_pattern == AaPattern::Grid);
+    }
+}
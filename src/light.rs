@@ -1,17 +1,69 @@
 use raylib::prelude::*;
+use rand::Rng;
+use rand::rngs::SmallRng;
 
+#[derive(Clone)]
 pub struct Light {
     pub position: Vector3,
-    pub color: Color,
+    // Color lineal por canal (1 = blanco); la potencia va aparte en `intensity`
+    pub color: Vector3,
     pub intensity: f32,
+    // 0 = luz puntual; mayor que 0 la vuelve una esfera que da sombras suaves
+    pub radius: f32,
+    // Atenuación con la distancia d: la intensidad se divide por constant + linear·d + quadratic·d².
+    // Por defecto (1, 0, 0) la luz no se atenúa
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
 }
 
 impl Light {
-    pub fn new(position: Vector3, color: Color, intensity: f32) -> Self {
+    pub fn new(position: Vector3, color: Vector3, intensity: f32) -> Self {
         Light {
             position,
             color,
             intensity,
+            radius: 0.0,
+            constant: 1.0,
+            linear: 0.0,
+            quadratic: 0.0,
         }
     }
-}
\ No newline at end of file
+
+    // Compatibilidad con colores de 8 bits: cada canal se lleva a [0, 1]
+    pub fn from_color(position: Vector3, color: Color, intensity: f32) -> Self {
+        let color = Vector3::new(color.r as f32, color.g as f32, color.b as f32) / 255.0;
+        Light::new(position, color, intensity)
+    }
+
+    pub fn with_attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> Self {
+        self.constant = constant;
+        self.linear = linear;
+        self.quadratic = quadratic;
+        self
+    }
+
+    // Fracción de la intensidad que llega a `distance`
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance).max(1e-4)
+    }
+
+    // Punto de la luz hacia el que se lanza un rayo de sombra: el centro si es puntual, o uno
+    // al azar dentro de su esfera (al acumular cuadros la penumbra se suaviza)
+    pub fn sample_position(&self, rng: &mut SmallRng) -> Vector3 {
+        if self.radius <= 0.0 {
+            return self.position;
+        }
+
+        loop {
+            let offset = Vector3::new(
+                rng.random::<f32>() * 2.0 - 1.0,
+                rng.random::<f32>() * 2.0 - 1.0,
+                rng.random::<f32>() * 2.0 - 1.0,
+            );
+            if offset.dot(offset) <= 1.0 {
+                return self.position + offset * self.radius;
+            }
+        }
+    }
+}
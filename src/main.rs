@@ -1,442 +1,824 @@
 use raylib::prelude::*;
 use std::f32::consts::PI;
-use rayon::prelude::*;
-
-mod framebuffer;
-mod ray_intersect;
-mod cube;
-mod camera;
-mod light;
-mod material;
-mod textures;
-mod blocks;
-
-use framebuffer::Framebuffer;
-use ray_intersect::{Intersect, RayIntersect};
-use cube::Cube;
-use camera::Camera;
-use light::Light;
-use material::vector3_to_color;
-use textures::TextureManager;
-use blocks::{create_cubes_from_layers, get_layers};
-
-const ORIGIN_BIAS: f32 = 1e-4;
-const SKYBOX_COLOR: Vector3 = Vector3::new(0.26, 0.55, 0.89);
-
-// Función para rotar un vector alrededor del eje Y
-fn rotate_around_y(point: Vector3, angle: f32) -> Vector3 {
-    let cos_a = angle.cos();
-    let sin_a = angle.sin();
-    Vector3::new(
-        point.x * cos_a - point.z * sin_a,
-        point.y,
-        point.x * sin_a + point.z * cos_a,
-    )
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use computer_graphics_v3::accumulation::AccumulationBuffer;
+use computer_graphics_v3::aov::AovBuffers;
+use computer_graphics_v3::blocks::{get_layers, load_heightmap, load_scene_file, save_grid_to_file, BlockGrid, Ground, SceneDefaults, SceneFile, CUBE_SIZE};
+use computer_graphics_v3::bookmarks::{CameraBookmarks, CameraGlide};
+use computer_graphics_v3::camera::Camera;
+use computer_graphics_v3::framebuffer::Framebuffer;
+use computer_graphics_v3::gltf_loader::load_gltf;
+use computer_graphics_v3::metadata::{load_metadata, save_metadata, RenderMetadata};
+use computer_graphics_v3::mesh::LEAF_SIZE;
+use computer_graphics_v3::scene::{Scene, SceneObject};
+use computer_graphics_v3::settings::{RenderMode, RenderSettings, StereoLayout};
+use computer_graphics_v3::textures::TextureManager;
+use computer_graphics_v3::{daynight, overlay};
+use computer_graphics_v3::{numbered_path, pick, preview_size, render, render_batch, render_preview, render_samples, save_image, scene_objects, update_caustics, update_culling, update_shadow_map, measure_traversal, BatchFrame, RenderControl, TraversalReport};
+
+mod cli;
+
+// Cuadros que tarda la cámara en llegar a una vista guardada
+const BOOKMARK_GLIDE_FRAMES: u32 = 45;
+
+// Lo enciende Enter durante un render sin ventana: se guarda lo acumulado hasta ahí y se termina
+static CANCEL: AtomicBool = AtomicBool::new(false);
+
+fn listen_for_cancel() {
+    std::thread::spawn(|| {
+        let mut line = String::new();
+        // Sin terminal (entrada cerrada) read_line devuelve 0 y no cancela
+        if std::io::stdin().read_line(&mut line).is_ok_and(|read| read > 0) {
+            CANCEL.store(true, Ordering::Relaxed);
+        }
+    });
 }
 
-fn offset_origin(intersect: &Intersect, direction: &Vector3) -> Vector3 {
-    let offset = intersect.normal * ORIGIN_BIAS;
-    if direction.dot(intersect.normal) < 0.0 {
-        intersect.point - offset
-    } else {
-        intersect.point + offset
-    }
+// Barra de progreso en una sola línea, con el tiempo restante estimado
+fn print_progress(done: usize, total: usize, unit: &str, elapsed: Duration) {
+    const BAR_WIDTH: usize = 30;
+    let filled = done * BAR_WIDTH / total.max(1);
+    let remaining = elapsed.as_secs_f64() / done.max(1) as f64 * (total - done) as f64;
+    print!("\r[{}{}] {}/{} {}, faltan ~{:.0} s ", "#".repeat(filled), " ".repeat(BAR_WIDTH - filled), done, total, unit, remaining);
+    let _ = std::io::stdout().flush();
 }
 
-fn reflect(incident: &Vector3, normal: &Vector3) -> Vector3 {
-    *incident - *normal * 2.0 * incident.dot(*normal)
+// Resumen de --bvh-stats: promedio y máximo por rayo que llegó a una malla, contra la búsqueda lineal
+fn print_traversal(report: &TraversalReport) {
+    if report.rays == 0 {
+        println!("BVH: ningún rayo primario cruza una malla (hace falta --gltf)");
+        return;
+    }
+    let per_ray = |total: u64| total as f64 / report.rays as f64;
+    println!(
+        "BVH (hojas de {}, corte por la mediana): {} rayos, {:.1} nodos por rayo (máx. {}), {:.1} triángulos por rayo (máx. {}) contra {:.0} sin jerarquía ({:.1}x menos)",
+        LEAF_SIZE, report.rays, per_ray(report.nodes), report.max_nodes, per_ray(report.tests), report.max_tests,
+        per_ray(report.baseline), report.baseline as f64 / report.tests.max(1) as f64
+    );
 }
 
-fn refract(incident: &Vector3, normal: &Vector3, refractive_index: f32) -> Option<Vector3> {
-    let mut cosi = incident.dot(*normal).max(-1.0).min(1.0);
-    let mut etai = 1.0;
-    let mut etat = refractive_index;
-    let mut n = *normal;
-
-    if cosi > 0.0 {
-        std::mem::swap(&mut etai, &mut etat);
-        n = -n;
-    } else {
-        cosi = -cosi;
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = match cli::parse_args(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    // --replay: la resolución y las muestras salen del render anterior; la cámara, el sol y los
+    // ajustes se aplican más abajo
+    let replay = options.replay.as_ref().map(|path| {
+        load_metadata(path).unwrap_or_else(|error| {
+            eprintln!("No se pudo leer {}: {}", path, error);
+            std::process::exit(1);
+        })
+    });
+    if let Some(saved) = &replay {
+        options.width = saved.width as i32;
+        options.height = saved.height as i32;
+        options.samples = saved.samples.max(1);
     }
 
-    let eta = etai / etat;
-    let k = 1.0 - eta * eta * (1.0 - cosi * cosi);
+    let mut window_width = options.width;
+    let mut window_height = options.height;
+
+    let mut framebuffer = Framebuffer::new(window_width as u32, window_height as u32);
 
-    if k < 0.0 {
-        None
-    } else {
-        Some(*incident * eta + n * (eta * cosi - k.sqrt()))
+    let block_size = |grid: BlockGrid| {
+        if options.cube_size.is_none() && options.spacing.is_none() {
+            return grid;
+        }
+        let cube_size = options.cube_size.unwrap_or(CUBE_SIZE);
+        grid.with_block_size(cube_size, options.spacing.unwrap_or(cube_size))
+    };
+    // Escenas de --scene: la activa vive en block_grid y las demás guardan su grilla (con lo
+    // editado) hasta que se vuelva a ellas
+    let mut scene_files: Vec<SceneFile> = Vec::new();
+    if options.heightmap.is_none() {
+        for path in &options.scenes {
+            match load_scene_file(path) {
+                Ok(file) => scene_files.push(SceneFile { grid: block_size(file.grid), ..file }),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
     }
-}
+    let mut active_scene = 0;
+    let mut block_grid = match (&options.heightmap, scene_files.first_mut()) {
+        (Some(path), _) => block_size(load_heightmap(path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        })),
+        (None, Some(file)) => std::mem::replace(&mut file.grid, BlockGrid::empty(1, 1)),
+        (None, None) => block_size(BlockGrid::from_layers(get_layers())),
+    };
+    let defaults = scene_files.first().map_or(SceneDefaults::default(), |file| file.defaults.clone());
+    // El techo, el salvavidas y los cristales están ubicados para la escena incorporada
+    let decorations = options.scenes.is_empty() && options.heightmap.is_none();
+    let mut scene_path = scene_files.first().map_or_else(|| "scene.txt".to_string(), |file| file.path.clone());
+    // Letra que se coloca al agregar un bloque sobre el piso
+    let mut brush = 'B';
+
+    // Vista inicial de una escena: la de su archivo o, si no trae, la de la escena incorporada
+    let initial_camera = |defaults: &SceneDefaults| {
+        let (position, target) = defaults.camera.unwrap_or((Vector3::new(0.0, 0.0, 5.0), Vector3::zero()));
+        Camera::new(position, target, Vector3::new(0.0, 1.0, 0.0))
+    };
+    let mut camera = replay.as_ref().map_or_else(|| initial_camera(&defaults), |saved| saved.camera.clone());
+    // Título de la ventana: el de la escena o el de siempre
+    let window_title = |defaults: &SceneDefaults| defaults.title.clone().unwrap_or_else(|| "Raytracer Example".to_string());
+    let rotation_speed = PI / 100.0;
+    let zoom_speed = 0.15;
+    let diorama_rotation_speed = PI / 80.0;
+    let mut diorama_angle = 0.0;
+    // Ejes de giro del diorama que recorre A; el primero (Y) es el de siempre
+    let diorama_axes = [Vector3::new(0.0, 1.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0)];
+    let mut diorama_axis = 0;
+    let mut watch_textures = false;
+    let mut show_bounds = false;
+    // Cuadros seguidos sin que el usuario mueva la vista o cambie la escena, para la vista previa
+    let mut idle_frames = 0u32;
+    let mut show_hud = false;
+    let mut bookmarks = CameraBookmarks::load("camera_bookmarks.txt");
+    let mut camera_glide: Option<CameraGlide> = None;
+    let bookmark_keys = [
+        KeyboardKey::KEY_ONE, KeyboardKey::KEY_TWO, KeyboardKey::KEY_THREE,
+        KeyboardKey::KEY_FOUR, KeyboardKey::KEY_FIVE, KeyboardKey::KEY_SIX,
+        KeyboardKey::KEY_SEVEN, KeyboardKey::KEY_EIGHT, KeyboardKey::KEY_NINE,
+    ];
+
+    let shutter = options.shutter.unwrap_or(if options.spin != 0.0 { 1.0 } else { 0.0 });
+    let mut settings = RenderSettings { crop: options.crop, shutter, linear_lighting: options.linear_lighting, ..RenderSettings::default() };
+    if let Some(saved) = &replay {
+        saved.apply(&mut settings);
+    }
+    let mut accumulation = AccumulationBuffer::new(window_width as u32, window_height as u32);
+    let mut aovs = AovBuffers::new(window_width as u32, window_height as u32);
+    // Buffers de la vista previa reducida; se rehacen solo al cambiar la escala o la ventana
+    let mut preview = Framebuffer::new(1, 1);
+    let mut preview_accumulation = AccumulationBuffer::new(1, 1);
 
-fn cast_shadow(
-    intersect: &Intersect,
-    light: &Light,
-    objects: &[Cube],
-) -> f32 {
-    let light_dir = (light.position - intersect.point).normalized();
-    let light_distance = (light.position - intersect.point).length();
+    // Configuración del ciclo día/noche (luz rotando alrededor del eje Y como el sol)
+    let sun_radius = 8.0; // Radio de la órbita del sol
+    // Ángulo inicial (0 = mediodía)
+    let mut sun_angle = replay.as_ref().map_or(defaults.sun_angle.unwrap_or(0.0), |saved| saved.sun_angle);
+    let sun_rotation_speed = PI / 300.0; // Velocidad del ciclo día/noche
+    let mut sun_paused = false;
+    // Degradados de color del sol y del cielo según la altura del sol
+    let sun_ramp = daynight::sun_ramp();
+    let sky_ramp = daynight::sky_ramp();
+    
+    let mut ground = None;
+    let lights = daynight::create_lights(sun_radius);
+    let mut texture_manager = TextureManager::new();
+    texture_manager.linear_lighting = settings.linear_lighting;
+    let model = options.gltf.as_ref().map(|path| {
+        // Incluye armar la jerarquía de cajas de cada malla
+        let load_start = Instant::now();
+        let model = load_gltf(path, &texture_manager).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        });
+        println!("Modelo cargado en {:.0} ms", load_start.elapsed().as_secs_f64() * 1000.0);
+        if model.skipped_primitives > 0 {
+            eprintln!("{}: se omitieron {} primitivas que no están hechas de triángulos", path, model.skipped_primitives);
+        }
+        model
+    });
+    // El modelo importado se suma cada vez que se rearman los objetos del diorama
+    let with_model = |mut objects: Vec<SceneObject>| {
+        if let Some(model) = &model {
+            objects.extend(model.instances());
+        }
+        objects
+    };
+    let mut scene = Scene::new(with_model(scene_objects(&block_grid, decorations, ground, &settings)), lights, texture_manager);
+    if !scene_files.is_empty() && defaults.camera.is_none() {
+        camera.frame_scene(&scene, settings.vertical_fov, window_width as f32 / window_height as f32);
+    }
+    let load_start = std::time::Instant::now();
+    if let Some(folder) = &options.cubemap {
+        let faces = ["px", "nx", "py", "ny", "pz", "nz"].map(|face| format!("{}/{}.png", folder, face));
+        if !scene.texture_manager.load_cubemap(faces.each_ref().map(String::as_str)) {
+            eprintln!("No se pudo cargar el cubemap de {}; se usa el color del cielo", folder);
+        }
+    }
+    scene.preload_textures();
+    println!("Texturas cargadas en {:.0} ms", load_start.elapsed().as_secs_f64() * 1000.0);
+
+    // --bvh-stats: cuánto ahorran las jerarquías de las mallas desde la vista inicial; en la
+    // ventana se vuelve a medir en cada cuadro y se imprime cuando cambia
+    let mut last_traversal = None;
+    if options.bvh_stats {
+        let report = measure_traversal(&scene, &camera, &settings, window_width as u32, window_height as u32);
+        print_traversal(&report);
+        last_traversal = Some(report);
+    }
 
-    let shadow_ray_origin = offset_origin(intersect, &light_dir);
+    let output_path = options.out.clone().unwrap_or_else(|| {
+        if options.headless { "render.png" } else { "screenshot.ppm" }.to_string()
+    });
 
-    for object in objects {
-        // Ignorar bloques emisivos (glowstone) al calcular sombras
-        if object.material.is_emissive {
-            continue;
+    // Sin ventana: acumular las muestras pedidas, guardar y salir. Con --glide se guarda
+    // cada cuadro del recorrido numerado (render_0000.png, render_0001.png, ...)
+    if options.headless || options.glide.is_some() {
+        daynight::update_day_night(&mut scene.lights, sun_angle, sun_radius, &sun_ramp);
+        if settings.dynamic_sky {
+            settings.sky_color = daynight::sky_color(sun_angle, &sky_ramp);
+            settings.star_visibility = daynight::star_visibility(sun_angle);
         }
-        
-        let shadow_intersect = object.ray_intersect(&shadow_ray_origin, &light_dir);
-        if shadow_intersect.is_intersecting && shadow_intersect.distance < light_distance {
-            return 1.0;
+        update_caustics(&mut scene, &settings);
+        update_shadow_map(&mut scene, &settings);
+        scene.rotation_speed = options.spin;
+
+        // --glide: los cuadros del recorrido son independientes y se renderizan en paralelo
+        if let Some((from, to)) = options.glide {
+            let mut glide = match (bookmarks.get(from), bookmarks.get(to)) {
+                (Some(from), Some(to)) => CameraGlide::from_bookmarks(&from, &to, options.frames),
+                _ => {
+                    eprintln!("--glide necesita que ambas vistas estén guardadas");
+                    std::process::exit(1);
+                }
+            };
+            let mut frames = Vec::new();
+            while !glide.is_done() {
+                let frame = frames.len() as u32;
+                frames.push(BatchFrame { camera: glide.step(), time: frame as f32, sun_angle, path: numbered_path(&output_path, frame), flip_y: options.flip_y });
+            }
+
+            let build_scene = || {
+                let mut frame_scene = Scene::new(
+                    with_model(scene_objects(&block_grid, decorations, ground, &settings)),
+                    scene.lights.clone(),
+                    scene.texture_manager.clone(),
+                );
+                frame_scene.rotation = scene.rotation;
+                frame_scene.rotation_axis = scene.rotation_axis;
+                frame_scene.rotation_speed = scene.rotation_speed;
+                frame_scene.caustics = scene.caustics.clone();
+                frame_scene.shadow_map = scene.shadow_map.clone();
+                frame_scene
+            };
+            let total = frames.len();
+            let finished = Mutex::new((0, false));
+            let start = Instant::now();
+            listen_for_cancel();
+            println!("Enter cancela y deja guardados los cuadros terminados");
+            render_batch(frames, (window_width as u32, window_height as u32), options.samples, &settings, &build_scene, &|path, result, _| {
+                let mut finished = finished.lock().unwrap();
+                if let Err(error) = result {
+                    eprintln!("\rNo se pudo guardar {}: {}", path, error);
+                    finished.1 = true;
+                }
+                finished.0 += 1;
+                print_progress(finished.0, total, "cuadros", start.elapsed());
+            }, &CANCEL);
+            let (done, failed) = finished.into_inner().unwrap();
+            println!("\n{} de {} cuadros en {:.1} s", done, total, start.elapsed().as_secs_f64());
+            if failed {
+                std::process::exit(1);
+            }
+            return;
         }
+
+        update_culling(&mut scene, &camera, &settings, window_width as f32 / window_height as f32);
+        listen_for_cancel();
+        println!("Enter cancela y guarda lo acumulado hasta ahí");
+        // Los bloques terminan en varios hilos: la barra se redibuja solo cuando sube el porcentaje
+        let last_percent = Mutex::new(0);
+        let start = Instant::now();
+        let on_progress = |done: usize, total: usize| {
+            let mut last_percent = last_percent.lock().unwrap();
+            if done * 100 / total.max(1) > *last_percent {
+                *last_percent = done * 100 / total.max(1);
+                print_progress(done, total, "bloques", start.elapsed());
+            }
+        };
+        let control = RenderControl { cancel: &CANCEL, on_progress: &on_progress };
+        let (stats, completed) = render_samples(&mut framebuffer, &scene, &camera, &settings, &mut accumulation, options.samples, &control);
+        println!();
+        if !completed {
+            println!("Cancelado con {} de {} muestras", accumulation.frame_count, options.samples);
+        }
+        let metadata = RenderMetadata { camera: &camera, sun_angle, samples: accumulation.frame_count, width: framebuffer.width, height: framebuffer.height };
+        let saved = save_image(&output_path, &mut framebuffer, &accumulation, options.flip_y)
+            .and_then(|()| save_metadata(&output_path, &metadata, &scene, &settings));
+        match saved {
+            Ok(()) => println!(
+                "Imagen guardada en {} ({} rayos, {} de sombra, profundidad máxima {})",
+                output_path, stats.rays, stats.shadow_rays, stats.max_depth
+            ),
+            Err(error) => {
+                eprintln!("No se pudo guardar {}: {}", output_path, error);
+                std::process::exit(1);
+            }
+        }
+        if let Some(reference) = &options.compare {
+            match framebuffer.count_differences(reference, options.tolerance) {
+                Ok(0) => println!("Coincide con {}", reference),
+                Ok(count) => {
+                    eprintln!("{} píxeles difieren de {} en más de {}", count, reference, options.tolerance);
+                    std::process::exit(1);
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        return;
     }
 
-    0.0
-}
+    let (mut window, thread) = raylib::init()
+        .size(window_width, window_height)
+        .title(&window_title(&defaults))
+        .resizable()
+        .log_level(TraceLogLevel::LOG_WARNING)
+        .build();
 
-pub fn cast_ray(
-    ray_origin: &Vector3,
-    ray_direction: &Vector3,
-    objects: &[Cube],
-    light: &Light,
-    texture_manager: &TextureManager,
-    depth: u32,
-) -> Vector3 {
-    if depth > 3 {
-        return SKYBOX_COLOR;
-    }
+    while !window.window_should_close() {
+        if window.is_key_down(KeyboardKey::KEY_LEFT) {
+            camera.orbit(rotation_speed, 0.0);
+        }
+        if window.is_key_down(KeyboardKey::KEY_RIGHT) {
+            camera.orbit(-rotation_speed, 0.0);
+        }
+        if window.is_key_down(KeyboardKey::KEY_UP) {
+            camera.orbit(0.0, -rotation_speed);
+        }
+        if window.is_key_down(KeyboardKey::KEY_DOWN) {
+            camera.orbit(0.0, rotation_speed);
+        }
+        // Coma y punto inclinan el horizonte
+        if window.is_key_down(KeyboardKey::KEY_COMMA) {
+            camera.roll(-rotation_speed);
+        }
+        if window.is_key_down(KeyboardKey::KEY_PERIOD) {
+            camera.roll(rotation_speed);
+        }
+        if window.is_key_down(KeyboardKey::KEY_W) {
+            camera.zoom(zoom_speed);
+        }
+        if window.is_key_down(KeyboardKey::KEY_S) {
+            camera.zoom(-zoom_speed);
+        }
+        
+        // 1-9 recuperan una vista guardada; Shift + 1-9 guarda la vista actual en esa casilla
+        // (con Ctrl cambian de escena, más abajo)
+        let control_down = window.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) || window.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+        for (slot, key) in bookmark_keys.iter().enumerate() {
+            if control_down || !window.is_key_pressed(*key) {
+                continue;
+            }
+            if window.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) || window.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT) {
+                bookmarks.store(slot, &camera);
+                match bookmarks.save() {
+                    Ok(()) => println!("Vista guardada en la casilla {}", slot + 1),
+                    Err(error) => eprintln!("No se pudieron guardar las vistas: {}", error),
+                }
+            } else if let Some(bookmark) = bookmarks.get(slot) {
+                camera_glide = Some(CameraGlide::new(camera.clone(), bookmark.to_camera(), BOOKMARK_GLIDE_FRAMES));
+            }
+        }
 
-    let mut intersect = Intersect::empty();
-    let mut zbuffer = f32::INFINITY;
+        // Deslizar la cámara hacia la vista recuperada
+        if let Some(glide) = &mut camera_glide {
+            camera = glide.step();
+            if glide.is_done() {
+                camera_glide = None;
+            }
+        }
 
-    for object in objects {
-        let i = object.ray_intersect(ray_origin, ray_direction);
-        if i.is_intersecting && i.distance < zbuffer {
-            zbuffer = i.distance;
-            intersect = i;
+        // T activa/desactiva la recarga de texturas modificadas en disco
+        if window.is_key_pressed(KeyboardKey::KEY_T) {
+            watch_textures = !watch_textures;
+            println!("Recarga de texturas: {}", if watch_textures { "activada" } else { "desactivada" });
+        }
+        let mut scene_changed = false;
+        // Cambios que no vienen del usuario: el sol y el tiempo de los materiales animados
+        let mut sun_moved = false;
+        let mut animated = false;
+        // Al cambiar el tamaño de la ventana los buffers se rehacen a la nueva resolución;
+        // la proporción de la vista sale de ella, así el diorama no se estira
+        if window.is_window_resized() {
+            window_width = window.get_screen_width().max(1);
+            window_height = window.get_screen_height().max(1);
+            framebuffer = Framebuffer::new(window_width as u32, window_height as u32);
+            accumulation = AccumulationBuffer::new(window_width as u32, window_height as u32);
+            aovs = AovBuffers::new(window_width as u32, window_height as u32);
+        }
+        if watch_textures && scene.texture_manager.reload_changed() {
+            println!("Texturas recargadas");
+            scene_changed = true;
         }
-    }
 
-    if !intersect.is_intersecting {
-        return SKYBOX_COLOR;
-    }
+        // F1 activa/desactiva el antialiasing adaptativo
+        if window.is_key_pressed(KeyboardKey::KEY_F1) {
+            settings.adaptive_aa = !settings.adaptive_aa;
+            scene_changed = true;
+        }
 
-    let light_dir = (light.position - intersect.point).normalized();
-    let view_dir = (*ray_origin - intersect.point).normalized();
+        // Calidad contra velocidad: [ y ] cambian los rayos de sombra por luz, - e = las muestras
+        // del antialiasing de bordes, Supr e Insert las del vidrio esmerilado y Av Pág y Re Pág
+        // los rayos de oclusión del cielo (0 apaga cada uno)
+        let sample_keys = [
+            (KeyboardKey::KEY_LEFT_BRACKET, KeyboardKey::KEY_RIGHT_BRACKET, &mut settings.shadow_samples, "Rayos de sombra"),
+            (KeyboardKey::KEY_MINUS, KeyboardKey::KEY_EQUAL, &mut settings.aa_samples, "Muestras de antialiasing"),
+            (KeyboardKey::KEY_DELETE, KeyboardKey::KEY_INSERT, &mut settings.frosted_samples, "Muestras del vidrio esmerilado"),
+            (KeyboardKey::KEY_PAGE_DOWN, KeyboardKey::KEY_PAGE_UP, &mut settings.sky_occlusion_samples, "Rayos de oclusión del cielo"),
+        ];
+        for (less, more, samples, name) in sample_keys {
+            let previous = *samples;
+            if window.is_key_pressed(less) {
+                *samples = samples.saturating_sub(1);
+            }
+            if window.is_key_pressed(more) {
+                *samples = (*samples + 1).min(16);
+            }
+            if *samples != previous {
+                println!("{}: {}", name, samples);
+                scene_changed = true;
+            }
+        }
 
-    let mut normal = intersect.normal;
-    if let Some(normal_map_path) = &intersect.material.normal_map_id {
-        let texture = texture_manager.get_texture(normal_map_path).unwrap();
-        let width = texture.width() as u32;
-        let height = texture.height() as u32;
-        let tx = (intersect.u * width as f32) as u32;
-        let ty = (intersect.v * height as f32) as u32;
+        // F2 muestra solo la máscara de sombras
+        if window.is_key_pressed(KeyboardKey::KEY_F2) {
+            settings.debug_shadow_mask = !settings.debug_shadow_mask;
+            scene_changed = true;
+        }
 
-        if let Some(tex_normal) = texture_manager.get_normal_from_map(normal_map_path, tx, ty) {
-            let tangent = Vector3::new(normal.y, -normal.x, 0.0).normalized();
-            let bitangent = normal.cross(tangent);
-            
-            let transformed_normal_x = tex_normal.x * tangent.x + tex_normal.y * bitangent.x + tex_normal.z * normal.x;
-            let transformed_normal_y = tex_normal.x * tangent.y + tex_normal.y * bitangent.y + tex_normal.z * normal.y;
-            let transformed_normal_z = tex_normal.x * tangent.z + tex_normal.y * bitangent.z + tex_normal.z * normal.z;
+        // Y colorea los píxeles según la profundidad de recursión alcanzada
+        if window.is_key_pressed(KeyboardKey::KEY_Y) {
+            settings.debug_depth = !settings.debug_depth;
+            scene_changed = true;
+        }
 
-            normal = Vector3::new(transformed_normal_x, transformed_normal_y, transformed_normal_z).normalized();
+        // R reusa el cuadro anterior al mover la cámara (reproyección temporal)
+        if window.is_key_pressed(KeyboardKey::KEY_R) {
+            settings.temporal_reprojection = !settings.temporal_reprojection;
+            scene_changed = true;
         }
-    }
 
-    let reflect_dir = reflect(&-light_dir, &normal).normalized();
+        // D compara la imagen con y sin tramado antes de pasar a 8 bits
+        if window.is_key_pressed(KeyboardKey::KEY_D) {
+            settings.dither = !settings.dither;
+            scene_changed = true;
+        }
 
-    let shadow_intensity = cast_shadow(&intersect, light, objects);
-    let light_intensity = light.intensity * (1.0 - shadow_intensity);
+        // B cambia el brillo de todos los materiales a Blinn-Phong
+        if window.is_key_pressed(KeyboardKey::KEY_B) {
+            settings.blinn_phong = !settings.blinn_phong;
+            scene_changed = true;
+        }
 
-    let diffuse_color = if let Some(texture_path) = &intersect.material.texture_id {
-        let texture = texture_manager.get_texture(texture_path).unwrap();
-        let width = texture.width() as u32;
-        let height = texture.height() as u32;
-        let tx = (intersect.u * width as f32) as u32;
-        let ty = (intersect.v * height as f32) as u32;
-        let texture_color = texture_manager.get_pixel_color(texture_path, tx, ty);
-        let texture_alpha = texture_manager.get_pixel_alpha(texture_path, tx, ty);
-        
-        // Si el píxel es transparente, mezclar con el color difuso del material
-        // Para materiales transparentes como vidrio, esto permite que la refracción se vea mejor
-        intersect.material.diffuse * (1.0 - texture_alpha) + texture_color * texture_alpha
-    } else {
-        intersect.material.diffuse
-    };
+        // F3 activa las AOV; con ellas activas, P guarda albedo, normal, profundidad y posición (EXR)
+        if window.is_key_pressed(KeyboardKey::KEY_F3) {
+            settings.output_aovs = !settings.output_aovs;
+        }
+        if settings.output_aovs && window.is_key_pressed(KeyboardKey::KEY_P) {
+            aovs.save_albedo_png("aov_albedo.png");
+            aovs.save_normal_png("aov_normal.png");
+            aovs.save_depth_png("aov_depth.png");
+            if let Err(error) = aovs.save_position_exr("aov_position.exr") {
+                eprintln!("No se pudo guardar la posición: {}", error);
+            }
+            println!("AOV guardadas");
+        }
 
-    let diffuse_intensity = normal.dot(light_dir).max(0.0) * light_intensity;
-    let diffuse = diffuse_color * diffuse_intensity;
-
-    let specular_intensity = view_dir.dot(reflect_dir).max(0.0).powf(intersect.material.specular) * light_intensity;
-    let light_color_v3 = Vector3::new(light.color.r as f32 / 255.0, light.color.g as f32 / 255.0, light.color.b as f32 / 255.0);
-    let specular = light_color_v3 * specular_intensity;
-
-    let albedo = intersect.material.albedo;
-    let phong_color = diffuse * albedo[0] + specular * albedo[1];
-
-    // Calcular iluminación de bloques emisivos (glowstone)
-    let mut emissive_light = Vector3::zero();
-    for object in objects {
-        if object.material.is_emissive {
-            let emissive_dir = (object.center - intersect.point).normalized();
-            let emissive_distance = (object.center - intersect.point).length();
-            
-            // Solo considerar bloques emisivos cercanos (dentro de un radio razonable)
-            if emissive_distance < 10.0 && emissive_distance > 0.01 {
-                // Verificar si hay sombra entre el punto y el bloque emisivo
-                let mut blocked = false;
-                let emissive_ray_origin = offset_origin(&intersect, &emissive_dir);
-                
-                for other_object in objects {
-                    // Ignorar el propio objeto emisivo y otros emisivos
-                    if other_object.material.is_emissive {
-                        continue;
-                    }
-                    
-                    let shadow_check = other_object.ray_intersect(&emissive_ray_origin, &emissive_dir);
-                    if shadow_check.is_intersecting && shadow_check.distance < emissive_distance {
-                        blocked = true;
-                        break;
-                    }
-                }
-                
-                if !blocked {
-                    // Calcular contribución de luz basada en distancia (atenuación)
-                    let attenuation = 1.0 / (1.0 + 0.1 * emissive_distance * emissive_distance);
-                    let emissive_intensity = normal.dot(emissive_dir).max(0.0) * object.material.emission_intensity * attenuation;
-                    // Multiplicar por el color de la textura del objeto iluminado para que se vea la textura
-                    emissive_light += object.material.emission_color * emissive_intensity * diffuse_color;
+        // F4 compara la imagen con y sin denoiser
+        if window.is_key_pressed(KeyboardKey::KEY_F4) {
+            settings.denoise = !settings.denoise;
+        }
+
+        // U dibuja contornos negros en los bordes de los bloques
+        if window.is_key_pressed(KeyboardKey::KEY_U) {
+            settings.outline = !settings.outline;
+        }
+
+        // F12 guarda una captura (en --out, o screenshot.ppm por defecto)
+        if window.is_key_pressed(KeyboardKey::KEY_F12) {
+            let metadata = RenderMetadata { camera: &camera, sun_angle, samples: accumulation.frame_count, width: framebuffer.width, height: framebuffer.height };
+            let saved = save_image(&output_path, &mut framebuffer, &accumulation, options.flip_y)
+                .and_then(|()| save_metadata(&output_path, &metadata, &scene, &settings));
+            match saved {
+                Ok(()) => println!("Captura guardada en {}", output_path),
+                Err(error) => eprintln!("No se pudo guardar la captura: {}", error),
+            }
+        }
+
+        // G alterna el piso: sin piso, pulido, mojado, mesa redonda y mesa cuadrada
+        if window.is_key_pressed(KeyboardKey::KEY_G) {
+            ground = Ground::next(ground);
+            scene.set_objects(with_model(scene_objects(&block_grid, decorations, ground, &settings)));
+            scene_changed = true;
+        }
+
+        // Ctrl + 1-9 pasa a otra escena de --scene con su vista y su hora del sol; la que se
+        // deja conserva lo editado
+        if control_down
+            && let Some(next) = bookmark_keys.iter().position(|key| window.is_key_pressed(*key))
+            && next < scene_files.len()
+            && next != active_scene
+        {
+            std::mem::swap(&mut scene_files[active_scene].grid, &mut block_grid);
+            std::mem::swap(&mut scene_files[next].grid, &mut block_grid);
+            active_scene = next;
+            let file = &scene_files[next];
+            scene_path = file.path.clone();
+            camera = initial_camera(&file.defaults);
+            camera_glide = None;
+            sun_angle = file.defaults.sun_angle.unwrap_or(0.0);
+            diorama_angle = 0.0;
+            window.set_window_title(&thread, &window_title(&file.defaults));
+            scene.set_objects(with_model(scene_objects(&block_grid, decorations, ground, &settings)));
+            // Sin vista en el archivo, se encuadra la escena entera
+            if file.defaults.camera.is_none() {
+                camera.frame_scene(&scene, settings.vertical_fov, window_width as f32 / window_height as f32);
+            }
+            scene_changed = true;
+            println!("Escena {}: {}", next + 1, scene_path);
+        }
+
+        // Edición en vivo con el mouse: X quita el bloque apuntado y C pone uno igual
+        // pegado a la cara apuntada (sobre el piso usa la última letra colocada)
+        let remove_block = window.is_key_pressed(KeyboardKey::KEY_X);
+        let add_block = window.is_key_pressed(KeyboardKey::KEY_C);
+        if remove_block || add_block {
+            let mouse = window.get_mouse_position();
+            if let Some((_, hit)) = pick(&scene, &camera, &settings, mouse.x, mouse.y, window_width as f32, window_height as f32) {
+                let hit_cell = block_grid.cell_at(hit.point - hit.normal * (block_grid.cube_size() / 2.0));
+                let hit_letter = hit_cell.map_or(' ', |cell| block_grid.letter(cell));
+                let edit = if remove_block {
+                    hit_cell.filter(|_| hit_letter != ' ').map(|cell| (cell, ' '))
+                } else {
+                    let target = match hit_cell {
+                        Some(cell) if hit_letter != ' ' => {
+                            brush = hit_letter;
+                            block_grid.neighbor(cell, hit.normal)
+                        }
+                        // Sobre el piso: la celda apoyada en el punto apuntado
+                        _ => block_grid.cell_at(hit.point + hit.normal * (block_grid.cube_size() / 2.0)),
+                    };
+                    target.map(|cell| (cell, brush))
+                };
+                if let Some((cell, letter)) = edit {
+                    block_grid.set(cell, letter);
+                    scene.set_objects(with_model(scene_objects(&block_grid, decorations, ground, &settings)));
+                    scene_changed = true;
                 }
             }
         }
-    }
 
-    // Agregar emisión propia si el objeto es emisivo
-    // La emisión se modifica por la textura si está disponible
-    let self_emission = if intersect.material.is_emissive {
-        let emission_base = intersect.material.emission_color * intersect.material.emission_intensity;
-        
-        // Si hay textura, multiplicar la emisión por el color de la textura para que sea visible
-        if let Some(texture_path) = &intersect.material.texture_id {
-            let texture = texture_manager.get_texture(texture_path).unwrap();
-            let width = texture.width() as u32;
-            let height = texture.height() as u32;
-            let tx = (intersect.u * width as f32) as u32;
-            let ty = (intersect.v * height as f32) as u32;
-            let texture_color = texture_manager.get_pixel_color(texture_path, tx, ty);
-            // Combinar la emisión con la textura (la textura modula la emisión)
-            emission_base * texture_color
-        } else {
-            emission_base
+        // F11 alterna entre cajas analíticas y bloques redondeados por campos de distancia
+        if window.is_key_pressed(KeyboardKey::KEY_F11) {
+            settings.sdf_blocks = !settings.sdf_blocks;
+            scene.set_objects(with_model(scene_objects(&block_grid, decorations, ground, &settings)));
+            scene_changed = true;
         }
-    } else {
-        Vector3::zero()
-    };
 
-    let reflectivity = intersect.material.albedo[2];
-    let reflect_color = if reflectivity > 0.0 {
-        let reflect_dir = reflect(ray_direction, &normal).normalized();
-        let reflect_origin = offset_origin(&intersect, &reflect_dir);
-        cast_ray(&reflect_origin, &reflect_dir, objects, light, texture_manager, depth + 1)
-    } else {
-        Vector3::zero()
-    };
+        // I alterna la luz difusa del cielo: apagada, de toda la cúpula y solo la del cielo que
+        // ve cada punto (las grietas entre bloques quedan más oscuras)
+        if window.is_key_pressed(KeyboardKey::KEY_I) {
+            (settings.sky_lighting, settings.sky_occlusion) = match (settings.sky_lighting, settings.sky_occlusion) {
+                (false, _) => (true, false),
+                (true, false) => (true, true),
+                (true, true) => (false, false),
+            };
+            scene_changed = true;
+        }
 
-    let transparency = intersect.material.albedo[3];
-    let refract_color = if transparency > 0.0 {
-        if let Some(refract_dir) = refract(ray_direction, &normal, intersect.material.refractive_index) {
-            let refract_origin = offset_origin(&intersect, &refract_dir);
-            cast_ray(&refract_origin, &refract_dir, objects, light, texture_manager, depth + 1)
-        } else {
-            let reflect_dir = reflect(ray_direction, &normal).normalized();
-            let reflect_origin = offset_origin(&intersect, &reflect_dir);
-            cast_ray(&reflect_origin, &reflect_dir, objects, light, texture_manager, depth + 1)
+        // ; oscurece las juntas entre bloques con la sombra de contacto
+        if window.is_key_pressed(KeyboardKey::KEY_SEMICOLON) {
+            settings.contact_shadows = !settings.contact_shadows;
+            scene_changed = true;
         }
-    } else {
-        Vector3::zero()
-    };
 
-    phong_color * (1.0 - reflectivity - transparency) + reflect_color * reflectivity + refract_color * transparency + emissive_light + self_emission
-}
+        // Z cambia entre el trazado de Whitted y el de caminos (luz indirecta, necesita acumular)
+        if window.is_key_pressed(KeyboardKey::KEY_Z) {
+            settings.render_mode = match settings.render_mode {
+                RenderMode::Whitted => RenderMode::PathTraced,
+                RenderMode::PathTraced => RenderMode::Whitted,
+            };
+            scene_changed = true;
+        }
 
-pub fn render(
-    framebuffer: &mut Framebuffer,
-    objects: &[Cube],
-    camera: &Camera,
-    light: &Light,
-    texture_manager: &TextureManager,
-) {
-    let width = framebuffer.width as f32;
-    let height = framebuffer.height as f32;
-    let aspect_ratio = width / height;
-    let fov = PI / 3.0;
-    let perspective_scale = (fov * 0.5).tan();
-
-    // Crear un buffer temporal para almacenar los colores de los píxeles
-    let mut pixel_buffer: Vec<Color> = vec![Color::BLACK; (framebuffer.width * framebuffer.height) as usize];
-
-    // Paralelizar el renderizado por filas
-    pixel_buffer.par_chunks_mut(framebuffer.width as usize).enumerate().for_each(|(y, row)| {
-        for (x, pixel) in row.iter_mut().enumerate() {
-            let screen_x = (2.0 * x as f32) / width - 1.0;
-            let screen_y = -(2.0 * y as f32) / height + 1.0;
-
-            let screen_x = screen_x * aspect_ratio * perspective_scale;
-            let screen_y = screen_y * perspective_scale;
-
-            let ray_direction = Vector3::new(screen_x, screen_y, -1.0).normalized();
-            
-            let rotated_direction = camera.basis_change(&ray_direction);
-
-            let pixel_color_v3 = cast_ray(&camera.eye, &rotated_direction, objects, light, texture_manager, 0);
-            *pixel = vector3_to_color(pixel_color_v3);
+        // N cubre el cielo de nubes
+        if window.is_key_pressed(KeyboardKey::KEY_N) {
+            settings.cloud_coverage = if settings.cloud_coverage > 0.0 { 0.0 } else { 0.45 };
+            scene_changed = true;
         }
-    });
 
-    // Copiar el buffer temporal al framebuffer
-    for y in 0..framebuffer.height {
-        for x in 0..framebuffer.width {
-            let index = (y * framebuffer.width + x) as usize;
-            framebuffer.set_pixel_color(x, y, pixel_buffer[index]);
+        // L apaga las texturas de los bloques lejanos
+        if window.is_key_pressed(KeyboardKey::KEY_L) {
+            settings.texture_lod = !settings.texture_lod;
+            scene_changed = true;
         }
-    }
-}
 
+        // J, K, M y O apagan sombras, reflejos, refracciones y la luz de los bloques emisivos
+        let effect_toggles = [
+            (KeyboardKey::KEY_J, &mut settings.enable_shadows),
+            (KeyboardKey::KEY_K, &mut settings.enable_reflections),
+            (KeyboardKey::KEY_M, &mut settings.enable_refractions),
+            (KeyboardKey::KEY_O, &mut settings.enable_emissive),
+        ];
+        for (key, enabled) in effect_toggles {
+            if window.is_key_pressed(key) {
+                *enabled = !*enabled;
+                scene_changed = true;
+            }
+        }
 
-fn main() {
-    let window_width = 1300;
-    let window_height = 900;
- 
-    let (mut window, thread) = raylib::init()
-        .size(window_width, window_height)
-        .title("Raytracer Example")
-        .log_level(TraceLogLevel::LOG_WARNING)
-        .build();
+        // H muestra la mira y resalta el bloque que editarían X y C
+        if window.is_key_pressed(KeyboardKey::KEY_H) {
+            show_hud = !show_hud;
+        }
 
-    let mut texture_manager = TextureManager::new();
-    // Cargar todas las texturas de assets
-    texture_manager.load_texture(&mut window, &thread, "assets/Furnace.png");
-    texture_manager.load_texture(&mut window, &thread, "assets/Bookshelf.png");
-    texture_manager.load_texture(&mut window, &thread, "assets/obsidiana.png");
-    texture_manager.load_texture(&mut window, &thread, "assets/glass.png");
-    texture_manager.load_texture(&mut window, &thread, "assets/glowstone.png");
-    texture_manager.load_texture(&mut window, &thread, "assets/chest.png");
-    texture_manager.load_texture(&mut window, &thread, "assets/wood_planks.png");
-    texture_manager.load_texture(&mut window, &thread, "assets/oak-wood-planks.png");
-    texture_manager.load_texture(&mut window, &thread, "assets/ball_normal.png");
-    let mut framebuffer = Framebuffer::new(window_width as u32, window_height as u32);
+        // F10 dibuja la caja de cada objeto encima de la imagen
+        if window.is_key_pressed(KeyboardKey::KEY_F10) {
+            show_bounds = !show_bounds;
+        }
 
-    let layers = get_layers();
-    let base_objects = create_cubes_from_layers(layers);
+        // V marca dónde está cada luz y hacia dónde apunta
+        if window.is_key_pressed(KeyboardKey::KEY_V) {
+            settings.show_light_markers = !settings.show_light_markers;
+            scene_changed = true;
+        }
 
-    let mut camera = Camera::new(
-        Vector3::new(0.0, 0.0, 5.0),
-        Vector3::new(0.0, 0.0, 0.0),
-        Vector3::new(0.0, 1.0, 0.0),
-    );
-    let rotation_speed = PI / 100.0;
-    let zoom_speed = 0.15;
-    let diorama_rotation_speed = PI / 80.0;
-    let mut diorama_angle = 0.0;
+        // F9 guarda los bloques editados en el archivo de la escena activa (o scene.txt)
+        if window.is_key_pressed(KeyboardKey::KEY_F9) {
+            let defaults = scene_files.get(active_scene).map_or(SceneDefaults::default(), |file| file.defaults.clone());
+            match save_grid_to_file(&block_grid, &defaults, &scene_path) {
+                Ok(()) => println!("Escena guardada en {}", scene_path),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
 
-    // Configuración del ciclo día/noche (luz rotando alrededor del eje Y como el sol)
-    let sun_radius = 8.0; // Radio de la órbita del sol
-    let mut sun_angle = 0.0; // Ángulo inicial (0 = mediodía)
-    let sun_rotation_speed = PI / 300.0; // Velocidad del ciclo día/noche
-    
-    let mut light = Light::new(
-        Vector3::new(0.0, sun_radius, 0.0),
-        Color::new(255, 255, 255, 255),
-        1.5,
-    );
+        // Espacio pausa el ciclo día/noche para que la imagen pueda refinarse
+        if window.is_key_pressed(KeyboardKey::KEY_SPACE) {
+            sun_paused = !sun_paused;
+        }
 
-    while !window.window_should_close() {
-        if window.is_key_down(KeyboardKey::KEY_LEFT) {
-            camera.orbit(rotation_speed, 0.0);
+        // F5 abre el obturador durante todo el cuadro (desenfoque de movimiento)
+        if window.is_key_pressed(KeyboardKey::KEY_F5) {
+            settings.shutter = if settings.shutter > 0.0 { 0.0 } else { 1.0 };
+            scene_changed = true;
         }
-        if window.is_key_down(KeyboardKey::KEY_RIGHT) {
-            camera.orbit(-rotation_speed, 0.0);
+
+        // F6 alterna el modo estéreo: apagado, lado a lado y arriba/abajo
+        if window.is_key_pressed(KeyboardKey::KEY_F6) {
+            settings.stereo = match settings.stereo {
+                None => Some(StereoLayout::SideBySide),
+                Some(StereoLayout::SideBySide) => Some(StereoLayout::TopBottom),
+                Some(StereoLayout::TopBottom) => None,
+            };
+            scene_changed = true;
         }
-        if window.is_key_down(KeyboardKey::KEY_UP) {
-            camera.orbit(0.0, -rotation_speed);
+
+        // F7 activa las cáusticas del sol a través del vidrio
+        if window.is_key_pressed(KeyboardKey::KEY_F7) {
+            settings.caustics = !settings.caustics;
+            scene_changed = true;
         }
-        if window.is_key_down(KeyboardKey::KEY_DOWN) {
-            camera.orbit(0.0, rotation_speed);
+
+        // F8 activa la niebla con rayos de luz (costosa: conviene bajar fog_steps)
+        if window.is_key_pressed(KeyboardKey::KEY_F8) {
+            settings.fog_density = if settings.fog_density > 0.0 { 0.0 } else { 0.08 };
+            scene_changed = true;
         }
-        if window.is_key_down(KeyboardKey::KEY_W) {
-            camera.zoom(zoom_speed);
+
+        // / usa el mapa de sombras para el sol en vez de un rayo de sombra por píxel
+        if window.is_key_pressed(KeyboardKey::KEY_SLASH) {
+            settings.shadow_map = !settings.shadow_map;
+            scene_changed = true;
         }
-        if window.is_key_down(KeyboardKey::KEY_S) {
-            camera.zoom(-zoom_speed);
+
+        // Inicio encuadra toda la escena sin cambiar el ángulo de la vista
+        if window.is_key_pressed(KeyboardKey::KEY_HOME) {
+            camera.frame_scene(&scene, settings.vertical_fov, window_width as f32 / window_height as f32);
+            camera_glide = None;
         }
-        
+
+        // F traza a 1/4 de resolución mientras algo se mueve
+        if window.is_key_pressed(KeyboardKey::KEY_F) {
+            settings.preview_scale = if settings.preview_scale > 1 { 1 } else { 4 };
+        }
+
+        // A cambia el eje de giro del diorama (Y, X, Z) y lo deja derecho para empezar de nuevo
+        if window.is_key_pressed(KeyboardKey::KEY_A) {
+            diorama_axis = (diorama_axis + 1) % diorama_axes.len();
+            scene.rotation_axis = diorama_axes[diorama_axis];
+            diorama_angle = 0.0;
+            scene_changed = true;
+        }
+
         // Rotación del diorama con Q y E
+        let mut diorama_spin = 0.0;
         if window.is_key_down(KeyboardKey::KEY_Q) {
-            diorama_angle += diorama_rotation_speed;
+            diorama_spin += diorama_rotation_speed;
         }
         if window.is_key_down(KeyboardKey::KEY_E) {
-            diorama_angle -= diorama_rotation_speed;
+            diorama_spin -= diorama_rotation_speed;
+        }
+        if diorama_spin != 0.0 {
+            diorama_angle += diorama_spin;
+            scene_changed = true;
         }
+        scene.rotation_speed = diorama_spin;
         
-        // Rotar todos los objetos del diorama alrededor del eje Y
-        let rotated_objects: Vec<Cube> = base_objects.iter().map(|cube| {
-            let rotated_center = rotate_around_y(cube.center, diorama_angle);
-            Cube {
-                center: rotated_center,
-                size: cube.size,
-                material: cube.material.clone(),
-            }
-        }).collect();
+        scene.rotation = diorama_angle;
 
         // Ciclo día/noche: rotar el sol alrededor del eje Y
-        sun_angle += sun_rotation_speed;
-        
-        // Calcular posición del sol (rotación en el plano XZ, altura en Y)
-        // El sol se mueve en un arco: alto durante el día, bajo durante la noche
-        // sun_angle: 0 = mediodía (alto), PI/2 = atardecer, PI = medianoche (bajo), 3*PI/2 = amanecer
-        let sun_height = sun_angle.cos(); // 1 (mediodía) a -1 (medianoche)
-        // Rotación horizontal alrededor del eje Y
-        let sun_x = sun_radius * sun_angle.cos();
-        let sun_y = sun_radius * sun_height; // Altura del sol
-        let sun_z = sun_radius * sun_angle.sin();
-        
-        light.position = Vector3::new(sun_x, sun_y, sun_z);
-        
-        // Calcular intensidad de la luz según la altura del sol
-        // Durante el día (sun_height > 0): más intensa
-        // Durante la noche (sun_height < 0): menos intensa
-        let normalized_height = (sun_height + 1.0) / 2.0; // Normalizar de 0 a 1
-        light.intensity = 0.1 + normalized_height * 1.4; // De 0.1 (noche) a 1.5 (día)
+        if !sun_paused {
+            sun_angle += sun_rotation_speed;
+            sun_moved = true;
+        }
         
-        // Calcular color de la luz según la hora del día
-        // Amanecer/Atardecer: cálido (naranja/rojo)
-        // Día: blanco/azul claro
-        // Noche: azul oscuro/morado
-        let (r, g, b) = if normalized_height > 0.7 {
-            // Día (alto en el cielo)
-            (255, 255, 255)
-        } else if normalized_height > 0.3 {
-            // Amanecer/Atardecer
-            let warmth = (normalized_height - 0.3) / 0.4; // 0 a 1
-            let r_val = (255.0 * (1.0 - warmth * 0.3) + 255.0 * warmth) as u8;
-            let g_val = (200.0 * (1.0 - warmth * 0.2) + 255.0 * warmth) as u8;
-            let b_val = (150.0 * (1.0 - warmth * 0.5) + 255.0 * warmth) as u8;
-            (r_val, g_val, b_val)
-        } else {
-            // Noche
-            let night_factor = normalized_height / 0.3; // 0 a 1
-            let r_val = (100.0 * night_factor) as u8;
-            let g_val = (120.0 * night_factor) as u8;
-            let b_val = (180.0 * night_factor) as u8;
-            (r_val, g_val, b_val)
-        };
+        // El agua y las nubes se mueven en cada cuadro (también se congela con la pausa, para poder
+        // refinar). Mientras corren la imagen se rehace en cada cuadro: acumular necesita la pausa
+        let clouds_drifting = settings.cloud_coverage > 0.0 && settings.cloud_drift != 0.0;
+        if !sun_paused && (scene.is_animated() || clouds_drifting) {
+            scene.time += 1.0;
+            animated = true;
+        }
         
-        light.color = Color::new(r, g, b, 255);
+        daynight::update_day_night(&mut scene.lights, sun_angle, sun_radius, &sun_ramp);
+        if settings.dynamic_sky {
+            settings.sky_color = daynight::sky_color(sun_angle, &sky_ramp);
+            settings.star_visibility = daynight::star_visibility(sun_angle);
+        }
+        update_culling(&mut scene, &camera, &settings, window_width as f32 / window_height as f32);
+        // Las cáusticas y el mapa de sombras dependen de la geometría y del sol, no del tiempo
+        if scene_changed || sun_moved {
+            update_caustics(&mut scene, &settings);
+            update_shadow_map(&mut scene, &settings);
+        }
+
+        // Cualquier movimiento invalida la imagen acumulada; un cambio en la escena invalida
+        // además la historia de la reproyección (un movimiento de cámara no). La vista previa
+        // solo cuenta lo que hace el usuario: con el sol andando no se queda en baja resolución
+        let user_moved = camera.is_changed() || scene_changed;
+        if user_moved || sun_moved || animated {
+            accumulation.reset();
+        }
+        idle_frames = if user_moved { 0 } else { idle_frames.saturating_add(1) };
+        if scene_changed || sun_moved || animated {
+            accumulation.invalidate_history();
+        }
 
-        // Renderizar siempre ya que la luz está rotando continuamente
-        render(&mut framebuffer, &rotated_objects, &camera, &light, &texture_manager);
+        // Con recorte se traza siempre completo: el rectángulo está en píxeles de la ventana
+        if settings.preview_scale > 1 && settings.crop.is_none() && idle_frames < settings.preview_idle_frames {
+            let (width, height) = preview_size(window_width as u32, window_height as u32, settings.preview_scale);
+            if (preview.width, preview.height) != (width, height) {
+                preview = Framebuffer::new(width, height);
+                preview_accumulation = AccumulationBuffer::new(width, height);
+            }
+            render_preview(&mut framebuffer, &mut preview, &mut preview_accumulation, &scene, &camera, &settings, settings.preview_scale);
+        } else {
+            let aov_target = if settings.output_aovs { Some(&mut aovs) } else { None };
+            render(&mut framebuffer, &scene, &camera, &settings, &mut accumulation, aov_target);
+        }
+        if options.bvh_stats {
+            let report = measure_traversal(&scene, &camera, &settings, window_width as u32, window_height as u32);
+            if last_traversal != Some(report) {
+                print_traversal(&report);
+                last_traversal = Some(report);
+            }
+        }
+        if show_bounds {
+            overlay::draw_bounds(&mut framebuffer, &scene, &camera, &settings);
+        }
+        if settings.show_light_markers {
+            overlay::draw_lights(&mut framebuffer, &scene, &camera, &settings);
+        }
+        // Se apunta con el mouse, igual que al editar. El bloque de la celda apuntada se marca
+        // aunque el mallado lo haya fusionado con sus vecinos; lo que no es un bloque (adornos,
+        // el piso) marca su propia caja
+        if show_hud {
+            let mouse = window.get_mouse_position();
+            if let Some((index, hit)) = pick(&scene, &camera, &settings, mouse.x, mouse.y, window_width as f32, window_height as f32) {
+                let cell = block_grid.cell_at(hit.point - hit.normal * (block_grid.cube_size() / 2.0))
+                    .filter(|&cell| block_grid.letter(cell) != ' ');
+                let (min, max) = match cell {
+                    Some((x, y, layer)) => {
+                        let center = block_grid.cell_center(x, y, layer);
+                        let half = Vector3::one() * (block_grid.cube_size() / 2.0);
+                        (center - half, center + half)
+                    }
+                    None => scene.objects()[index].bounds(),
+                };
+                if min.x.is_finite() && max.x.is_finite() {
+                    overlay::draw_box(&mut framebuffer, &scene, &camera, &settings, min, max, Color::WHITE);
+                }
+            }
+            overlay::draw_crosshair(&mut framebuffer, mouse, Color::WHITE);
+        }
         
         framebuffer.swap_buffers(&mut window, &thread);
     }
@@ -1,16 +1,101 @@
 use raylib::prelude::{Color, Vector3};
+use std::f32::consts::TAU;
+use std::sync::Mutex;
+use crate::noise;
+use crate::presets;
+use crate::textures::{srgb_to_linear, TextureFilter, TextureWrap};
+
+// Índices de refracción de referencia para definir materiales transparentes
+pub const IOR_WATER: f32 = 1.33;
+pub const IOR_GLASS: f32 = 1.5;
+pub const IOR_DIAMOND: f32 = 2.42;
+// Por encima de esto ningún material transparente común es creíble (el rutilo ronda 2.9)
+const MAX_PLAUSIBLE_IOR: f32 = 3.0;
+
+// Cómo se lee el mapa de normales: relativo a la cara (como ball_normal.png) o ya expresado en
+// los ejes de la escena, donde el color del texel es directamente la normal
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum NormalMapSpace {
+    #[default]
+    Tangent,
+    Object,
+}
+
+// Forma de la variación del brillo de un emisivo: latido suave (seno) o parpadeo irregular de
+// llama (ruido)
+#[derive(Clone, Copy, PartialEq)]
+pub enum EmissionWave {
+    Pulse,
+    Flicker,
+}
+
+// La intensidad oscila entre (1 - amplitude) y (1 + amplitude) veces emission_intensity, con
+// `frequency` ciclos por cuadro de scene.time
+#[derive(Clone, Copy)]
+pub struct EmissionAnimation {
+    pub wave: EmissionWave,
+    pub amplitude: f32,
+    pub frequency: f32,
+}
 
 #[derive(Clone)]
 pub struct Material {
     pub diffuse: Vector3,
+    // Pesos [difuso, especular, reflejo, transmisión]. Reflejo + transmisión ≤ 1: Phong se lleva
+    // lo que sobra (ver normalize_albedo)
     pub albedo: [f32; 4],
     pub specular: f32,
     pub refractive_index: f32,
     pub texture_id: Option<String>,
     pub normal_map_id: Option<String>,
+    pub normal_strength: f32,
+    pub normal_map_space: NormalMapSpace,
+    pub emission_map_id: Option<String>,
+    // Lectura de las texturas del material: píxeles nítidos (por defecto) o interpolados,
+    // y qué pasa con las UV fuera de [0, 1] (se estira el borde o se repite la textura)
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    pub alpha_cutout: Option<f32>,
+    // Dispersión del reflejo: 0 = espejo perfecto, valores chicos dan un reflejo borroso (piso mojado)
+    pub roughness: f32,
+    // Inclinación máxima de las ondas animadas de la superficie (0 = superficie quieta, agua > 0)
+    pub ripple: f32,
+    // Película delgada (mancha de aceite): cuánto tiñe el brillo con el arcoíris (0 = nada),
+    // grosor de la película en nanómetros y color base del brillo
+    pub iridescence: f32,
+    pub film_thickness: f32,
+    pub film_color: Vector3,
+    // Capa de barniz sobre el material: intensidad (0 = sin barniz) y rugosidad del barniz
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    // Con false solo se ven las caras que miran al rayo (objetos opacos y cerrados)
+    pub double_sided: bool,
+    // Ocultar el objeto sin quitarlo de la escena (ningún rayo lo ve ni lo tapa), o dejarlo
+    // visible pero sin que bloquee la luz (geometría de referencia "fantasma")
+    pub visible: bool,
+    pub casts_shadow: bool,
+    // Número de Abbe del vidrio (0 = sin dispersión): cuanto más bajo, más se separan los colores
+    // al refractar. Con dispersión se lanza un rayo refractado por canal
+    pub abbe_number: f32,
+    // Brillo con el vector medio (Blinn-Phong) en vez del reflejo de la luz (Phong)
+    pub blinn_phong: bool,
+    // Sombreado de dibujo animado: el difuso de cada luz se corta en toon_bands escalones
+    // (0 = suave) que suben en rampa desde toon_min en la banda más oscura hasta 1
+    pub toon_bands: u32,
+    pub toon_min: f32,
+    // Anisotropía del brillo (0 = redondo, hasta ~0.95) y dirección en la que se estira, como el
+    // metal cepillado. La dirección se proyecta sobre cada cara, así que no tiene que ser tangente
+    pub aniso: f32,
+    pub aniso_tangent: Vector3,
+    // Tinte del brillo especular: blanco en los dieléctricos, del color del metal en oro o cobre
+    pub specular_color: Vector3,
     pub is_emissive: bool,
     pub emission_intensity: f32,
     pub emission_color: Vector3,
+    // Distancia hasta la que ilumina a otros objetos (0 = la de settings.emissive_cutoff)
+    pub emission_reach: f32,
+    // Variación del brillo en el tiempo (None = emisión constante)
+    pub emission_animation: Option<EmissionAnimation>,
 }
 
 impl Material {
@@ -22,6 +107,8 @@ impl Material {
         texture_id: Option<String>,
         normal_map_id: Option<String>,
     ) -> Self {
+        warn_implausible_ior(refractive_index, albedo[3]);
+        let albedo = normalize_albedo(albedo);
         Material {
             diffuse,
             albedo,
@@ -29,12 +116,38 @@ impl Material {
             refractive_index,
             texture_id,
             normal_map_id,
+            normal_strength: 1.0,
+            normal_map_space: NormalMapSpace::Tangent,
+            emission_map_id: None,
+            filter: TextureFilter::Nearest,
+            wrap: TextureWrap::Clamp,
+            alpha_cutout: None,
+            roughness: 0.0,
+            ripple: 0.0,
+            iridescence: 0.0,
+            film_thickness: 0.0,
+            film_color: Vector3::one(),
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.0,
+            double_sided: true,
+            visible: true,
+            casts_shadow: true,
+            abbe_number: 0.0,
+            blinn_phong: false,
+            toon_bands: 0,
+            toon_min: 0.3,
+            aniso: 0.0,
+            aniso_tangent: Vector3::new(1.0, 0.0, 0.0),
+            specular_color: Vector3::one(),
             is_emissive: false,
             emission_intensity: 0.0,
             emission_color: Vector3::zero(),
+            emission_reach: 0.0,
+            emission_animation: None,
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_emissive(
         diffuse: Vector3,
         specular: f32,
@@ -46,38 +159,400 @@ impl Material {
         emission_color: Vector3,
     ) -> Self {
         Material {
-            diffuse,
-            albedo,
-            specular,
-            refractive_index,
-            texture_id,
-            normal_map_id,
             is_emissive: true,
             emission_intensity,
             emission_color,
+            ..Material::new(diffuse, specular, albedo, refractive_index, texture_id, normal_map_id)
         }
     }
 
+    // Intensidad de emisión en el instante `time` (en cuadros, como scene.time)
+    pub fn emission_intensity_at(&self, time: f32) -> f32 {
+        // El fbm se junta alrededor de 0.5 (desvío ~0.17): estirado así el parpadeo llega a los
+        // extremos de la amplitud y solo el 1% más alejado queda recortado
+        const FLICKER_CONTRAST: f32 = 2.8;
+        let Some(animation) = self.emission_animation else {
+            return self.emission_intensity;
+        };
+        let phase = time * animation.frequency;
+        let wave = match animation.wave {
+            EmissionWave::Pulse => (phase * TAU).sin(),
+            EmissionWave::Flicker => ((noise::fbm(Vector3::new(phase, 0.0, 0.0), 3) - 0.5) * FLICKER_CONTRAST).clamp(-1.0, 1.0),
+        };
+        self.emission_intensity * (1.0 + animation.amplitude * wave).max(0.0)
+    }
+
+    // Material mate a partir de un color "#RRGGBB" o "#RGB"
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        Ok(presets::matte(parse_hex_color(hex)?))
+    }
+
+    // Igual que `from_hex` pero decodificando el sRGB del color, para escenas con `linear_lighting`
+    pub fn from_hex_linear(hex: &str) -> Result<Self, String> {
+        let color = parse_hex_color(hex)?;
+        let linear = Vector3::new(srgb_to_linear(color.x), srgb_to_linear(color.y), srgb_to_linear(color.z));
+        Ok(presets::matte(linear))
+    }
+
+    pub fn builder() -> MaterialBuilder {
+        MaterialBuilder::new()
+    }
+
+    // Builder que parte de este material, para variar un preset
+    pub fn into_builder(self) -> MaterialBuilder {
+        MaterialBuilder { material: self }
+    }
+
     pub fn black() -> Self {
-        Material {
-            diffuse: Vector3::zero(),
-            albedo: [0.0, 0.0, 0.0, 0.0],
-            specular: 0.0,
-            refractive_index: 0.0,
-            texture_id: None,
-            normal_map_id: None,
-            is_emissive: false,
-            emission_intensity: 0.0,
-            emission_color: Vector3::zero(),
+        Material::new(Vector3::zero(), 0.0, [0.0; 4], 0.0, None, None)
+    }
+}
+
+pub struct MaterialBuilder {
+    material: Material,
+}
+
+impl MaterialBuilder {
+    // Por defecto: material mate blanco, sin reflexión ni refracción
+    pub fn new() -> Self {
+        MaterialBuilder {
+            material: Material::new(Vector3::one(), 10.0, [1.0, 0.0, 0.0, 0.0], 0.0, None, None),
         }
     }
+
+    pub fn diffuse(mut self, diffuse: Vector3) -> Self {
+        self.material.diffuse = diffuse;
+        self
+    }
+
+    pub fn specular(mut self, specular: f32) -> Self {
+        self.material.specular = specular;
+        self
+    }
+
+    pub fn albedo(mut self, albedo: [f32; 4]) -> Self {
+        self.material.albedo = albedo;
+        self
+    }
+
+    pub fn refractive_index(mut self, refractive_index: f32) -> Self {
+        self.material.refractive_index = refractive_index;
+        self
+    }
+
+    pub fn texture(mut self, path: &str) -> Self {
+        self.material.texture_id = Some(path.to_string());
+        self
+    }
+
+    pub fn normal_map(mut self, path: &str) -> Self {
+        self.material.normal_map_id = Some(path.to_string());
+        self
+    }
+
+    // 0 = normal geométrica, 1 = mapa completo, >1 exagera el relieve
+    pub fn normal_strength(mut self, strength: f32) -> Self {
+        self.material.normal_strength = strength;
+        self
+    }
+
+    pub fn normal_map_space(mut self, space: NormalMapSpace) -> Self {
+        self.material.normal_map_space = space;
+        self
+    }
+
+    pub fn filter(mut self, filter: TextureFilter) -> Self {
+        self.material.filter = filter;
+        self
+    }
+
+    pub fn wrap(mut self, wrap: TextureWrap) -> Self {
+        self.material.wrap = wrap;
+        self
+    }
+
+    pub fn emission_map(mut self, path: &str) -> Self {
+        self.material.emission_map_id = Some(path.to_string());
+        self
+    }
+
+    // Los texels con alpha menor al umbral se tratan como huecos
+    pub fn alpha_cutout(mut self, threshold: f32) -> Self {
+        self.material.alpha_cutout = Some(threshold);
+        self
+    }
+
+    pub fn roughness(mut self, roughness: f32) -> Self {
+        self.material.roughness = roughness;
+        self
+    }
+
+    pub fn ripple(mut self, ripple: f32) -> Self {
+        self.material.ripple = ripple;
+        self
+    }
+
+    pub fn iridescence(mut self, strength: f32, film_thickness: f32, film_color: Vector3) -> Self {
+        self.material.iridescence = strength;
+        self.material.film_thickness = film_thickness;
+        self.material.film_color = film_color;
+        self
+    }
+
+    pub fn visible(mut self, visible: bool) -> Self {
+        self.material.visible = visible;
+        self
+    }
+
+    pub fn casts_shadow(mut self, casts_shadow: bool) -> Self {
+        self.material.casts_shadow = casts_shadow;
+        self
+    }
+
+    pub fn toon(mut self, bands: u32, min: f32) -> Self {
+        self.material.toon_bands = bands;
+        self.material.toon_min = min;
+        self
+    }
+
+    pub fn clearcoat(mut self, clearcoat: f32, roughness: f32) -> Self {
+        self.material.clearcoat = clearcoat;
+        self.material.clearcoat_roughness = roughness;
+        self
+    }
+
+    pub fn double_sided(mut self, double_sided: bool) -> Self {
+        self.material.double_sided = double_sided;
+        self
+    }
+
+    pub fn dispersion(mut self, abbe_number: f32) -> Self {
+        self.material.abbe_number = abbe_number;
+        self
+    }
+
+    // Brillo especular escrito como rugosidad perceptual (0 = pulido, 1 = mate) en vez de exponente
+    pub fn specular_roughness(mut self, roughness: f32) -> Self {
+        self.material.specular = from_roughness(roughness);
+        self
+    }
+
+    pub fn blinn_phong(mut self) -> Self {
+        self.material.blinn_phong = true;
+        self
+    }
+
+    pub fn anisotropy(mut self, aniso: f32, tangent: Vector3) -> Self {
+        self.material.aniso = aniso;
+        self.material.aniso_tangent = tangent;
+        self
+    }
+
+    pub fn specular_color(mut self, color: Vector3) -> Self {
+        self.material.specular_color = color;
+        self
+    }
+
+    pub fn emissive(mut self, color: Vector3, intensity: f32) -> Self {
+        self.material.is_emissive = true;
+        self.material.emission_color = color;
+        self.material.emission_intensity = intensity;
+        self
+    }
+
+    pub fn emission_reach(mut self, reach: f32) -> Self {
+        self.material.emission_reach = reach;
+        self
+    }
+
+    pub fn emission_animation(mut self, wave: EmissionWave, amplitude: f32, frequency: f32) -> Self {
+        self.material.emission_animation = Some(EmissionAnimation { wave, amplitude, frequency });
+        self
+    }
+
+    pub fn build(self) -> Material {
+        warn_implausible_ior(self.material.refractive_index, self.material.albedo[3]);
+        let mut material = self.material;
+        material.albedo = normalize_albedo(material.albedo);
+        material
+    }
+}
+
+impl Default for MaterialBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Los materiales se vuelven a armar cada vez que se reconstruye la escena: cada aviso sale una
+// sola vez por ejecución para no llenar la consola en cada cuadro
+fn warn_once(message: String) {
+    static WARNED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    let mut warned = WARNED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !warned.contains(&message) {
+        eprintln!("{}", message);
+        warned.push(message);
+    }
+}
+
+// Avisa de índices físicamente imposibles (< 1) o exagerados, que doblan los rayos de más y
+// provocan reflexión total interna casi en todo el objeto. Solo importa si el material deja
+// pasar luz: en los opacos el índice 0 significa "sin refracción"
+fn warn_implausible_ior(refractive_index: f32, transparency: f32) {
+    if transparency > 0.0 && !(1.0..=MAX_PLAUSIBLE_IOR).contains(&refractive_index) {
+        warn_once(format!(
+            "Índice de refracción {} fuera del rango creíble (1.0 a {}); el vidrio ronda {}",
+            refractive_index, MAX_PLAUSIBLE_IOR, IOR_GLASS
+        ));
+    }
+}
+
+// Pesos [difuso, especular, reflejo, transmisión]: lo que se refleja más lo que se transmite no
+// puede pasar de 1, o la superficie devolvería más luz de la que recibe (y el peso que queda para
+// Phong sería negativo). Se avisa y se reparten proporcionalmente; los negativos quedan en 0
+fn normalize_albedo(albedo: [f32; 4]) -> [f32; 4] {
+    let mut albedo = albedo.map(|weight| weight.max(0.0));
+    let carried = albedo[2] + albedo[3];
+    if carried > 1.0 {
+        warn_once(format!(
+            "Albedo con reflejo {} + transmisión {} mayor que 1; se reescalan para que sumen 1",
+            albedo[2], albedo[3]
+        ));
+        albedo[2] /= carried;
+        albedo[3] /= carried;
+    }
+    albedo
+}
+
+// Exponente de Phong equivalente a una rugosidad perceptual de 0 a 1: se eleva al cuadrado para
+// obtener el ancho del lóbulo (α) y se usa la equivalencia de Blinn-Phong con Beckmann, 2/α² - 2.
+// 0.55 da ~20 (piedra), 0.3 da ~245 y 1 queda en el mínimo de 1
+pub fn from_roughness(roughness: f32) -> f32 {
+    let alpha = roughness.clamp(0.05, 1.0).powi(2);
+    (2.0 / (alpha * alpha) - 2.0).max(1.0)
+}
+
+pub fn parse_hex_color(hex: &str) -> Result<Vector3, String> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Color hexadecimal inválido: {}", hex));
+    }
+
+    let channel = |s: &str| u8::from_str_radix(s, 16).map(|v| v as f32 / 255.0);
+    let (r, g, b) = match digits.len() {
+        // "#RGB" se expande duplicando cada dígito ("#F80" -> "#FF8800")
+        3 => (
+            channel(&digits[0..1].repeat(2)),
+            channel(&digits[1..2].repeat(2)),
+            channel(&digits[2..3].repeat(2)),
+        ),
+        6 => (channel(&digits[0..2]), channel(&digits[2..4]), channel(&digits[4..6])),
+        _ => return Err(format!("Color hexadecimal inválido: {}", hex)),
+    };
+
+    match (r, g, b) {
+        (Ok(r), Ok(g), Ok(b)) => Ok(Vector3::new(r, g, b)),
+        _ => Err(format!("Color hexadecimal inválido: {}", hex)),
+    }
 }
 
 pub fn vector3_to_color(v: Vector3) -> Color {
+    vector3_to_color_dithered(v, 0.0)
+}
+
+// `noise` en [0, 1) se suma antes de truncar: en promedio cada canal redondea a su valor exacto
+// y un degradado suave se ve como ruido fino en vez de escalones
+pub fn vector3_to_color_dithered(v: Vector3, noise: f32) -> Color {
     Color::new(
-        (v.x * 255.0).min(255.0) as u8,
-        (v.y * 255.0).min(255.0) as u8,
-        (v.z * 255.0).min(255.0) as u8,
+        (v.x * 255.0 + noise).min(255.0) as u8,
+        (v.y * 255.0 + noise).min(255.0) as u8,
+        (v.z * 255.0 + noise).min(255.0) as u8,
         255,
     )
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+
+    fn assert_weights(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "esperaba {expected:?}, dio {actual:?}");
+        }
+    }
+
+    #[test]
+    fn over_unity_reflect_and_transmit_are_rescaled() {
+        assert_weights(normalize_albedo([0.0, 0.3, 0.4, 0.8]), [0.0, 0.3, 1.0 / 3.0, 2.0 / 3.0]);
+        // Dentro del límite no cambia nada
+        assert_weights(normalize_albedo([0.6, 0.3, 0.2, 0.8]), [0.6, 0.3, 0.2, 0.8]);
+        // Los negativos quedan en 0 antes de sumar
+        assert_weights(normalize_albedo([-0.5, 0.3, -0.2, 1.4]), [0.0, 0.3, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn every_constructor_normalizes_the_albedo() {
+        let built = Material::builder().albedo([0.2, 0.1, 1.5, 0.5]).build();
+        assert_weights(built.albedo, [0.2, 0.1, 0.75, 0.25]);
+        let direct = Material::new(Vector3::one(), 10.0, [0.2, 0.1, 1.5, 0.5], 1.0, None, None);
+        assert_weights(direct.albedo, [0.2, 0.1, 0.75, 0.25]);
+
+        let presets = [
+            presets::matte(Vector3::one()),
+            presets::plastic(Vector3::one()),
+            presets::metal(Vector3::one()),
+            presets::glass(IOR_GLASS),
+            presets::mirror(),
+            presets::lamp(Vector3::one(), 2.0),
+        ];
+        for material in presets {
+            assert!(material.albedo[2] + material.albedo[3] <= 1.0 + 1e-6, "{:?}", material.albedo);
+            assert!(material.albedo.iter().all(|&weight| weight >= 0.0));
+        }
+    }
+
+    #[test]
+    fn animated_emission_stays_within_its_amplitude() {
+        let still = Material::builder().emissive(Vector3::one(), 2.0).build();
+        for time in [0.0, 1.5, 37.0, 1000.0] {
+            assert_eq!(still.emission_intensity_at(time), 2.0);
+        }
+
+        for wave in [EmissionWave::Pulse, EmissionWave::Flicker] {
+            let animated = Material::builder().emissive(Vector3::one(), 2.0).emission_animation(wave, 0.4, 0.2).build();
+            let samples: Vec<f32> = (0..5000).map(|frame| animated.emission_intensity_at(frame as f32 * 0.37)).collect();
+            let (low, high) = samples.iter().fold((f32::MAX, f32::MIN), |(low, high), &i| (low.min(i), high.max(i)));
+            assert!(low >= 2.0 * 0.6 - 1e-4 && high <= 2.0 * 1.4 + 1e-4, "{low} a {high}");
+            // También el parpadeo recorre casi toda la amplitud, no solo el medio
+            assert!(low < 2.0 * 0.65 && high > 2.0 * 1.35, "{low} a {high}");
+        }
+    }
+
+    #[test]
+    fn hex_colors_accept_short_and_long_forms() {
+        assert_eq!(parse_hex_color("#FF8000"), Ok(Vector3::new(1.0, 128.0 / 255.0, 0.0)));
+        // "#F80" equivale a "#FF8800"
+        assert_eq!(parse_hex_color("#F80"), Ok(Vector3::new(1.0, 136.0 / 255.0, 0.0)));
+        // El '#' es opcional y las minúsculas valen
+        assert_eq!(parse_hex_color("ff8000"), parse_hex_color("#FF8000"));
+    }
+
+    #[test]
+    fn malformed_hex_colors_are_errors() {
+        for bad in ["#GG0000", "#12345Z", "#FF80", "#FF800000", "#", "", "#+1+"] {
+            assert!(parse_hex_color(bad).is_err(), "{bad} debería fallar");
+        }
+    }
+
+    #[test]
+    fn linear_hex_colors_decode_srgb() {
+        let srgb = Material::from_hex("#808080").unwrap();
+        let linear = Material::from_hex_linear("#808080").unwrap();
+        assert!((srgb.diffuse.x - 128.0 / 255.0).abs() < 1e-6);
+        // El gris medio sRGB es ~0.216 en lineal; blanco y negro no cambian
+        assert!((linear.diffuse.x - 0.2158).abs() < 1e-3, "{}", linear.diffuse.x);
+        assert_eq!(Material::from_hex_linear("#FFF").unwrap().diffuse, Vector3::one());
+        assert_eq!(Material::from_hex_linear("#000").unwrap().diffuse, Vector3::zero());
+    }
+}
@@ -0,0 +1,433 @@
+use raylib::prelude::Vector3;
+use rayon::prelude::*;
+use crate::aabb::Aabb;
+use crate::material::Material;
+use crate::ray_intersect::{inverse_direction, Intersect, RayIntersect, TraversalCost, SURFACE_EPSILON};
+
+// Triángulos por hoja de la jerarquía de cajas
+pub const LEAF_SIZE: usize = 4;
+// Desde esta cantidad de triángulos las dos mitades de un nodo se arman en hilos distintos
+const PARALLEL_BUILD_SIZE: usize = 4096;
+// Nodos pendientes del recorrido. Partir por la mediana deja el árbol balanceado (profundidad
+// log2 de los triángulos), así que una pila fija alcanza para cualquier malla sin pedir memoria
+const TRAVERSAL_STACK_SIZE: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BvhKind {
+    // Rango de `order` con los triángulos de la hoja
+    Leaf { start: usize, end: usize },
+    Inner { left: usize, right: usize },
+}
+
+struct BvhNode {
+    bounds: Aabb,
+    kind: BvhKind,
+}
+
+impl BvhNode {
+    // El mismo nodo dentro de un arreglo donde su subárbol empieza `offset` lugares más adelante
+    fn shifted(self, offset: usize) -> Self {
+        match self.kind {
+            BvhKind::Inner { left, right } => BvhNode { kind: BvhKind::Inner { left: left + offset, right: right + offset }, ..self },
+            BvhKind::Leaf { .. } => self,
+        }
+    }
+}
+
+// Arma la jerarquía de `order`, que ocupa desde `start` el orden completo de la malla, partiendo
+// por la mediana de los centroides sobre el eje más largo. Los hijos se indexan desde la raíz del
+// subárbol, en preorden; los rangos desde `parallel_size` arman sus mitades en paralelo y dan el
+// mismo árbol
+fn build_subtree(triangle_bounds: &[Aabb], order: &mut [usize], start: usize, parallel_size: usize) -> Vec<BvhNode> {
+    let bounds = order.iter().fold(Aabb::empty(), |bounds, &triangle| bounds.union(&triangle_bounds[triangle]));
+    let count = order.len();
+    if count <= LEAF_SIZE {
+        return vec![BvhNode { bounds, kind: BvhKind::Leaf { start, end: start + count } }];
+    }
+
+    let size = bounds.max - bounds.min;
+    let axis = |v: Vector3| if size.x >= size.y && size.x >= size.z { v.x } else if size.y >= size.z { v.y } else { v.z };
+    let middle = count / 2;
+    let mut keyed: Vec<(f32, usize)> = order.iter().map(|&triangle| (axis(triangle_bounds[triangle].centroid()), triangle)).collect();
+    keyed.select_nth_unstable_by(middle, |a, b| a.0.total_cmp(&b.0));
+    for (slot, (_, triangle)) in order.iter_mut().zip(keyed) {
+        *slot = triangle;
+    }
+
+    let (left_order, right_order) = order.split_at_mut(middle);
+    let (left, right) = if count >= parallel_size {
+        rayon::join(
+            || build_subtree(triangle_bounds, left_order, start, parallel_size),
+            || build_subtree(triangle_bounds, right_order, start + middle, parallel_size),
+        )
+    } else {
+        (
+            build_subtree(triangle_bounds, left_order, start, parallel_size),
+            build_subtree(triangle_bounds, right_order, start + middle, parallel_size),
+        )
+    };
+
+    let right_root = 1 + left.len();
+    let mut nodes = Vec::with_capacity(right_root + right.len());
+    nodes.push(BvhNode { bounds, kind: BvhKind::Inner { left: 1, right: right_root } });
+    nodes.extend(left.into_iter().map(|node| node.shifted(1)));
+    nodes.extend(right.into_iter().map(|node| node.shifted(right_root)));
+    nodes
+}
+
+// Malla de triángulos indexada (modelos importados). Las normales y UV por vértice son
+// opcionales: sin normales se usa la de cada cara y sin UV las baricéntricas. Los triángulos
+// se ordenan en una jerarquía de cajas propia, así una malla grande no prueba todos por rayo
+pub struct TriangleMesh {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    uvs: Vec<(f32, f32)>,
+    triangles: Vec<[usize; 3]>,
+    pub material: Material,
+    // Triángulos reordenados por hoja y los nodos de la jerarquía (el 0 es la raíz)
+    order: Vec<usize>,
+    nodes: Vec<BvhNode>,
+}
+
+impl TriangleMesh {
+    // `normals` y `uvs` van vacíos o con uno por posición. Los triángulos con índices fuera de
+    // rango se descartan
+    pub fn new(positions: Vec<Vector3>, normals: Vec<Vector3>, uvs: Vec<(f32, f32)>, triangles: Vec<[usize; 3]>, material: Material) -> Self {
+        let normals = if normals.len() == positions.len() { normals } else { Vec::new() };
+        let uvs = if uvs.len() == positions.len() { uvs } else { Vec::new() };
+        let triangles: Vec<[usize; 3]> = triangles.into_iter().filter(|triangle| triangle.iter().all(|&i| i < positions.len())).collect();
+
+        let mut mesh = TriangleMesh {
+            positions,
+            normals,
+            uvs,
+            order: (0..triangles.len()).collect(),
+            triangles,
+            material,
+            nodes: Vec::new(),
+        };
+        if !mesh.triangles.is_empty() {
+            let triangle_bounds: Vec<Aabb> = (0..mesh.triangles.len()).into_par_iter().map(|triangle| mesh.triangle_bounds(triangle)).collect();
+            mesh.nodes = build_subtree(&triangle_bounds, &mut mesh.order, 0, PARALLEL_BUILD_SIZE);
+        }
+        mesh
+    }
+
+    fn triangle_bounds(&self, triangle: usize) -> Aabb {
+        let [a, b, c] = self.triangles[triangle].map(|i| self.positions[i]);
+        Aabb::new(
+            Vector3::new(a.x.min(b.x).min(c.x), a.y.min(b.y).min(c.y), a.z.min(b.z).min(c.z)),
+            Vector3::new(a.x.max(b.x).max(c.x), a.y.max(b.y).max(c.y), a.z.max(b.z).max(c.z)),
+        )
+    }
+
+    // Möller-Trumbore: distancia y coordenadas baricéntricas de los vértices 1 y 2
+    fn intersect_triangle(&self, triangle: usize, ray_origin: &Vector3, ray_direction: &Vector3) -> Option<(f32, f32, f32)> {
+        let [p0, p1, p2] = self.triangles[triangle].map(|i| self.positions[i]);
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+        let p = ray_direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+
+        let inv_determinant = 1.0 / determinant;
+        let to_origin = *ray_origin - p0;
+        let b1 = to_origin.dot(p) * inv_determinant;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+        let q = to_origin.cross(edge1);
+        let b2 = ray_direction.dot(q) * inv_determinant;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inv_determinant;
+        (t > SURFACE_EPSILON).then_some((t, b1, b2))
+    }
+
+    fn face_normal(&self, triangle: usize) -> Vector3 {
+        let [p0, p1, p2] = self.triangles[triangle].map(|i| self.positions[i]);
+        (p1 - p0).cross(p2 - p0).normalized()
+    }
+
+    // Sombreado suave: mezcla las normales de los vértices con los pesos baricéntricos. Sin
+    // normales por vértice, o si se anulan entre sí (vértices con normales opuestas), queda la
+    // de la cara y el triángulo se ve plano
+    fn shading_normal(&self, triangle: usize, b0: f32, b1: f32, b2: f32) -> Vector3 {
+        if self.normals.is_empty() {
+            return self.face_normal(triangle);
+        }
+        let [i0, i1, i2] = self.triangles[triangle];
+        let normal = self.normals[i0] * b0 + self.normals[i1] * b1 + self.normals[i2] * b2;
+        if normal.length() > 1e-6 {
+            normal.normalized()
+        } else {
+            self.face_normal(triangle)
+        }
+    }
+
+    // Impacto más cercano antes de `max_distance`; con `any` vale el primero que aparezca y el
+    // recorrido termina ahí (sombras). `cost` suma los nodos visitados y los triángulos probados
+    fn find_hit(
+        &self,
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+        inv_direction: &Vector3,
+        max_distance: f32,
+        any: bool,
+        cost: &mut TraversalCost,
+    ) -> Option<(usize, f32, f32, f32)> {
+        let mut closest: Option<(usize, f32, f32, f32)> = None;
+        let mut stack = [0; TRAVERSAL_STACK_SIZE];
+        let mut pending = 1;
+        while pending > 0 {
+            pending -= 1;
+            let Some(node) = self.nodes.get(stack[pending]) else {
+                continue;
+            };
+            cost.nodes += 1;
+            let closest_distance = closest.map_or(max_distance, |(_, t, _, _)| t);
+            if !node.bounds.intersect(ray_origin, inv_direction).is_some_and(|(near, _)| near < closest_distance) {
+                continue;
+            }
+
+            match node.kind {
+                BvhKind::Leaf { start, end } => {
+                    for &triangle in &self.order[start..end] {
+                        cost.tests += 1;
+                        if let Some((t, b1, b2)) = self.intersect_triangle(triangle, ray_origin, ray_direction)
+                            && t < closest.map_or(max_distance, |(_, t, _, _)| t)
+                        {
+                            closest = Some((triangle, t, b1, b2));
+                            if any {
+                                return closest;
+                            }
+                        }
+                    }
+                }
+                BvhKind::Inner { left, right } => {
+                    stack[pending] = left;
+                    stack[pending + 1] = right;
+                    pending += 2;
+                }
+            }
+        }
+        closest
+    }
+
+    fn closest_hit(&self, ray_origin: &Vector3, ray_direction: &Vector3, inv_direction: &Vector3, cost: &mut TraversalCost) -> Option<(usize, f32, f32, f32)> {
+        self.find_hit(ray_origin, ray_direction, inv_direction, f32::INFINITY, false, cost)
+    }
+}
+
+impl RayIntersect for TriangleMesh {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        self.ray_intersect_inverse(ray_origin, ray_direction, &inverse_direction(ray_direction))
+    }
+
+    fn ray_intersect_inverse(&self, ray_origin: &Vector3, ray_direction: &Vector3, inv_direction: &Vector3) -> Intersect {
+        let Some((triangle, t, b1, b2)) = self.closest_hit(ray_origin, ray_direction, inv_direction, &mut TraversalCost::default()) else {
+            return Intersect::empty();
+        };
+
+        let [i0, i1, i2] = self.triangles[triangle];
+        let b0 = 1.0 - b1 - b2;
+        let normal = self.shading_normal(triangle, b0, b1, b2);
+        let (u, v) = if self.uvs.is_empty() {
+            (b1, b2)
+        } else {
+            let [uv0, uv1, uv2] = [self.uvs[i0], self.uvs[i1], self.uvs[i2]];
+            (uv0.0 * b0 + uv1.0 * b1 + uv2.0 * b2, uv0.1 * b0 + uv1.1 * b1 + uv2.1 * b2)
+        };
+
+        let mut intersect = Intersect::new(*ray_origin + *ray_direction * t, normal, t, self.material.clone(), u, v);
+        intersect.face_normal = self.face_normal(triangle);
+        intersect
+    }
+
+    fn any_hit(&self, ray_origin: &Vector3, ray_direction: &Vector3, inv_direction: &Vector3, max_distance: f32) -> bool {
+        self.find_hit(ray_origin, ray_direction, inv_direction, max_distance, true, &mut TraversalCost::default()).is_some()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.nodes.first().map_or(Vector3::zero(), |root| root.bounds.centroid())
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        match self.nodes.first() {
+            Some(root) => (root.bounds.min, root.bounds.max),
+            None => (Vector3::zero(), Vector3::zero()),
+        }
+    }
+
+    // Solo cuentan los rayos que llegan a la caja de la malla
+    fn traversal_cost(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Option<TraversalCost> {
+        let inv_direction = inverse_direction(ray_direction);
+        self.nodes.first()?.bounds.intersect(ray_origin, &inv_direction)?;
+        let mut cost = TraversalCost { baseline: self.triangles.len() as u32, ..TraversalCost::default() };
+        self.closest_hit(ray_origin, ray_direction, &inv_direction, &mut cost);
+        Some(cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Triángulo en el plano z = 0 con la cara hacia +Z
+    fn triangle(normals: Vec<Vector3>) -> TriangleMesh {
+        let positions = vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)];
+        TriangleMesh::new(positions, normals, Vec::new(), vec![[0, 1, 2]], Material::black())
+    }
+
+    fn normal_at(mesh: &TriangleMesh, x: f32, y: f32) -> Vector3 {
+        let hit = mesh.ray_intersect(&Vector3::new(x, y, 2.0), &Vector3::new(0.0, 0.0, -1.0));
+        assert!(hit.is_intersecting);
+        hit.normal
+    }
+
+    #[test]
+    fn vertex_normals_are_blended_across_the_face() {
+        let tilted = vec![
+            Vector3::new(-1.0, -1.0, 1.0).normalized(),
+            Vector3::new(1.0, 0.0, 1.0).normalized(),
+            Vector3::new(0.0, 1.0, 1.0).normalized(),
+        ];
+        let smooth = triangle(tilted.clone());
+        let flat = triangle(Vec::new());
+
+        // Sin normales por vértice todo el triángulo tiene la normal de la cara
+        for (x, y) in [(0.05, 0.05), (0.3, 0.3), (0.8, 0.1)] {
+            assert!((normal_at(&flat, x, y) - Vector3::new(0.0, 0.0, 1.0)).length() < 1e-5);
+        }
+
+        // Cerca de un vértice domina su normal y en el baricentro pesan igual
+        assert!((normal_at(&smooth, 0.01, 0.01) - tilted[0]).length() < 0.05);
+        assert!((normal_at(&smooth, 0.98, 0.01) - tilted[1]).length() < 0.05);
+        let center = normal_at(&smooth, 1.0 / 3.0, 1.0 / 3.0);
+        assert!((center - (tilted[0] + tilted[1] + tilted[2]).normalized()).length() < 1e-4);
+        assert!((center.length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn cancelling_vertex_normals_fall_back_to_the_face() {
+        // En (0.3, 0.4) los pesos de los vértices 0 y 1 son iguales y sus normales opuestas se anulan
+        let mesh = triangle(vec![Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0), Vector3::zero()]);
+        let normal = normal_at(&mesh, 0.3, 0.4);
+        assert!(!normal.x.is_nan());
+        assert!((normal - Vector3::new(0.0, 0.0, 1.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn parallel_build_matches_the_serial_one() {
+        // Una grilla ondulada con más triángulos que PARALLEL_BUILD_SIZE
+        let side = 48;
+        let positions: Vec<Vector3> = (0..side * side)
+            .map(|i| {
+                let (x, z) = ((i % side) as f32, (i / side) as f32);
+                Vector3::new(x, (x * 0.7).sin() + (z * 0.3).cos(), z)
+            })
+            .collect();
+        let triangles: Vec<[usize; 3]> = (0..(side - 1) * (side - 1))
+            .flat_map(|cell| {
+                let corner = cell / (side - 1) * side + cell % (side - 1);
+                [[corner, corner + side, corner + 1], [corner + 1, corner + side, corner + side + 1]]
+            })
+            .collect();
+        assert!(triangles.len() >= PARALLEL_BUILD_SIZE);
+        let mesh = TriangleMesh::new(positions, Vec::new(), Vec::new(), triangles, Material::black());
+
+        let triangle_bounds: Vec<Aabb> = (0..mesh.triangles.len()).map(|triangle| mesh.triangle_bounds(triangle)).collect();
+        let mut order: Vec<usize> = (0..mesh.triangles.len()).collect();
+        let serial = build_subtree(&triangle_bounds, &mut order, 0, usize::MAX);
+
+        assert_eq!(order, mesh.order);
+        assert_eq!(serial.len(), mesh.nodes.len());
+        for (serial, parallel) in serial.iter().zip(&mesh.nodes) {
+            assert_eq!(serial.kind, parallel.kind);
+            assert_eq!((serial.bounds.min, serial.bounds.max), (parallel.bounds.min, parallel.bounds.max));
+        }
+    }
+
+    #[test]
+    fn any_hit_stops_early_and_respects_the_distance() {
+        // 32 capas de 2x2 apiladas en z = 0..31, cada una de dos triángulos
+        let mut positions = Vec::new();
+        let mut triangles = Vec::new();
+        for layer in 0..32 {
+            let z = layer as f32;
+            let base = positions.len();
+            positions.extend([Vector3::new(-1.0, -1.0, z), Vector3::new(1.0, -1.0, z), Vector3::new(1.0, 1.0, z), Vector3::new(-1.0, 1.0, z)]);
+            triangles.extend([[base, base + 1, base + 2], [base, base + 2, base + 3]]);
+        }
+        let mesh = TriangleMesh::new(positions, Vec::new(), Vec::new(), triangles, Material::black());
+        let (origin, direction) = (Vector3::new(0.1, 0.2, 40.0), Vector3::new(0.0, 0.0, -1.0));
+        let inv_direction = inverse_direction(&direction);
+
+        // Lo más cercano es la capa de arriba, a 9
+        for max_distance in [5.0, 8.99, 9.01, 20.0, f32::INFINITY] {
+            assert_eq!(mesh.any_hit(&origin, &direction, &inv_direction, max_distance), max_distance > 9.0, "{max_distance}");
+        }
+
+        let mut closest = TraversalCost::default();
+        let mut any = TraversalCost::default();
+        mesh.find_hit(&origin, &direction, &inv_direction, f32::INFINITY, false, &mut closest);
+        let first = mesh.find_hit(&origin, &direction, &inv_direction, f32::INFINITY, true, &mut any);
+        assert!(first.is_some());
+        assert!(any.nodes <= closest.nodes && any.tests <= closest.tests, "{} nodos contra {}", any.nodes, closest.nodes);
+        // Desde el otro lado la primera capa queda a 5: con el límite antes no la alcanza
+        assert!(!mesh.any_hit(&Vector3::new(0.1, 0.2, -5.0), &-direction, &-inv_direction, 4.9));
+    }
+
+    #[test]
+    fn traversal_cost_beats_the_linear_scan_and_skips_misses() {
+        // Grilla plana de 32x32 celdas (2048 triángulos)
+        let side = 33;
+        let positions: Vec<Vector3> = (0..side * side).map(|i| Vector3::new((i % side) as f32, 0.0, (i / side) as f32)).collect();
+        let triangles: Vec<[usize; 3]> = (0..(side - 1) * (side - 1))
+            .flat_map(|cell| {
+                let corner = cell / (side - 1) * side + cell % (side - 1);
+                [[corner, corner + side, corner + 1], [corner + 1, corner + side, corner + side + 1]]
+            })
+            .collect();
+        let mesh = TriangleMesh::new(positions, Vec::new(), Vec::new(), triangles, Material::black());
+        let down = Vector3::new(0.0, -1.0, 0.0);
+
+        let cost = mesh.traversal_cost(&Vector3::new(10.3, 5.0, 20.6), &down).unwrap();
+        assert_eq!(cost.baseline, 2048);
+        assert!(cost.tests > 0 && cost.tests * 20 < cost.baseline, "{} triángulos", cost.tests);
+
+        // Un rayo que no llega a la malla no cuenta, y recorrerla cuesta solo la raíz
+        let (outside, inv_down) = (Vector3::new(50.0, 5.0, 50.0), inverse_direction(&down));
+        assert!(mesh.traversal_cost(&outside, &down).is_none());
+        let mut miss = TraversalCost::default();
+        assert!(mesh.find_hit(&outside, &down, &inv_down, f32::INFINITY, false, &mut miss).is_none());
+        assert_eq!((miss.nodes, miss.tests), (1, 0));
+    }
+
+    #[test]
+    fn hierarchy_depth_fits_the_traversal_stack() {
+        // Una tira larga de triángulos finos, el peor caso para cajas que se solapan
+        let count = 20_000;
+        let positions: Vec<Vector3> = (0..count + 2).map(|i| Vector3::new(i as f32 * 0.01, (i % 2) as f32, 0.0)).collect();
+        let triangles: Vec<[usize; 3]> = (0..count).map(|i| [i, i + 1, i + 2]).collect();
+        let mesh = TriangleMesh::new(positions, Vec::new(), Vec::new(), triangles, Material::black());
+
+        fn depth(nodes: &[BvhNode], node: usize) -> usize {
+            match nodes[node].kind {
+                BvhKind::Leaf { .. } => 1,
+                BvhKind::Inner { left, right } => 1 + depth(nodes, left).max(depth(nodes, right)),
+            }
+        }
+        // Con la mediana la profundidad es la de un árbol binario completo de count / LEAF_SIZE hojas
+        let balanced = (count as f32 / LEAF_SIZE as f32).log2().ceil() as usize + 1;
+        assert!(depth(&mesh.nodes, 0) <= balanced, "profundidad {}", depth(&mesh.nodes, 0));
+        assert!(balanced < TRAVERSAL_STACK_SIZE);
+    }
+}
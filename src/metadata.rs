@@ -0,0 +1,201 @@
+use raylib::prelude::Vector3;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::camera::Camera;
+use crate::scene::Scene;
+use crate::settings::{RenderMode, RenderSettings};
+
+// Estado con que se hizo una imagen, para poder repetirla: vista, hora del sol y muestras
+pub struct RenderMetadata<'a> {
+    pub camera: &'a Camera,
+    pub sun_angle: f32, // Radianes, 0 = mediodía (en el JSON va en grados)
+    pub samples: u32,   // Cuadros acumulados
+    pub width: u32,
+    pub height: u32,
+}
+
+// "render_0007.png" -> "render_0007.json"
+pub fn metadata_path(image_path: &str) -> String {
+    Path::new(image_path).with_extension("json").to_string_lossy().into_owned()
+}
+
+// Escribe junto a la imagen un JSON con la cámara, el sol, la resolución, las muestras y los
+// ajustes que más cambian el resultado
+pub fn save_metadata(image_path: &str, metadata: &RenderMetadata, scene: &Scene, settings: &RenderSettings) -> io::Result<()> {
+    fs::write(metadata_path(image_path), metadata_json(image_path, metadata, scene, settings))
+}
+
+// Se arma a mano: son pocos campos y todos planos. Los ángulos van en grados y un número no
+// finito se escribe como null, que JSON no admite NaN ni infinito
+fn metadata_json(image_path: &str, metadata: &RenderMetadata, scene: &Scene, settings: &RenderSettings) -> String {
+    let number = |value: f32| if value.is_finite() { value.to_string() } else { "null".to_string() };
+    let vector = |v: Vector3| format!("[{}, {}, {}]", number(v.x), number(v.y), number(v.z));
+    let image = Path::new(image_path).file_name().map_or_else(|| image_path.to_string(), |name| name.to_string_lossy().into_owned());
+    let render_mode = match settings.render_mode {
+        RenderMode::Whitted => "whitted",
+        RenderMode::PathTraced => "path_traced",
+    };
+
+    let fields = [
+        format!("\"image\": \"{}\"", image.replace('\\', "\\\\").replace('"', "\\\"")),
+        format!("\"width\": {}", metadata.width),
+        format!("\"height\": {}", metadata.height),
+        format!("\"samples\": {}", metadata.samples),
+        format!(
+            "\"camera\": {{\"eye\": {}, \"center\": {}, \"up\": {}}}",
+            vector(metadata.camera.eye), vector(metadata.camera.center), vector(metadata.camera.up)
+        ),
+        format!("\"sun_angle_deg\": {}", number(metadata.sun_angle.to_degrees())),
+        format!("\"diorama_rotation_deg\": {}", number(scene.rotation.to_degrees())),
+        format!("\"diorama_axis\": {}", vector(scene.rotation_axis)),
+        format!("\"time\": {}", number(scene.time)),
+        format!("\"render_mode\": \"{}\"", render_mode),
+        format!("\"max_depth\": {}", settings.max_depth),
+        format!("\"shadow_samples\": {}", settings.shadow_samples),
+        format!("\"aa_samples\": {}", settings.aa_samples),
+        format!("\"vertical_fov_deg\": {}", number(settings.vertical_fov.to_degrees())),
+    ];
+    format!("{{\n  {}\n}}\n", fields.join(",\n  "))
+}
+
+// Lo que se recupera de un JSON de metadatos para repetir la imagen (ver --replay)
+pub struct SavedRender {
+    pub camera: Camera,
+    pub sun_angle: f32, // Radianes
+    pub samples: u32,
+    pub width: u32,
+    pub height: u32,
+    pub render_mode: RenderMode,
+    pub max_depth: u32,
+    pub shadow_samples: u32,
+    pub aa_samples: u32,
+    pub vertical_fov: f32, // Radianes
+}
+
+impl SavedRender {
+    // Los ajustes guardados pisan los de `settings`; el resto queda como está
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        settings.render_mode = self.render_mode;
+        settings.max_depth = self.max_depth;
+        settings.shadow_samples = self.shadow_samples;
+        settings.aa_samples = self.aa_samples;
+        settings.vertical_fov = self.vertical_fov;
+    }
+}
+
+pub fn load_metadata(path: &str) -> io::Result<SavedRender> {
+    let json = fs::read_to_string(path)?;
+    parse_metadata(&json).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{}: metadatos incompletos", path)))
+}
+
+// Lee solo el formato que escribe `metadata_json`: cada clave aparece una vez, así que basta
+// con buscarla y tomar el valor que sigue. null vuelve como NaN
+fn parse_metadata(json: &str) -> Option<SavedRender> {
+    let value = |key: &str| field(json, key);
+    let number = |text: &str| if text == "null" { Some(f32::NAN) } else { text.parse::<f32>().ok() };
+    let vector = |key: &str| -> Option<Vector3> {
+        let parts = value(key)?.trim_matches(['[', ']']).split(',').map(|part| number(part.trim())).collect::<Option<Vec<f32>>>()?;
+        match parts[..] {
+            [x, y, z] => Some(Vector3::new(x, y, z)),
+            _ => None,
+        }
+    };
+
+    let render_mode = match value("render_mode")?.trim_matches('"') {
+        "whitted" => RenderMode::Whitted,
+        "path_traced" => RenderMode::PathTraced,
+        _ => return None,
+    };
+
+    Some(SavedRender {
+        camera: Camera::new(vector("eye")?, vector("center")?, vector("up")?),
+        sun_angle: number(value("sun_angle_deg")?)?.to_radians(),
+        samples: value("samples")?.parse().ok()?,
+        width: value("width")?.parse().ok()?,
+        height: value("height")?.parse().ok()?,
+        render_mode,
+        max_depth: value("max_depth")?.parse().ok()?,
+        shadow_samples: value("shadow_samples")?.parse().ok()?,
+        aa_samples: value("aa_samples")?.parse().ok()?,
+        vertical_fov: number(value("vertical_fov_deg")?)?.to_radians(),
+    })
+}
+
+fn field<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let rest = &json[json.find(&format!("\"{}\": ", key))? + key.len() + 4..];
+    let end = if rest.starts_with('[') { rest.find(']')? + 1 } else { rest.find([',', '\n', '}'])? };
+    Some(rest[..end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textures::TextureManager;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "esperaba {expected}, dio {actual}");
+    }
+
+    fn scene() -> Scene {
+        Scene::new(Vec::new(), Vec::new(), TextureManager::new())
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let camera = Camera::new(Vector3::new(3.0, 2.5, -4.0), Vector3::new(0.0, 0.5, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        let metadata = RenderMetadata { camera: &camera, sun_angle: 1.25, samples: 64, width: 640, height: 360 };
+        let json = metadata_json("out/render_0007.png", &metadata, &scene(), &RenderSettings::default());
+
+        let saved = parse_metadata(&json).expect("el JSON escrito debe poder leerse");
+        assert_eq!((saved.samples, saved.width, saved.height), (64, 640, 360));
+        assert_close(saved.sun_angle, 1.25);
+        assert_eq!(saved.camera.eye, camera.eye);
+        assert_eq!(saved.camera.center, camera.center);
+        assert!((saved.camera.up - camera.up).length() < 1e-4);
+        assert!(json.contains("\"image\": \"render_0007.png\""));
+    }
+
+    #[test]
+    fn non_finite_values_are_written_as_null() {
+        let camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+        let metadata = RenderMetadata { camera: &camera, sun_angle: f32::NAN, samples: 1, width: 8, height: 8 };
+        let mut scene = scene();
+        scene.time = f32::INFINITY;
+        let json = metadata_json("render.png", &metadata, &scene, &RenderSettings::default());
+
+        assert!(json.contains("\"sun_angle_deg\": null"));
+        assert!(json.contains("\"time\": null"));
+        assert!(!json.contains("NaN") && !json.contains("inf"));
+        assert!(parse_metadata(&json).is_some_and(|saved| saved.sun_angle.is_nan()));
+    }
+
+    #[test]
+    fn saved_sidecar_loads_back_into_the_settings() {
+        let camera = Camera::new(Vector3::new(-2.0, 4.0, 6.0), Vector3::new(0.5, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        let metadata = RenderMetadata { camera: &camera, sun_angle: -0.5, samples: 16, width: 320, height: 200 };
+        let settings = RenderSettings {
+            render_mode: RenderMode::PathTraced,
+            max_depth: 7,
+            shadow_samples: 3,
+            aa_samples: 9,
+            vertical_fov: 0.8,
+            ..RenderSettings::default()
+        };
+        let image = std::env::temp_dir().join("metadata_replay_test.png");
+        let image = image.to_str().unwrap();
+        save_metadata(image, &metadata, &scene(), &settings).unwrap();
+        let saved = load_metadata(&metadata_path(image)).unwrap();
+        std::fs::remove_file(metadata_path(image)).unwrap();
+
+        assert_eq!((saved.samples, saved.width, saved.height), (16, 320, 200));
+        assert_close(saved.sun_angle, -0.5);
+        assert_eq!(saved.camera.eye, camera.eye);
+        assert_eq!(saved.camera.center, camera.center);
+        let mut replayed = RenderSettings::default();
+        saved.apply(&mut replayed);
+        assert!(replayed.render_mode == RenderMode::PathTraced);
+        assert_eq!((replayed.max_depth, replayed.shadow_samples, replayed.aa_samples), (7, 3, 9));
+        assert_close(replayed.vertical_fov, 0.8);
+    }
+}
@@ -0,0 +1,46 @@
+use raylib::prelude::Vector3;
+
+// Valor pseudoaleatorio en [0, 1) fijo para cada punto entero de la grilla
+fn lattice(x: i32, y: i32, z: i32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(73_856_093)
+        ^ (y as u32).wrapping_mul(19_349_663)
+        ^ (z as u32).wrapping_mul(83_492_791);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    h as f32 / u32::MAX as f32
+}
+
+// Ruido de valor: interpola suavemente los valores de las ocho esquinas de la celda de `p`
+pub fn value_noise(p: Vector3) -> f32 {
+    let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, ty, tz) = (smooth(p.x - x0), smooth(p.y - y0), smooth(p.z - z0));
+    let (x, y, z) = (x0 as i32, y0 as i32, z0 as i32);
+    let mix = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let face = |dz: i32| {
+        mix(
+            mix(lattice(x, y, z + dz), lattice(x + 1, y, z + dz), tx),
+            mix(lattice(x, y + 1, z + dz), lattice(x + 1, y + 1, z + dz), tx),
+            ty,
+        )
+    };
+    mix(face(0), face(1), tz)
+}
+
+// Ruido fractal: `octaves` capas de ruido de valor, cada una al doble de frecuencia y la mitad
+// de amplitud. Devuelve un valor en [0, 1]
+pub fn fbm(p: Vector3, octaves: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 0.5;
+    let mut total = 0.0;
+    let mut point = p;
+    for _ in 0..octaves {
+        sum += value_noise(point) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        point *= 2.0;
+    }
+    sum / total
+}
@@ -0,0 +1,116 @@
+use raylib::prelude::*;
+use crate::camera::Camera;
+use crate::framebuffer::Framebuffer;
+use crate::material::vector3_to_color;
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+use crate::{rotate_around_axis, view_extent};
+
+// Aristas de una caja como pares de índices de esquina (bit 0 = x, bit 1 = y, bit 2 = z)
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1), (2, 3), (4, 5), (6, 7),
+    (0, 2), (1, 3), (4, 6), (5, 7),
+    (0, 4), (1, 5), (2, 6), (3, 7),
+];
+
+// Proyecta un punto del mundo al framebuffer; None si queda detrás de la cámara
+pub fn project(camera: &Camera, settings: &RenderSettings, point: Vector3, width: f32, height: f32) -> Option<Vector2> {
+    let relative = point - camera.eye;
+    let depth = relative.dot(camera.forward);
+    if depth <= 0.01 {
+        return None;
+    }
+
+    let (half_width, half_height) = view_extent(settings, width / height);
+    let screen_x = relative.dot(camera.right) / (depth * half_width);
+    let screen_y = relative.dot(camera.up) / (depth * half_height);
+
+    Some(Vector2::new(
+        (screen_x + 1.0) * width / 2.0,
+        (1.0 - screen_y) * height / 2.0,
+    ))
+}
+
+// Dibuja las aristas de la caja [min, max] (en el marco del diorama, girada con él)
+pub fn draw_box(framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera, settings: &RenderSettings, min: Vector3, max: Vector3, color: Color) {
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+    let corners: Vec<Option<Vector2>> = (0..8)
+        .map(|i| {
+            let corner = Vector3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            project(camera, settings, rotate_around_axis(corner, scene.rotation_axis, scene.rotation), width, height)
+        })
+        .collect();
+
+    for (a, b) in BOX_EDGES {
+        if let (Some(start), Some(end)) = (corners[a], corners[b]) {
+            framebuffer.draw_line(start, end, color);
+        }
+    }
+}
+
+// Dibuja encima de la imagen la caja de cada objeto (girada con el diorama), para revisar
+// el mallado voraz y el culling. Los objetos infinitos (el piso) no tienen caja que dibujar
+pub fn draw_bounds(framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera, settings: &RenderSettings) {
+    let finite = |v: Vector3| v.x.is_finite() && v.y.is_finite() && v.z.is_finite();
+
+    for object in scene.objects() {
+        let (min, max) = object.bounds();
+        if !finite(min) || !finite(max) {
+            continue;
+        }
+
+        let color = if object.material().is_emissive { Color::YELLOW } else { Color::LIME };
+        draw_box(framebuffer, scene, camera, settings, min, max, color);
+    }
+}
+
+// Mira de edición: una cruz con un hueco en el centro para no tapar el punto apuntado
+pub fn draw_crosshair(framebuffer: &mut Framebuffer, center: Vector2, color: Color) {
+    const GAP: f32 = 3.0;
+    const ARM: f32 = 8.0;
+    for (dx, dy) in [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)] {
+        let point = |distance: f32| Vector2::new(center.x + dx * distance, center.y + dy * distance);
+        framebuffer.draw_line(point(GAP), point(GAP + ARM), color);
+    }
+}
+
+// Ayuda para ubicar las luces: una flecha de su color hacia el centro del diorama, que es adonde
+// apunta el sol al orbitar. La esfera de cada luz la traza el render (`show_light_markers`); la
+// flecha se dibuja encima de la imagen
+pub fn draw_lights(framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera, settings: &RenderSettings) {
+    const ARROW_LENGTH: f32 = 1.0;
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+
+    for light in &scene.lights {
+        let Some(center) = project(camera, settings, light.position, width, height) else {
+            continue;
+        };
+        let color = vector3_to_color(light.color);
+
+        let to_center = -light.position;
+        if to_center.length() <= f32::EPSILON {
+            continue;
+        }
+        let tip_world = light.position + to_center.normalized() * ARROW_LENGTH.min(to_center.length());
+        if let Some(tip) = project(camera, settings, tip_world, width, height) {
+            framebuffer.draw_line(center, tip, color);
+            // Punta de la flecha: dos trazos que vuelven hacia atrás a ±30°
+            let (dx, dy) = (center.x - tip.x, center.y - tip.y);
+            let length = dx.hypot(dy);
+            if length > 1.0 {
+                let (dx, dy) = (dx / length * 8.0, dy / length * 8.0);
+                let (sin, cos) = std::f32::consts::FRAC_PI_6.sin_cos();
+                for side in [1.0, -1.0] {
+                    let head = Vector2::new(tip.x + dx * cos - dy * sin * side, tip.y + dx * sin * side + dy * cos);
+                    framebuffer.draw_line(tip, head, color);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use raylib::prelude::Vector3;
+use crate::ray_intersect::{Intersect, RayIntersect, SURFACE_EPSILON};
+use crate::material::Material;
+
+// Plano infinito que pasa por `point`, útil como piso bajo el diorama
+pub struct Plane {
+    pub point: Vector3,
+    pub normal: Vector3,
+    // La textura se repite cada tile_size unidades
+    pub tile_size: f32,
+    pub material: Material,
+}
+
+impl Plane {
+    pub fn new(point: Vector3, normal: Vector3, tile_size: f32, material: Material) -> Self {
+        Plane {
+            point,
+            normal: normal.normalized(),
+            tile_size,
+            material,
+        }
+    }
+
+    fn get_uv(&self, point: &Vector3) -> (f32, f32) {
+        let reference = if self.normal.y.abs() < 0.999 {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let tangent = reference.cross(self.normal).normalized();
+        let bitangent = self.normal.cross(tangent);
+
+        let local = (*point - self.point) / self.tile_size;
+        (local.dot(tangent).rem_euclid(1.0), local.dot(bitangent).rem_euclid(1.0))
+    }
+}
+
+impl RayIntersect for Plane {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        let denom = self.normal.dot(*ray_direction);
+        // Rayo paralelo al plano
+        if denom.abs() < 1e-6 {
+            return Intersect::empty();
+        }
+
+        let t = (self.point - *ray_origin).dot(self.normal) / denom;
+        if t <= SURFACE_EPSILON {
+            return Intersect::empty();
+        }
+
+        let point = *ray_origin + *ray_direction * t;
+        let (u, v) = self.get_uv(&point);
+
+        Intersect::new(point, self.normal, t, self.material.clone(), u, v)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.point
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        let mut min = Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        let mut max = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        // Un plano alineado a un eje es plano en ese eje e infinito en los otros dos
+        if self.normal.y == 0.0 && self.normal.z == 0.0 {
+            (min.x, max.x) = (self.point.x, self.point.x);
+        } else if self.normal.x == 0.0 && self.normal.z == 0.0 {
+            (min.y, max.y) = (self.point.y, self.point.y);
+        } else if self.normal.x == 0.0 && self.normal.y == 0.0 {
+            (min.z, max.z) = (self.point.z, self.point.z);
+        }
+        (min, max)
+    }
+}
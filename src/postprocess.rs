@@ -0,0 +1,241 @@
+use raylib::prelude::Vector3;
+use rayon::prelude::*;
+use crate::aov::AovBuffers;
+use crate::settings::RenderSettings;
+
+// Filtro bilateral conjunto: promedia vecinos con peso gaussiano por distancia,
+// pero deja de mezclar donde cambian la normal o el albedo (bordes de geometría y de textura)
+pub fn denoise(colors: &[Vector3], guides: &AovBuffers, settings: &RenderSettings) -> Vec<Vector3> {
+    let width = guides.width as i32;
+    let height = guides.height as i32;
+    let radius = settings.denoise_radius as i32;
+    let two_sigma_sq = 2.0 * settings.denoise_spatial_sigma * settings.denoise_spatial_sigma;
+
+    let mut output = vec![Vector3::zero(); colors.len()];
+    output.par_chunks_mut(width as usize).enumerate().for_each(|(y, row)| {
+        let y = y as i32;
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let x = x as i32;
+            let center = (y * width + x) as usize;
+            let center_normal = guides.normal[center];
+            let center_albedo = guides.albedo[center];
+
+            let mut sum = Vector3::zero();
+            let mut weight_sum = 0.0;
+
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let nx = x + dx;
+                    let ny = y + dy;
+                    if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                        continue;
+                    }
+                    let neighbor = (ny * width + nx) as usize;
+
+                    let normal_diff = guides.normal[neighbor] - center_normal;
+                    let albedo_diff = guides.albedo[neighbor] - center_albedo;
+
+                    let spatial = -((dx * dx + dy * dy) as f32) / two_sigma_sq;
+                    let normal_term = -normal_diff.dot(normal_diff) * settings.denoise_normal_weight;
+                    let albedo_term = -albedo_diff.dot(albedo_diff) * settings.denoise_albedo_weight;
+                    let weight = (spatial + normal_term + albedo_term).exp();
+
+                    sum += colors[neighbor] * weight;
+                    weight_sum += weight;
+                }
+            }
+
+            *pixel = sum / weight_sum;
+        }
+    });
+
+    output
+}
+
+// Contornos tipo dibujo animado: Sobel sobre la profundidad y la normal de las AOV. Un píxel es
+// borde si la profundidad inversa salta (relativa a la del vecindario, así pesa igual cerca y
+// lejos; el cielo vale 0) o si la normal cambia de golpe. Los bordes se ensanchan según
+// outline_thickness y se pintan de negro sin tocar el resto del sombreado
+pub fn outline(colors: &[Vector3], guides: &AovBuffers, settings: &RenderSettings) -> Vec<Vector3> {
+    const SOBEL: [(i32, i32, f32, f32); 8] = [
+        (-1, -1, -1.0, -1.0), (0, -1, 0.0, -2.0), (1, -1, 1.0, -1.0),
+        (-1, 0, -2.0, 0.0), (1, 0, 2.0, 0.0),
+        (-1, 1, -1.0, 1.0), (0, 1, 0.0, 2.0), (1, 1, 1.0, 1.0),
+    ];
+    let width = guides.width as i32;
+    let height = guides.height as i32;
+    let clamped = |x: i32, y: i32| (y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize;
+    let inverse_depth = |index: usize| if guides.depth[index].is_finite() && guides.depth[index] > 0.0 { 1.0 / guides.depth[index] } else { 0.0 };
+
+    let edges: Vec<bool> = (0..width * height).into_par_iter().map(|index| {
+        let (x, y) = (index % width, index / width);
+        let (mut depth_x, mut depth_y, mut nearest) = (0.0, 0.0, inverse_depth(index as usize));
+        let (mut normal_x, mut normal_y) = (Vector3::zero(), Vector3::zero());
+        for (dx, dy, weight_x, weight_y) in SOBEL {
+            let neighbor = clamped(x + dx, y + dy);
+            let depth = inverse_depth(neighbor);
+            depth_x += depth * weight_x;
+            depth_y += depth * weight_y;
+            nearest = f32::max(nearest, depth);
+            normal_x += guides.normal[neighbor] * weight_x;
+            normal_y += guides.normal[neighbor] * weight_y;
+        }
+        let depth_edge = if nearest > 0.0 { depth_x.hypot(depth_y) / nearest } else { 0.0 };
+        let normal_edge = (normal_x.dot(normal_x) + normal_y.dot(normal_y)).sqrt();
+        depth_edge > settings.outline_depth_threshold || normal_edge > settings.outline_normal_threshold
+    }).collect();
+
+    let reach = settings.outline_thickness.saturating_sub(1) as i32;
+    let mut output = colors.to_vec();
+    output.par_chunks_mut(width as usize).enumerate().for_each(|(y, row)| {
+        let y = y as i32;
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let x = x as i32;
+            let near_edge = (-reach..=reach).any(|dy| (-reach..=reach).any(|dx| edges[clamped(x + dx, y + dy)]));
+            if near_edge {
+                *pixel = Vector3::zero();
+            }
+        }
+    });
+
+    output
+}
+
+// Desenfoque gaussiano separable sobre un buffer HDR de width x height: una pasada horizontal
+// y una vertical con el mismo kernel 1D (sigma = radius / 2). Fuera de la imagen se repite
+// el píxel del borde
+pub fn blur_gaussian(buffer: &[Vector3], width: usize, height: usize, radius: u32) -> Vec<Vector3> {
+    if radius == 0 {
+        return buffer.to_vec();
+    }
+    let radius = radius as i32;
+    let sigma = radius as f32 / 2.0;
+    let kernel: Vec<f32> = (-radius..=radius).map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp()).collect();
+    let kernel_sum: f32 = kernel.iter().sum();
+    let kernel: Vec<f32> = kernel.iter().map(|w| w / kernel_sum).collect();
+
+    let mut horizontal = vec![Vector3::zero(); buffer.len()];
+    horizontal.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let mut sum = Vector3::zero();
+            for (k, weight) in kernel.iter().enumerate() {
+                let nx = (x as i32 + k as i32 - radius).clamp(0, width as i32 - 1) as usize;
+                sum += buffer[y * width + nx] * *weight;
+            }
+            *pixel = sum;
+        }
+    });
+
+    let mut output = vec![Vector3::zero(); buffer.len()];
+    output.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let mut sum = Vector3::zero();
+            for (k, weight) in kernel.iter().enumerate() {
+                let ny = (y as i32 + k as i32 - radius).clamp(0, height as i32 - 1) as usize;
+                sum += horizontal[ny * width + x] * *weight;
+            }
+            *pixel = sum;
+        }
+    });
+
+    output
+}
+
+// Resplandor: lo que supera bloom_threshold se desenfoca y se suma encima de la imagen. El umbral
+// es de luminancia lineal y se compara con el color HDR antes de recortarlo a 8 bits; sobre el
+// color ya cuantizado todo lo más brillante que el blanco quedaría en 1 y no se distinguiría
+pub fn bloom(colors: &[Vector3], width: usize, settings: &RenderSettings) -> Vec<Vector3> {
+    let threshold = settings.bloom_threshold;
+    // Se conserva el tono: del píxel pasa la fracción de su luminancia que supera el umbral
+    let bright: Vec<Vector3> = colors
+        .iter()
+        .map(|c| {
+            let luminance = luminance(*c);
+            if luminance > threshold { *c * ((luminance - threshold) / luminance) } else { Vector3::zero() }
+        })
+        .collect();
+    let glow = blur_gaussian(&bright, width, colors.len() / width, settings.bloom_radius);
+
+    colors.iter().zip(glow).map(|(c, g)| *c + g * settings.bloom_strength).collect()
+}
+
+// Luminancia relativa (Rec. 709) de un color lineal
+pub fn luminance(color: Vector3) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+pub fn is_grading_neutral(settings: &RenderSettings) -> bool {
+    settings.vignette_strength == 0.0
+        && settings.grade_lift == Vector3::zero()
+        && settings.grade_gamma == Vector3::one()
+        && settings.grade_gain == Vector3::one()
+}
+
+// Viñeta radial y corrección lift/gamma/gain por canal sobre el color HDR, antes de pasarlo a 8 bits.
+// lift levanta las sombras, gain escala las luces y gamma curva los medios tonos
+pub fn color_grade(colors: &[Vector3], width: usize, settings: &RenderSettings) -> Vec<Vector3> {
+    let height = colors.len() / width;
+    let center_x = width as f32 / 2.0;
+    let center_y = height as f32 / 2.0;
+    let max_distance_sq = center_x * center_x + center_y * center_y;
+    let lift = settings.grade_lift;
+    let gain = settings.grade_gain;
+    let inverse_gamma = Vector3::new(1.0 / settings.grade_gamma.x, 1.0 / settings.grade_gamma.y, 1.0 / settings.grade_gamma.z);
+
+    let mut output = colors.to_vec();
+    output.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let dx = x as f32 + 0.5 - center_x;
+            let dy = y as f32 + 0.5 - center_y;
+            // 0 en el centro, 1 en las esquinas
+            let distance_sq = (dx * dx + dy * dy) / max_distance_sq;
+            let vignette = (1.0 - settings.vignette_strength * distance_sq).max(0.0);
+
+            let c = *pixel;
+            let graded = (c + lift * (Vector3::one() - c)) * gain;
+            *pixel = Vector3::new(
+                graded.x.max(0.0).powf(inverse_gamma.x),
+                graded.y.max(0.0).powf(inverse_gamma.y),
+                graded.z.max(0.0).powf(inverse_gamma.z),
+            ) * vignette;
+        }
+    });
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_bright_pixel_spreads_over_the_kernel_footprint() {
+        let (size, radius) = (11usize, 2usize);
+        let mut buffer = vec![Vector3::zero(); size * size];
+        buffer[5 * size + 5] = Vector3::one();
+        let blurred = blur_gaussian(&buffer, size, size, radius as u32);
+
+        for y in 0..size {
+            for x in 0..size {
+                let inside = x.abs_diff(5) <= radius && y.abs_diff(5) <= radius;
+                assert_eq!(blurred[y * size + x].x > 0.0, inside, "píxel ({x}, {y})");
+            }
+        }
+        // Lejos de los bordes no se pierde ni se gana energía, y el kernel es simétrico
+        let total: f32 = blurred.iter().map(|pixel| pixel.x).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+        assert_eq!(blurred[5 * size + 3], blurred[5 * size + 7]);
+        assert_eq!(blurred[3 * size + 5], blurred[5 * size + 3]);
+        assert!(blurred[5 * size + 5].x > blurred[5 * size + 4].x);
+    }
+
+    #[test]
+    fn edges_repeat_the_border_pixel() {
+        // Con bordes repetidos una imagen uniforme no se oscurece en los costados
+        let buffer = vec![Vector3::new(0.2, 0.4, 0.8); 7 * 4];
+        for pixel in blur_gaussian(&buffer, 7, 4, 3) {
+            assert!((pixel - Vector3::new(0.2, 0.4, 0.8)).length() < 1e-5);
+        }
+        assert_eq!(blur_gaussian(&buffer, 7, 4, 0), buffer);
+    }
+}
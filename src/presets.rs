@@ -0,0 +1,61 @@
+use raylib::prelude::Vector3;
+use crate::material::Material;
+
+// Materiales de uso común ya ajustados. Para variarlos (una textura, otro tinte) se parte del
+// preset con `into_builder` y se cambia solo lo necesario
+
+// Mate: casi todo difuso, con un brillo leve y ancho
+pub fn matte(color: Vector3) -> Material {
+    Material::builder()
+        .diffuse(color)
+        .specular(10.0)
+        .albedo([0.9, 0.1, 0.0, 0.0])
+        .build()
+}
+
+// Plástico pulido: difuso con un brillo blanco y concentrado
+pub fn plastic(color: Vector3) -> Material {
+    Material::builder()
+        .diffuse(color)
+        .specular(60.0)
+        .albedo([0.7, 0.3, 0.0, 0.0])
+        .build()
+}
+
+// Metal pulido: el brillo toma el color del metal (oro, cobre) en vez de quedar blanco
+pub fn metal(color: Vector3) -> Material {
+    Material::builder()
+        .diffuse(color * 0.55)
+        .specular(60.0)
+        .albedo([0.4, 0.6, 0.0, 0.0])
+        .specular_color(color)
+        .build()
+}
+
+// Vidrio incoloro con el índice de refracción dado (IOR_GLASS, IOR_WATER, IOR_DIAMOND)
+pub fn glass(refractive_index: f32) -> Material {
+    Material::builder()
+        .diffuse(Vector3::one())
+        .specular(125.0)
+        .albedo([0.0, 0.3, 0.2, 0.8])
+        .refractive_index(refractive_index)
+        .build()
+}
+
+pub fn mirror() -> Material {
+    Material::builder()
+        .diffuse(Vector3::new(0.8, 0.8, 0.85))
+        .specular(200.0)
+        .albedo([0.05, 0.1, 0.85, 0.0])
+        .build()
+}
+
+// Fuente de luz: se ve del color que emite e ilumina a su alrededor con esa intensidad
+pub fn lamp(color: Vector3, intensity: f32) -> Material {
+    Material::builder()
+        .diffuse(color)
+        .specular(30.0)
+        .albedo([0.7, 0.3, 0.0, 0.0])
+        .emissive(color, intensity)
+        .build()
+}
@@ -0,0 +1,128 @@
+use raylib::prelude::Vector3;
+use rand::Rng;
+use rand::rngs::SmallRng;
+use crate::ray_intersect::{Intersect, RayIntersect, SURFACE_EPSILON};
+use crate::material::Material;
+
+// Paralelogramo con una esquina en `origin` y lados `edge1` y `edge2` (marcos, mesas, paneles de luz)
+pub struct Quad {
+    pub origin: Vector3,
+    pub edge1: Vector3,
+    pub edge2: Vector3,
+    pub material: Material,
+    normal: Vector3,
+    // n / (n · n) con n = edge1 × edge2: lleva un punto del plano a sus coordenadas sobre los lados
+    dual: Vector3,
+}
+
+impl Quad {
+    pub fn new(origin: Vector3, edge1: Vector3, edge2: Vector3, material: Material) -> Self {
+        let n = edge1.cross(edge2);
+        Quad {
+            origin,
+            edge1,
+            edge2,
+            material,
+            normal: n.normalized(),
+            dual: n / n.dot(n),
+        }
+    }
+}
+
+impl RayIntersect for Quad {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        let denom = self.normal.dot(*ray_direction);
+        // Rayo paralelo al quad
+        if denom.abs() < 1e-6 {
+            return Intersect::empty();
+        }
+
+        let t = (self.origin - *ray_origin).dot(self.normal) / denom;
+        if t <= SURFACE_EPSILON {
+            return Intersect::empty();
+        }
+
+        // Coordenadas del punto a lo largo de cada lado: dentro del quad ambas van de 0 a 1
+        let point = *ray_origin + *ray_direction * t;
+        let local = point - self.origin;
+        let alpha = self.dual.dot(local.cross(self.edge2));
+        let beta = self.dual.dot(self.edge1.cross(local));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return Intersect::empty();
+        }
+
+        Intersect::new(point, self.normal, t, self.material.clone(), alpha, beta)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.origin + (self.edge1 + self.edge2) / 2.0
+    }
+
+    // Con material emisivo el quad es una luz de área: cada muestra sale de un punto distinto
+    fn sample_emitter(&self, rng: &mut SmallRng) -> Vector3 {
+        self.origin + self.edge1 * rng.random::<f32>() + self.edge2 * rng.random::<f32>()
+    }
+
+    // Área del paralelogramo reducida por el ángulo con que se lo ve (emite por ambas caras)
+    fn emitting_area(&self, direction: &Vector3) -> f32 {
+        self.edge1.cross(self.edge2).length() * self.normal.dot(*direction).abs()
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        let corners = [self.origin, self.origin + self.edge1, self.origin + self.edge2, self.origin + self.edge1 + self.edge2];
+        let mut min = corners[0];
+        let mut max = corners[0];
+        for corner in &corners[1..] {
+            min = Vector3::new(min.x.min(corner.x), min.y.min(corner.y), min.z.min(corner.z));
+            max = Vector3::new(max.x.max(corner.x), max.y.max(corner.y), max.z.max(corner.z));
+        }
+        (min, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    // Paralelogramo inclinado en el plano z = 0
+    fn quad() -> Quad {
+        Quad::new(Vector3::zero(), Vector3::new(2.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 0.0), Material::black())
+    }
+
+    #[test]
+    fn hit_coordinates_follow_the_edges() {
+        let hit = quad().ray_intersect(&Vector3::new(1.5, 0.5, 3.0), &Vector3::new(0.0, 0.0, -1.0));
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 3.0).abs() < 1e-5);
+        assert!((hit.u - 0.5).abs() < 1e-5 && (hit.v - 0.5).abs() < 1e-5);
+        assert_eq!(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn misses_outside_the_parallelogram() {
+        let toward = Vector3::new(0.0, 0.0, -1.0);
+        // Dentro de la caja pero fuera del lado inclinado
+        assert!(!quad().ray_intersect(&Vector3::new(0.2, 0.8, 3.0), &toward).is_intersecting);
+        assert!(!quad().ray_intersect(&Vector3::new(3.5, 0.5, 3.0), &toward).is_intersecting);
+        assert!(!quad().ray_intersect(&Vector3::new(1.0, 0.5, 3.0), &Vector3::new(1.0, 0.0, 0.0)).is_intersecting);
+    }
+
+    #[test]
+    fn bounds_and_emitter_samples_cover_the_corners() {
+        let quad = quad();
+        let (min, max) = quad.bounds();
+        assert_eq!((min, max), (Vector3::zero(), Vector3::new(3.0, 1.0, 0.0)));
+        let mut rng = SmallRng::seed_from_u64(7);
+        for _ in 0..32 {
+            let point = quad.sample_emitter(&mut rng);
+            let hit = quad.ray_intersect(&(point + Vector3::new(0.0, 0.0, 1.0)), &Vector3::new(0.0, 0.0, -1.0));
+            assert!(hit.is_intersecting);
+        }
+        assert!((quad.emitting_area(&Vector3::new(0.0, 0.0, -1.0)) - 2.0).abs() < 1e-5);
+    }
+}
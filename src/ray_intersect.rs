@@ -1,15 +1,26 @@
-use raylib::prelude::Vector3;
+use raylib::prelude::{Matrix, Vector3};
+use rand::rngs::SmallRng;
+use crate::aabb::Aabb;
 use crate::material::Material;
 
 #[derive(Clone)]
 pub struct Intersect {
     pub point: Vector3,
     pub normal: Vector3,
+    // Normal de la cara sin suavizar (en las mallas, la del triángulo): decide cuál es la cara trasera
+    pub face_normal: Vector3,
     pub distance: f32,
     pub is_intersecting: bool,
     pub material: Material,
+    // Material que pone quien envuelve la geometría (una instancia); gana sobre `material`
+    pub material_override: Option<Material>,
     pub u: f32,
     pub v: f32,
+    // Cuánto abarca en UV el píxel que vio este impacto (0 = sin estimar, textura a resolución completa)
+    pub uv_footprint: f32,
+    // Inversa de la transformación de la instancia que envuelve la geometría (mundo -> objeto):
+    // las normales en espacio de objeto pasan al mundo con su transpuesta
+    pub object_inverse: Option<Matrix>,
 }
 
 impl Intersect {
@@ -24,11 +35,15 @@ impl Intersect {
         Intersect {
             point,
             normal,
+            face_normal: normal,
             distance,
             is_intersecting: true,
             material,
+            material_override: None,
             u,
             v,
+            uv_footprint: 0.0,
+            object_inverse: None,
         }
     }
 
@@ -36,15 +51,87 @@ impl Intersect {
         Intersect {
             point: Vector3::zero(),
             normal: Vector3::zero(),
+            face_normal: Vector3::zero(),
             distance: 0.0,
             is_intersecting: false,
             material: Material::black(),
+            material_override: None,
             u: 0.0,
             v: 0.0,
+            uv_footprint: 0.0,
+            object_inverse: None,
         }
     }
+
+    // Material con el que se sombrea el impacto
+    pub fn effective_material(&self) -> &Material {
+        self.material_override.as_ref().unwrap_or(&self.material)
+    }
+}
+
+// 1/dirección por eje, calculada una vez por rayo. Una componente nula da ±infinito, y el slab
+// test lo tolera porque min/max descartan el NaN que sale de 0·infinito
+pub fn inverse_direction(ray_direction: &Vector3) -> Vector3 {
+    Vector3::new(1.0 / ray_direction.x, 1.0 / ray_direction.y, 1.0 / ray_direction.z)
+}
+
+// Distancia mínima de un impacto. Uno más cercano es la superficie sobre la que está apoyado el
+// origen (la cámara sobre una cara, un rebote sin sesgo): se ignora siempre, así no depende del
+// redondeo si el rayo la ve o no
+pub const SURFACE_EPSILON: f32 = 1e-5;
+
+// Trabajo de un rayo dentro de una jerarquía de cajas: nodos visitados, triángulos probados y
+// los que habría probado recorriéndolos uno por uno (`baseline`)
+#[derive(Clone, Copy, Default)]
+pub struct TraversalCost {
+    pub nodes: u32,
+    pub tests: u32,
+    pub baseline: u32,
 }
 
+// Todo lo que puede estar en la escena. Se usa como objeto de trait (SceneObject, que además
+// pide Send + Sync para trazar en paralelo), así que una primitiva propia solo implementa esto y
+// se agrega con Box::new. Hay que implementar ray_intersect, material, center y bounds; lo demás
+// tiene una versión por defecto correcta, solo más lenta o más gruesa. Un impacto válido tiene
+// distancia mayor que SURFACE_EPSILON, normal unitaria hacia afuera y UV en [0, 1] si hay textura.
+// Si ilumina a otros lo decide material().is_emissive; `bounds` puede ser infinita en un eje
+// (planos), pero entonces el objeto no entra en los recortes por caja
 pub trait RayIntersect {
+    // Impacto más cercano del rayo, o Intersect::empty()
     fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect;
+    // Igual que ray_intersect, con la inversa de la dirección ya calculada por quien lanza el rayo;
+    // solo la aprovechan las cajas
+    fn ray_intersect_inverse(&self, ray_origin: &Vector3, ray_direction: &Vector3, _inv_direction: &Vector3) -> Intersect {
+        self.ray_intersect(ray_origin, ray_direction)
+    }
+    // Solo si el rayo toca el objeto antes de max_distance, sin armar el Intersect (para sombras)
+    fn any_hit(&self, ray_origin: &Vector3, ray_direction: &Vector3, inv_direction: &Vector3, max_distance: f32) -> bool {
+        let intersect = self.ray_intersect_inverse(ray_origin, ray_direction, inv_direction);
+        intersect.is_intersecting && intersect.distance < max_distance
+    }
+    fn material(&self) -> &Material;
+    // Punto desde el que ilumina el objeto si es emisivo
+    fn center(&self) -> Vector3;
+    // Punto de la superficie emisora hacia el que se lanza una muestra de luz. Por defecto
+    // el centro (fuente puntual); los paneles eligen uno al azar y dan penumbra suave
+    fn sample_emitter(&self, _rng: &mut SmallRng) -> Vector3 {
+        self.center()
+    }
+    // Área que la fuente muestra hacia `direction` (que apunta hacia afuera de ella). Por
+    // defecto la de su caja promediada en todas las direcciones: un cuarto de su superficie
+    fn emitting_area(&self, _direction: &Vector3) -> f32 {
+        let (min, max) = self.bounds();
+        let size = max - min;
+        (size.x * size.y + size.y * size.z + size.x * size.z) / 2.0
+    }
+    // Esquinas mínima y máxima de la caja que lo contiene (infinita en los ejes sin límite)
+    fn bounds(&self) -> (Vector3, Vector3);
+    fn bounding_box(&self) -> Aabb {
+        let (min, max) = self.bounds();
+        Aabb::new(min, max)
+    }
+    // Costo de buscar el impacto más cercano; solo lo miden las geometrías con jerarquía propia
+    fn traversal_cost(&self, _ray_origin: &Vector3, _ray_direction: &Vector3) -> Option<TraversalCost> {
+        None
+    }
 }
@@ -0,0 +1,145 @@
+use raylib::prelude::Vector3;
+use crate::aabb::Aabb;
+use crate::caustics::CausticMap;
+use crate::light::Light;
+use crate::shadow_map::ShadowMap;
+use crate::ray_intersect::{inverse_direction, RayIntersect};
+use crate::textures::TextureManager;
+
+pub type SceneObject = Box<dyn RayIntersect + Send + Sync>;
+
+// Todo lo que un rayo necesita consultar: objetos, luces y texturas
+pub struct Scene {
+    objects: Vec<SceneObject>,
+    // Índices de los objetos que pueden verse desde la cámara (ver `cull`)
+    visible: Vec<usize>,
+    pub lights: Vec<Light>,
+    pub texture_manager: TextureManager,
+    // Giro del diorama alrededor de `rotation_axis` (radianes; el eje es unitario y por defecto
+    // el Y). Los objetos no se mueven: los rayos y la luz se giran en sentido contrario hacia el
+    // marco del diorama
+    pub rotation: f32,
+    pub rotation_axis: Vector3,
+    // Giro durante el último cuadro, para el desenfoque de movimiento
+    pub rotation_speed: f32,
+    // Cuadros transcurridos, para los materiales animados (ondas del agua)
+    pub time: f32,
+    // Cáusticas precalculadas del sol (None = desactivadas)
+    pub caustics: Option<CausticMap>,
+    // Mapa de sombras del sol (None = sombras con rayos)
+    pub shadow_map: Option<ShadowMap>,
+    // Caja que envuelve todos los objetos; se recalcula al cambiar los objetos
+    bounds: Aabb,
+}
+
+impl Scene {
+    pub fn new(objects: Vec<SceneObject>, lights: Vec<Light>, texture_manager: TextureManager) -> Self {
+        let mut scene = Scene {
+            objects: Vec::new(),
+            visible: Vec::new(),
+            lights,
+            texture_manager,
+            rotation: 0.0,
+            rotation_axis: Vector3::new(0.0, 1.0, 0.0),
+            rotation_speed: 0.0,
+            time: 0.0,
+            caustics: None,
+            shadow_map: None,
+            bounds: Aabb::empty(),
+        };
+        scene.set_objects(objects);
+        scene
+    }
+
+    pub fn objects(&self) -> &[SceneObject] {
+        &self.objects
+    }
+
+    pub fn set_objects(&mut self, objects: Vec<SceneObject>) {
+        self.objects = objects;
+        self.show_all();
+        self.bounds = self.objects.iter().fold(Aabb::empty(), |bounds, object| bounds.union(&object.bounding_box()));
+    }
+
+    // Objetos que pueden tocar los rayos primarios. Sombras y reflejos usan `objects`,
+    // así lo que queda fuera de cuadro sigue apareciendo en espejos y proyectando sombra.
+    // Cada objeto viene con su índice en `objects`
+    pub fn primary_objects(&self) -> impl Iterator<Item = (usize, &SceneObject)> {
+        self.visible.iter().map(|&index| (index, &self.objects[index]))
+    }
+
+    pub fn show_all(&mut self) {
+        self.visible = (0..self.objects.len()).collect();
+    }
+
+    // Descarta los objetos cuya caja queda entera del lado exterior de algún plano.
+    // Cada plano pasa por `eye` y su normal apunta hacia adentro del frustum
+    pub fn cull(&mut self, eye: Vector3, plane_normals: &[Vector3]) {
+        let objects = &self.objects;
+        self.visible = (0..objects.len())
+            .filter(|&index| {
+                let (min, max) = objects[index].bounds();
+                plane_normals.iter().all(|normal| {
+                    // Distancia con signo de la esquina de la caja más adentro según este plano;
+                    // los ejes que el plano no mira no aportan (la caja puede ser infinita ahí)
+                    let axis_distance = |n: f32, low: f32, high: f32, eye: f32| {
+                        if n > 0.0 {
+                            n * (high - eye)
+                        } else if n < 0.0 {
+                            n * (low - eye)
+                        } else {
+                            0.0
+                        }
+                    };
+                    axis_distance(normal.x, min.x, max.x, eye.x)
+                        + axis_distance(normal.y, min.y, max.y, eye.y)
+                        + axis_distance(normal.z, min.z, max.z, eye.z)
+                        >= 0.0
+                })
+            })
+            .collect();
+    }
+
+    // Hay algún material que cambia con el tiempo: la imagen no puede acumularse
+    pub fn is_animated(&self) -> bool {
+        self.objects.iter().any(|object| object.material().ripple > 0.0 || object.material().emission_animation.is_some())
+    }
+
+    // Giro del diorama en el instante `time` del obturador (en cuadros):
+    // 0 es la posición actual y 1 la del cuadro anterior
+    pub fn rotation_at(&self, time: f32) -> f32 {
+        self.rotation - self.rotation_speed * time
+    }
+
+    // Prueba rápida (slab) contra la caja de toda la escena: si falla, el rayo solo ve el cielo
+    pub fn ray_hits_bounds(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> bool {
+        self.bounds.intersect(ray_origin, &inverse_direction(ray_direction)).is_some()
+    }
+
+    // Igual que ray_hits_bounds para varios rayos con el mismo origen a la vez
+    pub fn packet_hits_bounds<const N: usize>(&self, ray_origin: &Vector3, ray_directions: &[Vector3; N]) -> [bool; N] {
+        self.bounds.intersect_packet(ray_origin, &ray_directions.map(|direction| inverse_direction(&direction)))
+    }
+
+    // Texturas que usan los materiales de los objetos, sin repetir: (ruta, si es de color sRGB)
+    pub fn texture_paths(&self) -> Vec<(String, bool)> {
+        let mut paths: Vec<(String, bool)> = Vec::new();
+        for object in &self.objects {
+            let material = object.material();
+            for path in [&material.texture_id, &material.emission_map_id].into_iter().flatten() {
+                paths.push((path.clone(), true));
+            }
+            if let Some(path) = &material.normal_map_id {
+                paths.push((path.clone(), false));
+            }
+        }
+        paths.sort();
+        paths.dedup_by(|a, b| a.0 == b.0);
+        paths
+    }
+
+    // Precargar en paralelo las texturas usadas por los objetos (el resto se carga al usarse)
+    pub fn preload_textures(&self) {
+        self.texture_manager.load_all(&self.texture_paths());
+    }
+}
@@ -0,0 +1,97 @@
+use raylib::prelude::Matrix;
+use crate::instance::{Instance, SharedGeometry};
+use crate::scene::SceneObject;
+
+// Grupo de objetos con transformación propia (local -> padre): mover o girar un nodo mueve todo
+// lo que cuelga de él. Al armar la escena se aplana en instancias con la transformación ya
+// compuesta, así el trazado no recorre el árbol y nada se reconstruye por cuadro
+pub struct SceneNode {
+    pub transform: Matrix,
+    pub geometry: Vec<SharedGeometry>,
+    pub children: Vec<SceneNode>,
+}
+
+impl SceneNode {
+    pub fn new(transform: Matrix) -> Self {
+        SceneNode { transform, geometry: Vec::new(), children: Vec::new() }
+    }
+
+    // Geometría colocada con `transform` dentro del nodo
+    pub fn with_geometry(mut self, geometry: SharedGeometry, transform: Matrix) -> Self {
+        self.children.push(SceneNode { transform, geometry: vec![geometry], children: Vec::new() });
+        self
+    }
+
+    pub fn with_child(mut self, child: SceneNode) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    // Objetos del subárbol en el marco de `parent`. Las matrices se aplican de izquierda a
+    // derecha, así que la del hijo va antes que la del padre
+    pub fn flatten(&self, parent: Matrix) -> Vec<SceneObject> {
+        let world = self.transform * parent;
+        let mut objects: Vec<SceneObject> = self.geometry.iter()
+            .map(|geometry| Box::new(Instance::new(geometry.clone(), world, None)) as SceneObject)
+            .collect();
+        for child in &self.children {
+            objects.extend(child.flatten(world));
+        }
+        objects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raylib::prelude::Vector3;
+    use std::f32::consts::FRAC_PI_2;
+    use std::sync::Arc;
+    use crate::cube::Cube;
+    use crate::material::Material;
+
+    fn cube() -> SharedGeometry {
+        Arc::new(Cube::new(Vector3::zero(), 1.0, Material::black()))
+    }
+
+    fn centers(node: &SceneNode) -> Vec<Vector3> {
+        node.flatten(Matrix::identity()).iter().map(|object| object.center()).collect()
+    }
+
+    fn assert_centers(actual: Vec<Vector3>, expected: &[Vector3]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((*a - *e).length() < 1e-5, "esperaba {expected:?}, dio {actual:?}");
+        }
+    }
+
+    // Un cubo en el nodo raíz y otro dos niveles más abajo, corrido (1, 2, 0) de la raíz
+    fn tree(transform: Matrix) -> SceneNode {
+        let branch = SceneNode::new(Matrix::translate(0.0, 2.0, 0.0)).with_geometry(cube(), Matrix::translate(1.0, 0.0, 0.0));
+        SceneNode::new(transform).with_geometry(cube(), Matrix::identity()).with_child(branch)
+    }
+
+    #[test]
+    fn moving_a_parent_moves_its_whole_subtree() {
+        assert_centers(centers(&tree(Matrix::identity())), &[Vector3::zero(), Vector3::new(1.0, 2.0, 0.0)]);
+        assert_centers(
+            centers(&tree(Matrix::translate(5.0, 0.0, -3.0))),
+            &[Vector3::new(5.0, 0.0, -3.0), Vector3::new(6.0, 2.0, -3.0)],
+        );
+        // Girar la raíz un cuarto de vuelta sobre Y lleva el cubo de abajo de +X a -Z
+        assert_centers(centers(&tree(Matrix::rotate_y(FRAC_PI_2))), &[Vector3::zero(), Vector3::new(0.0, 2.0, -1.0)]);
+    }
+
+    #[test]
+    fn nested_transforms_apply_the_child_before_the_parent() {
+        // El corrimiento del hijo se gira y se escala con el padre; en el otro orden el cubo
+        // quedaría en (1, 0, 0) en los dos casos
+        let nested = |parent: Matrix| SceneNode::new(parent).with_child(SceneNode::new(Matrix::translate(1.0, 0.0, 0.0)).with_geometry(cube(), Matrix::identity()));
+        assert_centers(centers(&nested(Matrix::rotate_y(FRAC_PI_2))), &[Vector3::new(0.0, 0.0, -1.0)]);
+        assert_centers(centers(&nested(Matrix::scale(2.0, 2.0, 2.0))), &[Vector3::new(2.0, 0.0, 0.0)]);
+        // El padre gira primero y después se corre, como el banquito: la pieza queda corrida
+        // desde el lugar del padre y no orbita alrededor del origen
+        let placed = nested(Matrix::rotate_y(FRAC_PI_2) * Matrix::translate(10.0, 0.0, 0.0));
+        assert_centers(centers(&placed), &[Vector3::new(10.0, 0.0, -1.0)]);
+    }
+}
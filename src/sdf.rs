@@ -0,0 +1,189 @@
+use raylib::prelude::Vector3;
+use crate::material::Material;
+use crate::ray_intersect::{Intersect, RayIntersect, SURFACE_EPSILON};
+
+const MAX_STEPS: u32 = 128;
+const NORMAL_EPSILON: f32 = 1e-3;
+
+// Forma definida por su distancia con signo (negativa adentro). Para avanzar por esferas la
+// distancia no puede pasarse de la real; las uniones suaves la subestiman un poco, y eso sirve
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: Vector3) -> f32;
+    // Caja que envuelve la forma, para recortar el rayo antes de avanzar
+    fn bounds(&self) -> (Vector3, Vector3);
+}
+
+pub struct SdfSphere {
+    pub center: Vector3,
+    pub radius: f32,
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Vector3) -> f32 {
+        (p - self.center).length() - self.radius
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        let r = Vector3::new(self.radius, self.radius, self.radius);
+        (self.center - r, self.center + r)
+    }
+}
+
+// Caja con las aristas redondeadas por `rounding` (0 = aristas vivas)
+pub struct SdfBox {
+    pub center: Vector3,
+    pub half_size: Vector3,
+    pub rounding: f32,
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: Vector3) -> f32 {
+        let local = p - self.center;
+        let inner = self.half_size - Vector3::new(self.rounding, self.rounding, self.rounding);
+        let q = Vector3::new(local.x.abs() - inner.x, local.y.abs() - inner.y, local.z.abs() - inner.z);
+        let outside = Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+        let inside = q.x.max(q.y).max(q.z).min(0.0);
+        outside + inside - self.rounding
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        (self.center - self.half_size, self.center + self.half_size)
+    }
+}
+
+// Unión que funde las formas en una franja de ancho `k` (metaballs, vóxeles redondeados)
+pub struct SmoothUnion {
+    pub shapes: Vec<Box<dyn Sdf>>,
+    pub k: f32,
+}
+
+pub fn smooth_union(shapes: Vec<Box<dyn Sdf>>, k: f32) -> SmoothUnion {
+    SmoothUnion { shapes, k }
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Vector3) -> f32 {
+        let mut distances = self.shapes.iter().map(|shape| shape.distance(p));
+        let Some(first) = distances.next() else {
+            return f32::INFINITY;
+        };
+        // Mínimo suave polinómico, aplicado de a pares
+        distances.fold(first, |a, b| {
+            let h = (0.5 + 0.5 * (b - a) / self.k).clamp(0.0, 1.0);
+            b + (a - b) * h - self.k * h * (1.0 - h)
+        })
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        let mut min = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = -min;
+        for shape in &self.shapes {
+            let (shape_min, shape_max) = shape.bounds();
+            min = Vector3::new(min.x.min(shape_min.x), min.y.min(shape_min.y), min.z.min(shape_min.z));
+            max = Vector3::new(max.x.max(shape_max.x), max.y.max(shape_max.y), max.z.max(shape_max.z));
+        }
+        // La unión suave puede abultarse un poco entre formas vecinas
+        let k = Vector3::new(self.k, self.k, self.k);
+        (min - k, max + k)
+    }
+}
+
+// Distancia a lo largo del rayo hasta la superficie, avanzando por esferas entre t_min y t_max.
+// Si el origen está adentro, busca la salida
+pub fn march_ray(sdf: &dyn Sdf, origin: Vector3, direction: Vector3, t_min: f32, t_max: f32) -> Option<f32> {
+    let mut t = t_min;
+    let inside = sdf.distance(origin + direction * t) < 0.0;
+    for _ in 0..MAX_STEPS {
+        let d = sdf.distance(origin + direction * t);
+        let d = if inside { -d } else { d };
+        if d < SURFACE_EPSILON {
+            return Some(t);
+        }
+        t += d;
+        if t > t_max {
+            return None;
+        }
+    }
+    None
+}
+
+// Normal como gradiente de la distancia (diferencias centrales)
+pub fn estimate_normal(sdf: &dyn Sdf, p: Vector3) -> Vector3 {
+    let dx = Vector3::new(NORMAL_EPSILON, 0.0, 0.0);
+    let dy = Vector3::new(0.0, NORMAL_EPSILON, 0.0);
+    let dz = Vector3::new(0.0, 0.0, NORMAL_EPSILON);
+    Vector3::new(
+        sdf.distance(p + dx) - sdf.distance(p - dx),
+        sdf.distance(p + dy) - sdf.distance(p - dy),
+        sdf.distance(p + dz) - sdf.distance(p - dz),
+    )
+    .normalized()
+}
+
+// Una forma SDF con material, para mezclarla con los objetos analíticos: el resto del
+// sombreado (luces, reflejos, refracción) no nota la diferencia
+pub struct SdfObject {
+    pub sdf: Box<dyn Sdf>,
+    pub material: Material,
+    // La textura se repite cada uv_scale unidades, proyectada según el eje dominante de la normal
+    pub uv_scale: f32,
+    bounds_min: Vector3,
+    bounds_max: Vector3,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Box<dyn Sdf>, material: Material, uv_scale: f32) -> Self {
+        let (bounds_min, bounds_max) = sdf.bounds();
+        SdfObject { sdf, material, uv_scale, bounds_min, bounds_max }
+    }
+
+    fn get_uv(&self, point: &Vector3, normal: &Vector3) -> (f32, f32) {
+        let local = *point / self.uv_scale;
+        let (u, v) = if normal.x.abs() > normal.y.abs() && normal.x.abs() > normal.z.abs() {
+            (local.z, local.y)
+        } else if normal.y.abs() > normal.z.abs() {
+            (local.x, local.z)
+        } else {
+            (local.x, local.y)
+        };
+        (1.0 - u.rem_euclid(1.0), 1.0 - v.rem_euclid(1.0))
+    }
+}
+
+impl RayIntersect for SdfObject {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        // Recortar el rayo a la caja (slab) y avanzar solo dentro de ella
+        let t1 = (self.bounds_min.x - ray_origin.x) / ray_direction.x;
+        let t2 = (self.bounds_max.x - ray_origin.x) / ray_direction.x;
+        let t3 = (self.bounds_min.y - ray_origin.y) / ray_direction.y;
+        let t4 = (self.bounds_max.y - ray_origin.y) / ray_direction.y;
+        let t5 = (self.bounds_min.z - ray_origin.z) / ray_direction.z;
+        let t6 = (self.bounds_max.z - ray_origin.z) / ray_direction.z;
+        let t_enter = t1.min(t2).max(t3.min(t4)).max(t5.min(t6));
+        let t_exit = t1.max(t2).min(t3.max(t4)).min(t5.max(t6));
+        if t_exit < 0.0 || t_enter > t_exit {
+            return Intersect::empty();
+        }
+
+        let Some(t) = march_ray(self.sdf.as_ref(), *ray_origin, *ray_direction, t_enter.max(0.0), t_exit) else {
+            return Intersect::empty();
+        };
+
+        let point = *ray_origin + *ray_direction * t;
+        let normal = estimate_normal(self.sdf.as_ref(), point);
+        let (u, v) = self.get_uv(&point, &normal);
+        Intersect::new(point, normal, t, self.material.clone(), u, v)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        (self.bounds_min + self.bounds_max) / 2.0
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        (self.bounds_min, self.bounds_max)
+    }
+}
@@ -0,0 +1,251 @@
+use raylib::prelude::Vector3;
+
+// Cómo se acomodan las imágenes de los dos ojos en el framebuffer
+#[derive(Clone, Copy, PartialEq)]
+pub enum StereoLayout {
+    SideBySide, // Izquierdo a la izquierda, derecho a la derecha
+    TopBottom,  // Izquierdo arriba, derecho abajo
+}
+
+// Dónde caen las muestras dentro del píxel (cuadros acumulados y antialiasing de bordes)
+#[derive(Clone, Copy, PartialEq)]
+pub enum AaPattern {
+    Random,      // Al azar: sin estructura, pero con grumos que tardan en promediarse
+    Grid,        // Subgrilla regular: lo más simple, deja escalones en bordes casi rectos
+    RotatedGrid, // Cada muestra en su fila y columna (RGSS): estable en animación
+    Halton,      // Bases 2 y 3 sin período: un render quieto converge más rápido
+}
+
+// Cómo se sigue un rayo después del primer impacto
+#[derive(Clone, Copy, PartialEq)]
+pub enum RenderMode {
+    Whitted,    // Reflejos, refracciones y sombras exactos: el modo interactivo
+    PathTraced, // Rebotes al azar con luz indirecta; necesita acumular
+}
+
+// Rectángulo de la imagen en píxeles (esquina superior izquierda y tamaño)
+#[derive(Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct RenderSettings {
+    pub render_mode: RenderMode,
+    pub max_depth: u32, // Profundidad máxima de recursión para reflexión/refracción
+    // Ruleta rusa desde roulette_min_depth; roulette_max_depth es el tope de seguridad
+    pub russian_roulette: bool,
+    pub roulette_min_depth: u32,
+    pub roulette_max_depth: u32,
+    pub accumulate: bool, // Promediar cuadros mientras la cámara y el sol no se mueven
+    // Sesgos relativos a la magnitud del punto; el de pendiente crece con luz rasante
+    pub shadow_bias: f32,
+    pub shadow_slope_bias: f32,
+    pub origin_bias: f32, // Sesgo de los rayos reflejados, refractados, a emisivos y de fotones
+    pub debug_shadow_mask: bool, // Solo la máscara de sombra (blanco = iluminado)
+    pub debug_depth: bool, // Color según el rebote más hondo (ver depth_debug_color)
+    pub show_light_markers: bool, // Esfera del color de cada luz que solo ve la cámara (ver light_marker_color)
+    pub blinn_phong: bool, // En todos los materiales (si no, solo en los que lo piden)
+    // Reusar el color de los píxeles que ya se veían en el cuadro anterior al mover la cámara
+    pub temporal_reprojection: bool,
+    pub dither: bool, // Ruido fijo por píxel antes de cuantizar: quita las bandas
+    // Texturas de color decodificadas a lineal y salida en sRGB; va junto con TextureManager::linear_lighting
+    pub linear_lighting: bool,
+    pub reprojection_tolerance: f32,
+    pub output_aovs: bool, // Llenar los buffers AOV en cada cuadro
+    // Denoiser bilateral guiado por las AOV
+    pub denoise: bool,
+    pub denoise_radius: u32,
+    pub denoise_spatial_sigma: f32,
+    pub denoise_normal_weight: f32,
+    pub denoise_albedo_weight: f32,
+    // Contornos negros detectados en las AOV por saltos de profundidad y normal
+    pub outline: bool,
+    pub outline_thickness: u32,
+    pub outline_depth_threshold: f32,
+    pub outline_normal_threshold: f32,
+    // Antialiasing adaptativo: muestras extra solo en píxeles de borde
+    pub adaptive_aa: bool,
+    pub aa_threshold: f32,   // Diferencia de color con un vecino para considerar borde
+    pub aa_pattern: AaPattern,
+    // Muestras de cada efecto, lo que main deja cambiar con teclas para cambiar calidad por
+    // velocidad. Con 0 el efecto se apaga: no hay sombras, los bordes no se refinan, el vidrio
+    // esmerilado refracta nítido y el cielo ilumina sin oclusión
+    pub shadow_samples: u32,        // Rayos de sombra por luz (por defecto 1)
+    pub aa_samples: u32,            // Muestras totales por píxel de borde; 1 tampoco refina (por defecto 4)
+    pub frosted_samples: u32,       // Rayos refractados en el primer impacto con vidrio esmerilado (por defecto 4)
+    pub sky_occlusion_samples: u32, // Rayos hacia el cielo en el primer impacto con sky_occlusion (por defecto 8)
+    pub sun_angular_size: f32,  // Diámetro aparente del sol en grados (0 = sombra nítida)
+    pub shutter: f32,           // Fracción del cuadro con el obturador abierto (0 = estático)
+    // Viñeta y gradación lift/gamma/gain; 0, 0, 1 y 1 dejan la imagen igual
+    pub vignette_strength: f32,
+    pub grade_lift: Vector3,
+    pub grade_gamma: Vector3,
+    pub grade_gain: Vector3,
+    // Radianes; sin horizontal_fov el horizontal sale de la proporción de la vista
+    pub vertical_fov: f32,
+    pub horizontal_fov: Option<f32>,
+    pub stereo: Option<StereoLayout>,
+    pub interocular_distance: f32,
+    pub crop: Option<CropRect>, // Solo se trazan estos píxeles (None = la imagen completa)
+    pub clear_color: Vector3,   // Color lineal de los píxeles que no se trazan
+    // Al mover, trazar a 1/preview_scale hasta pasar preview_idle_frames cuadros quieto
+    pub preview_scale: u32,
+    pub preview_idle_frames: u32,
+    // Cáusticas con fotones del sol sobre un mapa de celdas
+    pub caustics: bool,
+    pub caustic_photons: u32,
+    pub caustic_cell_size: f32,
+    // Sombras del sol en el primer impacto desde un mapa de profundidad (pcf 0 = borde duro)
+    pub shadow_map: bool,
+    pub shadow_map_resolution: u32,
+    pub shadow_map_pcf: u32,
+    // Niebla volumétrica en los rayos primarios (densidad 0 = apagada)
+    pub fog_density: f32,
+    pub fog_steps: u32,
+    pub fog_anisotropy: f32,
+    pub fog_max_distance: f32,
+    // Luz de los emisivos: escala y alcance (salvo el emission_reach del material)
+    pub emissive_strength: f32,
+    pub emissive_cutoff: f32,
+    pub max_emissive_lights: usize, // Emisivos por punto, los más cercanos (0 = todos)
+    pub emissive_importance_sampling: bool, // Sortear un emisivo por punto según su aporte
+    // Bloques como formas de distancia con aristas redondeadas y vecinos fundidos
+    pub sdf_blocks: bool,
+    pub sdf_rounding: f32,
+    pub sdf_blend: f32,
+    pub sky_lighting: bool, // Relleno difuso con la luz del cielo
+    pub sky_lighting_intensity: f32,
+    // Rayos hacia el cielo para que las grietas reciban menos luz del cielo
+    pub sky_occlusion: bool,
+    pub sky_occlusion_distance: f32,
+    // Un rayo corto en el primer impacto oscurece las juntas entre superficies cercanas
+    pub contact_shadows: bool,
+    pub contact_shadow_distance: f32,
+    pub contact_shadow_strength: f32,
+    // Con dynamic_sky el ciclo día/noche fija sky_color
+    pub sky_color: Vector3,
+    pub dynamic_sky: bool,
+    pub skybox_intensity: f32,
+    // star_visibility la fija el ciclo día/noche (0 de día, 1 de noche)
+    pub star_density: f32,
+    pub star_brightness: f32,
+    pub star_visibility: f32,
+    pub cloud_coverage: f32, // Fracción del cielo con nubes (0 = despejado)
+    pub cloud_softness: f32,
+    pub cloud_drift: f32,
+    pub bloom_strength: f32, // 0 = sin resplandor
+    pub bloom_threshold: f32,
+    pub bloom_radius: u32,
+    // Difuso plano sin texturas más allá de texture_lod_distance, fundido en texture_lod_band
+    pub texture_lod: bool,
+    pub texture_lod_distance: f32,
+    pub texture_lod_band: f32,
+    pub packet_tracing: bool, // Rayos primarios de a 2x2 contra la caja de la escena
+    // Interruptores de depuración
+    pub enable_shadows: bool,
+    pub enable_reflections: bool,
+    pub enable_refractions: bool,
+    pub enable_emissive: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            render_mode: RenderMode::Whitted,
+            max_depth: 3,
+            russian_roulette: false,
+            roulette_min_depth: 2,
+            roulette_max_depth: 16,
+            accumulate: true,
+            shadow_bias: 1e-4,
+            shadow_slope_bias: 1e-3,
+            origin_bias: 1e-4,
+            debug_shadow_mask: false,
+            debug_depth: false,
+            show_light_markers: false,
+            blinn_phong: false,
+            temporal_reprojection: false,
+            dither: true,
+            linear_lighting: false,
+            reprojection_tolerance: 0.01,
+            output_aovs: false,
+            denoise: false,
+            denoise_radius: 2,
+            denoise_spatial_sigma: 1.5,
+            denoise_normal_weight: 64.0,
+            denoise_albedo_weight: 32.0,
+            outline: false,
+            outline_thickness: 1,
+            outline_depth_threshold: 0.5,
+            outline_normal_threshold: 1.0,
+            adaptive_aa: false,
+            aa_threshold: 0.1,
+            aa_pattern: AaPattern::Grid,
+            shadow_samples: 1,
+            aa_samples: 4,
+            frosted_samples: 4,
+            sky_occlusion_samples: 8,
+            sun_angular_size: 0.0,
+            shutter: 0.0,
+            vignette_strength: 0.0,
+            grade_lift: Vector3::zero(),
+            grade_gamma: Vector3::one(),
+            grade_gain: Vector3::one(),
+            stereo: None,
+            interocular_distance: 0.1,
+            crop: None,
+            clear_color: Vector3::zero(),
+            preview_scale: 1,
+            preview_idle_frames: 1,
+            vertical_fov: std::f32::consts::PI / 3.0,
+            horizontal_fov: None,
+            caustics: false,
+            caustic_photons: 20_000,
+            caustic_cell_size: 0.05,
+            shadow_map: false,
+            shadow_map_resolution: 512,
+            shadow_map_pcf: 1,
+            fog_density: 0.0,
+            fog_steps: 16,
+            fog_anisotropy: 0.6,
+            fog_max_distance: 20.0,
+            emissive_strength: 1.0,
+            emissive_cutoff: 10.0,
+            max_emissive_lights: 0,
+            emissive_importance_sampling: false,
+            sdf_blocks: false,
+            sdf_rounding: 0.08,
+            sdf_blend: 0.1,
+            sky_lighting: false,
+            sky_lighting_intensity: 0.5,
+            sky_occlusion: false,
+            sky_occlusion_distance: 2.0,
+            contact_shadows: false,
+            contact_shadow_distance: 0.15,
+            contact_shadow_strength: 0.5,
+            sky_color: Vector3::new(0.26, 0.55, 0.89),
+            dynamic_sky: true,
+            skybox_intensity: 1.0,
+            star_density: 0.03,
+            star_brightness: 3.0,
+            star_visibility: 0.0,
+            cloud_coverage: 0.0,
+            cloud_softness: 0.15,
+            cloud_drift: 0.003,
+            bloom_strength: 0.0,
+            bloom_threshold: 1.0,
+            bloom_radius: 8,
+            texture_lod: false,
+            texture_lod_distance: 25.0,
+            texture_lod_band: 3.0,
+            packet_tracing: false,
+            enable_shadows: true,
+            enable_reflections: true,
+            enable_refractions: true,
+            enable_emissive: true,
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use raylib::prelude::Vector3;
+use rayon::prelude::*;
+use crate::aabb::Aabb;
+use crate::material::Material;
+use crate::ray_intersect::inverse_direction;
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+use crate::{find_closest_hit, intersect_object, occluder_transparency, tangent_frame};
+
+// Mapa de sombras del sol, en el marco del diorama, en perspectiva desde la posición de la luz:
+// los rayos de sombra tratan al sol como una luz puntual en su órbita y el mapa mira desde el
+// mismo punto, así las dos sombras coinciden. Por texel guarda la distancia del sol al primer
+// oclusor opaco y el tinte de los transparentes que hay antes (con la distancia al primero), así
+// el vidrio sigue dando sombra de color. Solo cubre la caja de los objetos finitos y no se arma
+// con el sol dentro de ella
+#[derive(Clone)]
+pub struct ShadowMap {
+    // Posición del sol y la base de la vista: hacia el centro de la escena, derecha y arriba
+    light: Vector3,
+    forward: Vector3,
+    right: Vector3,
+    up: Vector3,
+    // Mitad del lado de la ventana a distancia 1 del sol (la tangente del semiángulo de visión)
+    half_extent: f32,
+    resolution: usize,
+    // Por texel: distancia del sol al oclusor opaco y al primer transparente (infinito = nada
+    // en el camino) y luz que dejan pasar los transparentes
+    depths: Vec<f32>,
+    transparent_depths: Vec<f32>,
+    tints: Vec<Vector3>,
+}
+
+impl ShadowMap {
+    // Traza un rayo por texel desde el sol hacia la escena. Los objetos emisivos, ocultos o que
+    // no dan sombra no entran, igual que con los rayos de sombra
+    pub fn build(scene: &Scene, light_position: &Vector3, settings: &RenderSettings) -> Option<Self> {
+        let bounds = scene.objects().iter()
+            .filter(|object| casts_shadow(object.material()))
+            .map(|object| object.bounding_box())
+            .filter(Aabb::is_finite)
+            .fold(Aabb::empty(), |bounds, object| bounds.union(&object));
+        if !bounds.is_finite() {
+            return None;
+        }
+
+        // La ventana abarca la esfera que envuelve la caja vista desde el sol
+        let center = bounds.centroid();
+        let radius = ((bounds.max - bounds.min).length() / 2.0).max(1e-3);
+        let distance = (center - *light_position).length();
+        if distance <= radius * 1.01 {
+            return None;
+        }
+        let forward = (center - *light_position) / distance;
+        let (right, up) = tangent_frame(&forward);
+        let half_extent = radius / (distance * distance - radius * radius).sqrt();
+        let resolution = settings.shadow_map_resolution.max(1) as usize;
+        let texel = 2.0 * half_extent / resolution as f32;
+
+        let texels: Vec<(f32, f32, Vector3)> = (0..resolution * resolution).into_par_iter()
+            .map(|index| {
+                let (column, row) = ((index % resolution) as f32, (index / resolution) as f32);
+                let ray_direction = (forward
+                    + right * ((column + 0.5) * texel - half_extent)
+                    + up * ((row + 0.5) * texel - half_extent)).normalized();
+                let inv_direction = inverse_direction(&ray_direction);
+                let opaque = scene.objects().iter().enumerate()
+                    .filter(|(_, object)| casts_shadow(object.material()) && object.material().albedo[3] <= 0.0);
+                let hit = find_closest_hit(light_position, &ray_direction, opaque, &scene.texture_manager);
+                let depth = if hit.is_intersecting { hit.distance } else { f32::INFINITY };
+
+                let (mut transparent_depth, mut tint) = (f32::INFINITY, Vector3::one());
+                for object in scene.objects().iter().filter(|object| casts_shadow(object.material()) && object.material().albedo[3] > 0.0) {
+                    let hit = intersect_object(object.as_ref(), light_position, &ray_direction, &inv_direction, &scene.texture_manager);
+                    if hit.is_intersecting && hit.distance < depth {
+                        transparent_depth = transparent_depth.min(hit.distance);
+                        let transparency = occluder_transparency(object.as_ref(), light_position, &ray_direction, &inv_direction, &scene.texture_manager);
+                        tint = tint * object.material().diffuse * transparency;
+                    }
+                }
+                (depth, transparent_depth, tint)
+            })
+            .collect();
+
+        Some(ShadowMap {
+            light: *light_position,
+            forward,
+            right,
+            up,
+            half_extent,
+            resolution,
+            depths: texels.iter().map(|texel| texel.0).collect(),
+            transparent_depths: texels.iter().map(|texel| texel.1).collect(),
+            tints: texels.iter().map(|texel| texel.2).collect(),
+        })
+    }
+
+    // Luz del sol (por canal) que llega a `point`, como la de light_transmittance, promediando
+    // los texels a `pcf` de distancia para suavizar el borde. None si cae fuera del mapa
+    pub fn visibility(&self, point: &Vector3, normal: &Vector3, pcf: u32) -> Option<Vector3> {
+        let relative = *point - self.light;
+        let forward_distance = relative.dot(self.forward);
+        if forward_distance <= 0.0 {
+            return None;
+        }
+        let texel = 2.0 * self.half_extent / self.resolution as f32;
+        let column = ((relative.dot(self.right) / forward_distance + self.half_extent) / texel).floor() as i64;
+        let row = ((relative.dot(self.up) / forward_distance + self.half_extent) / texel).floor() as i64;
+        let size = self.resolution as i64;
+        if column < 0 || row < 0 || column >= size || row >= size {
+            return None;
+        }
+
+        // Un texel abarca más profundidad cuanto más lejos y más inclinada está la superficie:
+        // medio texel más la pendiente, hasta dos texels en total
+        let distance = relative.length();
+        let footprint = texel * forward_distance;
+        let cos_theta = normal.dot(relative / distance).abs().max(1e-3);
+        let slope = (1.0 - cos_theta * cos_theta).max(0.0).sqrt() / cos_theta;
+        let depth = distance - footprint * (0.5 + slope.min(1.5));
+        let pcf = pcf as i64;
+        let (mut lit, mut taps) = (Vector3::zero(), 0);
+        for dy in -pcf..=pcf {
+            for dx in -pcf..=pcf {
+                let index = ((row + dy).clamp(0, size - 1) * size + (column + dx).clamp(0, size - 1)) as usize;
+                taps += 1;
+                if depth > self.depths[index] {
+                    continue;
+                }
+                lit += if depth > self.transparent_depths[index] { self.tints[index] } else { Vector3::one() };
+            }
+        }
+        Some(lit / taps as f32)
+    }
+}
+
+fn casts_shadow(material: &Material) -> bool {
+    material.visible && material.casts_shadow && !material.is_emissive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::light_transmittance;
+    use crate::quad::Quad;
+    use crate::scene::SceneObject;
+    use crate::textures::TextureManager;
+
+    // Sol alto y un poco corrido, a la distancia de la órbita del diorama
+    const SUN: Vector3 = Vector3 { x: 1.0, y: 8.0, z: 0.5 };
+    const UP: Vector3 = Vector3 { x: 0.0, y: 1.0, z: 0.0 };
+
+    // Un cubo flotando sobre un piso de 8x8 en y = 0
+    fn cube_over_floor() -> (Scene, ShadowMap) {
+        let floor = Quad::new(Vector3::new(-4.0, 0.0, -4.0), Vector3::new(0.0, 0.0, 8.0), Vector3::new(8.0, 0.0, 0.0), Material::black());
+        let objects: Vec<SceneObject> = vec![Box::new(floor), Box::new(Cube::new(Vector3::new(0.0, 1.5, 0.0), 1.0, Material::black()))];
+        let scene = Scene::new(objects, Vec::new(), TextureManager::new());
+        let settings = RenderSettings { shadow_map_resolution: 512, ..RenderSettings::default() };
+        let map = ShadowMap::build(&scene, &SUN, &settings).unwrap();
+        (scene, map)
+    }
+
+    // Lo que ve un rayo de sombra desde el piso
+    fn traced(scene: &Scene, point: Vector3) -> f32 {
+        light_transmittance(&(point + UP * 1e-3), &SUN, scene).x
+    }
+
+    #[test]
+    fn cube_shades_the_floor_under_it() {
+        let (_, map) = cube_over_floor();
+        // La sombra del centro del cubo cae donde la recta desde el sol corta el piso
+        let under = SUN + (Vector3::new(0.0, 1.5, 0.0) - SUN) * (8.0 / 6.5);
+        assert!(map.visibility(&under, &UP, 1).unwrap().x < 1e-3);
+        assert_eq!(map.visibility(&Vector3::new(3.0, 0.0, 3.0), &UP, 1).unwrap(), Vector3::one());
+        assert_eq!(map.visibility(&Vector3::new(-3.0, 0.0, 2.5), &UP, 0).unwrap(), Vector3::one());
+        // La cara de arriba del cubo no se sombrea a sí misma
+        assert_eq!(map.visibility(&Vector3::new(0.2, 2.0, 0.1), &UP, 1).unwrap(), Vector3::one());
+    }
+
+    #[test]
+    fn points_outside_the_map_have_no_visibility() {
+        let (_, map) = cube_over_floor();
+        assert!(map.visibility(&Vector3::new(30.0, 0.0, 30.0), &UP, 1).is_none());
+        // Detrás del sol
+        assert!(map.visibility(&(SUN * 2.0), &UP, 1).is_none());
+    }
+
+    #[test]
+    fn map_agrees_with_shadow_rays_away_from_edges() {
+        // El sol es puntual: la sombra de un cubo a 1.5 del piso sale un 23% más grande que el
+        // cubo. Un mapa direccional la dejaría del tamaño del cubo y fallaría en ese borde
+        let (scene, map) = cube_over_floor();
+        let mut compared = 0;
+        for z in -20..=20 {
+            for x in -20..=20 {
+                let point = Vector3::new(x as f32 * 0.1 + 0.033, 0.0, z as f32 * 0.1 + 0.033);
+                let expected = traced(&scene, point);
+                // A 512 texels un texel mide unos 0.045 en el piso; a 0.1 del borde ya no lo toca
+                let near_edge = [(0.1, 0.0), (-0.1, 0.0), (0.0, 0.1), (0.0, -0.1)]
+                    .iter()
+                    .any(|&(dx, dz)| traced(&scene, point + Vector3::new(dx, 0.0, dz)) != expected);
+                if near_edge {
+                    continue;
+                }
+                compared += 1;
+                assert_eq!(map.visibility(&point, &UP, 0).unwrap().x, expected, "en {point:?}");
+            }
+        }
+        assert!(compared > 1400, "{compared}");
+    }
+}
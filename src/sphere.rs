@@ -1,5 +1,5 @@
 use raylib::prelude::Vector3;
-use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::ray_intersect::{Intersect, RayIntersect, SURFACE_EPSILON};
 use crate::material::Material;
 use std::f32::consts::PI;
 
@@ -30,7 +30,7 @@ impl RayIntersect for Sphere {
 
         if discriminant > 0.0 {
             let t = (-b - discriminant.sqrt()) / (2.0 * a);
-            if t > 0.0 {
+            if t > SURFACE_EPSILON {
                 let point = *ray_origin + *ray_direction * t;
                 let normal = (point - self.center).normalized();
                 let distance = t;
@@ -42,4 +42,42 @@ impl RayIntersect for Sphere {
 
         Intersect::empty()
     }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.center
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        let half = Vector3::one() * self.radius;
+        (self.center - half, self.center + half)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sphere() -> Sphere {
+        Sphere { center: Vector3::new(0.0, 2.0, 0.0), radius: 0.5, material: Material::black() }
+    }
+
+    #[test]
+    fn hits_the_near_side_with_an_outward_normal() {
+        let hit = sphere().ray_intersect(&Vector3::new(0.0, 2.0, 3.0), &Vector3::new(0.0, 0.0, -1.0));
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 2.5).abs() < 1e-5);
+        assert_eq!(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+        // A un lado o detrás del origen no hay impacto
+        assert!(!sphere().ray_intersect(&Vector3::new(0.6, 2.0, 3.0), &Vector3::new(0.0, 0.0, -1.0)).is_intersecting);
+        assert!(!sphere().ray_intersect(&Vector3::new(0.0, 2.0, 3.0), &Vector3::new(0.0, 0.0, 1.0)).is_intersecting);
+    }
+
+    #[test]
+    fn bounds_enclose_the_radius() {
+        assert_eq!(sphere().bounds(), (Vector3::new(-0.5, 1.5, -0.5), Vector3::new(0.5, 2.5, 0.5)));
+    }
 }
@@ -1,28 +1,135 @@
 // textures.rs
 
 use raylib::prelude::*;
+use rayon::prelude::*;
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 
-struct CpuTexture {
+/// How a texture is read between texel centers
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum TextureFilter {
+    /// The texel under the point, for crisp pixel-art blocks
+    #[default]
+    Nearest,
+    /// A blend of the four nearest texels, for smooth and photographic textures
+    Bilinear,
+}
+
+/// What happens to UVs outside [0, 1]
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum TextureWrap {
+    /// The edge texels stretch outwards
+    #[default]
+    Clamp,
+    /// The texture tiles
+    Repeat,
+}
+
+impl TextureWrap {
+    /// Brings a UV coordinate into [0, 1]
+    fn coordinate(self, t: f32) -> f32 {
+        match self {
+            TextureWrap::Clamp => t.clamp(0.0, 1.0),
+            TextureWrap::Repeat => t.rem_euclid(1.0),
+        }
+    }
+
+    /// Brings a texel index into 0..size
+    fn index(self, i: i32, size: i32) -> i32 {
+        match self {
+            TextureWrap::Clamp => i.clamp(0, size - 1),
+            TextureWrap::Repeat => i.rem_euclid(size),
+        }
+    }
+}
+
+/// Blends the four texels around (u, v) of a `width` x `height` grid; neighbours past the edge
+/// follow `wrap`, so a repeating texture blends across the seam
+fn bilinear<T>(width: i32, height: i32, u: f32, v: f32, wrap: TextureWrap, texel: impl Fn(i32, i32) -> T) -> T
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Mul<f32, Output = T>,
+{
+    let x = u * width as f32 - 0.5;
+    let y = v * height as f32 - 0.5;
+    let (fx, fy) = (x - x.floor(), y - y.floor());
+    let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+    let (x0, x1) = (wrap.index(x0, width), wrap.index(x0 + 1, width));
+    let (y0, y1) = (wrap.index(y0, height), wrap.index(y0 + 1, height));
+    let top = texel(x0, y0) * (1.0 - fx) + texel(x1, y0) * fx;
+    let bottom = texel(x0, y1) * (1.0 - fx) + texel(x1, y1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+pub struct CpuTexture {
     width: i32,
     height: i32,
-    pixels: Vec<Vector3>, // Normalized RGB values
+    pixels: Vec<Vector3>, // Normalized RGB values (linear if the texture was decoded from sRGB)
     alphas: Vec<f32>, // Alpha channel values (0.0 to 1.0)
+    is_srgb: bool, // Color texture stored as sRGB on disk; normal maps and data textures are false
+    mips: Vec<MipLevel>, // Progressively halved copies of `pixels` (level 1 onwards)
+    modified: Option<SystemTime>, // File modification time when it was loaded
+}
+
+/// A downsampled copy of the texture colors
+struct MipLevel {
+    width: i32,
+    height: i32,
+    pixels: Vec<Vector3>,
+}
+
+/// Builds each level by averaging 2x2 blocks of the previous one, down to 1x1
+fn build_mips(width: i32, height: i32, pixels: &[Vector3]) -> Vec<MipLevel> {
+    let mut mips: Vec<MipLevel> = Vec::new();
+    let (mut src_width, mut src_height) = (width, height);
+
+    while src_width > 1 || src_height > 1 {
+        let src = mips.last().map_or(pixels, |level| &level.pixels[..]);
+        let dst_width = (src_width / 2).max(1);
+        let dst_height = (src_height / 2).max(1);
+        let mut dst = Vec::with_capacity((dst_width * dst_height) as usize);
+        for y in 0..dst_height {
+            for x in 0..dst_width {
+                let texel = |sx: i32, sy: i32| src[(sy.min(src_height - 1) * src_width + sx.min(src_width - 1)) as usize];
+                let (sx, sy) = (x * 2, y * 2);
+                dst.push((texel(sx, sy) + texel(sx + 1, sy) + texel(sx, sy + 1) + texel(sx + 1, sy + 1)) / 4.0);
+            }
+        }
+        mips.push(MipLevel { width: dst_width, height: dst_height, pixels: dst });
+        (src_width, src_height) = (dst_width, dst_height);
+    }
+
+    mips
+}
+
+/// Converts an sRGB channel in [0, 1] to linear
+pub(crate) fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Inverse of `srgb_to_linear`, applied to the output when lighting is linear. Values above 1
+/// stay above 1 and are clamped when quantized
+pub fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.max(0.0);
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
 }
 
 impl CpuTexture {
-    fn from_image(image: &Image) -> Self {
+    fn from_image(image: &Image, decode_srgb: bool) -> Self {
         // Safe: Raylib handles pixel format internally
         let colors = image.get_image_data(); // Vec<Color>
+        let channel = |c: u8| if decode_srgb { srgb_to_linear(c as f32 / 255.0) } else { c as f32 / 255.0 };
         let pixels: Vec<Vector3> = colors
             .iter()
-            .map(|c| {
-                Vector3::new(
-                    c.r as f32 / 255.0,
-                    c.g as f32 / 255.0,
-                    c.b as f32 / 255.0,
-                )
-            })
+            .map(|c| Vector3::new(channel(c.r), channel(c.g), channel(c.b)))
             .collect();
         
         let alphas: Vec<f32> = colors
@@ -33,15 +140,84 @@ impl CpuTexture {
         CpuTexture {
             width: image.width,
             height: image.height,
+            mips: build_mips(image.width, image.height, &pixels),
             pixels,
             alphas,
+            is_srgb: false,
+            modified: None,
+        }
+    }
+
+    /// Decodes encoded image data (PNG, ...); the format comes from the extension of `name`
+    fn from_memory(name: &str, bytes: &[u8], is_srgb: bool, linear_lighting: bool) -> Option<Self> {
+        let extension = std::path::Path::new(name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("png");
+        match Image::load_image_from_mem(&format!(".{}", extension), bytes) {
+            Ok(image) => {
+                let mut texture = CpuTexture::from_image(&image, is_srgb && linear_lighting);
+                texture.is_srgb = is_srgb;
+                Some(texture)
+            }
+            Err(_) => {
+                eprintln!("Failed to decode image {}", name);
+                None
+            }
+        }
+    }
+
+    fn load_from_disk(path: &str, is_srgb: bool, linear_lighting: bool) -> Option<Self> {
+        let Ok(bytes) = std::fs::read(path) else {
+            eprintln!("Failed to load image {}", path);
+            return None;
+        };
+        let mut texture = CpuTexture::from_memory(path, &bytes, is_srgb, linear_lighting)?;
+        texture.modified = file_modified(path);
+        Some(texture)
+    }
+
+    /// Color at (u, v) in mip `level` (0 = full resolution). Nearest keeps the level-0 texel
+    /// and shifts it down to the level, so every level lines up with the pixel-art grid
+    fn sample(&self, level: usize, u: f32, v: f32, filter: TextureFilter, wrap: TextureWrap) -> Vector3 {
+        let (width, height, pixels) = match level {
+            0 => (self.width, self.height, &self.pixels[..]),
+            _ => {
+                let mip = &self.mips[level - 1];
+                (mip.width, mip.height, &mip.pixels[..])
+            }
+        };
+        let texel = |x: i32, y: i32| pixels.get((y * width + x) as usize).copied().unwrap_or(Vector3::one());
+        let (u, v) = (wrap.coordinate(u), wrap.coordinate(v));
+
+        match filter {
+            TextureFilter::Nearest => {
+                let x = ((u * self.width as f32) as u32 >> level).min(width as u32 - 1);
+                let y = ((v * self.height as f32) as u32 >> level).min(height as u32 - 1);
+                texel(x as i32, y as i32)
+            }
+            TextureFilter::Bilinear => bilinear(width, height, u, v, wrap, texel),
         }
     }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
 }
 
 pub struct TextureManager {
-    cpu_textures: HashMap<String, CpuTexture>,
-    textures: HashMap<String, Texture2D>, // Store GPU textures for rendering
+    // Textures are loaded on first use from any render thread.
+    // A `None` entry remembers a path that failed to load so it isn't retried every ray.
+    cpu_textures: RwLock<HashMap<String, Option<Arc<CpuTexture>>>>,
+    // Decode sRGB color textures to linear on load. Set it together with
+    // RenderSettings::linear_lighting, which gamma-encodes the output to match
+    pub linear_lighting: bool,
+    // Face paths of the loaded skybox cubemap, in +X, -X, +Y, -Y, +Z, -Z order
+    cubemap: Option<[String; 6]>,
 }
 
 impl TextureManager {
@@ -49,114 +225,216 @@ impl TextureManager {
         Self::default()
     }
 
-    pub fn load_texture(
-        &mut self,
-        rl: &mut RaylibHandle,
-        thread: &RaylibThread,
-        path: &str,
-    ) {
-        if self.textures.contains_key(path) {
-            return;
+    /// Loads a texture into the cache ahead of time (otherwise it is loaded on first use).
+    /// `is_srgb` marks color textures; normal maps and other data textures stay linear
+    pub fn load_texture(&self, path: &str, is_srgb: bool) {
+        self.load_cached(path, is_srgb);
+    }
+
+    /// Loads a batch of `(path, is_srgb)` textures, decoding them in parallel.
+    /// Paths already in the cache are skipped. Everything stays on the CPU, so no main-thread upload is needed
+    pub fn load_all(&self, paths: &[(String, bool)]) {
+        let pending: Vec<&(String, bool)> = {
+            let cache = self.cpu_textures.read().unwrap();
+            paths.iter().filter(|(path, _)| !cache.contains_key(path)).collect()
+        };
+        let loaded: Vec<(String, Option<Arc<CpuTexture>>)> = pending
+            .into_par_iter()
+            .map(|(path, is_srgb)| (path.clone(), CpuTexture::load_from_disk(path, *is_srgb, self.linear_lighting).map(Arc::new)))
+            .collect();
+
+        let mut cache = self.cpu_textures.write().unwrap();
+        for (path, entry) in loaded {
+            cache.entry(path).or_insert(entry);
         }
+    }
 
-        let image = Image::load_image(path)
-            .unwrap_or_else(|_| panic!("Failed to load image {}", path));
+    /// Loads six color images as the skybox cubemap, ordered +X, -X, +Y, -Y, +Z, -Z.
+    /// Returns false (leaving any previous cubemap in place) if a face fails to load
+    pub fn load_cubemap(&mut self, faces: [&str; 6]) -> bool {
+        if faces.iter().any(|face| self.load_cached(face, true).is_none()) {
+            return false;
+        }
+        self.cubemap = Some(faces.map(str::to_string));
+        true
+    }
 
-        let texture = rl
-            .load_texture_from_image(thread, &image)
-            .unwrap_or_else(|_| panic!("Failed to load texture {}", path));
+    /// Skybox color seen along `direction`, or None without a cubemap. The face is the one of the
+    /// dominant axis; face UVs follow the usual cubemap layout (row 0 at the top of each image)
+    pub fn sample_cubemap(&self, direction: &Vector3) -> Option<Vector3> {
+        let faces = self.cubemap.as_ref()?;
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        let (face, u, v) = if ax >= ay && ax >= az {
+            if x > 0.0 { (0, -z / ax, -y / ax) } else { (1, z / ax, -y / ax) }
+        } else if ay >= az {
+            if y > 0.0 { (2, x / ay, z / ay) } else { (3, x / ay, -z / ay) }
+        } else if z > 0.0 {
+            (4, x / az, -y / az)
+        } else {
+            (5, -x / az, -y / az)
+        };
 
-        let cpu_texture = CpuTexture::from_image(&image);
+        Some(self.get_pixel_color(&faces[face], (u + 1.0) / 2.0, (v + 1.0) / 2.0, 0.0, TextureFilter::Bilinear, TextureWrap::Clamp))
+    }
 
-        self.cpu_textures.insert(path.to_string(), cpu_texture);
-        self.textures.insert(path.to_string(), texture);
+    /// Decodes an embedded texture (e.g. from `include_bytes!`) and caches it under `name`,
+    /// which materials then use as their texture path. The extension of `name` picks the decoder
+    /// and `is_srgb` is false for normal maps and other data textures, like in `load_texture`.
+    /// Returns false if the data could not be decoded
+    pub fn load_texture_from_memory(&self, name: &str, bytes: &[u8], is_srgb: bool) -> bool {
+        let entry = CpuTexture::from_memory(name, bytes, is_srgb, self.linear_lighting).map(Arc::new);
+        let loaded = entry.is_some();
+        self.cpu_textures.write().unwrap().insert(name.to_string(), entry);
+        loaded
     }
 
-    pub fn get_pixel_color(
-        &self,
-        path: &str,
-        tx: u32,
-        ty: u32,
-    ) -> Vector3 {
-        if let Some(cpu_texture) = self.cpu_textures.get(path) {
-            let x = tx.min(cpu_texture.width as u32 - 1) as i32;
-            let y = ty.min(cpu_texture.height as u32 - 1) as i32;
+    /// Returns the cached texture, loading it from disk as a color texture if it isn't cached yet
+    pub fn get_texture(&self, path: &str) -> Option<Arc<CpuTexture>> {
+        self.load_cached(path, true)
+    }
 
-            if x < 0 || y < 0 || x >= cpu_texture.width || y >= cpu_texture.height {
-                return Vector3::one(); // default white
-            }
+    fn load_cached(&self, path: &str, is_srgb: bool) -> Option<Arc<CpuTexture>> {
+        if let Some(entry) = self.cpu_textures.read().unwrap().get(path) {
+            return entry.clone();
+        }
+
+        let mut cache = self.cpu_textures.write().unwrap();
+        // Another thread may have loaded it while we waited for the write lock
+        if let Some(entry) = cache.get(path) {
+            return entry.clone();
+        }
+
+        let entry = CpuTexture::load_from_disk(path, is_srgb, self.linear_lighting).map(Arc::new);
+        cache.insert(path.to_string(), entry.clone());
+        entry
+    }
+
+    /// Reloads every cached texture whose file changed on disk.
+    /// Returns true if anything was reloaded so the caller can re-render.
+    pub fn reload_changed(&self) -> bool {
+        let mut cache = self.cpu_textures.write().unwrap();
+        let mut reloaded = false;
 
-            let index = (y * cpu_texture.width + x) as usize;
-            if index < cpu_texture.pixels.len() {
-                cpu_texture.pixels[index]
-            } else {
-                Vector3::one()
+        for (path, entry) in cache.iter_mut() {
+            let modified = file_modified(path);
+            let cached = entry.as_ref().and_then(|texture| texture.modified);
+            let is_srgb = entry.as_ref().is_none_or(|texture| texture.is_srgb);
+            if modified.is_some()
+                && modified != cached
+                && let Some(texture) = CpuTexture::load_from_disk(path, is_srgb, self.linear_lighting)
+            {
+                *entry = Some(Arc::new(texture));
+                reloaded = true;
             }
-        } else {
-            Vector3::one()
+        }
+
+        reloaded
+    }
+
+    /// Mip level for a surface patch that spans `uv_footprint` in UV space (0 = full resolution)
+    pub fn texture_lod(&self, path: &str, uv_footprint: f32) -> f32 {
+        match self.get_texture(path) {
+            Some(texture) => (uv_footprint * texture.width.max(texture.height) as f32).log2().max(0.0),
+            None => 0.0,
         }
     }
 
-    pub fn get_pixel_alpha(
+    /// Color at (u, v), read from the mip level nearest to `lod`
+    pub fn get_pixel_color(
         &self,
         path: &str,
-        tx: u32,
-        ty: u32,
-    ) -> f32 {
-        if let Some(cpu_texture) = self.cpu_textures.get(path) {
-            let x = tx.min(cpu_texture.width as u32 - 1) as i32;
-            let y = ty.min(cpu_texture.height as u32 - 1) as i32;
-
-            if x < 0 || y < 0 || x >= cpu_texture.width || y >= cpu_texture.height {
-                return 1.0; // default opaque
+        u: f32,
+        v: f32,
+        lod: f32,
+        filter: TextureFilter,
+        wrap: TextureWrap,
+    ) -> Vector3 {
+        match self.get_texture(path) {
+            Some(cpu_texture) if !cpu_texture.pixels.is_empty() => {
+                let level = (lod.round() as usize).min(cpu_texture.mips.len());
+                cpu_texture.sample(level, u, v, filter, wrap)
             }
+            _ => Vector3::one(), // default white
+        }
+    }
 
-            let index = (y * cpu_texture.width + x) as usize;
-            if index < cpu_texture.alphas.len() {
-                cpu_texture.alphas[index]
-            } else {
-                1.0
+    /// Alpha of the texel under (u, v)
+    pub fn get_pixel_alpha(&self, path: &str, u: f32, v: f32, wrap: TextureWrap) -> f32 {
+        match self.get_texture(path) {
+            Some(cpu_texture) if !cpu_texture.alphas.is_empty() => {
+                let x = ((wrap.coordinate(u) * cpu_texture.width as f32) as i32).min(cpu_texture.width - 1);
+                let y = ((wrap.coordinate(v) * cpu_texture.height as f32) as i32).min(cpu_texture.height - 1);
+                cpu_texture.alphas.get((y * cpu_texture.width + x) as usize).copied().unwrap_or(1.0)
             }
-        } else {
-            1.0
+            _ => 1.0, // default opaque
+        }
+    }
+
+    /// Alpha at (u, v) interpolated between the four nearest texels, so edges between
+    /// transparent and opaque texels fade over one texel instead of stepping
+    pub fn get_alpha_bilinear(&self, path: &str, u: f32, v: f32, wrap: TextureWrap) -> f32 {
+        match self.get_texture(path) {
+            Some(cpu_texture) if !cpu_texture.alphas.is_empty() => {
+                let alpha = |x: i32, y: i32| cpu_texture.alphas.get((y * cpu_texture.width + x) as usize).copied().unwrap_or(1.0);
+                bilinear(cpu_texture.width, cpu_texture.height, wrap.coordinate(u), wrap.coordinate(v), wrap, alpha)
+            }
+            _ => 1.0,
         }
     }
 
-    pub fn get_texture(
+    pub fn get_normal_from_map(
         &self,
         path: &str,
-    ) -> Option<&Texture2D> {
-        self.textures.get(path)
+        u: f32,
+        v: f32,
+        filter: TextureFilter,
+        wrap: TextureWrap,
+    ) -> Option<Vector3> {
+        let cpu_texture = self.load_cached(path, false).filter(|texture| !texture.pixels.is_empty())?;
+        Some(decode_normal(cpu_texture.sample(0, u, v, filter, wrap)))
     }
 
-    pub fn get_normal_from_map(
+    /// Normal from an object-space normal map: all three channels map from [0, 1] to [-1, 1]
+    /// and the result is used as is, so it may point anywhere
+    pub fn get_object_normal_from_map(
         &self,
         path: &str,
-        tx: u32,
-        ty: u32,
+        u: f32,
+        v: f32,
+        filter: TextureFilter,
+        wrap: TextureWrap,
     ) -> Option<Vector3> {
-        if let Some(cpu_texture) = self.cpu_textures.get(path) {
-            let x = tx.min(cpu_texture.width as u32 - 1) as i32;
-            let y = ty.min(cpu_texture.height as u32 - 1) as i32;
+        let cpu_texture = self.load_cached(path, false).filter(|texture| !texture.pixels.is_empty())?;
+        let normal = cpu_texture.sample(0, u, v, filter, wrap) * 2.0 - Vector3::one();
+        (normal.length() >= 1e-3).then(|| normal.normalized())
+    }
+}
 
-            if x < 0 || y < 0 || x >= cpu_texture.width || y >= cpu_texture.height {
-                return None;
-            }
+/// Tangent-space normal from a normal-map texel. The normal is kept in the z > 0 hemisphere,
+/// and texels that decode to (almost) nothing fall back to the unperturbed (0, 0, 1)
+fn decode_normal(color: Vector3) -> Vector3 {
+    let normal = Vector3::new(color.x * 2.0 - 1.0, color.y * 2.0 - 1.0, color.z.max(0.0));
+    if normal.length() < 1e-3 {
+        return Vector3::new(0.0, 0.0, 1.0);
+    }
+    let normal = normal.normalized();
+    // Grazing normals would point into the surface once strength blending is applied
+    Vector3::new(normal.x, normal.y, normal.z.max(0.05)).normalized()
+}
 
-            let index = (y * cpu_texture.width + x) as usize;
-            if index < cpu_texture.pixels.len() {
-                let color = cpu_texture.pixels[index];
-                let normal = Vector3::new(
-                    color.x * 2.0 - 1.0,
-                    color.y * 2.0 - 1.0,
-                    color.z,
-                );
-                Some(normal.normalized())
-            } else {
-                None
-            }
-        } else {
-            None
+fn file_modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// The copy shares the already decoded textures (they are reference counted); textures
+/// loaded or reloaded afterwards only land in the manager that loaded them
+impl Clone for TextureManager {
+    fn clone(&self) -> Self {
+        TextureManager {
+            cpu_textures: RwLock::new(self.cpu_textures.read().unwrap().clone()),
+            linear_lighting: self.linear_lighting,
+            cubemap: self.cubemap.clone(),
         }
     }
 }
@@ -164,8 +442,102 @@ impl TextureManager {
 impl Default for TextureManager {
     fn default() -> Self {
         TextureManager {
-            cpu_textures: HashMap::new(),
-            textures: HashMap::new(),
+            cpu_textures: RwLock::new(HashMap::new()),
+            linear_lighting: false,
+            cubemap: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_unit_in_front(normal: Vector3) {
+        assert!((normal.length() - 1.0).abs() < 1e-4, "{normal:?} no es unitaria");
+        assert!(normal.z > 0.0, "{normal:?} apunta hacia adentro");
+    }
+
+    #[test]
+    fn flat_texel_decodes_to_the_unperturbed_normal() {
+        let normal = decode_normal(Vector3::new(128.0, 128.0, 255.0) / 255.0);
+        assert_unit_in_front(normal);
+        assert!((normal - Vector3::new(0.0, 0.0, 1.0)).length() < 1e-2);
+    }
+
+    #[test]
+    fn degenerate_texels_never_leave_the_hemisphere() {
+        // Un texel que decodifica a cero (o casi) vuelve a la normal sin perturbar
+        assert_eq!(decode_normal(Vector3::new(0.5, 0.5, 0.0)), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(decode_normal(Vector3::new(0.5002, 0.4999, 0.0)), Vector3::new(0.0, 0.0, 1.0));
+
+        // Rasantes o con z negativa se levantan apenas sobre la superficie
+        for color in [Vector3::new(1.0, 0.5, 0.0), Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.5, 1.0, -0.3)] {
+            let normal = decode_normal(color);
+            assert_unit_in_front(normal);
+            assert!(normal.z < 0.1);
         }
     }
-}
\ No newline at end of file
+
+    // Textura de 2x1: rojo opaco a la izquierda, azul transparente a la derecha
+    fn red_blue_texture(name: &str) -> (TextureManager, String) {
+        let mut image = Image::gen_image_color(2, 1, Color::RED);
+        image.draw_pixel(1, 0, Color::new(0, 0, 255, 0));
+        let path = std::env::temp_dir().join(format!("textures_{name}.png")).to_str().unwrap().to_string();
+        image.export_image(&path);
+
+        let textures = TextureManager::new();
+        textures.load_texture(&path, false);
+        (textures, path)
+    }
+
+    #[test]
+    fn out_of_range_uvs_clamp_or_repeat() {
+        let (textures, path) = red_blue_texture("wrap");
+        let red = Vector3::new(1.0, 0.0, 0.0);
+        let blue = Vector3::new(0.0, 0.0, 1.0);
+        let color = |u: f32, v: f32, wrap| textures.get_pixel_color(&path, u, v, 0.0, TextureFilter::Nearest, wrap);
+
+        // Clamp estira el borde más cercano; Repeat vuelve a empezar la textura
+        assert_eq!(color(-0.1, 0.5, TextureWrap::Clamp), red);
+        assert_eq!(color(1.5, 0.5, TextureWrap::Clamp), blue);
+        assert_eq!(color(-0.1, 0.5, TextureWrap::Repeat), blue);
+        assert_eq!(color(1.25, 0.5, TextureWrap::Repeat), red);
+        assert_eq!(color(0.25, -3.7, TextureWrap::Repeat), red);
+
+        assert_eq!(textures.get_pixel_alpha(&path, -0.1, 1.5, TextureWrap::Clamp), 1.0);
+        assert_eq!(textures.get_pixel_alpha(&path, 1.5, -0.1, TextureWrap::Clamp), 0.0);
+        assert_eq!(textures.get_pixel_alpha(&path, -0.1, 0.5, TextureWrap::Repeat), 0.0);
+    }
+
+    #[test]
+    fn bilinear_lookups_stay_inside_the_texture() {
+        let (textures, path) = red_blue_texture("bilinear_wrap");
+        for wrap in [TextureWrap::Clamp, TextureWrap::Repeat] {
+            for (u, v) in [(-0.1, 0.5), (1.5, 0.5), (0.5, -0.1), (0.5, 1.5), (-7.3, 12.9)] {
+                let color = textures.get_pixel_color(&path, u, v, 0.0, TextureFilter::Bilinear, wrap);
+                assert!([color.x, color.y, color.z].iter().all(|c| (0.0..=1.0).contains(c)));
+                assert!((0.0..=1.0).contains(&textures.get_alpha_bilinear(&path, u, v, wrap)));
+            }
+        }
+        // Repeat mezcla a través de la costura: el borde izquierdo se funde con el derecho
+        let seam = textures.get_pixel_color(&path, 0.0, 0.5, 0.0, TextureFilter::Bilinear, TextureWrap::Repeat);
+        assert!(seam.x > 0.2 && seam.z > 0.2);
+    }
+
+    #[test]
+    fn srgb_curve_meets_at_its_breakpoints_and_round_trips() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        // Los dos tramos se juntan en 0.04045 (lineal 0.0031308)
+        assert!((srgb_to_linear(0.04045) - 0.0031308).abs() < 1e-6);
+        assert!((srgb_to_linear(0.04046) - srgb_to_linear(0.04045)).abs() < 1e-5);
+        assert!((linear_to_srgb(0.0031308) - 0.04045).abs() < 1e-5);
+
+        // Decodificar un byte y volver a codificarlo para la salida da el mismo byte
+        for byte in 0..=255u8 {
+            let encoded = linear_to_srgb(srgb_to_linear(byte as f32 / 255.0));
+            assert_eq!((encoded * 255.0).round() as u8, byte);
+        }
+    }
+}
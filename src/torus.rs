@@ -0,0 +1,153 @@
+use raylib::prelude::Vector3;
+use std::f32::consts::PI;
+use crate::ray_intersect::{Intersect, RayIntersect, SURFACE_EPSILON};
+use crate::material::Material;
+use crate::tangent_frame;
+
+const MAX_STEPS: u32 = 128;
+
+// Toro (anillo) alrededor de `axis`: el centro del tubo describe un círculo de radio
+// major_radius y el tubo tiene radio minor_radius
+pub struct Torus {
+    pub center: Vector3,
+    pub axis: Vector3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub material: Material,
+    // Ejes del plano del anillo, para pasar puntos al espacio local (eje = Y local)
+    tangent: Vector3,
+    bitangent: Vector3,
+}
+
+impl Torus {
+    pub fn new(center: Vector3, axis: Vector3, major_radius: f32, minor_radius: f32, material: Material) -> Self {
+        let axis = axis.normalized();
+        let (tangent, bitangent) = tangent_frame(&axis);
+        Torus { center, axis, major_radius, minor_radius, material, tangent, bitangent }
+    }
+
+    fn local_vector(&self, v: Vector3) -> Vector3 {
+        Vector3::new(v.dot(self.tangent), v.dot(self.axis), v.dot(self.bitangent))
+    }
+
+    fn world_vector(&self, v: Vector3) -> Vector3 {
+        self.tangent * v.x + self.axis * v.y + self.bitangent * v.z
+    }
+
+    // Distancia con signo a la superficie (exacta, así el avance por esferas no se pasa)
+    fn distance(&self, p: Vector3) -> f32 {
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        (ring * ring + p.y * p.y).sqrt() - self.minor_radius
+    }
+}
+
+impl RayIntersect for Torus {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        let origin = self.local_vector(*ray_origin - self.center);
+        let direction = self.local_vector(*ray_direction);
+
+        // Recortar el rayo a la esfera que envuelve el toro
+        let bound = self.major_radius + self.minor_radius;
+        let b = origin.dot(direction);
+        let c = origin.dot(origin) - bound * bound;
+        let discriminant = b * b - c;
+        if discriminant < 0.0 {
+            return Intersect::empty();
+        }
+        let sqrt_d = discriminant.sqrt();
+        let t_exit = -b + sqrt_d;
+        if t_exit < 0.0 {
+            return Intersect::empty();
+        }
+
+        // Avanzar por esferas: cada paso es la distancia a la superficie, que nunca la cruza.
+        // Si el origen está dentro del tubo se avanza por la distancia negada hasta salir
+        let mut t = (-b - sqrt_d).max(0.0);
+        let inside = self.distance(origin + direction * t) < 0.0;
+        let mut hit = None;
+        for _ in 0..MAX_STEPS {
+            let d = self.distance(origin + direction * t);
+            let d = if inside { -d } else { d };
+            if d < SURFACE_EPSILON {
+                hit = Some(t);
+                break;
+            }
+            t += d;
+            if t > t_exit {
+                break;
+            }
+        }
+        let Some(t) = hit else {
+            return Intersect::empty();
+        };
+
+        let local = origin + direction * t;
+        let ring_length = (local.x * local.x + local.z * local.z).sqrt();
+        let ring_point = if ring_length > 0.0 {
+            Vector3::new(local.x, 0.0, local.z) * (self.major_radius / ring_length)
+        } else {
+            Vector3::zero()
+        };
+        let normal = self.world_vector(local - ring_point).normalized();
+
+        // u da la vuelta al anillo y v la vuelta al tubo
+        let u = (local.z.atan2(local.x) + PI) / (2.0 * PI);
+        let v = (local.y.atan2(ring_length - self.major_radius) + PI) / (2.0 * PI);
+
+        Intersect::new(*ray_origin + *ray_direction * t, normal, t, self.material.clone(), u, v)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.center
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        // Como un disco de radio mayor engordado por el radio del tubo en todos los ejes
+        let extent = |n: f32| self.major_radius * (1.0 - n * n).max(0.0).sqrt() + self.minor_radius;
+        let half = Vector3::new(extent(self.axis.x), extent(self.axis.y), extent(self.axis.z));
+        (self.center - half, self.center + half)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(axis: Vector3) -> Torus {
+        Torus::new(Vector3::zero(), axis, 1.0, 0.25, Material::black())
+    }
+
+    fn assert_hit(hit: &Intersect, distance: f32, normal: Vector3) {
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - distance).abs() < 1e-3, "distancia {}", hit.distance);
+        assert!(hit.normal.dot(normal) > 0.999, "normal {:?}", hit.normal);
+    }
+
+    #[test]
+    fn ray_through_the_hole_misses() {
+        let hit = ring(Vector3::new(0.0, 1.0, 0.0)).ray_intersect(&Vector3::new(0.0, 5.0, 0.0), &Vector3::new(0.0, -1.0, 0.0));
+        assert!(!hit.is_intersecting);
+    }
+
+    #[test]
+    fn ray_into_the_tube_hits_at_the_expected_distance() {
+        let torus = ring(Vector3::new(0.0, 1.0, 0.0));
+        let down = torus.ray_intersect(&Vector3::new(1.0, 5.0, 0.0), &Vector3::new(0.0, -1.0, 0.0));
+        assert_hit(&down, 4.75, Vector3::new(0.0, 1.0, 0.0));
+        let side = torus.ray_intersect(&Vector3::new(-5.0, 0.0, 0.0), &Vector3::new(1.0, 0.0, 0.0));
+        assert_hit(&side, 3.75, Vector3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn tilted_axis_and_origin_inside_the_tube() {
+        // Anillo en el plano YZ: el agujero queda mirando a X
+        let torus = ring(Vector3::new(1.0, 0.0, 0.0));
+        assert!(!torus.ray_intersect(&Vector3::new(5.0, 0.0, 0.0), &Vector3::new(-1.0, 0.0, 0.0)).is_intersecting);
+        let hit = torus.ray_intersect(&Vector3::new(0.0, 1.0, 0.0), &Vector3::new(0.0, 1.0, 0.0));
+        assert_hit(&hit, 0.25, Vector3::new(0.0, 1.0, 0.0));
+    }
+}
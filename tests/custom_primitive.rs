@@ -0,0 +1,139 @@
+// Una primitiva definida fuera del crate, solo con la API pública: implementa RayIntersect y
+// entra a la escena como cualquier otro objeto
+use raylib::prelude::*;
+
+use computer_graphics_v3::accumulation::AccumulationBuffer;
+use computer_graphics_v3::camera::Camera;
+use computer_graphics_v3::cube::Cube;
+use computer_graphics_v3::framebuffer::Framebuffer;
+use computer_graphics_v3::material::Material;
+use computer_graphics_v3::ray_intersect::{inverse_direction, Intersect, RayIntersect, SURFACE_EPSILON};
+use computer_graphics_v3::scene::{Scene, SceneObject};
+use computer_graphics_v3::settings::RenderSettings;
+use computer_graphics_v3::textures::TextureManager;
+use computer_graphics_v3::{cast_ray, daynight, pick, pixel_sampler, render, update_culling, RayPath};
+
+// Esfera mínima: lo justo que pide el trait (impacto, material, centro y caja)
+struct Ball {
+    center: Vector3,
+    radius: f32,
+    material: Material,
+}
+
+impl RayIntersect for Ball {
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        let to_origin = *ray_origin - self.center;
+        let a = ray_direction.dot(*ray_direction);
+        let b = 2.0 * to_origin.dot(*ray_direction);
+        let c = to_origin.dot(to_origin) - self.radius * self.radius;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return Intersect::empty();
+        }
+
+        // La raíz más cercana que quede delante del origen (la lejana si el origen está adentro)
+        let root = discriminant.sqrt();
+        let t = [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+            .into_iter()
+            .find(|&t| t > SURFACE_EPSILON);
+        let Some(t) = t else {
+            return Intersect::empty();
+        };
+
+        let point = *ray_origin + *ray_direction * t;
+        let normal = (point - self.center).normalized();
+        let u = 0.5 + normal.z.atan2(normal.x) / (2.0 * std::f32::consts::PI);
+        let v = 0.5 - normal.y.asin() / std::f32::consts::PI;
+        Intersect::new(point, normal, t, self.material.clone(), u, v)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn center(&self) -> Vector3 {
+        self.center
+    }
+
+    fn bounds(&self) -> (Vector3, Vector3) {
+        let extent = Vector3::one() * self.radius;
+        (self.center - extent, self.center + extent)
+    }
+}
+
+fn red_ball(center: Vector3) -> Ball {
+    let material = Material::builder().diffuse(Vector3::new(0.9, 0.1, 0.1)).albedo([0.9, 0.1, 0.0, 0.0]).build();
+    Ball { center, radius: 1.0, material }
+}
+
+#[test]
+fn ball_intersects_through_the_trait_object() {
+    let ball: SceneObject = Box::new(red_ball(Vector3::zero()));
+
+    let hit = ball.ray_intersect(&Vector3::new(0.0, 0.0, 5.0), &Vector3::new(0.0, 0.0, -1.0));
+    assert!(hit.is_intersecting);
+    assert!((hit.distance - 4.0).abs() < 1e-4);
+    assert!((hit.normal - Vector3::new(0.0, 0.0, 1.0)).length() < 1e-4);
+    assert!((0.0..=1.0).contains(&hit.u) && (0.0..=1.0).contains(&hit.v));
+
+    // Desde adentro sale por el otro lado; de costado no la toca
+    let inside = ball.ray_intersect(&Vector3::zero(), &Vector3::new(1.0, 0.0, 0.0));
+    assert!((inside.distance - 1.0).abs() < 1e-4);
+    assert!(!ball.ray_intersect(&Vector3::new(0.0, 2.0, 5.0), &Vector3::new(0.0, 0.0, -1.0)).is_intersecting);
+
+    // Las versiones por defecto del trait se apoyan en ray_intersect y bounds
+    let (eye, forward) = (Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+    assert!(ball.any_hit(&eye, &forward, &inverse_direction(&forward), 10.0));
+    assert!(!ball.any_hit(&eye, &forward, &inverse_direction(&forward), 3.0));
+    assert_eq!(ball.bounding_box().min, Vector3::new(-1.0, -1.0, -1.0));
+}
+
+#[test]
+fn ball_renders_and_is_picked_next_to_builtin_objects() {
+    // La pelota delante y un cubo incorporado detrás, corrido a la derecha
+    let objects: Vec<SceneObject> = vec![
+        Box::new(red_ball(Vector3::zero())),
+        Box::new(Cube::new(Vector3::new(2.5, 0.0, -2.0), 1.0, Material::black())),
+    ];
+    let mut lights = daynight::create_lights(8.0);
+    daynight::update_day_night(&mut lights, 0.3, 8.0, &daynight::sun_ramp());
+    let mut scene = Scene::new(objects, lights, TextureManager::new());
+
+    let (width, height) = (48, 32);
+    let settings = RenderSettings::default();
+    let camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+    update_culling(&mut scene, &camera, &settings, width as f32 / height as f32);
+
+    let mut framebuffer = Framebuffer::new(width, height);
+    let mut accumulation = AccumulationBuffer::new(width, height);
+    render(&mut framebuffer, &scene, &camera, &settings, &mut accumulation, None);
+
+    // El centro es la pelota roja; la esquina es cielo
+    let center = framebuffer.color_buffer.get_color(width as i32 / 2, height as i32 / 2);
+    let corner = framebuffer.color_buffer.get_color(0, 0);
+    assert!(center.r > 2 * center.g.max(center.b), "{center:?}");
+    assert_ne!(center, corner);
+
+    let (index, hit) = pick(&scene, &camera, &settings, width as f32 / 2.0, height as f32 / 2.0, width as f32, height as f32).unwrap();
+    assert_eq!(index, 0);
+    assert!((hit.distance - 4.0).abs() < 0.05);
+}
+
+#[test]
+fn emissive_ball_lights_its_neighbors() {
+    // Sin luces ni cielo, lo único que puede iluminar el cubo es la pelota que tiene arriba
+    let settings = RenderSettings { sky_color: Vector3::zero(), ..RenderSettings::default() };
+    let white = Material::builder().diffuse(Vector3::one()).albedo([1.0, 0.0, 0.0, 0.0]).build();
+    let top_of_cube = |ball: Ball| {
+        let objects: Vec<SceneObject> = vec![Box::new(ball), Box::new(Cube::new(Vector3::zero(), 1.0, white.clone()))];
+        let scene = Scene::new(objects, Vec::new(), TextureManager::new());
+        let eye = Vector3::new(2.0, 3.0, 0.0);
+        let direction = (Vector3::new(0.2, 0.5, 0.2) - eye).normalized();
+        cast_ray(&eye, &direction, &scene, &settings, RayPath::primary(), &mut pixel_sampler(0, 0, 0))
+    };
+
+    let dark = top_of_cube(Ball { radius: 0.5, ..red_ball(Vector3::new(0.0, 2.0, 0.0)) });
+    let glowing = Material::builder().diffuse(Vector3::one()).emissive(Vector3::one(), 2.0).build();
+    let lit = top_of_cube(Ball { center: Vector3::new(0.0, 2.0, 0.0), radius: 0.5, material: glowing });
+    assert!(lit.x > dark.x + 0.05, "{lit:?} contra {dark:?}");
+}
@@ -0,0 +1,99 @@
+// Renders sin ventana de escenas fijas comparados contra imágenes de referencia en
+// tests/reference. Las texturas se arman en código, así el resultado no depende de assets/.
+// Para regenerar las referencias después de un cambio buscado en la imagen:
+// `UPDATE_REFERENCES=1 cargo test --test reference_scenes`
+use raylib::prelude::*;
+
+use computer_graphics_v3::accumulation::AccumulationBuffer;
+use computer_graphics_v3::blocks::{get_layers, load_scene_file, BlockGrid};
+use computer_graphics_v3::camera::Camera;
+use computer_graphics_v3::framebuffer::Framebuffer;
+use computer_graphics_v3::scene::Scene;
+use computer_graphics_v3::settings::RenderSettings;
+use computer_graphics_v3::textures::TextureManager;
+use computer_graphics_v3::{daynight, render, save_image, scene_objects, update_caustics, update_culling, update_shadow_map};
+
+const WIDTH: u32 = 96;
+const HEIGHT: u32 = 64;
+const SUN_RADIUS: f32 = 8.0;
+// Diferencia por canal que se tolera, la misma que usa --compare por defecto
+const TOLERANCE: u8 = 2;
+
+// Mismo armado que el camino --headless de main, con un solo cuadro acumulado
+fn render_scene(grid: &BlockGrid, decorations: bool, camera: Option<Camera>, sun_angle: f32) -> (Framebuffer, AccumulationBuffer) {
+    let mut settings = RenderSettings::default();
+    let mut lights = daynight::create_lights(SUN_RADIUS);
+    daynight::update_day_night(&mut lights, sun_angle, SUN_RADIUS, &daynight::sun_ramp());
+    settings.sky_color = daynight::sky_color(sun_angle, &daynight::sky_ramp());
+    settings.star_visibility = daynight::star_visibility(sun_angle);
+
+    let mut scene = Scene::new(scene_objects(grid, decorations, None, &settings), lights, TextureManager::new());
+    register_textures(&scene);
+    scene.preload_textures();
+    update_caustics(&mut scene, &settings);
+    update_shadow_map(&mut scene, &settings);
+
+    let aspect_ratio = WIDTH as f32 / HEIGHT as f32;
+    let camera = camera.unwrap_or_else(|| {
+        let mut camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+        camera.frame_scene(&scene, settings.vertical_fov, aspect_ratio);
+        camera
+    });
+    update_culling(&mut scene, &camera, &settings, aspect_ratio);
+
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    let mut accumulation = AccumulationBuffer::new(WIDTH, HEIGHT);
+    render(&mut framebuffer, &scene, &camera, &settings, &mut accumulation, None);
+    (framebuffer, accumulation)
+}
+
+// Un damero de 16x16 en lugar de cada textura que usan los materiales, con colores que salen del
+// nombre: distintos entre texturas y los mismos en cada corrida. Las normales quedan planas
+fn register_textures(scene: &Scene) {
+    for (path, is_srgb) in scene.texture_paths() {
+        let image = if is_srgb {
+            let hash = path.bytes().fold(17u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32));
+            let [r, g, b, _] = hash.to_le_bytes();
+            Image::gen_image_checked(16, 16, 4, 4, Color::new(r, g, b, 255), Color::new(r / 2, g / 2, b / 2, 255))
+        } else {
+            Image::gen_image_color(16, 16, Color::new(128, 128, 255, 255))
+        };
+        let bytes = image.export_image_to_memory(".png").expect("no se pudo codificar la textura");
+        assert!(scene.texture_manager.load_texture_from_memory(&path, bytes, is_srgb), "no se pudo cargar {}", path);
+    }
+}
+
+fn assert_matches_reference(name: &str, (mut framebuffer, accumulation): (Framebuffer, AccumulationBuffer)) {
+    let path = format!("{}/tests/reference/{}.png", env!("CARGO_MANIFEST_DIR"), name);
+    if std::env::var_os("UPDATE_REFERENCES").is_some() {
+        std::fs::create_dir_all(format!("{}/tests/reference", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        save_image(&path, &mut framebuffer, &accumulation, false).expect("no se pudo guardar la referencia");
+        return;
+    }
+    match framebuffer.count_differences(&path, TOLERANCE) {
+        Ok(count) => assert_eq!(count, 0, "{} píxeles de {} difieren en más de {}", count, name, TOLERANCE),
+        Err(error) => panic!("{} (regenerar con UPDATE_REFERENCES=1)", error),
+    }
+}
+
+#[test]
+fn builtin_diorama_at_noon_matches_reference() {
+    let camera = Camera::new(Vector3::new(0.0, 0.0, 5.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+    let image = render_scene(&BlockGrid::from_layers(get_layers()), true, Some(camera), 0.0);
+    assert_matches_reference("diorama_noon", image);
+}
+
+#[test]
+fn builtin_diorama_at_night_matches_reference() {
+    let camera = Camera::new(Vector3::new(3.0, 2.0, 4.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+    let image = render_scene(&BlockGrid::from_layers(get_layers()), true, Some(camera), std::f32::consts::PI);
+    assert_matches_reference("diorama_night", image);
+}
+
+#[test]
+fn mirror_glowstone_scene_matches_reference() {
+    let file = load_scene_file(&format!("{}/scenes/mirror_glowstone.txt", env!("CARGO_MANIFEST_DIR"))).unwrap();
+    let camera = file.defaults.camera.map(|(position, target)| Camera::new(position, target, Vector3::new(0.0, 1.0, 0.0)));
+    let image = render_scene(&file.grid, false, camera, file.defaults.sun_angle.unwrap_or(0.0));
+    assert_matches_reference("mirror_glowstone", image);
+}